@@ -0,0 +1,82 @@
+//! 流缓冲池基准测试
+//!
+//! 对比启用/禁用缓冲池时流缓冲路径的耗时与内存分配次数。
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use psi_detector::stream::{BufferConfig, BufferPool, StreamBuffer};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 统计分配次数的全局分配器
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const PACKET: [u8; 1400] = [0x5a; 1400];
+const PACKETS_PER_ROUND: usize = 64;
+
+fn run_round(buffer: &mut StreamBuffer, out: &mut Vec<u8>) {
+    for _ in 0..PACKETS_PER_ROUND {
+        buffer.push_slice(&PACKET).unwrap();
+    }
+    while !buffer.is_empty() {
+        let chunk = buffer.pop(PACKET.len());
+        out.extend_from_slice(&chunk);
+        if let Some(pool) = buffer.pool() {
+            pool.release(chunk);
+        }
+    }
+    out.clear();
+}
+
+fn count_allocations(buffer: &mut StreamBuffer) -> usize {
+    let mut out = Vec::with_capacity(PACKET.len() * PACKETS_PER_ROUND);
+    // 预热，让缓冲池填满
+    run_round(buffer, &mut out);
+    
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    run_round(buffer, &mut out);
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_buffer_pool(c: &mut Criterion) {
+    let mut unpooled = StreamBuffer::new(BufferConfig::default());
+    let mut pooled = StreamBuffer::with_pool(
+        BufferConfig::default(),
+        BufferPool::new(PACKETS_PER_ROUND * 2),
+    );
+    
+    println!(
+        "每轮 {} 个数据包的分配次数: 无缓冲池 {}, 缓冲池 {}",
+        PACKETS_PER_ROUND,
+        count_allocations(&mut unpooled),
+        count_allocations(&mut pooled),
+    );
+    
+    let mut group = c.benchmark_group("stream_buffer");
+    let mut out = Vec::with_capacity(PACKET.len() * PACKETS_PER_ROUND);
+    group.bench_function("unpooled", |b| {
+        b.iter(|| run_round(black_box(&mut unpooled), &mut out))
+    });
+    group.bench_function("pooled", |b| {
+        b.iter(|| run_round(black_box(&mut pooled), &mut out))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_buffer_pool);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! 探测结果克隆基准测试
+//!
+//! 对比共享元数据的 `DetectionResult` 克隆与深拷贝元数据表的耗时。
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use psi_detector::core::detector::{DetectionMethod, DetectionResult};
+use psi_detector::core::protocol::{ProtocolInfo, ProtocolType};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const METADATA_ENTRIES: usize = 16;
+
+fn sample_result() -> DetectionResult {
+    let mut info = ProtocolInfo::new(ProtocolType::TLS, 0.95);
+    for i in 0..METADATA_ENTRIES {
+        info.add_metadata(format!("key_{}", i), format!("value_{}", i));
+    }
+    DetectionResult::new(info, Duration::from_micros(50), DetectionMethod::Passive, "PassiveProbe".to_string())
+}
+
+fn bench_result_clone(c: &mut Criterion) {
+    let result = sample_result();
+    let metadata: HashMap<String, String> = (*result.protocol_info.metadata).clone();
+    
+    let mut group = c.benchmark_group("detection_result");
+    group.bench_function("clone_shared_metadata", |b| {
+        b.iter(|| black_box(&result).clone())
+    });
+    group.bench_function("deep_copy_metadata", |b| {
+        b.iter(|| black_box(&metadata).clone())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_result_clone);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! SIMD探测器基准测试
+//!
+//! 在WebSocket/gRPC样本上对比通用实现与AVX2、AVX-512实现的耗时。
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use psi_detector::core::protocol::ProtocolType;
+use psi_detector::simd::detector::GenericSimdDetector;
+use psi_detector::simd::SimdDetector;
+
+const PROTOCOLS: [ProtocolType; 2] = [ProtocolType::GRPC, ProtocolType::WebSocket];
+
+fn samples() -> Vec<Vec<u8>> {
+    vec![
+        b"GET /chat HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n".to_vec(),
+        b"POST /pkg.Service/Method HTTP/2\r\ncontent-type: application/grpc+proto\r\n\r\n".to_vec(),
+        vec![0x00, 0x00, 0x00, 0x00, 0x03, b'a', b'b', b'c'],
+        b"SSH-2.0-OpenSSH_9.6\r\n".to_vec(),
+        b"U".repeat(1024),
+    ]
+}
+
+fn detectors() -> Vec<(&'static str, Box<dyn SimdDetector>)> {
+    #[allow(unused_mut)]
+    let mut detectors: Vec<(&'static str, Box<dyn SimdDetector>)> = vec![("generic", Box::new(GenericSimdDetector::new()))];
+    #[cfg(target_arch = "x86_64")]
+    {
+        use psi_detector::simd::x86_64::{Avx2Detector, Avx512Detector};
+        detectors.push(("avx2", Box::new(Avx2Detector::new())));
+        detectors.push(("avx512", Box::new(Avx512Detector::new())));
+    }
+    detectors
+}
+
+fn bench_simd_detectors(c: &mut Criterion) {
+    let samples = samples();
+    
+    let mut group = c.benchmark_group("simd_detect_multiple");
+    for (name, detector) in detectors() {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for sample in &samples {
+                    let _ = black_box(detector.detect_multiple(black_box(sample), &PROTOCOLS));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_simd_detectors);
+criterion_main!(benches);
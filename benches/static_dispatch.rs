@@ -0,0 +1,62 @@
+//! 静态分发探测基准测试
+//!
+//! 在固定的4协议集合上对比 `Box<dyn ProtocolDetector>` 与 `StaticDetector` 的耗时。
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::core::detector::ProtocolDetector;
+use psi_detector::core::protocol::ProtocolType;
+use psi_detector::core::StaticDetector;
+
+const PROTOCOLS: [ProtocolType; 4] = [
+    ProtocolType::HTTP1_1,
+    ProtocolType::HTTP2,
+    ProtocolType::TLS,
+    ProtocolType::SSH,
+];
+
+fn samples() -> Vec<Vec<u8>> {
+    let mut tls = vec![0x16, 0x03, 0x01, 0x00, 0x2f, 0x01, 0x00, 0x00, 0x2b, 0x03, 0x03];
+    tls.extend_from_slice(&[0x11; 32]);
+    tls.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+    vec![
+        b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec(),
+        b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x00\x04\x00\x00\x00\x00\x00".to_vec(),
+        tls,
+        b"SSH-2.0-OpenSSH_9.6\r\n".to_vec(),
+    ]
+}
+
+fn bench_static_dispatch(c: &mut Criterion) {
+    let samples = samples();
+    let dynamic: Box<dyn ProtocolDetector> = Box::new(
+        DetectorBuilder::new()
+            .enable_http()
+            .enable_http2()
+            .enable_tls()
+            .enable_ssh()
+            .build()
+            .unwrap(),
+    );
+    let fixed = StaticDetector::new(PROTOCOLS);
+    
+    let mut group = c.benchmark_group("fixed_protocol_set");
+    group.bench_function("dynamic_dispatch", |b| {
+        b.iter(|| {
+            for sample in &samples {
+                let _ = black_box(dynamic.detect(black_box(sample)));
+            }
+        })
+    });
+    group.bench_function("static_dispatch", |b| {
+        b.iter(|| {
+            for sample in &samples {
+                black_box(fixed.detect(black_box(sample)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_static_dispatch);
+criterion_main!(benches);
@@ -10,7 +10,7 @@
 use psi_detector::{
     DetectorBuilder, ProtocolDetector, ProtocolType,
     core::ProbeStrategy,
-    core::detector::DetectionResult,
+    core::detector::{DefaultProtocolDetector, DetectionResult},
 };
 use std::{
     collections::HashMap,
@@ -42,7 +42,7 @@ struct DetectionStats {
 /// 多协议服务端
 struct MultiProtocolServer {
     config: ServerConfig,
-    detector: Arc<dyn ProtocolDetector>,
+    detector: Arc<DefaultProtocolDetector>,
     stats: Arc<Mutex<DetectionStats>>,
 }
 
@@ -101,7 +101,7 @@ impl MultiProtocolServer {
     /// 在指定端口监听连接
     fn listen_on_port(
         port: u16,
-        detector: Arc<dyn ProtocolDetector>,
+        detector: Arc<DefaultProtocolDetector>,
         stats: Arc<Mutex<DetectionStats>>,
         expected_protocol: Option<ProtocolType>,
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -139,15 +139,15 @@ impl MultiProtocolServer {
     fn handle_connection(
         stream: &mut TcpStream,
         port: u16,
-        detector: Arc<dyn ProtocolDetector>,
+        detector: Arc<DefaultProtocolDetector>,
         stats: Arc<Mutex<DetectionStats>>,
         expected_protocol: Option<ProtocolType>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let peer_addr = stream.peer_addr()?;
         println!("🔗 新连接来自 {} -> 端口 {}", peer_addr, port);
 
-        // 读取初始数据进行协议探测
-        let mut buffer = vec![0u8; 1024];
+        // 读取初始数据进行协议探测，大小由启用的协议决定
+        let mut buffer = vec![0u8; detector.recommended_initial_read()];
         stream.set_read_timeout(Some(Duration::from_secs(5)))?;
         
         let bytes_read = stream.read(&mut buffer)?;
@@ -4,17 +4,44 @@
 
 use crate::core::{
     detector::{
-        ProtocolDetector, DetectionConfig, DefaultProtocolDetector,
+        ProtocolDetector, DetectionConfig, DefaultProtocolDetector, DetectionMethod,
         Agent, AgentConfig, Role, LoadBalancerConfig, LoadBalanceStrategy
     },
-    protocol::ProtocolType,
-    probe::{ProbeStrategy, ProbeConfig, ProbeRegistry, ProtocolProbe},
+    magic::MagicSignature,
+    protocol::{ProtocolType, ProtocolRegistry},
+    probe::{ProbeStrategy, MatchMode, DetectionStage, ProbeConfig, ProbeRegistry, ProtocolProbe},
+    cache::DetectionCache,
+    sink::DetectionSink,
 };
 use crate::error::{DetectorError, Result};
+use crate::upgrade::ProtocolUpgrader;
 use std::time::Duration;
 use std::collections::HashSet;
 use std::sync::Arc;
 
+/// 探测器预设配置
+///
+/// 通过 [`DetectorBuilder::preset`] 一次设置超时、置信度、流水线等参数。
+/// 预设不启用任何协议，之后的链式调用可以覆盖预设中的单项配置。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// 边缘代理：在连接入口快速分流，延迟优先
+    ///
+    /// - 被动探测，20ms超时
+    /// - 最小置信度0.9，宁可不判定也不误判
+    /// - 流水线只有魔法包快速检测和被动探测器，`FirstMatch` 命中即返回
+    /// - 禁用启发式、主动探测和全局探测器补充扫描
+    EdgeProxy,
+    /// 深度检测：离线分析或旁路嗅探，准确性和信息量优先
+    ///
+    /// - 被动探测，500ms超时
+    /// - 启用启发式探测和全局探测器补充扫描，运行全部流水线阶段
+    /// - `BestMatch` 模式，置信度不低于0.1的次优候选写入 `alternatives`
+    /// - 64KB探测缓冲区，保留最多32个候选结果
+    /// - 最小置信度保持构造器当前的设置
+    DeepInspection,
+}
+
 /// 探测器构造器
 /// 
 /// 提供流畅的API来配置和创建协议探测器实例。
@@ -26,9 +53,16 @@ pub struct DetectorBuilder {
     probe_config: ProbeConfig,
     detection_config: DetectionConfig,
     custom_probes: Vec<Box<dyn ProtocolProbe>>,
+    custom_protocols: ProtocolRegistry,
+    custom_signatures: Vec<MagicSignature>,
     yuri_theme: bool,
     agent_config: Option<AgentConfig>,
     load_balancer_config: Option<LoadBalancerConfig>,
+    upgrader: Option<Arc<dyn ProtocolUpgrader>>,
+    low_confidence_as_unknown: bool,
+    sink: Option<Arc<dyn DetectionSink>>,
+    profiling: bool,
+    detection_cache: Option<usize>,
 }
 
 impl Default for DetectorBuilder {
@@ -45,14 +79,22 @@ impl DetectorBuilder {
             probe_config: ProbeConfig::default(),
             detection_config: DetectionConfig::default(),
             custom_probes: Vec::new(),
+            custom_protocols: ProtocolRegistry::new(),
+            custom_signatures: Vec::new(),
             yuri_theme: false,
             agent_config: None,
             load_balancer_config: None,
+            upgrader: None,
+            low_confidence_as_unknown: false,
+            sink: None,
+            profiling: false,
+            detection_cache: None,
         }
     }
     
-    /// 启用HTTP协议探测
+    /// 启用HTTP协议探测（HTTP/1.1，以及没有版本号的HTTP/0.9简单请求，归为HTTP/1.0）
     pub fn enable_http(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::HTTP1_0);
         self.enabled_protocols.insert(ProtocolType::HTTP1_1);
         self
     }
@@ -105,6 +147,124 @@ impl DetectorBuilder {
         self
     }
     
+    /// 启用WebTransport协议探测
+    pub fn enable_webtransport(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::WebTransport);
+        self
+    }
+    
+    /// 启用IRC协议探测
+    pub fn enable_irc(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::IRC);
+        self
+    }
+    
+    /// 启用XMPP协议探测
+    pub fn enable_xmpp(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::XMPP);
+        self
+    }
+    
+    /// 启用VNC协议探测
+    pub fn enable_vnc(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::VNC);
+        self
+    }
+    
+    /// 启用RDP协议探测
+    pub fn enable_rdp(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::RDP);
+        self
+    }
+    
+    /// 启用MySQL协议探测
+    pub fn enable_mysql(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::MySQL);
+        self
+    }
+    
+    /// 启用PostgreSQL协议探测
+    pub fn enable_postgresql(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::PostgreSQL);
+        self
+    }
+    
+    /// 启用长度前缀Protobuf协议探测
+    ///
+    /// 该格式本身有歧义，不包含在 `enable_all` 中，需显式启用。
+    pub fn enable_protobuf(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::Protobuf);
+        self
+    }
+    
+    /// 启用SMTP协议探测
+    pub fn enable_smtp(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::SMTP);
+        self
+    }
+    
+    /// 启用IMAP协议探测
+    pub fn enable_imap(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::IMAP);
+        self
+    }
+    
+    /// 启用POP3协议探测
+    pub fn enable_pop3(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::POP3);
+        self
+    }
+    
+    /// 启用FTP协议探测
+    pub fn enable_ftp(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::FTP);
+        self
+    }
+    
+    /// 启用Memcached协议探测（文本和二进制协议）
+    pub fn enable_memcached(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::Memcached);
+        self
+    }
+    
+    /// 启用ClickHouse原生协议探测
+    pub fn enable_clickhouse(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::ClickHouse);
+        self
+    }
+    
+    /// 启用Thrift协议探测（分帧传输的binary/compact协议）
+    pub fn enable_thrift(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::Thrift);
+        self
+    }
+    
+    /// 启用Avro协议探测（RPC握手和对象容器）
+    pub fn enable_avro(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::Avro);
+        self
+    }
+    
+    /// 启用OpenVPN协议探测（UDP和TCP）
+    pub fn enable_openvpn(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::OpenVPN);
+        self
+    }
+    
+    /// 启用IKE/ISAKMP协议探测（含NAT-T封装）
+    pub fn enable_ike(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::IKE);
+        self
+    }
+    
+    /// 启用混淆传输识别
+    ///
+    /// 仅由启发式探测器给出低置信度结果，不包含在 `enable_all` 中。
+    pub fn enable_obfuscated(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::Obfuscated);
+        self
+    }
+    
     /// 启用自定义协议探测
     pub fn enable_custom(mut self) -> Self {
         self.enabled_protocols.insert(ProtocolType::Custom);
@@ -113,6 +273,7 @@ impl DetectorBuilder {
     
     /// 启用所有支持的协议
     pub fn enable_all(mut self) -> Self {
+        self.enabled_protocols.insert(ProtocolType::HTTP1_0);
         self.enabled_protocols.insert(ProtocolType::HTTP1_1);
         self.enabled_protocols.insert(ProtocolType::HTTP2);
         self.enabled_protocols.insert(ProtocolType::GRPC);
@@ -121,6 +282,23 @@ impl DetectorBuilder {
         self.enabled_protocols.insert(ProtocolType::SSH);
         self.enabled_protocols.insert(ProtocolType::UDP);
         self.enabled_protocols.insert(ProtocolType::WebSocket);
+        self.enabled_protocols.insert(ProtocolType::WebTransport);
+        self.enabled_protocols.insert(ProtocolType::IRC);
+        self.enabled_protocols.insert(ProtocolType::XMPP);
+        self.enabled_protocols.insert(ProtocolType::VNC);
+        self.enabled_protocols.insert(ProtocolType::RDP);
+        self.enabled_protocols.insert(ProtocolType::PostgreSQL);
+        self.enabled_protocols.insert(ProtocolType::MySQL);
+        self.enabled_protocols.insert(ProtocolType::SMTP);
+        self.enabled_protocols.insert(ProtocolType::IMAP);
+        self.enabled_protocols.insert(ProtocolType::POP3);
+        self.enabled_protocols.insert(ProtocolType::FTP);
+        self.enabled_protocols.insert(ProtocolType::Thrift);
+        self.enabled_protocols.insert(ProtocolType::Avro);
+        self.enabled_protocols.insert(ProtocolType::Memcached);
+        self.enabled_protocols.insert(ProtocolType::ClickHouse);
+        self.enabled_protocols.insert(ProtocolType::OpenVPN);
+        self.enabled_protocols.insert(ProtocolType::IKE);
         self
     }
     
@@ -137,6 +315,16 @@ impl DetectorBuilder {
         self
     }
     
+    /// 设置所有探测器的总耗时上限
+    ///
+    /// 与 [`Self::with_timeout`] 不同，超出上限时探测返回 `DetectorError::Timeout`，
+    /// 而不是聚合已完成探测器的结果，调用方可据此走降级逻辑。
+    /// 探测器无法被中途打断，上限在每个探测器执行完成后检查。
+    pub fn with_hard_deadline(mut self, deadline: Duration) -> Self {
+        self.detection_config.hard_deadline = Some(deadline);
+        self
+    }
+    
     /// 设置最小置信度阈值
     pub fn with_min_confidence(mut self, confidence: f32) -> Self {
         self.probe_config.min_confidence = confidence;
@@ -188,18 +376,139 @@ impl DetectorBuilder {
         self
     }
     
+    /// 启用全局探测器补充扫描
+    pub fn enable_global_probes(mut self) -> Self {
+        self.probe_config.run_global_probes = true;
+        self
+    }
+    
+    /// 禁用全局探测器补充扫描（降低延迟）
+    pub fn disable_global_probes(mut self) -> Self {
+        self.probe_config.run_global_probes = false;
+        self
+    }
+    
+    /// 设置单次探测保留的最大候选结果数
+    pub fn with_max_candidates(mut self, max: usize) -> Self {
+        self.probe_config.max_candidates = max;
+        self
+    }
+    
+    /// 设置结果选择模式
+    ///
+    /// `BestMatch` 会运行所有探测器，结果不受启用协议顺序影响。
+    pub fn with_match_mode(mut self, mode: MatchMode) -> Self {
+        self.probe_config.match_mode = mode;
+        self
+    }
+    
+    /// 设置探测流水线的阶段顺序
+    ///
+    /// 例如把 `Simd` 放在 `Magic` 之前，以更高的开销换取SIMD结果总是参与聚合。
+    pub fn with_pipeline(mut self, stages: Vec<DetectionStage>) -> Self {
+        self.probe_config.pipeline = stages;
+        self
+    }
+    
+    /// 设置某种探测方法的置信度区间
+    ///
+    /// 该方法产生的候选结果在聚合前会被钳制到 `[floor, ceil]`。
+    /// 魔法包检测的结果按 `DetectionMethod::SimdAccelerated` 匹配。
+    pub fn with_confidence_bounds(mut self, method: DetectionMethod, floor: f32, ceil: f32) -> Self {
+        self.probe_config.confidence_bounds.insert(method, (floor, ceil));
+        self
+    }
+    
+    /// 设置某类探测引擎的置信度权重（见 [`crate::probe::ProbeAggregator`]）
+    pub fn with_probe_weight(mut self, probe_type: crate::probe::ProbeType, weight: f32) -> Self {
+        self.probe_config.probe_weights.insert(probe_type, weight);
+        self
+    }
+    
+    /// 设置置信度相同时的协议优先级，靠前的协议胜出
+    ///
+    /// 例如WebSocket升级请求同时命中HTTP/1.1和WebSocket时，可把 `WebSocket` 排在前面。
+    pub fn with_protocol_priority(mut self, protocols: &[ProtocolType]) -> Self {
+        self.probe_config.protocol_priority = protocols.to_vec();
+        self
+    }
+    
+    /// 设置备选协议的最低置信度
+    ///
+    /// 达到该值的次优候选会写入 `DetectionResult::alternatives`，
+    /// 便于网关自行决定（例如gRPC与HTTP/2同时匹配时优先gRPC）。
+    pub fn with_alternative_min_confidence(mut self, confidence: f32) -> Self {
+        self.probe_config.alternative_min_confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+    
+    /// 设置歧义判定的置信度差
+    ///
+    /// 最高的两个协议都达到最低置信度且相差不超过 `margin` 时，探测返回
+    /// [`DetectorError::Ambiguous`] 而不是猜测其一。默认0.0，始终选择最佳结果。
+    /// `FirstMatch` 模式在高置信度结果处提前结束，通常应配合 `MatchMode::BestMatch` 使用。
+    pub fn with_ambiguity_margin(mut self, margin: f32) -> Self {
+        self.probe_config.ambiguity_margin = margin.clamp(0.0, 1.0);
+        self
+    }
+    
+    /// 设置某协议接受结论所需的最少字节数
+    ///
+    /// 例如 `.with_protocol_min_bytes(ProtocolType::SSH, 4)` 可从 `SSH-` 前缀识别SSH，
+    /// 其余协议仍按 [`Self::with_min_probe_size`] 等待足够的数据。
+    pub fn with_protocol_min_bytes(mut self, protocol: ProtocolType, bytes: usize) -> Self {
+        self.probe_config.protocol_min_bytes.insert(protocol, bytes);
+        self
+    }
+    
     /// 设置最小探测数据大小
     pub fn with_min_probe_size(mut self, size: usize) -> Self {
         self.detection_config.min_probe_size = size;
         self
     }
     
+    /// 注册命名的自定义协议
+    ///
+    /// 特征的协议类型被改为 `ProtocolType::Custom` 并自动启用该类型；
+    /// 命中时探测结果通过 `custom_name` 报告协议名称。名称重复时返回错误。
+    pub fn register_custom_protocol<S: Into<String>>(mut self, name: S, signature: MagicSignature) -> Result<Self> {
+        let name = name.into();
+        self.custom_protocols.register(name.clone())?;
+        
+        let mut signature = signature.with_custom_name(name);
+        signature.protocol = ProtocolType::Custom;
+        self.custom_signatures.push(signature);
+        self.enabled_protocols.insert(ProtocolType::Custom);
+        Ok(self)
+    }
+    
     /// 添加自定义探测器
     pub fn add_custom_probe(mut self, probe: Box<dyn ProtocolProbe>) -> Self {
         self.custom_probes.push(probe);
         self
     }
     
+    /// 设置探测结果输出
+    pub fn with_sink(mut self, sink: Arc<dyn DetectionSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+    
+    /// 启用探测结果缓存，最多保留 `capacity` 条结果
+    ///
+    /// 以探测数据前64字节的哈希为键，只缓存置信度不低于0.9的结果，
+    /// 命中时结果的探测方法为 `DetectionMethod::Cached`。
+    pub fn with_detection_cache(mut self, capacity: usize) -> Self {
+        self.detection_cache = Some(capacity);
+        self
+    }
+    
+    /// 开启或关闭各探测器耗时统计（默认关闭，避免额外开销）
+    pub fn with_profiling(mut self, enabled: bool) -> Self {
+        self.profiling = enabled;
+        self
+    }
+    
     /// 启用尤里主题
     pub fn with_yuri_theme(mut self) -> Self {
         self.yuri_theme = true;
@@ -219,6 +528,8 @@ impl DetectorBuilder {
                  enabled_protocols: self.enabled_protocols.iter().cloned().collect(),
                  enable_upgrade: true,
                  load_balancer_config: None,
+                 fallback_protocol: None,
+                 return_low_confidence_as_unknown: false,
              });
          }
          self
@@ -237,6 +548,8 @@ impl DetectorBuilder {
                  enabled_protocols: self.enabled_protocols.iter().cloned().collect(),
                  enable_upgrade: true,
                  load_balancer_config: None,
+                 fallback_protocol: None,
+                 return_low_confidence_as_unknown: false,
              });
          }
          self
@@ -261,6 +574,27 @@ impl DetectorBuilder {
          self.load_balancer_config = Some(lb_config);
          self
      }
+    
+    /// 使用完整的负载均衡配置
+    pub fn with_load_balancer_config(mut self, lb_config: LoadBalancerConfig) -> Self {
+        if let Some(ref mut config) = self.agent_config {
+            config.load_balancer_config = Some(lb_config.clone());
+        }
+        self.load_balancer_config = Some(lb_config);
+        self
+    }
+    
+    /// Agent探测结果低于最低置信度时返回 `Unknown` 结果，而不是 `LowConfidence` 错误
+    pub fn with_low_confidence_as_unknown(mut self) -> Self {
+        self.low_confidence_as_unknown = true;
+        self
+    }
+    
+    /// 设置Agent使用的协议升级器（禁用协议升级时忽略）
+    pub fn with_upgrader(mut self, upgrader: Arc<dyn ProtocolUpgrader>) -> Self {
+        self.upgrader = Some(upgrader);
+        self
+    }
      
      /// 启用协议升级
      pub fn enable_protocol_upgrade(mut self) -> Self {
@@ -301,6 +635,24 @@ impl DetectorBuilder {
             ));
         }
         
+        // 验证硬性截止时间
+        self.validate_hard_deadline()?;
+        
+        // 验证候选数量上限
+        self.validate_max_candidates()?;
+        
+        // 验证置信度区间
+        self.validate_confidence_bounds()?;
+        
+        // 验证探测引擎权重
+        self.validate_probe_weights()?;
+        
+        // 验证流水线阶段
+        self.validate_pipeline()?;
+        
+        // 验证探测结果缓存容量
+        self.validate_detection_cache()?;
+        
         // 验证Agent配置（如果存在）
         if let Some(ref config) = self.agent_config {
             if config.instance_id.is_empty() {
@@ -313,6 +665,75 @@ impl DetectorBuilder {
         Ok(())
     }
     
+    /// 验证硬性截止时间大于0
+    fn validate_hard_deadline(&self) -> Result<()> {
+        if self.detection_config.hard_deadline.is_some_and(|deadline| deadline.is_zero()) {
+            return Err(DetectorError::config_error(
+                "硬性截止时间必须大于0"
+            ));
+        }
+        Ok(())
+    }
+    
+    /// 验证探测结果缓存容量
+    fn validate_detection_cache(&self) -> Result<()> {
+        if self.detection_cache == Some(0) {
+            return Err(DetectorError::config_error(
+                "探测结果缓存容量必须大于0"
+            ));
+        }
+        Ok(())
+    }
+    
+    /// 验证候选数量上限
+    fn validate_max_candidates(&self) -> Result<()> {
+        if self.probe_config.max_candidates == 0 {
+            return Err(DetectorError::config_error(
+                "最大候选数量必须大于0"
+            ));
+        }
+        Ok(())
+    }
+    
+    /// 验证各探测方法的置信度区间
+    fn validate_confidence_bounds(&self) -> Result<()> {
+        for (method, &(floor, ceil)) in &self.probe_config.confidence_bounds {
+            if !(0.0..=1.0).contains(&floor) || !(0.0..=1.0).contains(&ceil) || floor > ceil {
+                return Err(DetectorError::config_error(
+                    format!("{:?} 的置信度区间无效: [{}, {}]", method, floor, ceil)
+                ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// 验证探测引擎权重为非负有限值
+    fn validate_probe_weights(&self) -> Result<()> {
+        for (probe_type, &weight) in &self.probe_config.probe_weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(DetectorError::config_error(
+                    format!("{:?} 的探测权重无效: {}", probe_type, weight)
+                ));
+            }
+        }
+        Ok(())
+    }
+    
+    /// 验证流水线阶段非空且不重复
+    fn validate_pipeline(&self) -> Result<()> {
+        let stages = &self.probe_config.pipeline;
+        if stages.is_empty() {
+            return Err(DetectorError::config_error("探测流水线至少需要一个阶段"));
+        }
+        let unique: HashSet<_> = stages.iter().collect();
+        if unique.len() != stages.len() {
+            return Err(DetectorError::config_error(
+                format!("探测流水线阶段重复: {:?}", stages)
+            ));
+        }
+        Ok(())
+    }
+    
     /// 创建高性能配置
     /// 
     /// 优化配置以获得最佳性能：
@@ -366,6 +787,34 @@ impl DetectorBuilder {
         self
     }
     
+    /// 应用预设配置，各预设的具体设置见 [`Preset`]
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.probe_config.strategy = ProbeStrategy::Passive;
+        self.detection_config.enable_active_probing = false;
+        match preset {
+            Preset::EdgeProxy => {
+                self.probe_config.enable_heuristic = false;
+                self.probe_config.run_global_probes = false;
+                self.probe_config.match_mode = MatchMode::FirstMatch;
+                self.probe_config.pipeline = vec![DetectionStage::Magic, DetectionStage::Passive];
+                self.detection_config.enable_heuristic = false;
+                self.with_timeout(Duration::from_millis(20))
+                    .with_min_confidence(0.9)
+            }
+            Preset::DeepInspection => {
+                self.probe_config.enable_heuristic = true;
+                self.probe_config.run_global_probes = true;
+                self.probe_config.match_mode = MatchMode::BestMatch;
+                self.probe_config.pipeline = DetectionStage::default_pipeline();
+                self.probe_config.alternative_min_confidence = 0.1;
+                self.probe_config.buffer_size = 64 * 1024;
+                self.probe_config.max_candidates = 32;
+                self.detection_config.enable_heuristic = true;
+                self.with_timeout(Duration::from_millis(500))
+            }
+        }
+    }
+    
     /// 构建探测器实例
     pub fn build(self) -> Result<DefaultProtocolDetector> {
         // 🚨 严格模式验证：必须配置协议，否则禁止启动
@@ -393,6 +842,24 @@ impl DetectorBuilder {
             ));
         }
         
+        // 验证硬性截止时间
+        self.validate_hard_deadline()?;
+        
+        // 验证候选数量上限
+        self.validate_max_candidates()?;
+        
+        // 验证置信度区间
+        self.validate_confidence_bounds()?;
+        
+        // 验证探测引擎权重
+        self.validate_probe_weights()?;
+        
+        // 验证流水线阶段
+        self.validate_pipeline()?;
+        
+        // 验证探测结果缓存容量
+        self.validate_detection_cache()?;
+        
         // 创建探测器注册表
         let mut registry = ProbeRegistry::new();
         
@@ -405,15 +872,34 @@ impl DetectorBuilder {
         }
         
         // 创建探测器实例
-         DefaultProtocolDetector::new(
-             registry,
-             self.probe_config,
-             self.detection_config,
-             self.enabled_protocols.into_iter().collect(),
-         )
+        let detector = DefaultProtocolDetector::new(
+            registry,
+            self.probe_config,
+            self.detection_config,
+            self.enabled_protocols.into_iter().collect(),
+        )?
+        .with_custom_protocols(self.custom_protocols, self.custom_signatures)
+        .with_profiling(self.profiling);
+        let detector = match self.detection_cache {
+            Some(capacity) => detector.with_detection_cache(DetectionCache::new(capacity)),
+            None => detector,
+        };
+        
+        Ok(match self.sink {
+            Some(sink) => detector.with_sink(sink),
+            None => detector,
+        })
      }
      
+    /// 以指定角色构建Agent实例，等同于 `with_role(role).build_agent()`
+    pub fn build_agent_with_role(self, role: Role) -> Result<Agent> {
+        self.with_role(role).build_agent()
+    }
+     
      /// 构建Agent实例
+     ///
+     /// Agent配置中的协议列表、探测配置和负载均衡配置以构建时构造器的状态为准，
+     /// 与 `with_role` 等方法的调用顺序无关。
      pub fn build_agent(self) -> Result<Agent> {
          // 🚨 严格模式验证：Agent必须明确配置协议
          if self.enabled_protocols.is_empty() {
@@ -444,29 +930,43 @@ impl DetectorBuilder {
          
          // 创建探测器实例
          let enabled_protocols_vec: Vec<ProtocolType> = self.enabled_protocols.iter().cloned().collect();
-         let detector = Arc::new(DefaultProtocolDetector::new(
+         let mut detector = DefaultProtocolDetector::new(
              registry,
              self.probe_config.clone(),
              self.detection_config.clone(),
              enabled_protocols_vec.clone(),
-         )?);
+         )?
+         .with_custom_protocols(self.custom_protocols, self.custom_signatures)
+         .with_profiling(self.profiling);
+         if let Some(sink) = self.sink {
+             detector = detector.with_sink(sink);
+         }
+         if let Some(capacity) = self.detection_cache {
+             detector = detector.with_detection_cache(DetectionCache::new(capacity));
+         }
+         let detector = Arc::new(detector);
          
-         // 获取或创建Agent配置
-         let agent_config = self.agent_config.unwrap_or_else(|| AgentConfig {
+         // 获取或创建Agent配置，并同步构造器的最终状态
+         let mut agent_config = self.agent_config.unwrap_or_else(|| AgentConfig {
              role: Role::Server,
              instance_id: uuid::Uuid::new_v4().to_string(),
-             detection_config: self.detection_config,
-             probe_config: self.probe_config,
-             enabled_protocols: enabled_protocols_vec,
+             detection_config: DetectionConfig::default(),
+             probe_config: ProbeConfig::default(),
+             enabled_protocols: Vec::new(),
              enable_upgrade: true,
-             load_balancer_config: self.load_balancer_config,
+             load_balancer_config: None,
+             fallback_protocol: None,
+             return_low_confidence_as_unknown: false,
          });
+         agent_config.return_low_confidence_as_unknown |= self.low_confidence_as_unknown;
+         agent_config.detection_config = self.detection_config;
+         agent_config.probe_config = self.probe_config;
+         agent_config.enabled_protocols = enabled_protocols_vec;
+         agent_config.load_balancer_config = self.load_balancer_config;
          
          // 创建升级器（如果启用）
          let upgrader = if agent_config.enable_upgrade {
-             // 这里可以根据配置创建相应的升级器
-             // 暂时返回None，后续可以扩展
-             None
+             self.upgrader
          } else {
              None
          };
@@ -491,12 +991,10 @@ impl DetectorBuilder {
 
 /// 尤里主题构造器扩展
 impl DetectorBuilder {
-    /// 心灵探测模式 - 高精度被动探测
+    /// 心灵探测模式 - 深度被动探测（[`Preset::DeepInspection`]）
     pub fn psychic_detection(self) -> Self {
         self.with_yuri_theme()
-            .with_strategy(ProbeStrategy::Passive)
-            .high_accuracy()
-            .enable_heuristic()
+            .preset(Preset::DeepInspection)
     }
     
     /// 心灵控制模式 - 高性能被动探测和协议升级
@@ -0,0 +1,188 @@
+//! 探测结果缓存模块
+//!
+//! 以探测数据前若干字节的哈希为键缓存高置信度的探测结论，
+//! 用于CDN边缘等反复看到相同首包（相同客户端指纹）的场景。
+//! 只缓存协议、版本和置信度，请求相关的元数据（HTTP路径、TLS SNI等）由探测器在命中时按本次数据重新生成。
+
+use crate::core::detector::{DetectionMethod, DetectionResult};
+use crate::core::protocol::{ProtocolInfo, ProtocolType};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 默认参与缓存键计算的字节数
+pub const DEFAULT_KEY_BYTES: usize = 64;
+
+/// 写入缓存所需的最低置信度，低置信度的猜测不缓存以免污染后续结果
+pub const MIN_CACHED_CONFIDENCE: f32 = 0.9;
+
+/// 缓存条目
+#[derive(Debug)]
+struct CacheEntry {
+    /// 参与键计算的原始字节，命中时逐字节比较以排除哈希碰撞
+    prefix: Vec<u8>,
+    /// 缓存的结论：协议、版本、置信度和自定义协议名称，不含元数据
+    verdict: ProtocolInfo,
+    /// 给出结论的探测器
+    detector_name: String,
+    /// 最近一次使用的序号
+    last_used: u64,
+}
+
+/// LRU存储：条目表和按使用序号排序的淘汰顺序
+#[derive(Debug, Default)]
+struct LruStore {
+    entries: HashMap<u64, CacheEntry>,
+    order: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl LruStore {
+    /// 标记条目为最近使用
+    fn touch(&mut self, key: u64) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.order.remove(&entry.last_used);
+            entry.last_used = self.tick;
+            self.order.insert(self.tick, key);
+        }
+    }
+}
+
+/// 探测结果LRU缓存
+///
+/// 键为探测数据前 `key_bytes` 字节（不足时为全部数据）的哈希，哈希使用进程内随机密钥，
+/// 命中时还会比较原始字节。只缓存置信度不低于 [`MIN_CACHED_CONFIDENCE`] 的已知协议结论，
+/// 不缓存元数据、特征、参与探测器和备选协议。
+#[derive(Debug)]
+pub struct DetectionCache {
+    capacity: usize,
+    key_bytes: usize,
+    hasher: RandomState,
+    store: Mutex<LruStore>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl DetectionCache {
+    /// 创建指定容量的缓存
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            key_bytes: DEFAULT_KEY_BYTES,
+            hasher: RandomState::new(),
+            store: Mutex::new(LruStore::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+    
+    /// 设置参与缓存键计算的字节数
+    ///
+    /// 前缀相同的数据共享同一结论；键越短命中率越高，但前缀之后的内容不同时结论也可能不同。
+    pub fn with_key_bytes(mut self, key_bytes: usize) -> Self {
+        self.key_bytes = key_bytes.max(1);
+        self
+    }
+    
+    /// 缓存容量
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// 当前缓存条目数
+    pub fn len(&self) -> usize {
+        self.store.lock().unwrap().entries.len()
+    }
+    
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// 命中次数
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+    
+    /// 未命中次数
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+    
+    /// 查找缓存结论，命中时返回的结果探测方法为 `Cached`，没有元数据
+    pub fn get(&self, data: &[u8]) -> Option<DetectionResult> {
+        let prefix = self.prefix(data);
+        let key = self.hasher.hash_one(prefix);
+        
+        let mut store = self.store.lock().unwrap();
+        let found = store.entries.get(&key).filter(|entry| entry.prefix == prefix).is_some();
+        if !found {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        
+        store.touch(key);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        let entry = &store.entries[&key];
+        Some(DetectionResult::new(
+            entry.verdict.clone(),
+            Duration::ZERO,
+            DetectionMethod::Cached,
+            entry.detector_name.clone(),
+        ))
+    }
+    
+    /// 写入探测结果，未达到缓存条件的结果被忽略
+    pub fn insert(&self, data: &[u8], result: &DetectionResult) {
+        if self.capacity == 0
+            || result.confidence() < MIN_CACHED_CONFIDENCE
+            || result.protocol_type() == ProtocolType::Unknown
+        {
+            return;
+        }
+        
+        let prefix = self.prefix(data);
+        let key = self.hasher.hash_one(prefix);
+        
+        let mut store = self.store.lock().unwrap();
+        if let Some(old) = store.entries.remove(&key) {
+            store.order.remove(&old.last_used);
+        }
+        while store.entries.len() >= self.capacity {
+            let Some((_, oldest)) = store.order.pop_first() else {
+                break;
+            };
+            store.entries.remove(&oldest);
+        }
+        
+        store.tick += 1;
+        let last_used = store.tick;
+        store.order.insert(last_used, key);
+        let info = &result.protocol_info;
+        let mut verdict = ProtocolInfo::new(info.protocol_type, info.confidence);
+        verdict.version = info.version.clone();
+        verdict.custom_name = info.custom_name.clone();
+        store.entries.insert(key, CacheEntry {
+            prefix: prefix.to_vec(),
+            verdict,
+            detector_name: result.detector_name.clone(),
+            last_used,
+        });
+    }
+    
+    /// 清空缓存条目（保留命中统计）
+    pub fn clear(&self) {
+        let mut store = self.store.lock().unwrap();
+        store.entries.clear();
+        store.order.clear();
+    }
+    
+    /// 参与缓存键计算的前缀
+    fn prefix<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[..data.len().min(self.key_bytes)]
+    }
+}
@@ -2,14 +2,23 @@
 //!
 //! 定义协议探测的核心trait和相关类型。
 
-use crate::core::protocol::{ProtocolType, ProtocolInfo};
-use crate::core::probe::{ProbeRegistry, ProbeConfig, ProbeContext, ProbeAggregator};
-use crate::core::magic::MagicDetector;
+use crate::core::protocol::{ProtocolType, ProtocolInfo, ProtocolRegistry, UpgradePath};
+use crate::core::probe::{
+    ProbeRegistry, ProbeConfig, ProbeContext, ProbeAggregator, ProtocolProbe, MatchMode, DetectionStage,
+};
+use crate::core::magic::{MagicDetector, MagicSignature};
+use crate::core::fingerprint::HttpRequestInfo;
+use crate::core::sink::DetectionSink;
+use crate::core::cache::DetectionCache;
+use crate::utils::framing::MessageFraming;
 use crate::error::{DetectorError, Result};
 use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap};
+
+/// 早期数据（TFO / 0-RTT）的置信度折减系数
+const EARLY_DATA_CONFIDENCE_FACTOR: f32 = 0.9;
 
 /// 协议代理角色
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -49,6 +58,11 @@ pub struct AgentConfig {
     pub enable_upgrade: bool,
     /// 负载均衡配置（仅服务器模式）
     pub load_balancer_config: Option<LoadBalancerConfig>,
+    /// 探测无结果时使用的兜底协议，未设置时返回错误
+    pub fallback_protocol: Option<ProtocolType>,
+    /// 置信度低于 `detection_config.min_confidence` 时返回 `Unknown` 结果而不是
+    /// [`DetectorError::LowConfidence`] 错误
+    pub return_low_confidence_as_unknown: bool,
 }
 
 impl Default for AgentConfig {
@@ -65,7 +79,98 @@ impl Default for AgentConfig {
             ],
             enable_upgrade: true,
             load_balancer_config: None,
+            fallback_protocol: None,
+            return_low_confidence_as_unknown: false,
+        }
+    }
+}
+
+impl AgentConfig {
+    /// 创建Agent配置构建器
+    pub fn builder() -> AgentConfigBuilder {
+        AgentConfigBuilder::new()
+    }
+}
+
+/// Agent配置构建器
+///
+/// 未设置的字段使用 `AgentConfig::default()` 的值。
+#[derive(Debug, Clone, Default)]
+pub struct AgentConfigBuilder {
+    config: AgentConfig,
+}
+
+impl AgentConfigBuilder {
+    /// 创建新的构建器
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 设置代理角色
+    pub fn role(mut self, role: Role) -> Self {
+        self.config.role = role;
+        self
+    }
+    
+    /// 设置实例ID
+    pub fn instance_id<S: Into<String>>(mut self, instance_id: S) -> Self {
+        self.config.instance_id = instance_id.into();
+        self
+    }
+    
+    /// 设置启用的协议列表
+    pub fn enabled_protocols(mut self, protocols: Vec<ProtocolType>) -> Self {
+        self.config.enabled_protocols = protocols;
+        self
+    }
+    
+    /// 设置是否启用协议升级
+    pub fn enable_upgrade(mut self, enabled: bool) -> Self {
+        self.config.enable_upgrade = enabled;
+        self
+    }
+    
+    /// 设置负载均衡配置（仅服务器模式）
+    pub fn load_balancer(mut self, config: LoadBalancerConfig) -> Self {
+        self.config.load_balancer_config = Some(config);
+        self
+    }
+    
+    /// 设置探测无结果时使用的兜底协议
+    pub fn fallback_protocol(mut self, protocol: ProtocolType) -> Self {
+        self.config.fallback_protocol = Some(protocol);
+        self
+    }
+    
+    /// 设置置信度不足时是否返回 `Unknown` 结果而不是错误
+    pub fn return_low_confidence_as_unknown(mut self, enabled: bool) -> Self {
+        self.config.return_low_confidence_as_unknown = enabled;
+        self
+    }
+    
+    /// 设置探测配置
+    pub fn detection_config(mut self, config: DetectionConfig) -> Self {
+        self.config.detection_config = config;
+        self
+    }
+    
+    /// 设置探测器配置
+    pub fn probe_config(mut self, config: ProbeConfig) -> Self {
+        self.config.probe_config = config;
+        self
+    }
+    
+    /// 校验并生成配置
+    ///
+    /// 协议列表为空、客户端角色配置了负载均衡时返回配置错误。
+    pub fn build(self) -> Result<AgentConfig> {
+        if self.config.enabled_protocols.is_empty() {
+            return Err(DetectorError::config_error("Agent至少需要启用一个协议"));
+        }
+        if self.config.role == Role::Client && self.config.load_balancer_config.is_some() {
+            return Err(DetectorError::config_error("负载均衡配置仅适用于服务器角色"));
         }
+        Ok(self.config)
     }
 }
 
@@ -89,11 +194,14 @@ pub enum LoadBalanceStrategy {
     LeastConnections,
     /// 加权轮询
     WeightedRoundRobin,
-    /// 一致性哈希
+    /// 一致性哈希，按客户端标识选择后端，见 [`LoadBalancer::select_backend_for_key`]
     ConsistentHash,
 }
 
 /// 探测结果
+///
+/// 克隆时只有元数据表通过 `Arc` 共享，不会深拷贝；
+/// `detector_name` 以及协议信息中的 `version`、`features` 仍按值复制。
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DetectionResult {
     /// 探测到的协议信息
@@ -104,10 +212,23 @@ pub struct DetectionResult {
     pub detection_method: DetectionMethod,
     /// 探测器名称
     pub detector_name: String,
+    /// 数据完整度：已看到字节数 / 完整确认首个报文所需字节数
+    #[serde(default = "full_completeness")]
+    pub completeness: f32,
+    /// 支持该结论的探测器及其（加权后的）置信度
+    #[serde(default)]
+    pub contributing_probes: Vec<(String, f32)>,
+    /// 同样可能成立的其他协议及其置信度，按置信度降序排列
+    #[serde(default)]
+    pub alternatives: Vec<(ProtocolType, f32)>,
+}
+
+fn full_completeness() -> f32 {
+    1.0
 }
 
 impl DetectionResult {
-    /// 创建新的探测结果
+    /// 创建新的探测结果（完整度为1）
     pub fn new(
         protocol_info: ProtocolInfo,
         detection_time: Duration,
@@ -119,14 +240,57 @@ impl DetectionResult {
             detection_time,
             detection_method,
             detector_name,
+            completeness: full_completeness(),
+            contributing_probes: Vec::new(),
+            alternatives: Vec::new(),
         }
     }
     
+    /// 设置备选协议
+    pub fn with_alternatives(mut self, alternatives: Vec<(ProtocolType, f32)>) -> Self {
+        self.alternatives = alternatives;
+        self
+    }
+    
+    /// 设置支持该结论的探测器
+    pub fn with_contributing_probes(mut self, probes: Vec<(String, f32)>) -> Self {
+        self.contributing_probes = probes;
+        self
+    }
+    
+    /// 设置数据完整度（限制在0到1之间）
+    pub fn with_completeness(mut self, completeness: f32) -> Self {
+        self.completeness = completeness.clamp(0.0, 1.0);
+        self
+    }
+    
+    /// 按数据完整度折减后的置信度
+    ///
+    /// 只凭前缀确认的协议低于看到完整报文时的结果。
+    pub fn effective_confidence(&self) -> f32 {
+        self.confidence() * self.completeness
+    }
+    
     /// 获取协议类型
     pub fn protocol_type(&self) -> ProtocolType {
         self.protocol_info.protocol_type
     }
     
+    /// 获取自定义协议名称
+    pub fn custom_name(&self) -> Option<&str> {
+        self.protocol_info.custom_name.as_deref()
+    }
+    
+    /// 获取HTTP/1.x请求的方法、路径、主机和版本
+    pub fn http_request(&self) -> Option<HttpRequestInfo> {
+        HttpRequestInfo::from_metadata(&self.protocol_info)
+    }
+    
+    /// 获取可修改的元数据（写时复制，不影响共享同一元数据的其他克隆）
+    pub fn make_mut_metadata(&mut self) -> &mut HashMap<String, String> {
+        self.protocol_info.make_mut_metadata()
+    }
+    
     /// 获取置信度
     pub fn confidence(&self) -> f32 {
         self.protocol_info.confidence
@@ -141,10 +305,92 @@ impl DetectionResult {
     pub fn is_acceptable(&self, min_confidence: f32) -> bool {
         self.confidence() >= min_confidence
     }
+    
+    /// 对多个节点的探测结果进行置信度加权投票，得到共识结果
+    ///
+    /// 每个结果按其置信度为所属协议投票，累计权重最高的协议胜出；权重相同时依次比较票数、
+    /// 最高单票置信度，仍相同则取协议类型声明顺序靠前者，结果与输入顺序无关。
+    /// 共识结果以胜出协议中置信度最高的结果为基础，合并同协议结果的特征和元数据，
+    /// 置信度取同协议结果的平均值，并写入 `agreement`（同意票占比）、`vote_weight`
+    /// （胜出权重占比）和 `voters`（有效票数）元数据。没有有效结果时返回置信度为0的 `Unknown`。
+    pub fn combine(results: &[DetectionResult]) -> DetectionResult {
+        let voters: Vec<&DetectionResult> = results.iter()
+            .filter(|result| !result.confidence().is_nan())
+            .collect();
+        
+        // 协议 -> (累计权重, 票数, 最高单票置信度)
+        let mut tallies: BTreeMap<ProtocolType, (f32, usize, f32)> = BTreeMap::new();
+        for result in &voters {
+            let tally = tallies.entry(result.protocol_type()).or_insert((0.0, 0, 0.0));
+            tally.0 += result.confidence();
+            tally.1 += 1;
+            tally.2 = tally.2.max(result.confidence());
+        }
+        
+        // 按协议声明顺序遍历，只有严格更优时才替换，平局保留靠前的协议
+        let mut winner: Option<(ProtocolType, (f32, usize, f32))> = None;
+        for (&protocol, &tally) in &tallies {
+            let better = winner.is_none_or(|(_, best)| {
+                tally.0.total_cmp(&best.0)
+                    .then(tally.1.cmp(&best.1))
+                    .then(tally.2.total_cmp(&best.2))
+                    .is_gt()
+            });
+            if better {
+                winner = Some((protocol, tally));
+            }
+        }
+        let Some((protocol, (weight, votes, _))) = winner else {
+            return DetectionResult::new(
+                ProtocolInfo::new(ProtocolType::Unknown, 0.0),
+                Duration::ZERO,
+                DetectionMethod::Hybrid,
+                "Consensus".to_string(),
+            );
+        };
+        
+        let mut agreeing: Vec<&DetectionResult> = voters.iter()
+            .copied()
+            .filter(|result| result.protocol_type() == protocol)
+            .collect();
+        agreeing.sort_by(|a, b| {
+            b.confidence().total_cmp(&a.confidence())
+                .then_with(|| a.detector_name.cmp(&b.detector_name))
+        });
+        
+        let mut info = agreeing[0].protocol_info.clone();
+        for other in &agreeing[1..] {
+            for feature in &other.protocol_info.features {
+                if !info.has_feature(feature) {
+                    info.add_feature(feature.clone());
+                }
+            }
+            for (key, value) in other.protocol_info.metadata.iter() {
+                if !info.metadata.contains_key(key) {
+                    info.add_metadata(key.clone(), value.clone());
+                }
+            }
+        }
+        
+        let total_weight: f32 = voters.iter().map(|result| result.confidence()).sum();
+        info.confidence = (weight / votes as f32).clamp(0.0, 1.0);
+        info.add_metadata("agreement", format!("{:.3}", votes as f32 / voters.len() as f32));
+        info.add_metadata("vote_weight", format!("{:.3}", if total_weight > 0.0 { weight / total_weight } else { 0.0 }));
+        info.add_metadata("voters", voters.len().to_string());
+        
+        let method = agreeing[0].detection_method;
+        let detection_method = if agreeing.iter().all(|result| result.detection_method == method) {
+            method
+        } else {
+            DetectionMethod::Hybrid
+        };
+        let detection_time = voters.iter().map(|result| result.detection_time).max().unwrap_or_default();
+        
+        DetectionResult::new(info, detection_time, detection_method, "Consensus".to_string())
+    }
 }
 
 /// 默认协议探测器实现
-#[derive(Debug)]
 pub struct DefaultProtocolDetector {
     registry: ProbeRegistry,
     probe_config: ProbeConfig,
@@ -152,6 +398,28 @@ pub struct DefaultProtocolDetector {
     enabled_protocols: Vec<ProtocolType>,
     aggregator: ProbeAggregator,
     magic_detector: MagicDetector,
+    custom_protocols: ProtocolRegistry,
+    sink: Option<Arc<dyn DetectionSink>>,
+    profiling: bool,
+    last_timings: Mutex<Option<ProbeTimings>>,
+    cache: Option<DetectionCache>,
+}
+
+impl std::fmt::Debug for DefaultProtocolDetector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DefaultProtocolDetector")
+            .field("registry", &self.registry)
+            .field("probe_config", &self.probe_config)
+            .field("detection_config", &self.detection_config)
+            .field("enabled_protocols", &self.enabled_protocols)
+            .field("aggregator", &self.aggregator)
+            .field("magic_detector", &self.magic_detector)
+            .field("custom_protocols", &self.custom_protocols)
+            .field("has_sink", &self.sink.is_some())
+            .field("profiling", &self.profiling)
+            .field("cache", &self.cache)
+            .finish()
+    }
 }
 
 impl DefaultProtocolDetector {
@@ -182,9 +450,112 @@ impl DefaultProtocolDetector {
             enabled_protocols,
             aggregator,
             magic_detector,
+            custom_protocols: ProtocolRegistry::new(),
+            sink: None,
+            profiling: false,
+            last_timings: Mutex::new(None),
+            cache: None,
         })
     }
     
+    /// 推荐的首次读取字节数
+    ///
+    /// 取所有启用协议推荐大小的最大值，不低于 `absolute_min`，不超过最大探测大小。
+    pub fn recommended_initial_read(&self) -> usize {
+        self.enabled_protocols.iter()
+            .map(|protocol| protocol.recommended_probe_size())
+            .max()
+            .unwrap_or(0)
+            .max(self.absolute_min())
+            .min(self.detection_config.max_probe_size)
+    }
+    
+    /// 探测所需的最少字节数，少于该值时 `detect` 会直接返回错误
+    ///
+    /// 取所有启用协议最少字节数（见 [`Self::protocol_min_bytes`]）中的最小值。
+    pub fn absolute_min(&self) -> usize {
+        self.enabled_protocols.iter()
+            .map(|&protocol| self.protocol_min_bytes(protocol))
+            .min()
+            .unwrap_or(self.detection_config.min_probe_size)
+    }
+    
+    /// 接受某协议结论所需的最少字节数
+    pub fn protocol_min_bytes(&self, protocol: ProtocolType) -> usize {
+        self.probe_config.protocol_min_bytes.get(&protocol)
+            .copied()
+            .unwrap_or(self.detection_config.min_probe_size)
+    }
+    
+    /// 数据少于协议最少字节数时返回所需字节数（短升级请求除外）
+    fn missing_protocol_bytes(&self, protocol: ProtocolType, data: &[u8]) -> Option<usize> {
+        let min = self.protocol_min_bytes(protocol);
+        (data.len() < min && !crate::probe::passive::is_short_upgrade_request(data)).then_some(min)
+    }
+    
+    /// 为缓存命中的结论按本次数据重新生成请求相关的元数据
+    ///
+    /// 包括TLS ClientHello（SNI、ALPN等）、加密握手的封装格式和HTTP/1.x请求行；
+    /// 其余探测器在探测时写入的协议细节不会出现在缓存结果中。
+    fn annotate_cached(&self, info: &mut ProtocolInfo, data: &[u8]) {
+        if let Some(hello) = self.magic_detector.client_hello_metadata(data) {
+            for (key, value) in hello.metadata.iter() {
+                info.add_metadata(key, value);
+            }
+        }
+        self.aggregator.annotate_transport(info, data);
+        annotate_http1(info, data);
+    }
+    
+    /// 设置自定义协议注册表及其魔法包特征
+    pub fn with_custom_protocols(mut self, registry: ProtocolRegistry, signatures: Vec<MagicSignature>) -> Self {
+        for signature in signatures {
+            self.magic_detector.add_signature(signature);
+        }
+        self.custom_protocols = registry;
+        self
+    }
+    
+    /// 获取自定义协议注册表
+    pub fn custom_protocols(&self) -> &ProtocolRegistry {
+        &self.custom_protocols
+    }
+    
+    /// 设置探测结果输出，每次探测成功后调用
+    pub fn with_sink(mut self, sink: Arc<dyn DetectionSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+    
+    /// 开启或关闭探测耗时统计
+    ///
+    /// 开启后每次探测都会记录各探测器的耗时，可通过 `last_probe_timings` 读取。
+    pub fn with_profiling(mut self, enabled: bool) -> Self {
+        self.profiling = enabled;
+        self
+    }
+    
+    /// 设置探测结果缓存
+    ///
+    /// 命中时跳过探测流水线，直接返回缓存结果（探测方法为 `Cached`）。
+    /// 早期数据不查询缓存，带关联ID的探测结果不写入缓存。
+    pub fn with_detection_cache(mut self, cache: DetectionCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    
+    /// 获取探测结果缓存
+    pub fn detection_cache(&self) -> Option<&DetectionCache> {
+        self.cache.as_ref()
+    }
+    
+    /// 获取最近一次探测的各探测器耗时
+    ///
+    /// 未开启耗时统计时返回 `None`；并发探测时为最后完成的那一次。
+    pub fn last_probe_timings(&self) -> Option<ProbeTimings> {
+        self.last_timings.lock().unwrap().clone()
+    }
+    
     /// 获取探测配置
     pub fn probe_config(&self) -> &ProbeConfig {
         &self.probe_config
@@ -199,18 +570,634 @@ impl DefaultProtocolDetector {
     pub fn enabled_protocols(&self) -> &[ProtocolType] {
         &self.enabled_protocols
     }
+    
+    /// 获取魔法包检测器（其协议过滤器与启用的协议列表一致）
+    pub fn magic_detector(&self) -> &MagicDetector {
+        &self.magic_detector
+    }
+    
+    /// 执行单个探测器并收集结果
+    ///
+    /// 返回是否得到高置信度（>= 0.9）的启用协议结果。
+    fn run_probe(
+        &self,
+        probe: &dyn ProtocolProbe,
+        data: &[u8],
+        context: &mut ProbeContext,
+        all_results: &mut Vec<(String, ProtocolInfo)>,
+        requested_bytes: &mut usize,
+    ) -> bool {
+        let method = probe.detection_method();
+        let candidates_before = context.candidates.len();
+        let outcome = probe.probe(data, context);
+        
+        // 探测器写入上下文的候选结果同样按其方法钳制
+        for candidate in context.candidates.iter_mut().skip(candidates_before) {
+            self.aggregator.clamp_confidence(method, candidate);
+        }
+        
+        match outcome {
+            Ok(Some(mut protocol_info)) => {
+                self.aggregator.clamp_confidence(method, &mut protocol_info);
+                
+                // 只接受启用协议的结果
+                if self.enabled_protocols.contains(&protocol_info.protocol_type) {
+                    // 数据不足该协议的最少字节数时等待更多数据
+                    if let Some(min) = self.missing_protocol_bytes(protocol_info.protocol_type, data) {
+                        *requested_bytes = (*requested_bytes).max(min);
+                        return false;
+                    }
+                    let high_confidence = protocol_info.confidence >= 0.9;
+                    all_results.push((probe.name().to_string(), protocol_info));
+                    return high_confidence;
+                }
+            }
+            Ok(None) => {
+                // 探测器没有检测到协议，继续
+            }
+            Err(DetectorError::NeedMoreData(needed)) => {
+                *requested_bytes = (*requested_bytes).max(needed);
+            }
+            Err(_) => {
+                // 静默忽略错误，避免性能开销
+            }
+        }
+        
+        false
+    }
+    
+    /// 超过硬性截止时间时返回超时错误
+    fn check_hard_deadline(&self, hard_deadline: Option<Instant>) -> Result<()> {
+        match (hard_deadline, self.detection_config.hard_deadline) {
+            (Some(at), Some(limit)) if Instant::now() >= at => {
+                Err(DetectorError::timeout(limit.as_millis() as u64))
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// 探测十六进制文本表示的数据
+    ///
+    /// 忽略空白和 `:` 分隔符，便于直接粘贴Wireshark复制的报文。
+    pub fn detect_hex(&self, hex: &str) -> Result<DetectionResult> {
+        let data = crate::utils::encoding::decode_hex(hex)?;
+        self.detect(&data)
+    }
+    
+    /// 探测Base64文本表示的数据
+    pub fn detect_base64(&self, b64: &str) -> Result<DetectionResult> {
+        let data = crate::utils::encoding::decode_base64(b64)?;
+        self.detect(&data)
+    }
+    
+    /// 对分散在多个缓冲区中的数据进行协议探测
+    ///
+    /// 各分片按顺序视为同一数据流。魔数前缀窗口完全落在首个分片内时直接借用，
+    /// 跨越分片边界时只复制窗口内的字节；前缀即可高置信度确定协议时不再拼接整段数据。
+    /// HTTP/1.x、WebSocket和HTTP/2的结果需要扫描完整头部或帧序列，仍会拼接后再探测。
+    pub fn detect_vectored(&self, slices: &[&[u8]]) -> Result<DetectionResult> {
+        let mut non_empty = slices.iter().copied().filter(|slice| !slice.is_empty());
+        let first = non_empty.next().unwrap_or_default();
+        if non_empty.next().is_none() {
+            return self.detect(first);
+        }
+        
+        let total: usize = slices.iter().map(|slice| slice.len()).sum();
+        if total > self.max_probe_size() {
+            return Err(DetectorError::DataTooLarge(
+                format!("数据大小 {} 字节超过最大限制 {} 字节", total, self.max_probe_size())
+            ));
+        }
+        
+        // 前缀结论只在魔法包阶段最先运行且可以短路时成立
+        let magic_first = self.probe_config.pipeline.first() == Some(&DetectionStage::Magic);
+        if total >= self.min_probe_size() && magic_first && self.probe_config.match_mode != MatchMode::BestMatch {
+            let window = self.magic_detector.prefix_window().max(self.min_probe_size()).min(total);
+            let boundary;
+            let prefix = match first.get(..window) {
+                Some(prefix) => prefix,
+                None => {
+                    boundary = gather_prefix(slices, window);
+                    &boundary
+                }
+            };
+            
+            let prefix_only = self.magic_detector.quick_detect(prefix).is_some_and(|mut info| {
+                self.aggregator.clamp_confidence(DetectionMethod::SimdAccelerated, &mut info);
+                info.confidence >= 0.95
+                    && self.magic_detector.is_prefix_decidable(info.protocol_type)
+                    && !matches!(
+                        info.protocol_type,
+                        ProtocolType::HTTP1_0 | ProtocolType::HTTP1_1 | ProtocolType::WebSocket | ProtocolType::HTTP2
+                    )
+            });
+            if prefix_only {
+                return self.detect(prefix);
+            }
+        }
+        
+        self.detect(&slices.concat())
+    }
+    
+    /// 只判断前缀所属的协议，供检测后原样转发的多路复用服务使用
+    ///
+    /// 命中魔法包特征时不构造 `DetectionResult`，`bytes_examined` 为做出判断所需的最少字节数；
+    /// 否则退回完整探测，此时整个缓冲区都参与了判断。无法识别时协议为 `Unknown`、置信度为0。
+    /// 该方法不消费任何数据，原始字节应全部转发给后端。
+    pub fn classify_prefix(&self, data: &[u8]) -> ClassifyOutcome {
+        if let Some(signature) = self.magic_detector.match_signature(data) {
+            let bytes_examined = signature.matched_len(data).unwrap_or(data.len());
+            let bounds = self.probe_config.confidence_bounds.get(&DetectionMethod::SimdAccelerated);
+            let confidence = match bounds {
+                Some(&(floor, ceil)) => signature.confidence.clamp(floor, ceil),
+                None => signature.confidence,
+            };
+            if confidence >= self.detection_config.min_confidence {
+                return ClassifyOutcome {
+                    protocol: signature.protocol,
+                    confidence,
+                    bytes_examined,
+                };
+            }
+        }
+        
+        match self.detect(data) {
+            Ok(result) => ClassifyOutcome {
+                protocol: result.protocol_type(),
+                confidence: result.confidence(),
+                bytes_examined: data.len(),
+            },
+            Err(_) => ClassifyOutcome {
+                protocol: ProtocolType::Unknown,
+                confidence: 0.0,
+                bytes_examined: data.len(),
+            },
+        }
+    }
+    
+    /// 探测协议并规划到目标协议的升级路径
+    ///
+    /// 升级路径由 `manager` 中已注册的升级器决定，无法到达目标协议时为 `None`。
+    pub fn detect_and_plan_upgrade(
+        &self,
+        data: &[u8],
+        desired: ProtocolType,
+        manager: &crate::upgrade::UpgradeManager,
+    ) -> Result<(DetectionResult, Option<Vec<UpgradePath>>)> {
+        let result = self.detect(data)?;
+        let plan = manager.plan_upgrade(result.protocol_type(), desired);
+        Ok((result, plan))
+    }
+    
+    /// 计算各探测来源给出的置信度
+    ///
+    /// 与 `detect` 不同，这里会运行所有启用协议的探测器且不提前退出、不做阈值过滤，
+    /// 用于调试和分析分类结果。
+    pub fn confidence_map(&self, data: &[u8]) -> ConfidenceMap {
+        let mut map = ConfidenceMap::default();
+        let mut context = ProbeContext::new();
+        context.bytes_read = data.len();
+        context.enabled_protocols = Some(self.enabled_protocols.clone());
+        
+        for info in self.magic_detector.deep_detect(data) {
+            map.record("MagicBytesDetector", info);
+        }
+        
+        let mut probes = Vec::new();
+        for &protocol in &self.enabled_protocols {
+            probes.extend(self.registry.get_probes_for_enabled_protocol(protocol, &self.enabled_protocols));
+        }
+        if self.probe_config.run_global_probes {
+            probes.extend(self.registry.global_probes());
+        }
+        
+        let mut processed_probes = std::collections::HashSet::new();
+        for probe in probes {
+            let probe_name = probe.name();
+            if !processed_probes.insert(probe_name) || probe.needs_more_data(data) {
+                continue;
+            }
+            
+            if let Ok(Some(info)) = probe.probe(data, &mut context) {
+                if self.enabled_protocols.contains(&info.protocol_type) {
+                    map.record(probe_name, info);
+                }
+            }
+        }
+        
+        map
+    }
+    
+    /// 结合双向数据进行协议探测
+    ///
+    /// 分别收集客户端→服务端和服务端→客户端两个方向的置信度，
+    /// 两个方向都支持同一协议时按 `1 - (1 - a)(1 - b)` 合并，因此单向都不足阈值的证据也能相互印证。
+    /// 结果元数据 `bidirectional` 为 `agree`（双向一致）、`c2s` 或 `s2c`（仅单向支持）。
+    pub fn detect_bidirectional(&self, c2s: &[u8], s2c: &[u8]) -> Result<DetectionResult> {
+        let start_time = Instant::now();
+        
+        if c2s.is_empty() && s2c.is_empty() {
+            return Err(DetectorError::InsufficientData("双向数据均为空".to_string()));
+        }
+        
+        for data in [c2s, s2c] {
+            if data.len() > self.max_probe_size() {
+                return Err(DetectorError::DataTooLarge(
+                    format!("数据大小 {} 字节超过最大限制 {} 字节", data.len(), self.max_probe_size())
+                ));
+            }
+        }
+        
+        let forward = self.confidence_map(c2s).protocol_confidences();
+        let backward = self.confidence_map(s2c).protocol_confidences();
+        
+        let mut best: Option<(ProtocolType, f32, &'static str)> = None;
+        for protocol in forward.keys().chain(backward.keys()) {
+            let (confidence, source) = match (forward.get(protocol), backward.get(protocol)) {
+                (Some(&a), Some(&b)) => (1.0 - (1.0 - a) * (1.0 - b), "agree"),
+                (Some(&a), None) => (a, "c2s"),
+                (None, Some(&b)) => (b, "s2c"),
+                (None, None) => continue,
+            };
+            if best.is_none_or(|(_, best_confidence, _)| confidence > best_confidence) {
+                best = Some((*protocol, confidence, source));
+            }
+        }
+        
+        let (protocol, confidence, source) = best
+            .ok_or_else(|| DetectorError::NoProtocolDetected("双向数据均未检测到协议".to_string()))?;
+        
+        let mut info = ProtocolInfo::new(protocol, confidence.min(1.0));
+        info.add_metadata("bidirectional", source);
+        for (key, confidences) in [("c2s_confidence", &forward), ("s2c_confidence", &backward)] {
+            if let Some(confidence) = confidences.get(&protocol) {
+                info.add_metadata(key, format!("{:.3}", confidence));
+            }
+        }
+        
+        let mut info = self.aggregator.aggregate(vec![info])
+            .ok_or_else(|| DetectorError::NoProtocolDetected("双向合并后的置信度仍低于阈值".to_string()))?;
+        self.aggregator.annotate_transport(&mut info, if source == "s2c" { s2c } else { c2s });
+        
+        let result = self.aggregator.create_result(
+            info,
+            start_time.elapsed(),
+            "DefaultProtocolDetector".to_string(),
+        );
+        if let Some(ref sink) = self.sink {
+            sink.emit(&result);
+        }
+        
+        Ok(result)
+    }
 }
 
-impl ProtocolDetector for DefaultProtocolDetector {
-    fn detect(&self, data: &[u8]) -> Result<DetectionResult> {
+/// 前缀分类结果（见 [`DefaultProtocolDetector::classify_prefix`]）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassifyOutcome {
+    /// 协议类型
+    pub protocol: ProtocolType,
+    /// 置信度
+    pub confidence: f32,
+    /// 做出判断检查的字节数
+    pub bytes_examined: usize,
+}
+
+/// 增量探测的进度（见 [`ProtocolDetector::detect_incremental`]）
+#[derive(Debug, Clone)]
+pub enum DetectionProgress {
+    /// 至少还需要 `min_additional` 字节才能继续判断
+    NeedMore {
+        /// 最少需要追加的字节数
+        min_additional: usize,
+    },
+    /// 已识别出协议
+    Detected(DetectionResult),
+    /// 已达到探测上限仍无法识别
+    Failed,
+}
+
+impl DetectionProgress {
+    /// 是否已得出结论（识别成功或失败）
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, DetectionProgress::NeedMore { .. })
+    }
+}
+
+/// 增量探测状态
+///
+/// 累积调用方按读取顺序传入的数据，最多保留探测器的 `max_probe_size` 字节。
+/// 得出结论后缓存结果，后续调用直接返回，不再追加数据或重复探测。
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalState {
+    buffer: Vec<u8>,
+    min_confidence: f32,
+    outcome: Option<DetectionProgress>,
+}
+
+impl IncrementalState {
+    /// 创建空的增量探测状态
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 设置结论所需的最低置信度，低于该值的结果视为尚未识别
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence.clamp(0.0, 1.0);
+        self
+    }
+    
+    /// 已累积的数据
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+    
+    /// 是否已得出结论
+    pub fn is_finished(&self) -> bool {
+        self.outcome.is_some()
+    }
+    
+    /// 清空数据和结论，用于复用到新的连接
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.outcome = None;
+    }
+    
+    /// 取出已累积的数据，便于转发给后续处理器
+    pub fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+    
+    /// 记录结论并返回
+    fn finish(&mut self, progress: DetectionProgress) -> DetectionProgress {
+        self.outcome = Some(progress.clone());
+        progress
+    }
+}
+
+/// 单次探测的调用上下文
+///
+/// 用于把探测接入已有请求ID和截止时间的处理管线。
+#[derive(Debug, Clone, Default)]
+pub struct DetectionContext {
+    /// 关联ID，会写入探测结果的 `correlation_id` 元数据
+    pub correlation_id: Option<String>,
+    /// 截止时间，设置后优先于配置中的超时时间
+    pub deadline: Option<Instant>,
+    /// 数据是否为早期数据（TCP Fast Open 的SYN载荷或QUIC 0-RTT）
+    ///
+    /// 早期数据可能被重放或不完整，探测时会降低置信度。
+    pub early_data: bool,
+}
+
+impl DetectionContext {
+    /// 创建空的调用上下文
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 设置关联ID
+    pub fn with_correlation_id<S: Into<String>>(mut self, correlation_id: S) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+    
+    /// 设置截止时间
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    
+    /// 标记数据是否为早期数据
+    pub fn with_early_data(mut self, early_data: bool) -> Self {
+        self.early_data = early_data;
+        self
+    }
+    
+    /// 早期数据时折减置信度
+    fn adjust_confidence(&self, info: &mut ProtocolInfo) {
+        info.confidence = self.adjusted_confidence(info.confidence);
+    }
+    
+    /// 计算折减后的置信度，不修改原结果
+    fn adjusted_confidence(&self, confidence: f32) -> f32 {
+        if self.early_data {
+            confidence * EARLY_DATA_CONFIDENCE_FACTOR
+        } else {
+            confidence
+        }
+    }
+    
+    /// 把关联ID和早期数据标记写入协议信息元数据
+    fn annotate(&self, mut info: ProtocolInfo) -> ProtocolInfo {
+        if let Some(ref correlation_id) = self.correlation_id {
+            info.add_metadata("correlation_id", correlation_id.as_str());
+        }
+        if self.early_data {
+            info.add_metadata("early_data", "true");
+        }
+        info
+    }
+}
+
+/// 置信度图
+///
+/// 记录每个探测来源（探测器名称）报告的协议结果。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfidenceMap {
+    /// 探测来源名称 -> 该来源报告的协议结果
+    pub probes: BTreeMap<String, Vec<ProtocolInfo>>,
+}
+
+impl ConfidenceMap {
+    /// 记录某个探测来源的结果
+    pub fn record(&mut self, probe_name: &str, info: ProtocolInfo) {
+        self.probes.entry(probe_name.to_string()).or_default().push(info);
+    }
+    
+    /// 获取某个探测来源对指定协议给出的最高置信度
+    pub fn get(&self, probe_name: &str, protocol: ProtocolType) -> Option<f32> {
+        self.probes.get(probe_name)?
+            .iter()
+            .filter(|info| info.protocol_type == protocol)
+            .map(|info| info.confidence)
+            .reduce(f32::max)
+    }
+    
+    /// 按协议汇总，取所有探测来源中的最高置信度
+    pub fn protocol_confidences(&self) -> BTreeMap<ProtocolType, f32> {
+        let mut confidences = BTreeMap::new();
+        for info in self.probes.values().flatten() {
+            let entry = confidences.entry(info.protocol_type).or_insert(0.0f32);
+            *entry = entry.max(info.confidence);
+        }
+        confidences
+    }
+}
+
+/// 单次探测的耗时统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbeTimings {
+    /// 探测器名称 -> 累计耗时（只包含实际运行的探测器）
+    pub probes: BTreeMap<String, Duration>,
+    /// 整次探测耗时
+    pub total: Duration,
+}
+
+impl ProbeTimings {
+    /// 累加某个探测器的耗时
+    pub fn record(&mut self, probe_name: &str, elapsed: Duration) {
+        *self.probes.entry(probe_name.to_string()).or_default() += elapsed;
+    }
+    
+    /// 获取某个探测器的耗时
+    pub fn get(&self, probe_name: &str) -> Option<Duration> {
+        self.probes.get(probe_name).copied()
+    }
+    
+    /// 所有探测器耗时之和
+    pub fn probes_total(&self) -> Duration {
+        self.probes.values().sum()
+    }
+    
+    /// 耗时最长的探测器
+    pub fn slowest(&self) -> Option<(&str, Duration)> {
+        self.probes.iter()
+            .max_by_key(|(_, elapsed)| **elapsed)
+            .map(|(name, elapsed)| (name.as_str(), *elapsed))
+    }
+}
+
+/// 为HTTP/1.x结果写入请求行、`upgrade_to`（`Upgrade` 头中的目标协议列表）和临时响应相关元数据
+fn annotate_http1(info: &mut ProtocolInfo, data: &[u8]) {
+    if !matches!(
+        info.protocol_type,
+        ProtocolType::HTTP1_0 | ProtocolType::HTTP1_1 | ProtocolType::WebSocket
+    ) {
+        return;
+    }
+    
+    if let Ok(request) = HttpRequestInfo::parse(data) {
+        request.annotate(info);
+    }
+    if let Some(intent) = crate::upgrade::detect_http_upgrade(data) {
+        info.add_metadata("upgrade_to", intent.targets_value());
+    }
+    crate::utils::http::annotate_interim(info, data);
+}
+
+/// 筛选结论为 `protocol` 的探测器，每个探测器保留最高置信度，按置信度降序排列
+fn contributing_probes(sources: Vec<(String, ProtocolType, f32)>, protocol: ProtocolType) -> Vec<(String, f32)> {
+    let mut best: BTreeMap<String, f32> = BTreeMap::new();
+    for (name, _, confidence) in sources.into_iter().filter(|(_, p, c)| *p == protocol && !c.is_nan()) {
+        let entry = best.entry(name).or_insert(confidence);
+        *entry = entry.max(confidence);
+    }
+    let mut probes: Vec<(String, f32)> = best.into_iter().collect();
+    probes.sort_by(|a, b| b.1.total_cmp(&a.1));
+    probes
+}
+
+/// 从多个分片中复制前 `len` 个字节
+fn gather_prefix(slices: &[&[u8]], len: usize) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(len);
+    for slice in slices {
+        let take = (len - prefix.len()).min(slice.len());
+        prefix.extend_from_slice(&slice[..take]);
+        if prefix.len() == len {
+            break;
+        }
+    }
+    prefix
+}
+
+/// 开启耗时统计时记录闭包的执行时间
+fn timed<T>(timings: &mut Option<ProbeTimings>, probe_name: &str, f: impl FnOnce() -> T) -> T {
+    match timings {
+        Some(timings) => {
+            let started = Instant::now();
+            let output = f();
+            timings.record(probe_name, started.elapsed());
+            output
+        }
+        None => f(),
+    }
+}
+
+impl DefaultProtocolDetector {
+    /// 携带调用上下文进行协议探测
+    ///
+    /// 关联ID会写入探测日志和结果元数据；截止时间优先于配置中的超时时间。
+    pub fn detect_with_context(&self, data: &[u8], ctx: &DetectionContext) -> Result<DetectionResult> {
+        let started = Instant::now();
+        let mut timings = self.profiling.then(ProbeTimings::default);
+        let cache = self.cache.as_ref().filter(|_| !ctx.early_data);
+        let cached = cache.and_then(|cache| cache.get(data)).map(|mut detection| {
+            detection.detection_time = started.elapsed();
+            self.annotate_cached(&mut detection.protocol_info, data);
+            detection.protocol_info = ctx.annotate(detection.protocol_info);
+            detection
+        });
+        let result = match cached {
+            Some(detection) => Ok(detection),
+            None => self.detect_inner(data, ctx, &mut timings),
+        }
+        .map(|detection| {
+            let completeness = MessageFraming::of(detection.protocol_type(), data).completeness(data.len());
+            detection.with_completeness(completeness)
+        });
+        
+        if let (Some(cache), Ok(detection)) = (cache, &result) {
+            if detection.detection_method != DetectionMethod::Cached && ctx.correlation_id.is_none() {
+                cache.insert(data, detection);
+            }
+        }
+        
+        if let Some(mut timings) = timings {
+            timings.total = started.elapsed();
+            *self.last_timings.lock().unwrap() = Some(timings);
+        }
+        
+        if let (Ok(ref detection), Some(ref sink)) = (&result, &self.sink) {
+            sink.emit(detection);
+        }
+        
+        result
+    }
+    
+    fn detect_inner(
+        &self,
+        data: &[u8],
+        ctx: &DetectionContext,
+        timings: &mut Option<ProbeTimings>,
+    ) -> Result<DetectionResult> {
         let start_time = Instant::now();
+        let hard_deadline = self.detection_config.hard_deadline.map(|limit| start_time + limit);
+        let mut deadline = ctx.deadline.unwrap_or(start_time + self.detection_config.timeout);
+        if let Some(hard_deadline) = hard_deadline {
+            deadline = deadline.min(hard_deadline);
+        }
+        
         let mut context = ProbeContext::new();
         context.bytes_read = data.len();
+        context.correlation_id = ctx.correlation_id.clone();
+        context.deadline = Some(deadline);
+        context.enabled_protocols = Some(self.enabled_protocols.clone());
         
-        // 检查数据大小
-        if data.len() < self.min_probe_size() {
+        if let Some(ref correlation_id) = ctx.correlation_id {
+            crate::psi_debug!("开始协议探测 correlation_id={} bytes={}", correlation_id, data.len());
+        }
+        
+        // 截止时间已过，不再探测
+        if start_time >= deadline {
+            return Err(DetectorError::timeout(0));
+        }
+        
+        // 检查数据大小（PostgreSQL SSLRequest、STARTTLS命令行等短升级请求除外）；
+        // 各协议的最少字节数在接受探测结论时再逐一检查
+        if data.len() < self.absolute_min() && !crate::probe::passive::is_short_upgrade_request(data) {
             return Err(DetectorError::InsufficientData(
-                format!("需要至少 {} 字节，但只有 {} 字节", self.min_probe_size(), data.len())
+                format!("需要至少 {} 字节，但只有 {} 字节", self.absolute_min(), data.len())
             ));
         }
         
@@ -220,117 +1207,202 @@ impl ProtocolDetector for DefaultProtocolDetector {
             ));
         }
         
-        // 🚀 第一阶段：超快速魔法包检测（前几个字节启发式判断）
-        if let Some(magic_result) = self.magic_detector.quick_detect(data) {
-            // 如果魔法包检测置信度很高，直接返回结果
-            if magic_result.confidence >= 0.95 {
-                let detection_time = start_time.elapsed();
-                return Ok(DetectionResult::new(
-                    magic_result,
-                    detection_time,
-                    DetectionMethod::SimdAccelerated, // 魔法包检测视为SIMD加速
-                    "MagicBytesDetector".to_string(),
-                ));
-            }
-            
-            // 中等置信度的魔法包结果作为候选
-            context.add_candidate(magic_result);
-        }
+        // 最佳匹配模式下运行所有探测器，不提前结束
+        let best_match = self.probe_config.match_mode == MatchMode::BestMatch;
         
         // 预分配结果容器以减少内存重分配
         let mut all_results = Vec::with_capacity(self.enabled_protocols.len());
         
-        // 缓存超时时间以避免重复访问
-        let max_detection_time = self.detection_config.timeout;
-        
         // 优化探测器循环：避免重复探测，快速失败策略
         let mut processed_probes = std::collections::HashSet::new();
         
-        // 🎯 严格协议过滤：只运行启用协议的探测器（核心性能优化）
-        for &protocol in &self.enabled_protocols {
-            // 快速超时检查
-            if start_time.elapsed() > max_detection_time {
+        // 已运行探测器的优先级，用于最佳匹配模式下的平局裁决
+        let mut priorities: HashMap<&'static str, u8> = HashMap::new();
+        
+        // 记录探测器请求的最大额外数据量
+        let mut requested_bytes = 0usize;
+        
+        // 按配置的流水线顺序执行各阶段，高置信度结果跳过后续阶段
+        'pipeline: for &stage in &self.probe_config.pipeline {
+            self.check_hard_deadline(hard_deadline)?;
+            if Instant::now() > deadline {
                 break;
             }
             
-            let probes = self.registry.get_probes_for_enabled_protocol(protocol, &self.enabled_protocols);
+            if stage == DetectionStage::Magic {
+                // 🚀 超快速魔法包检测（前几个字节启发式判断）
+                let quick_result = timed(timings, "MagicBytesDetector", || self.magic_detector.quick_detect(data));
+                let missing = quick_result.as_ref()
+                    .filter(|info| self.enabled_protocols.contains(&info.protocol_type))
+                    .and_then(|info| self.missing_protocol_bytes(info.protocol_type, data));
+                if let Some(min) = missing {
+                    // 数据不足该协议的最少字节数，等待更多数据
+                    requested_bytes = requested_bytes.max(min);
+                } else if let Some(mut magic_result) = quick_result {
+                    self.aggregator.clamp_confidence(DetectionMethod::SimdAccelerated, &mut magic_result);
+                    
+                    // 如果魔法包检测置信度很高，直接返回结果（最佳匹配模式下仅作为候选）
+                    if !best_match && ctx.adjusted_confidence(magic_result.confidence) >= 0.95 {
+                        ctx.adjust_confidence(&mut magic_result);
+                        self.aggregator.annotate_transport(&mut magic_result, data);
+                        annotate_http1(&mut magic_result, data);
+                        let detection_time = start_time.elapsed();
+                        let contributing = vec![("MagicBytesDetector".to_string(), magic_result.confidence)];
+                        return Ok(DetectionResult::new(
+                            ctx.annotate(magic_result),
+                            detection_time,
+                            DetectionMethod::SimdAccelerated, // 魔法包检测视为SIMD加速
+                            "MagicBytesDetector".to_string(),
+                        ).with_contributing_probes(contributing));
+                    }
+                    
+                    // 中等置信度的魔法包结果作为候选（早期数据折减在聚合前统一进行）
+                    context.add_candidate(magic_result);
+                }
+                continue;
+            }
+            
+            // 🎯 严格协议过滤：只运行启用协议的探测器（核心性能优化）；
+            // 全局探测器补充扫描只覆盖上面未运行的探测器，配置关闭时跳过
+            let global_probes = if self.probe_config.run_global_probes {
+                self.registry.global_probes()
+            } else {
+                Vec::new()
+            };
+            let probes = self.enabled_protocols.iter()
+                .flat_map(|&protocol| self.registry.get_probes_for_enabled_protocol(protocol, &self.enabled_protocols))
+                .chain(global_probes)
+                .filter(|probe| DetectionStage::for_method(probe.detection_method()) == stage);
+            
             for probe in probes {
                 let probe_name = probe.name();
                 
                 // 避免重复运行同一探测器
-                if processed_probes.contains(probe_name) {
+                if !processed_probes.insert(probe_name) {
                     continue;
                 }
-                processed_probes.insert(probe_name);
+                priorities.insert(probe_name, probe.priority());
                 
                 // 检查是否需要更多数据（快速失败）
                 if probe.needs_more_data(data) {
                     continue;
                 }
                 
-                // 执行探测
-                match probe.probe(data, &mut context) {
-                    Ok(Some(protocol_info)) => {
-                        // 只接受启用协议的结果
-                        if self.enabled_protocols.contains(&protocol_info.protocol_type) {
-                            let high_confidence = protocol_info.confidence >= 0.9;
-                            all_results.push(protocol_info);
-                            
-                            // 如果找到高置信度结果，可以提前结束
-                            if high_confidence {
-                                break;
-                            }
-                        }
-                    }
-                    Ok(None) => {
-                        // 探测器没有检测到协议，继续
-                    }
-                    Err(_) => {
-                        // 静默忽略错误，避免性能开销
-                    }
+                // 执行探测，找到高置信度结果时提前结束
+                let high_confidence = timed(timings, probe_name, || {
+                    self.run_probe(probe, data, &mut context, &mut all_results, &mut requested_bytes)
+                });
+                // 单个探测器可能耗时过长，硬性截止时间在每个探测器之后检查
+                self.check_hard_deadline(hard_deadline)?;
+                if high_confidence && !best_match {
+                    break 'pipeline;
                 }
                 
                 // 每5个探测器检查一次超时
-                if processed_probes.len() % 5 == 0 && start_time.elapsed() > max_detection_time {
-                    break;
+                if processed_probes.len() % 5 == 0 && Instant::now() > deadline {
+                    break 'pipeline;
                 }
             }
         }
         
-        // 🚨 性能优化：不再运行全局探测器，严格按配置执行
-        // 如果没有找到结果但有启用的协议，说明可能是不匹配的流量
-        // 在严格模式下，这种流量应该被拒绝而不是继续探测
+        // 探测器通过上下文报告的截断报文
+        if context.requested_bytes <= self.max_probe_size() {
+            requested_bytes = requested_bytes.max(context.requested_bytes);
+        }
         
-        // 🔍 第三阶段：如果没有找到结果，尝试深度魔法包检测
-        if all_results.is_empty() {
-            let deep_magic_results = self.magic_detector.deep_detect(data);
-            all_results.extend(deep_magic_results);
+        // 🔍 如果没有找到结果（或最佳匹配模式），尝试深度魔法包检测
+        let magic_enabled = self.probe_config.pipeline.contains(&DetectionStage::Magic);
+        if magic_enabled && (all_results.is_empty() || best_match) {
+            let deep_magic_results = timed(timings, "MagicBytesDetector", || self.magic_detector.deep_detect(data));
+            all_results.extend(
+                deep_magic_results.into_iter()
+                    .filter(|info| self.missing_protocol_bytes(info.protocol_type, data).is_none())
+                    .map(|mut info| {
+                        self.aggregator.clamp_confidence(DetectionMethod::SimdAccelerated, &mut info);
+                        ("MagicBytesDetector".to_string(), info)
+                    })
+            );
         }
         
-        // 合并魔法包候选结果
-        all_results.extend(context.candidates.clone());
+        self.check_hard_deadline(hard_deadline)?;
+        
+        // 合并魔法包候选结果（探测器写入的候选可能属于未启用的协议，需再次过滤）
+        all_results.extend(
+            context.candidates.iter()
+                .filter(|info| self.enabled_protocols.contains(&info.protocol_type))
+                .filter(|info| self.missing_protocol_bytes(info.protocol_type, data).is_none())
+                .cloned()
+                .map(|info| ("MagicBytesDetector".to_string(), info))
+        );
+        
+        // 记录各探测器的结论，用于说明最终结果的来源
+        let sources: Vec<(String, ProtocolType, f32)> = all_results.iter()
+            .map(|(name, info)| (name.clone(), info.protocol_type, info.confidence))
+            .collect();
+        
+        // 去重并限制候选数量，控制聚合开销
+        let mut candidates = self.aggregator.rank_candidates(all_results, |name| {
+            priorities.get(name).copied().unwrap_or(0)
+        });
+        
+        // 早期数据在阈值判断前折减置信度
+        for candidate in &mut candidates {
+            ctx.adjust_confidence(candidate);
+            
+            // 候选协议的首个报文被截断时同样计入所需数据量，超过探测上限的不再等待
+            if let Some(needed) = MessageFraming::of(candidate.protocol_type, data).needed() {
+                if needed <= self.max_probe_size() {
+                    requested_bytes = requested_bytes.max(needed);
+                }
+            }
+        }
         
         // 聚合结果
-        let best_result = self.aggregator.aggregate(all_results)
-            .ok_or_else(|| DetectorError::NoProtocolDetected("未检测到任何协议".to_string()))?;
+        let mut best_result = match self.aggregator.aggregate(candidates.clone()) {
+            Some(result) => result,
+            // 没有协议达到阈值，但有探测器需要更多数据时，告知调用方所需的最大数据量
+            None if requested_bytes > 0 => return Err(DetectorError::NeedMoreData(requested_bytes)),
+            None => return Err(DetectorError::NoProtocolDetected("未检测到任何协议".to_string())),
+        };
+        if let Some(candidates) = self.aggregator.ambiguous_candidates(&candidates) {
+            return Err(DetectorError::Ambiguous { candidates });
+        }
+        let alternatives = self.aggregator.alternatives(&candidates, best_result.protocol_type);
+        
+        // 区分QUIC与TCP上的TLS
+        self.aggregator.annotate_transport(&mut best_result, data);
+        annotate_http1(&mut best_result, data);
+        
+        let contributing = contributing_probes(sources, best_result.protocol_type);
         
         // 创建最终结果
         let detection_time = start_time.elapsed();
         Ok(self.aggregator.create_result(
-            best_result,
+            ctx.annotate(best_result),
             detection_time,
             "DefaultProtocolDetector".to_string(),
-        ))
+        ).with_contributing_probes(contributing).with_alternatives(alternatives))
+    }
+    
+}
+
+impl ProtocolDetector for DefaultProtocolDetector {
+    fn detect(&self, data: &[u8]) -> Result<DetectionResult> {
+        self.detect_with_context(data, &DetectionContext::default())
     }
     
     fn min_probe_size(&self) -> usize {
-        self.detection_config.min_probe_size
+        self.absolute_min()
     }
     
     fn max_probe_size(&self) -> usize {
         self.detection_config.max_probe_size
     }
     
+    fn timeout(&self) -> Duration {
+        self.detection_config.timeout
+    }
+    
     fn supported_protocols(&self) -> Vec<ProtocolType> {
         self.enabled_protocols.clone()
     }
@@ -338,6 +1410,10 @@ impl ProtocolDetector for DefaultProtocolDetector {
     fn name(&self) -> &str {
         "DefaultProtocolDetector"
     }
+    
+    fn cache_counters(&self) -> Option<(u64, u64)> {
+        self.cache.as_ref().map(|cache| (cache.hits(), cache.misses()))
+    }
 }
 
 /// 探测方法
@@ -353,6 +1429,8 @@ pub enum DetectionMethod {
     SimdAccelerated,
     /// 混合探测
     Hybrid,
+    /// 来自探测结果缓存（见 [`crate::core::cache::DetectionCache`]）
+    Cached,
 }
 
 /// 协议代理trait - 统一的双向框架接口
@@ -360,6 +1438,18 @@ pub trait ProtocolAgent: Send + Sync + std::fmt::Debug {
     /// 探测协议类型（被动模式 - 服务器角色）
     fn detect(&self, data: &[u8]) -> Result<DetectionResult>;
     
+    /// 预览传输层中的前 `peek_size` 字节进行探测，不消费数据
+    ///
+    /// 依赖 `Transport::peek` 不移动读取位置的约定：探测结束后这些字节仍留在传输层中，
+    /// 由升级后的协议处理器通过 `read` 完整读取。
+    fn detect_via_peek(&self, transport: &dyn Transport, peek_size: usize) -> Result<DetectionResult> {
+        let data = transport.peek(peek_size)?;
+        if data.is_empty() {
+            return Err(DetectorError::InsufficientData("传输层没有可预览的数据".to_string()));
+        }
+        self.detect(&data[..data.len().min(peek_size)])
+    }
+    
     /// 主动探测协议能力（主动模式 - 客户端角色）
     fn probe_capabilities(&self, transport: &mut dyn Transport) -> Result<Vec<ProtocolType>> {
         match self.role() {
@@ -567,6 +1657,8 @@ pub trait Transport: Send + Sync {
     fn write(&mut self, data: &[u8]) -> Result<usize>;
     
     /// 预览数据（不消费）
+    ///
+    /// 实现必须保证返回的字节之后仍能被 `read` 读到；可用数据不足 `size` 时返回已有部分。
     fn peek(&self, size: usize) -> Result<Vec<u8>>;
     
     /// 关闭连接
@@ -596,12 +1688,22 @@ pub trait ProtocolDetector: Send + Sync + std::fmt::Debug {
         4096 // 默认4KB
     }
     
+    /// 单次探测（包括等待数据）的超时时间
+    fn timeout(&self) -> Duration {
+        DetectionConfig::default().timeout
+    }
+    
     /// 支持的协议类型
     fn supported_protocols(&self) -> Vec<ProtocolType>;
     
     /// 探测器名称
     fn name(&self) -> &str;
     
+    /// 探测结果缓存的（命中, 未命中）次数，未启用缓存时返回 `None`
+    fn cache_counters(&self) -> Option<(u64, u64)> {
+        None
+    }
+    
     /// 检查是否可以探测指定协议
     fn can_detect(&self, protocol: ProtocolType) -> bool {
         self.supported_protocols().contains(&protocol)
@@ -614,6 +1716,41 @@ pub trait ProtocolDetector: Send + Sync + std::fmt::Debug {
             .map(|chunk| self.detect(chunk))
             .collect()
     }
+    
+    /// 增量探测：追加一段数据并尝试得出结论
+    ///
+    /// 超出 `max_probe_size` 的部分不会被缓冲。数据不足时返回 `NeedMore`，
+    /// 缓冲区已满仍无法识别时返回 `Failed`；其它探测错误直接返回。
+    fn detect_incremental(&self, state: &mut IncrementalState, chunk: &[u8]) -> Result<DetectionProgress> {
+        if let Some(outcome) = &state.outcome {
+            return Ok(outcome.clone());
+        }
+        
+        let room = self.max_probe_size().saturating_sub(state.buffer.len());
+        state.buffer.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let len = state.buffer.len();
+        let full = len >= self.max_probe_size();
+        
+        if len < self.min_probe_size() && !full {
+            return Ok(DetectionProgress::NeedMore { min_additional: self.min_probe_size() - len });
+        }
+        
+        match self.detect(&state.buffer) {
+            Ok(result) if result.confidence() >= state.min_confidence => {
+                Ok(state.finish(DetectionProgress::Detected(result)))
+            }
+            Err(DetectorError::NeedMoreData(needed)) if !full => {
+                Ok(DetectionProgress::NeedMore { min_additional: needed.saturating_sub(len).max(1) })
+            }
+            Ok(_) | Err(DetectorError::InsufficientData(_) | DetectorError::NoProtocolDetected(_)) if !full => {
+                Ok(DetectionProgress::NeedMore { min_additional: 1 })
+            }
+            Ok(_) | Err(DetectorError::NeedMoreData(_) | DetectorError::InsufficientData(_) | DetectorError::NoProtocolDetected(_)) => {
+                Ok(state.finish(DetectionProgress::Failed))
+            }
+            Err(err) => Err(err),
+        }
+    }
 }
 
 /// 异步协议探测器trait
@@ -661,6 +1798,8 @@ pub struct DetectionConfig {
     pub min_confidence: f32,
     /// 探测超时时间
     pub timeout: Duration,
+    /// 所有探测器的总耗时上限，超出时返回超时错误而不是聚合已有结果（默认不启用）
+    pub hard_deadline: Option<Duration>,
     /// 是否启用启发式探测
     pub enable_heuristic: bool,
     /// 是否启用主动探测
@@ -678,6 +1817,7 @@ impl Default for DetectionConfig {
         Self {
             min_confidence: 0.7,
             timeout: Duration::from_millis(1000),
+            hard_deadline: None,
             enable_heuristic: true,
             enable_active_probing: false,
             max_probe_size: 1024 * 1024,  // 增加到1MB以支持gRPC大帧
@@ -717,6 +1857,8 @@ pub struct AgentState {
     pub last_activity: Instant,
     /// 是否健康
     pub is_healthy: bool,
+    /// 被动探测统计
+    pub detection_stats: DetectionStats,
 }
 
 impl Default for AgentState {
@@ -728,6 +1870,7 @@ impl Default for AgentState {
             failed_upgrades: 0,
             last_activity: Instant::now(),
             is_healthy: true,
+            detection_stats: DetectionStats::default(),
         }
     }
 }
@@ -741,6 +1884,38 @@ pub struct LoadBalancer {
     backends: Arc<std::sync::RwLock<HashMap<String, BackendState>>>,
     /// 当前轮询索引
     round_robin_index: Arc<std::sync::atomic::AtomicUsize>,
+    /// 一致性哈希环：虚拟节点哈希 -> 实例ID
+    hash_ring: Arc<std::sync::RwLock<BTreeMap<u64, String>>>,
+}
+
+/// 每单位权重在哈希环上的虚拟节点数
+const VIRTUAL_NODES_PER_WEIGHT: u32 = 160;
+
+/// 哈希环使用的稳定哈希（FNV-1a加64位混合），不随进程或版本变化
+fn ring_hash(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    // FNV对相近的短字符串分布不均，再做一次murmur3的fmix64
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// 按后端权重构建哈希环
+fn build_hash_ring(backends: &HashMap<String, BackendState>) -> BTreeMap<u64, String> {
+    let mut ring = BTreeMap::new();
+    for backend in backends.values() {
+        for replica in 0..backend.weight.saturating_mul(VIRTUAL_NODES_PER_WEIGHT) {
+            let node = format!("{}#{}", backend.instance_id, replica);
+            ring.insert(ring_hash(node.as_bytes()), backend.instance_id.clone());
+        }
+    }
+    ring
 }
 
 /// 后端实例状态
@@ -783,6 +1958,11 @@ impl Agent {
         &self.config
     }
     
+    /// 获取协议升级器
+    pub fn upgrader(&self) -> Option<&Arc<dyn crate::upgrade::ProtocolUpgrader>> {
+        self.upgrader.as_ref()
+    }
+    
     /// 获取代理状态
     pub fn state(&self) -> Result<AgentState> {
         self.state.read()
@@ -812,6 +1992,11 @@ impl Agent {
         self.load_balancer.as_ref()?.select_backend()
     }
     
+    /// 按客户端标识选择后端实例（一致性哈希）
+    pub fn select_backend_for_key(&self, key: &str) -> Option<String> {
+        self.load_balancer.as_ref()?.select_backend_for_key(key)
+    }
+    
     /// 健康检查
     pub fn health_check(&self) -> bool {
         if let Ok(state) = self.state.read() {
@@ -820,6 +2005,21 @@ impl Agent {
             false
         }
     }
+    
+    /// 获取可序列化的指标快照，供服务端暴露给监控系统抓取
+    #[cfg(feature = "metrics")]
+    pub fn metrics_snapshot(&self) -> Result<crate::metrics::MetricsSnapshot> {
+        let state = self.state()?;
+        Ok(crate::metrics::MetricsSnapshot {
+            instance_id: self.config.instance_id.clone(),
+            role: self.config.role,
+            active_connections: state.active_connections,
+            total_requests: state.total_requests,
+            successful_upgrades: state.successful_upgrades,
+            failed_upgrades: state.failed_upgrades,
+            detection: state.detection_stats,
+        })
+    }
 }
 
 impl LoadBalancer {
@@ -837,15 +2037,73 @@ impl LoadBalancer {
                 (instance_id.clone(), state)
             })
             .collect();
+        let hash_ring = build_hash_ring(&backends);
         
         Self {
             config,
             backends: Arc::new(std::sync::RwLock::new(backends)),
             round_robin_index: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            hash_ring: Arc::new(std::sync::RwLock::new(hash_ring)),
         }
     }
     
+    /// 添加后端实例，已存在时更新权重
+    ///
+    /// 权重决定该实例在哈希环上的虚拟节点数，权重为0的实例不参与一致性哈希。
+    pub fn add_backend(&self, instance_id: impl Into<String>, weight: u32) -> Result<()> {
+        let instance_id = instance_id.into();
+        let mut backends = self.backends.write()
+            .map_err(|_| DetectorError::internal_error("Failed to write load balancer backends"))?;
+        backends.entry(instance_id.clone())
+            .and_modify(|backend| backend.weight = weight)
+            .or_insert_with(|| BackendState {
+                instance_id,
+                active_connections: 0,
+                weight,
+                is_healthy: true,
+                last_health_check: Instant::now(),
+            });
+        self.rebuild_hash_ring(&backends)
+    }
+    
+    /// 移除后端实例，返回实例是否存在
+    pub fn remove_backend(&self, instance_id: &str) -> Result<bool> {
+        let mut backends = self.backends.write()
+            .map_err(|_| DetectorError::internal_error("Failed to write load balancer backends"))?;
+        if backends.remove(instance_id).is_none() {
+            return Ok(false);
+        }
+        self.rebuild_hash_ring(&backends)?;
+        Ok(true)
+    }
+    
+    /// 持有后端写锁时重建哈希环，保证两者一致
+    fn rebuild_hash_ring(&self, backends: &HashMap<String, BackendState>) -> Result<()> {
+        let ring = build_hash_ring(backends);
+        *self.hash_ring.write()
+            .map_err(|_| DetectorError::internal_error("Failed to write load balancer hash ring"))? = ring;
+        Ok(())
+    }
+    
+    /// 按客户端标识（如客户端地址或会话ID）选择后端实例
+    ///
+    /// 同一标识总是映射到同一后端；增删后端时只有约 1/N 的标识会被重新分配。
+    /// 顺时针查找第一个健康实例的虚拟节点，不健康的实例被跳过而不会打乱其他映射。
+    pub fn select_backend_for_key(&self, key: &str) -> Option<String> {
+        let backends = self.backends.read().ok()?;
+        let ring = self.hash_ring.read().ok()?;
+        let hash = ring_hash(key.as_bytes());
+        ring.range(hash..)
+            .chain(ring.range(..hash))
+            .map(|(_, instance_id)| instance_id)
+            .find(|instance_id| backends.get(*instance_id).is_some_and(|backend| backend.is_healthy))
+            .cloned()
+    }
+    
     /// 选择后端实例
+    ///
+    /// 一致性哈希需要客户端标识，应使用 [`Self::select_backend_for_key`]；
+    /// 在这里没有标识可用，退化为轮询。
     pub fn select_backend(&self) -> Option<String> {
         let backends = self.backends.read().ok()?;
         let healthy_backends: Vec<_> = backends.values()
@@ -857,7 +2115,7 @@ impl LoadBalancer {
         }
         
         match self.config.strategy {
-            LoadBalanceStrategy::RoundRobin => {
+            LoadBalanceStrategy::RoundRobin | LoadBalanceStrategy::ConsistentHash => {
                 let index = self.round_robin_index.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                 Some(healthy_backends[index % healthy_backends.len()].instance_id.clone())
             },
@@ -884,10 +2142,6 @@ impl LoadBalancer {
                 }
                 None
             },
-            LoadBalanceStrategy::ConsistentHash => {
-                // 简化实现，使用第一个健康实例
-                healthy_backends.first().map(|backend| backend.instance_id.clone())
-            },
         }
     }
 }
@@ -903,7 +2157,8 @@ impl ProtocolAgent for Agent {
         }
         
         // 根据角色选择探测策略
-        match self.config.role {
+        let started = Instant::now();
+        let result = match self.config.role {
             Role::Server => {
                 // 服务器模式：被动探测传入数据
                 self.detector.detect(data)
@@ -912,6 +2167,61 @@ impl ProtocolAgent for Agent {
                 // 客户端模式：通常不需要被动探测，但可以用于验证
                 self.detector.detect(data)
             },
+        };
+        
+        // 内部探测器的阈值可能与Agent配置不同，低于Agent阈值的猜测不直接返回
+        let threshold = self.config.detection_config.min_confidence;
+        let result = result.and_then(|detection| {
+            if detection.confidence() >= threshold {
+                return Ok(detection);
+            }
+            if self.config.return_low_confidence_as_unknown {
+                let mut info = ProtocolInfo::new(ProtocolType::Unknown, detection.confidence());
+                info.add_metadata("best_guess", detection.protocol_type().to_string());
+                info.add_metadata("low_confidence", "true");
+                return Ok(DetectionResult::new(
+                    info,
+                    detection.detection_time,
+                    detection.detection_method,
+                    detection.detector_name,
+                ));
+            }
+            Err(DetectorError::LowConfidence {
+                protocol: detection.protocol_type(),
+                confidence: detection.confidence(),
+                threshold,
+            })
+        });
+        
+        if let Ok(mut state) = self.state.write() {
+            match &result {
+                Ok(detection) => state.detection_stats.record_success(detection.protocol_type(), started.elapsed()),
+                Err(_) => state.detection_stats.record_failure(started.elapsed()),
+            }
+            if self.detector.cache_counters().is_some() {
+                match &result {
+                    Ok(detection) if detection.detection_method == DetectionMethod::Cached => {
+                        state.detection_stats.record_cache_hit()
+                    }
+                    _ => state.detection_stats.record_cache_miss(),
+                }
+            }
+        }
+        
+        // 没有协议匹配时按配置回退，数据不足等错误仍交给调用方处理
+        match (result, self.config.fallback_protocol) {
+            (
+                Err(err @ (DetectorError::NoProtocolDetected(_)
+                    | DetectorError::LowConfidence { .. }
+                    | DetectorError::DetectionFailed { .. })),
+                Some(fallback),
+            ) => {
+                let mut info = ProtocolInfo::new(fallback, 0.0);
+                info.add_metadata("fallback", "true");
+                info.add_metadata("fallback_reason", err.to_string());
+                Ok(DetectionResult::new(info, Duration::ZERO, DetectionMethod::Passive, "Fallback".to_string()))
+            }
+            (result, _) => result,
         }
     }
     
@@ -1163,6 +2473,12 @@ impl DetectionConfig {
         self
     }
     
+    /// 设置硬性截止时间
+    pub fn with_hard_deadline(mut self, deadline: Duration) -> Self {
+        self.hard_deadline = Some(deadline);
+        self
+    }
+    
     /// 启用启发式探测
     pub fn enable_heuristic(mut self) -> Self {
         self.enable_heuristic = true;
@@ -1225,6 +2541,12 @@ pub struct DetectionStats {
     pub avg_detection_time: Duration,
     /// 各协议探测次数
     pub protocol_counts: std::collections::HashMap<ProtocolType, u64>,
+    /// 探测结果缓存命中次数
+    #[serde(default)]
+    pub cache_hits: u64,
+    /// 探测结果缓存未命中次数（仅统计启用缓存的探测器）
+    #[serde(default)]
+    pub cache_misses: u64,
 }
 
 impl DetectionStats {
@@ -1248,6 +2570,26 @@ impl DetectionStats {
         self.update_avg_time(duration);
     }
     
+    /// 记录缓存命中
+    pub fn record_cache_hit(&mut self) {
+        self.cache_hits += 1;
+    }
+    
+    /// 记录缓存未命中
+    pub fn record_cache_miss(&mut self) {
+        self.cache_misses += 1;
+    }
+    
+    /// 获取缓存命中率
+    pub fn cache_hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / lookups as f64
+        }
+    }
+    
     /// 获取成功率
     pub fn success_rate(&self) -> f64 {
         if self.total_detections == 0 {
@@ -2,8 +2,11 @@
 //!
 //! 定义协议指纹和特征匹配功能。
 
-use crate::core::protocol::ProtocolType;
+use crate::core::protocol::{ProtocolInfo, ProtocolType};
+use crate::core::tls_alpn::is_grease;
 use crate::error::{DetectorError, Result};
+use crate::utils::digest::md5;
+use crate::utils::encoding::encode_hex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -496,4 +499,282 @@ impl Default for FingerprintDatabase {
         db.load_default_fingerprints();
         db
     }
-}
\ No newline at end of file
+}
+
+/// TLS握手记录类型
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// ClientHello握手类型
+const TLS_CLIENT_HELLO: u8 = 0x01;
+
+/// ServerHello握手类型
+const TLS_SERVER_HELLO: u8 = 0x02;
+
+/// supported_groups（elliptic_curves）扩展类型
+const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+
+/// ec_point_formats扩展类型
+const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+
+/// JA3指纹类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Ja3Kind {
+    /// 客户端指纹（JA3，来自ClientHello）
+    Client,
+    /// 服务端指纹（JA3S，来自ServerHello）
+    Server,
+}
+
+/// JA3指纹结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ja3Result {
+    /// 指纹类型
+    pub kind: Ja3Kind,
+    /// 指纹字符串，如 `771,4865-4866,0-10-11,29-23,0`
+    pub raw: String,
+    /// 指纹字符串的MD5（小写十六进制）
+    pub hash: String,
+}
+
+/// JA3/JA3S TLS指纹计算器
+///
+/// ClientHello按 `版本,密码套件,扩展,椭圆曲线,点格式` 生成JA3，
+/// ServerHello按 `版本,密码套件,扩展` 生成JA3S。各列表中的GREASE值（RFC 8701）不参与计算。
+#[derive(Debug, Clone, Default)]
+pub struct Ja3Fingerprinter;
+
+impl Ja3Fingerprinter {
+    /// 创建新的指纹计算器
+    pub fn new() -> Self {
+        Self
+    }
+    
+    /// 计算TLS握手记录的JA3（ClientHello）或JA3S（ServerHello）指纹
+    pub fn fingerprint(&self, data: &[u8]) -> Result<Ja3Result> {
+        if data.len() < 5 {
+            return Err(DetectorError::InsufficientData("TLS记录头不完整".to_string()));
+        }
+        if data[0] != TLS_HANDSHAKE_RECORD {
+            return Err(DetectorError::detection_failed("不是TLS握手记录"));
+        }
+        
+        let record_len = u16::from_be_bytes([data[3], data[4]]) as usize;
+        let record = &data[5..data.len().min(5 + record_len)];
+        if record.len() < 4 {
+            return Err(DetectorError::InsufficientData("TLS握手消息头不完整".to_string()));
+        }
+        let handshake_len = u32::from_be_bytes([0, record[1], record[2], record[3]]) as usize;
+        let body = record.get(4..4 + handshake_len).ok_or_else(|| {
+            DetectorError::InsufficientData(format!("TLS握手消息需要 {} 字节", handshake_len))
+        })?;
+        
+        let (kind, raw) = match record[0] {
+            TLS_CLIENT_HELLO => (Ja3Kind::Client, Self::client_hello_string(body)),
+            TLS_SERVER_HELLO => (Ja3Kind::Server, Self::server_hello_string(body)),
+            other => return Err(DetectorError::detection_failed(format!("不支持的TLS握手类型 {}", other))),
+        };
+        let raw = raw.ok_or_else(|| DetectorError::detection_failed("TLS握手消息格式错误"))?;
+        let hash = encode_hex(&md5(raw.as_bytes()));
+        
+        Ok(Ja3Result { kind, raw, hash })
+    }
+    
+    /// 生成ClientHello的JA3字符串
+    fn client_hello_string(body: &[u8]) -> Option<String> {
+        let mut reader = HandshakeReader::new(body);
+        let version = reader.u16()?;
+        reader.skip(32)?;
+        let session_id_len = reader.u8()? as usize;
+        reader.skip(session_id_len)?;
+        
+        let cipher_len = reader.u16()? as usize;
+        let ciphers = u16_list(reader.bytes(cipher_len)?)?;
+        let compression_len = reader.u8()? as usize;
+        reader.skip(compression_len)?;
+        
+        let mut extensions = Vec::new();
+        let mut groups = Vec::new();
+        let mut point_formats: Vec<u8> = Vec::new();
+        for (extension_type, extension_data) in reader.extensions()? {
+            extensions.push(extension_type);
+            match extension_type {
+                EXT_SUPPORTED_GROUPS => {
+                    let mut ext = HandshakeReader::new(extension_data);
+                    let len = ext.u16()? as usize;
+                    groups = u16_list(ext.bytes(len)?)?;
+                }
+                EXT_EC_POINT_FORMATS => {
+                    let mut ext = HandshakeReader::new(extension_data);
+                    let len = ext.u8()? as usize;
+                    point_formats = ext.bytes(len)?.to_vec();
+                }
+                _ => {}
+            }
+        }
+        
+        Some(format!(
+            "{},{},{},{},{}",
+            version,
+            join_values(ciphers.into_iter().filter(|&v| !is_grease(v))),
+            join_values(extensions.into_iter().filter(|&v| !is_grease(v))),
+            join_values(groups.into_iter().filter(|&v| !is_grease(v))),
+            join_values(point_formats.into_iter()),
+        ))
+    }
+    
+    /// 生成ServerHello的JA3S字符串
+    fn server_hello_string(body: &[u8]) -> Option<String> {
+        let mut reader = HandshakeReader::new(body);
+        let version = reader.u16()?;
+        reader.skip(32)?;
+        let session_id_len = reader.u8()? as usize;
+        reader.skip(session_id_len)?;
+        let cipher = reader.u16()?;
+        reader.skip(1)?; // 压缩方法
+        
+        let extensions = reader.extensions()?.into_iter().map(|(extension_type, _)| extension_type);
+        
+        Some(format!(
+            "{},{},{}",
+            version,
+            cipher,
+            join_values(extensions.filter(|&v| !is_grease(v))),
+        ))
+    }
+}
+
+/// 握手消息的顺序读取器，越界时返回 `None`
+struct HandshakeReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HandshakeReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+    
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+    
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.bytes(len).map(|_| ())
+    }
+    
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+    
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+    
+    /// 读取扩展列表；没有扩展字段时为空
+    fn extensions(&mut self) -> Option<Vec<(u16, &'a [u8])>> {
+        if self.pos == self.data.len() {
+            return Some(Vec::new());
+        }
+        let len = self.u16()? as usize;
+        let mut reader = HandshakeReader::new(self.bytes(len)?);
+        let mut extensions = Vec::new();
+        while reader.pos < reader.data.len() {
+            let extension_type = reader.u16()?;
+            let extension_len = reader.u16()? as usize;
+            extensions.push((extension_type, reader.bytes(extension_len)?));
+        }
+        Some(extensions)
+    }
+}
+
+/// 解析大端u16列表，长度必须为偶数
+fn u16_list(data: &[u8]) -> Option<Vec<u16>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(data.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect())
+}
+
+/// 以 `-` 连接十进制数值
+fn join_values<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<_>>().join("-")
+}
+
+/// HTTP/1.x请求行与Host头
+///
+/// 在HTTP/1.x探测结果上解析，供按路径或主机名分流的L7路由直接使用，无需再次解析请求。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpRequestInfo {
+    /// 请求方法，如 `GET`
+    pub method: String,
+    /// 请求目标，如 `/index.html?page=1`
+    pub path: String,
+    /// `Host` 头（尚未接收到或缺失时为 `None`）
+    pub host: Option<String>,
+    /// 协议版本，如 `1.1`
+    pub version: String,
+}
+
+impl HttpRequestInfo {
+    /// 解析请求行和已完整接收的 `Host` 头
+    ///
+    /// 忽略请求行前的空白和空行，方法不区分大小写并统一为大写；
+    /// 没有版本号的 `GET <path>` 按HTTP/0.9处理，版本记为 `0.9`。
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let data = crate::utils::http::trim_leading_blank(data);
+        let line_end = data.iter().position(|&b| b == b'\n')
+            .ok_or_else(|| DetectorError::InsufficientData("HTTP请求行不完整".to_string()))?;
+        let line = std::str::from_utf8(&data[..line_end])
+            .map_err(|_| DetectorError::detection_failed("HTTP请求行不是UTF-8"))?
+            .trim_end_matches('\r');
+        
+        let mut parts = line.split(' ');
+        let (Some(method), Some(path), version, None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(DetectorError::detection_failed("HTTP请求行格式错误"));
+        };
+        let version = match version {
+            Some(version) => version.strip_prefix("HTTP/")
+                .filter(|version| version.starts_with("1."))
+                .ok_or_else(|| DetectorError::detection_failed("不是HTTP/1.x请求"))?,
+            None if method.eq_ignore_ascii_case("GET") && path.starts_with('/') => "0.9",
+            None => return Err(DetectorError::detection_failed("HTTP请求行格式错误")),
+        };
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_alphabetic()) || path.is_empty() {
+            return Err(DetectorError::detection_failed("HTTP请求行格式错误"));
+        }
+        
+        // 只在完整的头部行中查找Host，避免截断的值
+        let complete_lines = data.iter().rposition(|&b| b == b'\n').map_or(data, |end| &data[..end]);
+        let host = crate::utils::http::header_value(complete_lines, "host").map(str::to_string);
+        
+        Ok(Self {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            host,
+            version: version.to_string(),
+        })
+    }
+    
+    /// 写入 `http_method`、`http_path`、`http_version` 和 `http_host` 元数据
+    pub fn annotate(&self, info: &mut ProtocolInfo) {
+        info.add_metadata("http_method", self.method.as_str());
+        info.add_metadata("http_path", self.path.as_str());
+        info.add_metadata("http_version", self.version.as_str());
+        if let Some(ref host) = self.host {
+            info.add_metadata("http_host", host.as_str());
+        }
+    }
+    
+    /// 从探测结果的元数据中读取
+    pub fn from_metadata(info: &ProtocolInfo) -> Option<Self> {
+        let metadata = &info.metadata;
+        Some(Self {
+            method: metadata.get("http_method")?.clone(),
+            path: metadata.get("http_path")?.clone(),
+            host: metadata.get("http_host").cloned(),
+            version: metadata.get("http_version")?.clone(),
+        })
+    }
+}
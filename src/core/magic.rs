@@ -1,18 +1,39 @@
 //! 魔法包特征检测模块
 //!
 //! 基于协议的魔法字节（Magic Bytes）实现超高速启发式协议检测
+//!
+//! 特征匹配和 [`MagicDetector::quick_classify`] 只依赖 `alloc`，关闭 `std` 特性后仍可使用；
+//! 构造 `ProtocolInfo`、TLS ALPN识别和JSON导入导出需要 `std`。
 
-use crate::core::protocol::{ProtocolType, ProtocolInfo};
+use crate::core::protocol::ProtocolType;
+#[cfg(feature = "std")]
+use crate::core::protocol::ProtocolInfo;
+#[cfg(feature = "std")]
 use crate::core::tls_alpn::TlsAlpnDetector;
+#[cfg(feature = "std")]
 use crate::error::{DetectorError, Result};
+#[cfg(feature = "std")]
+use crate::utils::{http, http2};
+use crate::utils::{mqtt, starttls};
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+use alloc::{string::{String, ToString}, vec, vec::Vec};
+
+/// 特征结构校验函数，通过时返回校验覆盖的字节数
+pub type SignatureValidator = fn(&[u8]) -> Option<usize>;
 
 /// 魔法包特征结构
+///
+/// 序列化时魔法字节编码为十六进制字符串，结构校验函数不参与序列化。
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct MagicSignature {
     /// 协议类型
     pub protocol: ProtocolType,
     /// 魔法字节序列
+    #[cfg_attr(feature = "std", serde(with = "hex_bytes"))]
     pub magic_bytes: Vec<u8>,
     /// 字节偏移量（从哪个位置开始匹配）
     pub offset: usize,
@@ -24,6 +45,28 @@ pub struct MagicSignature {
     pub description: String,
     /// 是否区分大小写
     pub case_sensitive: bool,
+    /// 自定义协议名称，命中时写入 `ProtocolInfo::custom_name`
+    #[cfg_attr(feature = "std", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub custom_name: Option<String>,
+    /// 字节匹配后的结构校验
+    #[cfg_attr(feature = "std", serde(skip))]
+    pub validator: Option<SignatureValidator>,
+}
+
+/// 魔法字节的十六进制序列化
+#[cfg(feature = "std")]
+mod hex_bytes {
+    use crate::utils::encoding::{decode_hex, encode_hex};
+    use serde::{Deserialize, Deserializer, Serializer};
+    
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_hex(bytes))
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        decode_hex(&hex).map_err(serde::de::Error::custom)
+    }
 }
 
 impl MagicSignature {
@@ -44,9 +87,23 @@ impl MagicSignature {
             confidence,
             description,
             case_sensitive: true,
+            custom_name: None,
+            validator: None,
         }
     }
     
+    /// 设置结构校验函数，适用于魔法字节过短、需要解析报文头才能确认的协议
+    pub fn with_validator(mut self, validator: SignatureValidator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+    
+    /// 设置自定义协议名称
+    pub fn with_custom_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.custom_name = Some(name.into());
+        self
+    }
+    
     /// 创建不区分大小写的特征
     pub fn case_insensitive(mut self) -> Self {
         self.case_sensitive = false;
@@ -61,18 +118,170 @@ impl MagicSignature {
     
     /// 检测数据是否匹配此特征
     pub fn matches(&self, data: &[u8]) -> bool {
-        if data.len() < self.offset + self.match_length {
-            return false;
+        self.matched_len(data).is_some()
+    }
+    
+    /// 匹配时返回做出判断所检查的字节数
+    pub fn matched_len(&self, data: &[u8]) -> Option<usize> {
+        let end = self.offset + self.match_length;
+        if data.len() < end {
+            return None;
         }
         
-        let data_slice = &data[self.offset..self.offset + self.match_length];
+        let data_slice = &data[self.offset..end];
         let magic_slice = &self.magic_bytes[..self.match_length];
         
-        if self.case_sensitive {
+        let bytes_match = if self.case_sensitive {
             data_slice == magic_slice
         } else {
             data_slice.iter().zip(magic_slice.iter())
                 .all(|(a, b)| a.to_ascii_lowercase() == b.to_ascii_lowercase())
+        };
+        if !bytes_match {
+            return None;
+        }
+        
+        match self.validator {
+            Some(validator) => validator(data).map(|len| len.max(end)),
+            None => Some(end),
+        }
+    }
+}
+
+/// 排除特征的搜索范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativeScope {
+    /// 整个缓冲区
+    Anywhere,
+    /// 第一行（到首个 `\n` 为止）
+    FirstLine,
+    /// 前N个字节
+    Prefix(usize),
+}
+
+/// 排除特征（负特征）
+///
+/// 数据中出现指定字节序列时，压制对应协议的弱匹配结果。
+#[derive(Debug, Clone)]
+pub struct NegativeSignature {
+    /// 被压制的协议类型
+    pub protocol: ProtocolType,
+    /// 出现即排除的字节序列
+    pub pattern: Vec<u8>,
+    /// 搜索范围
+    pub scope: NegativeScope,
+    /// 仅压制置信度不高于此值的结果
+    pub max_confidence: f32,
+    /// 特征描述
+    pub description: String,
+}
+
+impl NegativeSignature {
+    /// 创建排除特征，默认在整个缓冲区中搜索并压制任意置信度的结果
+    pub fn new(protocol: ProtocolType, pattern: Vec<u8>, description: &str) -> Self {
+        Self {
+            protocol,
+            pattern,
+            scope: NegativeScope::Anywhere,
+            max_confidence: 1.0,
+            description: description.to_string(),
+        }
+    }
+    
+    /// 设置搜索范围
+    pub fn with_scope(mut self, scope: NegativeScope) -> Self {
+        self.scope = scope;
+        self
+    }
+    
+    /// 设置可压制的最高置信度
+    pub fn with_max_confidence(mut self, confidence: f32) -> Self {
+        self.max_confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+    
+    /// 判断是否应压制该检测结果
+    #[cfg(feature = "std")]
+    pub fn excludes(&self, info: &ProtocolInfo, data: &[u8]) -> bool {
+        self.suppresses(info.protocol_type, info.confidence, data)
+    }
+    
+    /// 判断是否应压制给定协议和置信度的匹配
+    fn suppresses(&self, protocol: ProtocolType, confidence: f32, data: &[u8]) -> bool {
+        if protocol != self.protocol || confidence > self.max_confidence {
+            return false;
+        }
+        
+        let window = match self.scope {
+            NegativeScope::Anywhere => data,
+            NegativeScope::FirstLine => {
+                let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+                &data[..end]
+            }
+            NegativeScope::Prefix(len) => &data[..len.min(data.len())],
+        };
+        
+        !self.pattern.is_empty()
+            && window.windows(self.pattern.len()).any(|w| w == self.pattern.as_slice())
+    }
+}
+
+/// 按第一字节索引的特征表
+///
+/// 启用 `std` 时使用 `HashMap`，否则使用按字节排序的 `Vec` 二分查找。
+#[derive(Debug, Default)]
+struct SignatureIndex {
+    #[cfg(feature = "std")]
+    map: HashMap<u8, Vec<MagicSignature>>,
+    #[cfg(not(feature = "std"))]
+    sorted: Vec<(u8, Vec<MagicSignature>)>,
+}
+
+impl SignatureIndex {
+    /// 获取以该字节开头的特征
+    fn get(&self, byte: u8) -> Option<&[MagicSignature]> {
+        #[cfg(feature = "std")]
+        {
+            self.map.get(&byte).map(Vec::as_slice)
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            self.sorted.binary_search_by_key(&byte, |(key, _)| *key)
+                .ok()
+                .map(|index| self.sorted[index].1.as_slice())
+        }
+    }
+    
+    /// 追加以该字节开头的特征
+    fn push(&mut self, byte: u8, signature: MagicSignature) {
+        #[cfg(feature = "std")]
+        {
+            self.map.entry(byte).or_default().push(signature);
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            match self.sorted.binary_search_by_key(&byte, |(key, _)| *key) {
+                Ok(index) => self.sorted[index].1.push(signature),
+                Err(index) => self.sorted.insert(index, (byte, vec![signature])),
+            }
+        }
+    }
+    
+    /// 只保留满足条件的特征，并删除变空的索引项
+    fn retain<F: FnMut(&MagicSignature) -> bool>(&mut self, mut keep: F) {
+        #[cfg(feature = "std")]
+        {
+            self.map.retain(|_, signatures| {
+                signatures.retain(&mut keep);
+                !signatures.is_empty()
+            });
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            self.sorted.retain_mut(|(_, signatures)| {
+                signatures.retain(&mut keep);
+                !signatures.is_empty()
+            });
         }
     }
 }
@@ -81,27 +290,38 @@ impl MagicSignature {
 #[derive(Debug)]
 pub struct MagicDetector {
     /// 按第一字节索引的特征表（快速查找）
-    byte_indexed_signatures: HashMap<u8, Vec<MagicSignature>>,
+    byte_indexed_signatures: SignatureIndex,
     /// 所有特征的列表（备用）
     all_signatures: Vec<MagicSignature>,
     /// 启用的协议过滤器
     enabled_protocols: Option<Vec<ProtocolType>>,
     /// TLS ALPN检测器
+    #[cfg(feature = "std")]
     tls_alpn_detector: TlsAlpnDetector,
+    /// 排除特征（作为检测结果的后置过滤器）
+    negative_signatures: Vec<NegativeSignature>,
 }
 
 impl MagicDetector {
     /// 创建新的魔法包检测器
     pub fn new() -> Self {
         let mut detector = Self {
-            byte_indexed_signatures: HashMap::new(),
+            byte_indexed_signatures: SignatureIndex::default(),
             all_signatures: Vec::new(),
             enabled_protocols: None,
+            #[cfg(feature = "std")]
             tls_alpn_detector: TlsAlpnDetector::new(),
+            negative_signatures: Vec::new(),
         };
         
         // 预加载常见协议的魔法包特征
         detector.load_common_signatures();
+        // 首行含NUL字节的数据不会是文本HTTP，压制首字节启发式的弱匹配
+        detector.add_negative_signature(
+            NegativeSignature::new(ProtocolType::HTTP1_1, vec![0x00], "NUL in HTTP request line")
+                .with_scope(NegativeScope::FirstLine)
+                .with_max_confidence(0.5)
+        );
         detector
     }
     
@@ -195,14 +415,14 @@ impl MagicDetector {
                 "SSH protocol".to_string(),
             ),
             
-            // FTP
+            // FTP和SMTP的问候语都以 `220 ` 开头，依据之后的文本区分
             MagicSignature::new(
                 ProtocolType::FTP,
                 b"220 ".to_vec(),
                 0,
                 0.85,
                 "FTP welcome message".to_string(),
-            ),
+            ).with_validator(starttls::ftp_greeting_len),
             
             // SMTP
             MagicSignature::new(
@@ -211,7 +431,7 @@ impl MagicDetector {
                 0,
                 0.8,
                 "SMTP welcome".to_string(),
-            ),
+            ).with_validator(starttls::smtp_greeting_len),
             
             // WebSocket升级
             MagicSignature::new(
@@ -240,14 +460,14 @@ impl MagicDetector {
                 "DNS query".to_string(),
             ).with_match_length(4),
             
-            // MQTT
+            // MQTT：单字节报文类型过于常见，需校验完整的CONNECT报文头
             MagicSignature::new(
                 ProtocolType::MQTT,
-                vec![0x10], // MQTT CONNECT packet
+                vec![mqtt::PACKET_CONNECT],
                 0,
-                0.7,
+                0.95,
                 "MQTT CONNECT".to_string(),
-            ).with_match_length(1),
+            ).with_validator(mqtt::connect_header_len),
             
             // Redis
             MagicSignature::new(
@@ -290,19 +510,13 @@ impl MagicDetector {
                 signature.magic_bytes[0].to_ascii_lowercase()
             };
             
-            self.byte_indexed_signatures
-                .entry(first_byte)
-                .or_insert_with(Vec::new)
-                .push(signature.clone());
+            self.byte_indexed_signatures.push(first_byte, signature.clone());
                 
             // 如果不区分大小写，也为大写版本建立索引
             if !signature.case_sensitive {
                 let upper_byte = signature.magic_bytes[0].to_ascii_uppercase();
                 if upper_byte != first_byte {
-                    self.byte_indexed_signatures
-                        .entry(upper_byte)
-                        .or_insert_with(Vec::new)
-                        .push(signature.clone());
+                    self.byte_indexed_signatures.push(upper_byte, signature.clone());
                 }
             }
         }
@@ -310,62 +524,213 @@ impl MagicDetector {
         self.all_signatures.push(signature);
     }
     
+    /// 从JSON数组加载特征并加入检测器，返回加载的数量
+    ///
+    /// 所有特征通过校验后才会加入：魔法字节不能为空，`match_length` 不能超过魔法字节长度，
+    /// 置信度必须在 `[0, 1]` 之间。
+    #[cfg(feature = "std")]
+    pub fn load_signatures_from_json(&mut self, json: &str) -> Result<usize> {
+        let signatures: Vec<MagicSignature> = serde_json::from_str(json)
+            .map_err(|e| DetectorError::config_error(format!("特征JSON无效: {}", e)))?;
+        
+        for (index, signature) in signatures.iter().enumerate() {
+            let valid = !signature.magic_bytes.is_empty()
+                && (1..=signature.magic_bytes.len()).contains(&signature.match_length)
+                && (0.0..=1.0).contains(&signature.confidence);
+            if !valid {
+                return Err(DetectorError::config_error(
+                    format!("第 {} 个特征 `{}` 无效", index + 1, signature.description)
+                ));
+            }
+        }
+        
+        let count = signatures.len();
+        for signature in signatures {
+            self.add_signature(signature);
+        }
+        Ok(count)
+    }
+    
+    /// 把所有特征导出为JSON数组
+    ///
+    /// 带结构校验函数的内置特征（如MQTT CONNECT）无法用字节描述，不会导出。
+    #[cfg(feature = "std")]
+    pub fn export_signatures_to_json(&self) -> Result<String> {
+        let signatures: Vec<&MagicSignature> = self.all_signatures.iter()
+            .filter(|signature| signature.validator.is_none())
+            .collect();
+        Ok(serde_json::to_string_pretty(&signatures)?)
+    }
+    
+    /// 添加排除特征
+    pub fn add_negative_signature(&mut self, signature: NegativeSignature) {
+        self.negative_signatures.push(signature);
+    }
+    
+    /// 特征匹配需要检查的前缀长度
+    ///
+    /// 即所有特征 `offset + match_length` 以及 `Prefix` 范围排除特征的最大值。
+    pub fn prefix_window(&self) -> usize {
+        let signatures = self.all_signatures.iter()
+            .map(|signature| signature.offset + signature.match_length);
+        let negatives = self.negative_signatures.iter()
+            .filter_map(|neg| match neg.scope {
+                NegativeScope::Prefix(len) => Some(len),
+                _ => None,
+            });
+        signatures.chain(negatives).max().unwrap_or(0)
+    }
+    
+    /// 判断仅凭前缀窗口得到的匹配结论是否与完整数据一致
+    ///
+    /// 该协议存在作用于首行或整个缓冲区的排除特征时，窗口之后的数据仍可能压制匹配。
+    pub fn is_prefix_decidable(&self, protocol: ProtocolType) -> bool {
+        !self.negative_signatures.iter().any(|neg| {
+            neg.protocol == protocol && !matches!(neg.scope, NegativeScope::Prefix(_))
+        })
+    }
+    
+    /// 查找首个匹配的特征，不构造 `ProtocolInfo`
+    ///
+    /// 与 `quick_detect` 使用相同的索引和排除特征，但不做首字节启发式和元数据标注。
+    pub fn match_signature(&self, data: &[u8]) -> Option<&MagicSignature> {
+        self.byte_indexed_signatures.get(*data.first()?)?
+            .iter()
+            .find(|signature| {
+                self.is_enabled(signature.protocol)
+                    && signature.matches(data)
+                    && !self.negative_signatures.iter()
+                        .any(|neg| neg.suppresses(signature.protocol, signature.confidence, data))
+            })
+    }
+    
+    /// 检测结果是否被任一排除特征压制
+    #[cfg(feature = "std")]
+    fn is_suppressed(&self, info: &ProtocolInfo, data: &[u8]) -> bool {
+        self.negative_signatures.iter().any(|neg| neg.excludes(info, data))
+    }
+    
     /// 设置启用的协议过滤器
     pub fn with_enabled_protocols(mut self, protocols: Vec<ProtocolType>) -> Self {
         self.enabled_protocols = Some(protocols);
         self
     }
     
+    /// 获取启用的协议过滤器，未设置时所有协议都会匹配
+    pub fn enabled_protocols(&self) -> Option<&[ProtocolType]> {
+        self.enabled_protocols.as_deref()
+    }
+    
+    /// TLS ClientHello携带的元数据（ALPN、SNI、版本、密码套件、扫描器特征），不是ClientHello时返回 `None`
+    #[cfg(feature = "std")]
+    pub(crate) fn client_hello_metadata(&self, data: &[u8]) -> Option<ProtocolInfo> {
+        let alpn_result = self.tls_alpn_detector.detect_alpn(data)?;
+        let mut info = self.tls_alpn_detector.create_protocol_info(alpn_result)?;
+        info.add_metadata("tls_scanner_hint", self.tls_alpn_detector.is_scanner_like(data).to_string());
+        Some(info)
+    }
+    
+    /// 协议是否通过过滤器
+    fn is_enabled(&self, protocol: ProtocolType) -> bool {
+        self.enabled_protocols.as_ref()
+            .is_none_or(|enabled| enabled.contains(&protocol))
+    }
+    
+    /// 只依赖 `alloc` 的快速检测，返回协议和置信度
+    ///
+    /// 与 `quick_detect` 使用相同的特征索引、首字节启发式和排除特征，
+    /// 但不做TLS ALPN识别和HTTP/0.9区分，也不构造带元数据的 `ProtocolInfo`。
+    pub fn quick_classify(&self, data: &[u8]) -> Option<(ProtocolType, f32)> {
+        let (protocol, confidence) = match self.first_indexed_match(data) {
+            Some(signature) => (signature.protocol, signature.confidence),
+            None => {
+                let (protocol, confidence, _) = self.first_byte_guess(data)?;
+                (protocol, confidence)
+            }
+        };
+        let suppressed = self.negative_signatures.iter()
+            .any(|neg| neg.suppresses(protocol, confidence, data));
+        (!suppressed).then_some((protocol, confidence))
+    }
+    
+    /// 按第一字节索引查找首个匹配的启用特征
+    fn first_indexed_match(&self, data: &[u8]) -> Option<&MagicSignature> {
+        self.byte_indexed_signatures.get(*data.first()?)?
+            .iter()
+            .find(|signature| self.is_enabled(signature.protocol) && signature.matches(data))
+    }
+    
     /// 超快速魔法包检测（前几个字节启发式判断）
+    #[cfg(feature = "std")]
     pub fn quick_detect(&self, data: &[u8]) -> Option<ProtocolInfo> {
-        if data.is_empty() {
-            return None;
-        }
-        
-        let first_byte = data[0];
-        
+        self.quick_match(data)
+            .filter(|info| !self.is_suppressed(info, data))
+    }
+    
+    #[cfg(feature = "std")]
+    fn quick_match(&self, data: &[u8]) -> Option<ProtocolInfo> {
         // 1. 查找按第一字节索引的特征
-        if let Some(signatures) = self.byte_indexed_signatures.get(&first_byte) {
-            for signature in signatures {
-                // 检查协议过滤器
-                if let Some(ref enabled) = self.enabled_protocols {
-                    if !enabled.contains(&signature.protocol) {
-                        continue;
-                    }
-                }
-                
-                if signature.matches(data) {
-                    // 如果检测到TLS，尝试ALPN检测
-                    if signature.protocol == ProtocolType::TLS {
-                        if let Some(alpn_result) = self.tls_alpn_detector.detect_alpn(data) {
-                            if let Some(alpn_info) = self.tls_alpn_detector.create_protocol_info(alpn_result) {
-                                // 检查ALPN检测到的协议是否在启用列表中
-                                if let Some(ref enabled) = self.enabled_protocols {
-                                    if enabled.contains(&alpn_info.protocol_type) {
-                                        return Some(alpn_info);
-                                    }
-                                } else {
-                                    return Some(alpn_info);
-                                }
-                            }
+        if let Some(signature) = self.first_indexed_match(data) {
+            // HTTP/0.9简单请求没有版本号，不按HTTP/1.1特征处理，交给被动探测器识别
+            if signature.protocol == ProtocolType::HTTP1_1 && http::is_http09_request(data) {
+                return None;
+            }
+            
+            // 如果检测到TLS，尝试ALPN检测
+            if signature.protocol == ProtocolType::TLS {
+                if let Some(alpn_result) = self.tls_alpn_detector.detect_alpn(data) {
+                    if let Some(mut alpn_info) = self.tls_alpn_detector.create_protocol_info(alpn_result) {
+                        alpn_info.add_metadata(
+                            "tls_scanner_hint",
+                            self.tls_alpn_detector.is_scanner_like(data).to_string(),
+                        );
+                        
+                        // 检查ALPN检测到的协议是否在启用列表中
+                        if self.is_enabled(alpn_info.protocol_type) {
+                            return Some(alpn_info);
                         }
                     }
-                    
-                    let mut info = ProtocolInfo::new(signature.protocol, signature.confidence);
-                    info.add_metadata("detection_method", "magic_bytes");
-                    info.add_metadata("signature_desc", &signature.description);
-                    return Some(info);
                 }
             }
+            
+            let mut info = ProtocolInfo::new(signature.protocol, signature.confidence);
+            info.custom_name = signature.custom_name.clone();
+            info.add_metadata("detection_method", "magic_bytes");
+            info.add_metadata("signature_desc", &signature.description);
+            if signature.protocol == ProtocolType::TLS {
+                info.add_metadata(
+                    "tls_scanner_hint",
+                    self.tls_alpn_detector.is_scanner_like(data).to_string(),
+                );
+            }
+            if let Some(encoding) = http::response_content_encoding(data) {
+                encoding.annotate(&mut info);
+            }
+            if signature.protocol == ProtocolType::HTTP2 {
+                if let Some(anomaly) = http2::detect_anomaly(data) {
+                    anomaly.annotate(&mut info);
+                }
+            }
+            return Some(info);
         }
         
         // 2. 特殊的启发式检测（基于第一字节）
-        self.heuristic_by_first_byte(data, first_byte)
+        self.heuristic_by_first_byte(data)
     }
     
     /// 基于第一字节的启发式检测
-    fn heuristic_by_first_byte(&self, data: &[u8], first_byte: u8) -> Option<ProtocolInfo> {
-        let confidence = match first_byte {
+    #[cfg(feature = "std")]
+    fn heuristic_by_first_byte(&self, data: &[u8]) -> Option<ProtocolInfo> {
+        let (protocol, conf, desc) = self.first_byte_guess(data)?;
+        let mut info = ProtocolInfo::new(protocol, conf);
+        info.add_metadata("detection_method", "heuristic");
+        info.add_metadata("heuristic_desc", desc);
+        Some(info)
+    }
+    
+    /// 第一字节启发式表，返回协议、置信度和描述
+    fn first_byte_guess(&self, data: &[u8]) -> Option<(ProtocolType, f32, &'static str)> {
+        let guess = match *data.first()? {
             // TLS 内容类型
             0x14 | 0x15 | 0x16 | 0x17 => {
                 if data.len() >= 3 && data[1] == 0x03 {
@@ -405,35 +770,24 @@ impl MagicDetector {
             _ => None,
         };
         
-        confidence.and_then(|(protocol, conf, desc)| {
-            // 🎯 检查协议过滤器
-            if let Some(ref enabled) = self.enabled_protocols {
-                if !enabled.contains(&protocol) {
-                    return None;
-                }
-            }
-            
-            let mut info = ProtocolInfo::new(protocol, conf);
-            info.add_metadata("detection_method", "heuristic");
-            info.add_metadata("heuristic_desc", desc);
-            Some(info)
-        })
+        // 🎯 检查协议过滤器
+        guess.filter(|(protocol, _, _)| self.is_enabled(*protocol))
     }
     
     /// 深度魔法包检测（检查所有已知特征）
+    #[cfg(feature = "std")]
     pub fn deep_detect(&self, data: &[u8]) -> Vec<ProtocolInfo> {
         let mut results = Vec::new();
         
         for signature in &self.all_signatures {
             // 检查协议过滤器
-            if let Some(ref enabled) = self.enabled_protocols {
-                if !enabled.contains(&signature.protocol) {
-                    continue;
-                }
+            if !self.is_enabled(signature.protocol) {
+                continue;
             }
             
             if signature.matches(data) {
                 let mut info = ProtocolInfo::new(signature.protocol, signature.confidence);
+                info.custom_name = signature.custom_name.clone();
                 info.add_metadata("detection_method", "magic_bytes");
                 info.add_metadata("signature_desc", &signature.description);
                 info.add_metadata("match_offset", &signature.offset.to_string());
@@ -441,6 +795,8 @@ impl MagicDetector {
             }
         }
         
+        results.retain(|info| !self.is_suppressed(info, data));
+        
         // 按置信度排序
         results.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
         results
@@ -448,11 +804,32 @@ impl MagicDetector {
     
     /// 获取所有支持的协议
     pub fn supported_protocols(&self) -> Vec<ProtocolType> {
-        self.all_signatures.iter()
+        let mut protocols: Vec<ProtocolType> = self.all_signatures.iter()
             .map(|sig| sig.protocol)
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect()
+            .collect();
+        protocols.sort();
+        protocols.dedup();
+        protocols
+    }
+    
+    /// 按添加顺序遍历所有已注册的特征（含内置特征）
+    pub fn signatures(&self) -> impl Iterator<Item = &MagicSignature> {
+        self.all_signatures.iter()
+    }
+    
+    /// 已注册的特征数量
+    pub fn signature_count(&self) -> usize {
+        self.all_signatures.len()
+    }
+    
+    /// 移除某协议的所有特征（含内置特征），返回移除的数量
+    ///
+    /// 适用于关闭误报较多的内置特征，如Redis的 `*` 或SMTP的 `220 `。
+    pub fn remove_signatures_for_protocol(&mut self, protocol: ProtocolType) -> usize {
+        let before = self.all_signatures.len();
+        self.all_signatures.retain(|signature| signature.protocol != protocol);
+        self.byte_indexed_signatures.retain(|signature| signature.protocol != protocol);
+        before - self.all_signatures.len()
     }
     
     /// 获取特定协议的所有特征
@@ -550,10 +927,48 @@ impl CustomSignatureBuilder {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_remove_signatures_for_protocol() {
+        let mut detector = MagicDetector::new();
+        let total = detector.signature_count();
+        assert_eq!(detector.signatures().count(), total);
+        
+        let smtp_banner = b"220 mx.example.com ESMTP Postfix\r\n";
+        assert_eq!(detector.quick_detect(smtp_banner).unwrap().protocol_type, ProtocolType::SMTP);
+        
+        let removed = detector.remove_signatures_for_protocol(ProtocolType::SMTP);
+        assert!(removed > 0);
+        assert_eq!(detector.signature_count(), total - removed);
+        assert!(detector.signatures().all(|signature| signature.protocol != ProtocolType::SMTP));
+        assert!(detector.quick_detect(smtp_banner).is_none_or(|info| info.protocol_type != ProtocolType::SMTP));
+        assert_eq!(detector.remove_signatures_for_protocol(ProtocolType::SMTP), 0);
+        
+        // 其他协议的特征不受影响
+        let result = detector.quick_detect(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(result.protocol_type, ProtocolType::HTTP1_1);
+    }
+    
+    #[test]
+    fn test_quick_classify_agrees_with_quick_detect() {
+        let detector = MagicDetector::new();
+        let samples: [&[u8]; 5] = [
+            b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n",
+            b"Dx\x00\x01\x02\x03",
+            b"SSH-2.0-OpenSSH_8.9\r\n",
+            b"\x01\x02\x03",
+        ];
+        
+        for data in samples {
+            let expected = detector.quick_detect(data).map(|info| (info.protocol_type, info.confidence));
+            assert_eq!(detector.quick_classify(data), expected);
+        }
+    }
+    
     #[test]
     fn test_http_magic_detection() {
         let detector = MagicDetector::new();
@@ -599,4 +1014,31 @@ mod tests {
         assert_eq!(result.protocol_type, ProtocolType::Custom);
         assert_eq!(result.confidence, 0.95);
     }
+    
+    #[test]
+    fn test_negative_signature_suppresses_http() {
+        let mut detector = MagicDetector::new();
+        
+        // 默认排除特征：首行含NUL的弱HTTP启发式匹配被压制
+        assert!(detector.quick_detect(b"Dx\x00\x01\x02\x03").is_none());
+        let result = detector.quick_detect(b"Dxyz\r\n\x00").unwrap();
+        assert_eq!(result.protocol_type, ProtocolType::HTTP1_1);
+        
+        // 强特征匹配不受默认排除特征影响
+        let binary_get = b"GET \x00\x7f\x00\x01 HTTP/1.1\r\n";
+        let result = detector.quick_detect(binary_get).unwrap();
+        assert_eq!(result.protocol_type, ProtocolType::HTTP1_1);
+        
+        // 自定义排除特征压制误报
+        detector.add_negative_signature(
+            NegativeSignature::new(ProtocolType::HTTP1_1, vec![0x00], "binary request line")
+                .with_scope(NegativeScope::FirstLine)
+        );
+        assert!(detector.quick_detect(binary_get).is_none());
+        assert!(detector.deep_detect(binary_get).iter()
+            .all(|info| info.protocol_type != ProtocolType::HTTP1_1));
+        
+        let result = detector.quick_detect(b"GET / HTTP/1.1\r\n\x00").unwrap();
+        assert_eq!(result.protocol_type, ProtocolType::HTTP1_1);
+    }
 }
\ No newline at end of file
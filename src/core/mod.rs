@@ -1,16 +1,40 @@
 //! 核心抽象模块
 //!
 //! 包含协议探测的核心接口和协议定义。
+//! 关闭 `std` 特性时只保留 `protocol` 中的协议类型和 `magic` 魔法包检测。
 
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
 pub mod detector;
 pub mod protocol;
+#[cfg(feature = "std")]
 pub mod fingerprint;
+#[cfg(feature = "std")]
 pub mod probe;
 pub mod magic;
+#[cfg(feature = "std")]
 pub mod tls_alpn;
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod static_detector;
 
-pub use detector::{ProtocolDetector, DetectionResult};
-pub use protocol::{ProtocolType, ProtocolInfo};
-pub use probe::{ProbeStrategy, ProbeConfig, ProbeContext, ProtocolProbe, ProbeRegistry};
-pub use magic::{MagicDetector, MagicSignature, CustomSignatureBuilder};
-pub use tls_alpn::{TlsAlpnDetector, AlpnDetectionResult, TlsRecordType, TlsHandshakeType, TlsExtensionType};
\ No newline at end of file
+#[cfg(feature = "std")]
+pub use detector::{ProtocolDetector, DetectionResult, DetectionContext, ConfidenceMap, ProbeTimings, ClassifyOutcome, DetectionProgress, IncrementalState};
+pub use protocol::ProtocolType;
+#[cfg(feature = "std")]
+pub use protocol::{ProtocolInfo, ProtocolRegistry};
+#[cfg(feature = "std")]
+pub use probe::{ProbeStrategy, MatchMode, DetectionStage, ProbeConfig, ProbeContext, ProtocolProbe, ProbeRegistry};
+pub use magic::{MagicDetector, MagicSignature, CustomSignatureBuilder, NegativeSignature, NegativeScope};
+#[cfg(feature = "std")]
+pub use cache::DetectionCache;
+#[cfg(feature = "std")]
+pub use sink::{DetectionSink, ChannelSink, CountingSink};
+#[cfg(feature = "std")]
+pub use static_detector::StaticDetector;
+#[cfg(feature = "std")]
+pub use fingerprint::{Ja3Fingerprinter, Ja3Kind, Ja3Result, HttpRequestInfo};
+#[cfg(feature = "std")]
+pub use tls_alpn::{TlsAlpnDetector, AlpnDetectionResult, TlsRecordType, TlsHandshakeType, TlsExtensionType, tls_version_name};
\ No newline at end of file
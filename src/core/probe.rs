@@ -5,8 +5,10 @@
 use crate::core::protocol::{ProtocolType, ProtocolInfo};
 use crate::core::detector::{DetectionResult, DetectionMethod};
 use crate::error::{DetectorError, Result};
+use crate::probe::ProbeType;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 
 /// 探测策略
 #[derive(Debug, Clone, PartialEq)]
@@ -21,6 +23,53 @@ pub enum ProbeStrategy {
     Adaptive,
 }
 
+/// 结果选择模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// 首个高置信度匹配 - 命中后跳过剩余探测，结果可能受探测顺序影响
+    FirstMatch,
+    /// 最佳匹配 - 运行所有探测器后选择置信度最高的结果
+    ///
+    /// 结果与启用协议的顺序无关；置信度相同时按探测器优先级决定。
+    /// 超过截止时间时仍会提前结束。
+    BestMatch,
+}
+
+/// 探测流水线阶段
+///
+/// 默认顺序为 `Magic → Passive → Heuristic → Simd`。`FirstMatch` 模式下，
+/// 任一阶段得到高置信度结果（魔法包 >= 0.95，探测器 >= 0.9）即跳过后续阶段；
+/// `BestMatch` 模式下所有阶段都会运行并把结果交给聚合器。未列出的阶段不会运行。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DetectionStage {
+    /// 魔法包快速检测
+    Magic,
+    /// 被动、主动和混合方法的已注册探测器
+    Passive,
+    /// 检测方法为 `Heuristic` 的已注册探测器
+    Heuristic,
+    /// 检测方法为 `SimdAccelerated` 的已注册探测器
+    Simd,
+}
+
+impl DetectionStage {
+    /// 默认流水线顺序
+    pub fn default_pipeline() -> Vec<DetectionStage> {
+        vec![Self::Magic, Self::Passive, Self::Heuristic, Self::Simd]
+    }
+    
+    /// 探测器按其检测方法归入的阶段
+    pub fn for_method(method: DetectionMethod) -> Self {
+        match method {
+            DetectionMethod::Heuristic => Self::Heuristic,
+            DetectionMethod::SimdAccelerated => Self::Simd,
+            DetectionMethod::Passive | DetectionMethod::Active | DetectionMethod::Hybrid | DetectionMethod::Cached => {
+                Self::Passive
+            }
+        }
+    }
+}
+
 /// 探测配置
 #[derive(Debug, Clone)]
 pub struct ProbeConfig {
@@ -36,6 +85,38 @@ pub struct ProbeConfig {
     pub enable_heuristic: bool,
     /// 探测缓冲区大小
     pub buffer_size: usize,
+    /// 单次探测保留的最大候选结果数（默认16）
+    pub max_candidates: usize,
+    /// 是否在按协议探测后运行全局探测器补充扫描（默认关闭）
+    ///
+    /// 支持启用协议的全局探测器在按协议探测阶段已会运行，补充扫描只覆盖其余探测器。
+    pub run_global_probes: bool,
+    /// 各探测方法的置信度区间 `(下限, 上限)`，聚合前对候选结果钳制
+    ///
+    /// 魔法包检测结果按 `SimdAccelerated` 方法处理。
+    pub confidence_bounds: HashMap<DetectionMethod, (f32, f32)>,
+    /// 结果选择模式（默认 `FirstMatch`）
+    pub match_mode: MatchMode,
+    /// 探测流水线阶段及顺序（默认见 [`DetectionStage::default_pipeline`]）
+    pub pipeline: Vec<DetectionStage>,
+    /// 各探测引擎类型的置信度权重，未配置的类型权重为1
+    ///
+    /// 由 [`crate::probe::ProbeAggregator`] 在合并引擎结果时使用。
+    pub probe_weights: HashMap<ProbeType, f32>,
+    /// 置信度相同时的协议优先级，靠前的协议优先，未列出的协议排在最后
+    pub protocol_priority: Vec<ProtocolType>,
+    /// 写入 `DetectionResult::alternatives` 的备选协议最低置信度（默认0.3）
+    pub alternative_min_confidence: f32,
+    /// 歧义判定的置信度差（默认0.0，不判定）
+    ///
+    /// 大于0时，若另一协议也达到 `min_confidence` 且与最高置信度之差不超过该值，
+    /// 探测返回 [`crate::error::DetectorError::Ambiguous`] 而不是选出其中一个。
+    pub ambiguity_margin: f32,
+    /// 各协议接受结论所需的最少字节数，未配置的协议使用 `DetectionConfig::min_probe_size`
+    ///
+    /// 前缀即可确定的协议（SSH `SSH-`、HTTP方法）可设置较小的值以便从很短的读取中识别，
+    /// 需要校验长度的协议可设置较大的值，数据不足时等待更多数据。
+    pub protocol_min_bytes: HashMap<ProtocolType, usize>,
 }
 
 impl Default for ProbeConfig {
@@ -47,6 +128,16 @@ impl Default for ProbeConfig {
             enable_simd: true,
             enable_heuristic: true,
             buffer_size: 4096,
+            max_candidates: 16,
+            run_global_probes: false,
+            confidence_bounds: HashMap::new(),
+            match_mode: MatchMode::FirstMatch,
+            pipeline: DetectionStage::default_pipeline(),
+            probe_weights: HashMap::new(),
+            protocol_priority: Vec::new(),
+            alternative_min_confidence: 0.3,
+            ambiguity_margin: 0.0,
+            protocol_min_bytes: HashMap::new(),
         }
     }
 }
@@ -64,6 +155,14 @@ pub struct ProbeContext {
     pub current_confidence: f32,
     /// 候选协议列表
     pub candidates: Vec<ProtocolInfo>,
+    /// 调用方提供的关联ID
+    pub correlation_id: Option<String>,
+    /// 探测截止时间
+    pub deadline: Option<Instant>,
+    /// 启用的协议列表，探测器据此跳过未启用的协议（`None` 表示不限制）
+    pub enabled_protocols: Option<Vec<ProtocolType>>,
+    /// 探测器为得出结论请求的总数据量（0表示无需更多数据）
+    pub requested_bytes: usize,
 }
 
 impl ProbeContext {
@@ -75,9 +174,24 @@ impl ProbeContext {
             attempt_count: 0,
             current_confidence: 0.0,
             candidates: Vec::new(),
+            correlation_id: None,
+            deadline: None,
+            enabled_protocols: None,
+            requested_bytes: 0,
         }
     }
     
+    /// 报文头合法但报文尚未收全时，请求至少 `total` 字节的数据
+    pub fn request_more_data(&mut self, total: usize) {
+        self.requested_bytes = self.requested_bytes.max(total);
+    }
+    
+    /// 协议是否已启用
+    pub fn is_protocol_enabled(&self, protocol: ProtocolType) -> bool {
+        self.enabled_protocols.as_ref()
+            .is_none_or(|enabled| enabled.contains(&protocol))
+    }
+    
     /// 添加候选协议
     pub fn add_candidate(&mut self, protocol: ProtocolInfo) {
         self.candidates.push(protocol);
@@ -91,11 +205,16 @@ impl ProbeContext {
     
     /// 获取最佳候选协议
     pub fn best_candidate(&self) -> Option<&ProtocolInfo> {
-        self.candidates.iter().max_by(|a, b| a.confidence.partial_cmp(&b.confidence).unwrap())
+        self.candidates.iter()
+            .filter(|info| !info.confidence.is_nan())
+            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
     }
     
     /// 检查是否超时
     pub fn is_timeout(&self, max_time: Duration) -> bool {
+        if self.deadline.is_some_and(|deadline| Instant::now() > deadline) {
+            return true;
+        }
         self.start_time.elapsed() > max_time
     }
 }
@@ -120,6 +239,13 @@ pub trait ProtocolProbe: Send + Sync {
     fn needs_more_data(&self, data: &[u8]) -> bool {
         data.len() < 64 // 默认需要至少64字节
     }
+    
+    /// 探测方法，用于匹配 `ProbeConfig::confidence_bounds`
+    ///
+    /// 默认视为被动探测；启发式、主动探测器需覆盖此方法。
+    fn detection_method(&self) -> DetectionMethod {
+        DetectionMethod::Passive
+    }
 }
 
 /// 探测器注册表
@@ -205,6 +331,13 @@ impl ProbeRegistry {
         probes
     }
     
+    /// 获取所有全局探测器（按优先级排序）
+    pub fn global_probes(&self) -> Vec<&dyn ProtocolProbe> {
+        let mut probes: Vec<&dyn ProtocolProbe> = self.global_probes.iter().map(|p| p.as_ref()).collect();
+        probes.sort_by_key(|p| std::cmp::Reverse(p.priority()));
+        probes
+    }
+    
     /// 获取所有探测器（保留用于向后兼容，但不推荐使用）
     #[deprecated(note = "使用 get_probes_for_enabled_protocol 以获得更好的性能")]
     pub fn get_all_probes(&self) -> Vec<&dyn ProtocolProbe> {
@@ -242,18 +375,21 @@ impl ProbeAggregator {
             return None;
         }
 
-        // 过滤掉Unknown类型的结果，但保留Custom类型
+        // 过滤掉Unknown类型及置信度为NaN的结果，但保留Custom类型
         let valid_results: Vec<ProtocolInfo> = results.into_iter()
-            .filter(|info| info.protocol_type != ProtocolType::Unknown)
+            .filter(|info| info.protocol_type != ProtocolType::Unknown && !info.confidence.is_nan())
             .collect();
 
         if valid_results.is_empty() {
             return None;
         }
 
-        // 按置信度排序
+        // 按置信度排序，相同时按协议优先级
         let mut sorted_results = valid_results;
-        sorted_results.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+        sorted_results.sort_by(|a, b| {
+            b.confidence.total_cmp(&a.confidence)
+                .then_with(|| self.protocol_rank(a.protocol_type).cmp(&self.protocol_rank(b.protocol_type)))
+        });
 
         // 检查最高置信度是否满足阈值
         let best = &sorted_results[0];
@@ -264,6 +400,144 @@ impl ProbeAggregator {
         }
     }
     
+    /// 从已排序的候选中提取备选协议
+    ///
+    /// 每个协议只保留最高置信度，排除 `Unknown` 和胜出协议，
+    /// 低于 `alternative_min_confidence` 的候选被丢弃，顺序与候选排序一致。
+    pub fn alternatives(&self, candidates: &[ProtocolInfo], winner: ProtocolType) -> Vec<(ProtocolType, f32)> {
+        let mut alternatives: Vec<(ProtocolType, f32)> = Vec::new();
+        for info in candidates {
+            if info.protocol_type == winner
+                || info.protocol_type == ProtocolType::Unknown
+                || info.confidence < self.config.alternative_min_confidence
+                || alternatives.iter().any(|(protocol, _)| *protocol == info.protocol_type)
+            {
+                continue;
+            }
+            alternatives.push((info.protocol_type, info.confidence));
+        }
+        alternatives
+    }
+    
+    /// 检查达到阈值的候选中是否有多个协议的置信度过于接近
+    ///
+    /// 返回与最高置信度之差不超过 `ambiguity_margin` 的协议（每个协议只保留最高置信度，按置信度降序）；
+    /// 只有一个协议或未启用歧义判定时返回 `None`。
+    pub fn ambiguous_candidates(&self, candidates: &[ProtocolInfo]) -> Option<Vec<(ProtocolType, f32)>> {
+        let margin = self.config.ambiguity_margin;
+        if margin <= 0.0 {
+            return None;
+        }
+        
+        let mut best: Vec<(ProtocolType, f32)> = Vec::new();
+        for info in candidates {
+            if info.protocol_type == ProtocolType::Unknown
+                || info.confidence.is_nan()
+                || info.confidence < self.config.min_confidence
+            {
+                continue;
+            }
+            match best.iter_mut().find(|(protocol, _)| *protocol == info.protocol_type) {
+                Some((_, confidence)) => *confidence = confidence.max(info.confidence),
+                None => best.push((info.protocol_type, info.confidence)),
+            }
+        }
+        
+        let top = best.iter().map(|(_, confidence)| *confidence).fold(f32::MIN, f32::max);
+        best.retain(|(_, confidence)| top - confidence <= margin);
+        best.sort_by(|a, b| b.1.total_cmp(&a.1));
+        (best.len() > 1).then_some(best)
+    }
+    
+    /// 协议在优先级列表中的位置，未列出的协议排在所有已列出的协议之后
+    fn protocol_rank(&self, protocol: ProtocolType) -> usize {
+        self.config.protocol_priority.iter()
+            .position(|&p| p == protocol)
+            .unwrap_or(self.config.protocol_priority.len())
+    }
+    
+    /// 按探测方法的置信度区间钳制候选结果
+    pub fn clamp_confidence(&self, method: DetectionMethod, info: &mut ProtocolInfo) {
+        if let Some(&(floor, ceil)) = self.config.confidence_bounds.get(&method) {
+            info.confidence = info.confidence.clamp(floor, ceil);
+        }
+    }
+    
+    /// 按 (协议, 探测器名称) 去重并限制候选数量
+    ///
+    /// 相同键只保留置信度最高的结果，最终按置信度降序截断到 `max_candidates`。
+    pub fn limit_candidates(&self, candidates: Vec<(String, ProtocolInfo)>) -> Vec<ProtocolInfo> {
+        self.rank_candidates(candidates, |_| 0)
+    }
+    
+    /// 去重、排序并限制候选数量，置信度相同时依次按协议优先级和探测器优先级排序
+    ///
+    /// 排序只依赖候选内容，与候选的输入顺序无关。
+    pub fn rank_candidates<F>(&self, candidates: Vec<(String, ProtocolInfo)>, priority: F) -> Vec<ProtocolInfo>
+    where
+        F: Fn(&str) -> u8,
+    {
+        let mut best: HashMap<(ProtocolType, String), ProtocolInfo> = HashMap::with_capacity(candidates.len());
+        
+        // 自定义探测器可能返回NaN置信度，直接丢弃
+        for (detector_name, info) in candidates.into_iter().filter(|(_, info)| !info.confidence.is_nan()) {
+            match best.entry((info.protocol_type, detector_name)) {
+                Entry::Occupied(mut entry) => {
+                    if info.confidence > entry.get().confidence {
+                        entry.insert(info);
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(info);
+                }
+            }
+        }
+        
+        let mut ranked: Vec<(u8, String, ProtocolInfo)> = best.into_iter()
+            .map(|((_, name), info)| (priority(&name), name, info))
+            .collect();
+        ranked.sort_by(|(pa, na, a), (pb, nb, b)| {
+            b.confidence.total_cmp(&a.confidence)
+                .then_with(|| self.protocol_rank(a.protocol_type).cmp(&self.protocol_rank(b.protocol_type)))
+                .then_with(|| pb.cmp(pa))
+                .then_with(|| a.protocol_type.cmp(&b.protocol_type))
+                .then_with(|| na.cmp(nb))
+        });
+        ranked.truncate(self.config.max_candidates);
+        ranked.into_iter().map(|(_, _, info)| info).collect()
+    }
+    
+    /// 根据报文封装区分QUIC与TCP上的TLS，写入 `transport` 元数据
+    ///
+    /// QUIC握手内嵌TLS 1.3（长包头 + CRYPTO帧），TCP上的TLS使用TLS记录头，
+    /// 两者都可能被识别为加密协议，需由封装格式区分。
+    pub fn annotate_transport(&self, info: &mut ProtocolInfo, data: &[u8]) {
+        // OpenVPN和IKE的封装方式由被动探测器解析时写入
+        if !info.protocol_type.is_encrypted()
+            || matches!(info.protocol_type, ProtocolType::SSH | ProtocolType::OpenVPN | ProtocolType::IKE)
+        {
+            return;
+        }
+        
+        if let Some(transport) = Self::transport_framing(data) {
+            info.add_metadata("transport", transport);
+        }
+    }
+    
+    /// 识别承载加密握手的封装格式
+    fn transport_framing(data: &[u8]) -> Option<&'static str> {
+        match data {
+            // TLS记录头：内容类型 + 主版本3 + 次版本0~4
+            [0x14..=0x17, 0x03, 0x00..=0x04, ..] => Some("tcp-tls"),
+            // QUIC长包头：Header Form与Fixed Bit置位，版本号非0（0为版本协商），DCID不超过20字节
+            [first, v0, v1, v2, v3, dcid_len, ..]
+                if first & 0xc0 == 0xc0
+                    && u32::from_be_bytes([*v0, *v1, *v2, *v3]) != 0
+                    && *dcid_len <= 20 => Some("udp-quic"),
+            _ => None,
+        }
+    }
+    
     /// 创建最终的探测结果
     pub fn create_result(
         &self,
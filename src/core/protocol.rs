@@ -2,11 +2,16 @@
 //!
 //! 定义PSI-Detector支持的协议类型和相关信息。
 
+#[cfg(feature = "std")]
+use crate::error::{DetectorError, Result};
+#[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use alloc::{vec, vec::Vec};
+use core::fmt;
 
 /// 协议类型枚举
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub enum ProtocolType {
     /// HTTP/1.0
     HTTP1_0,
@@ -36,12 +41,44 @@ pub enum ProtocolType {
     FTP,
     /// SMTP
     SMTP,
+    /// IMAP
+    IMAP,
+    /// POP3
+    POP3,
     /// DNS
     DNS,
     /// Redis
     Redis,
     /// MySQL
     MySQL,
+    /// PostgreSQL
+    PostgreSQL,
+    /// Memcached（文本或二进制协议）
+    Memcached,
+    /// ClickHouse原生TCP协议
+    ClickHouse,
+    /// IRC
+    IRC,
+    /// XMPP
+    XMPP,
+    /// 长度前缀的裸Protobuf（TCP上的RPC，无HTTP）
+    Protobuf,
+    /// Apache Thrift（分帧传输）
+    Thrift,
+    /// Apache Avro（RPC握手或对象容器）
+    Avro,
+    /// VNC (RFB协议)
+    VNC,
+    /// RDP (远程桌面协议)
+    RDP,
+    /// WebTransport (基于HTTP/3)
+    WebTransport,
+    /// OpenVPN（UDP或带长度前缀的TCP）
+    OpenVPN,
+    /// IKE/ISAKMP（IPsec密钥交换）
+    IKE,
+    /// 疑似混淆传输（obfs4等无握手特征的随机字节流，启发式推断）
+    Obfuscated,
     /// 自定义协议
     Custom,
     /// 未知协议
@@ -65,9 +102,25 @@ impl fmt::Display for ProtocolType {
             Self::SSH => write!(f, "SSH"),
             Self::FTP => write!(f, "FTP"),
             Self::SMTP => write!(f, "SMTP"),
+            Self::IMAP => write!(f, "IMAP"),
+            Self::POP3 => write!(f, "POP3"),
             Self::DNS => write!(f, "DNS"),
             Self::Redis => write!(f, "Redis"),
             Self::MySQL => write!(f, "MySQL"),
+            Self::PostgreSQL => write!(f, "PostgreSQL"),
+            Self::Memcached => write!(f, "Memcached"),
+            Self::ClickHouse => write!(f, "ClickHouse"),
+            Self::IRC => write!(f, "IRC"),
+            Self::XMPP => write!(f, "XMPP"),
+            Self::Protobuf => write!(f, "Protobuf"),
+            Self::Thrift => write!(f, "Thrift"),
+            Self::Avro => write!(f, "Avro"),
+            Self::VNC => write!(f, "VNC"),
+            Self::RDP => write!(f, "RDP"),
+            Self::WebTransport => write!(f, "WebTransport"),
+            Self::OpenVPN => write!(f, "OpenVPN"),
+            Self::IKE => write!(f, "IKE"),
+            Self::Obfuscated => write!(f, "Obfuscated"),
             Self::Custom => write!(f, "Custom"),
             Self::Unknown => write!(f, "Unknown"),
         }
@@ -79,7 +132,7 @@ impl ProtocolType {
     pub fn default_port(&self) -> Option<u16> {
         match self {
             Self::HTTP1_0 | Self::HTTP1_1 => Some(80),
-            Self::HTTP2 | Self::HTTP3 => Some(443),
+            Self::HTTP2 | Self::HTTP3 | Self::WebTransport => Some(443),
             Self::GRPC => Some(443),
             Self::WebSocket => Some(80),
             Self::QUIC => Some(443),
@@ -88,10 +141,50 @@ impl ProtocolType {
             Self::SSH => Some(22),
             Self::FTP => Some(21),
             Self::SMTP => Some(25),
+            Self::IMAP => Some(143),
+            Self::POP3 => Some(110),
             Self::DNS => Some(53),
             Self::Redis => Some(6379),
             Self::MySQL => Some(3306),
-            Self::TCP | Self::UDP | Self::Custom | Self::Unknown => None,
+            Self::PostgreSQL => Some(5432),
+            Self::Memcached => Some(11211),
+            Self::ClickHouse => Some(9000),
+            Self::IRC => Some(6667),
+            Self::XMPP => Some(5222),
+            Self::VNC => Some(5900),
+            Self::RDP => Some(3389),
+            Self::Thrift => Some(9090),
+            Self::OpenVPN => Some(1194),
+            Self::IKE => Some(500),
+            Self::TCP | Self::UDP | Self::Protobuf | Self::Avro | Self::Obfuscated | Self::Custom
+                | Self::Unknown => None,
+        }
+    }
+    
+    /// 获取首次探测推荐读取的字节数
+    ///
+    /// 足以覆盖该协议典型的首个握手报文（请求行、连接前言、ClientHello等）。
+    pub fn recommended_probe_size(&self) -> usize {
+        match self {
+            Self::HTTP1_0 | Self::HTTP1_1 => 64,
+            Self::HTTP2 => 64,
+            Self::GRPC | Self::WebSocket => 256,
+            // QUIC Initial 数据报至少1200字节
+            Self::HTTP3 | Self::QUIC | Self::WebTransport => 1200,
+            // 带SNI/ALPN的典型ClientHello
+            Self::TLS => 512,
+            Self::SSH | Self::FTP | Self::SMTP | Self::IMAP | Self::POP3 | Self::IRC | Self::RDP => 32,
+            Self::XMPP => 128,
+            Self::MySQL | Self::PostgreSQL | Self::Thrift | Self::Avro | Self::Custom => 64,
+            Self::ClickHouse => 64,
+            // 硬重置包和ISAKMP头都很短，64字节可覆盖tls-auth的HMAC
+            Self::OpenVPN | Self::IKE => 64,
+            // 二进制协议的请求头为24字节
+            Self::Memcached => 24,
+            // 覆盖obfs4客户端握手的最小长度
+            Self::Obfuscated => 256,
+            Self::MQTT | Self::TCP | Self::UDP | Self::DNS | Self::Redis
+                | Self::Protobuf | Self::VNC | Self::Unknown => 16,
         }
     }
     
@@ -105,6 +198,7 @@ impl ProtocolType {
                 | Self::HTTP3
                 | Self::GRPC
                 | Self::WebSocket
+                | Self::WebTransport
         )
     }
     
@@ -121,6 +215,7 @@ impl ProtocolType {
         matches!(
             self,
             Self::HTTP2 | Self::HTTP3 | Self::GRPC | Self::QUIC | Self::TLS | Self::SSH
+                | Self::WebTransport | Self::Obfuscated | Self::OpenVPN | Self::IKE
         )
     }
     
@@ -128,14 +223,20 @@ impl ProtocolType {
     pub fn protocol_family(&self) -> ProtocolFamily {
         match self {
             Self::HTTP1_0 | Self::HTTP1_1 | Self::HTTP2 | Self::HTTP3 => ProtocolFamily::HTTP,
-            Self::GRPC => ProtocolFamily::RPC,
+            Self::GRPC | Self::Protobuf | Self::Thrift | Self::Avro => ProtocolFamily::RPC,
             Self::WebSocket => ProtocolFamily::WebSocket,
+            Self::WebTransport => ProtocolFamily::HTTP,
             Self::QUIC => ProtocolFamily::QUIC,
             Self::MQTT => ProtocolFamily::IoT,
             Self::TCP | Self::UDP => ProtocolFamily::Transport,
             Self::TLS => ProtocolFamily::Security,
-            Self::SSH => ProtocolFamily::Remote,
+            Self::SSH | Self::VNC | Self::RDP => ProtocolFamily::Remote,
             Self::FTP | Self::SMTP | Self::DNS | Self::Redis | Self::MySQL => ProtocolFamily::Transport,
+            Self::IMAP | Self::POP3 => ProtocolFamily::Transport,
+            Self::PostgreSQL => ProtocolFamily::Transport,
+            Self::Memcached | Self::ClickHouse => ProtocolFamily::Transport,
+            Self::IRC | Self::XMPP => ProtocolFamily::Transport,
+            Self::Obfuscated | Self::OpenVPN | Self::IKE => ProtocolFamily::Security,
             Self::Custom => ProtocolFamily::Unknown,
             Self::Unknown => ProtocolFamily::Unknown,
         }
@@ -158,16 +259,33 @@ impl ProtocolType {
             Self::SSH,
             Self::FTP,
             Self::SMTP,
+            Self::IMAP,
+            Self::POP3,
             Self::DNS,
             Self::Redis,
             Self::MySQL,
+            Self::PostgreSQL,
+            Self::Memcached,
+            Self::ClickHouse,
+            Self::IRC,
+            Self::XMPP,
+            Self::Protobuf,
+            Self::Thrift,
+            Self::Avro,
+            Self::VNC,
+            Self::RDP,
+            Self::WebTransport,
+            Self::OpenVPN,
+            Self::IKE,
+            Self::Obfuscated,
             Self::Custom,
         ]
     }
 }
 
 /// 协议族
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub enum ProtocolFamily {
     /// HTTP协议族
     HTTP,
@@ -190,6 +308,7 @@ pub enum ProtocolFamily {
 }
 
 /// 协议信息
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProtocolInfo {
     /// 协议类型
@@ -201,9 +320,15 @@ pub struct ProtocolInfo {
     /// 检测到的特征
     pub features: Vec<String>,
     /// 额外元数据
-    pub metadata: std::collections::HashMap<String, String>,
+    ///
+    /// 以 `Arc` 共享，克隆时不复制整张表；修改时按需复制（写时复制）。
+    pub metadata: std::sync::Arc<std::collections::HashMap<String, String>>,
+    /// 自定义协议名称（仅 `ProtocolType::Custom`，见 [`ProtocolRegistry`]）
+    #[serde(default)]
+    pub custom_name: Option<String>,
 }
 
+#[cfg(feature = "std")]
 impl ProtocolInfo {
     /// 创建新的协议信息
     pub fn new(protocol_type: ProtocolType, confidence: f32) -> Self {
@@ -212,10 +337,17 @@ impl ProtocolInfo {
             version: None,
             confidence: confidence.clamp(0.0, 1.0),
             features: Vec::new(),
-            metadata: std::collections::HashMap::new(),
+            metadata: std::sync::Arc::default(),
+            custom_name: None,
         }
     }
     
+    /// 设置自定义协议名称
+    pub fn with_custom_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.custom_name = Some(name.into());
+        self
+    }
+    
     /// 设置版本
     pub fn with_version<S: Into<String>>(mut self, version: S) -> Self {
         self.version = Some(version.into());
@@ -233,7 +365,12 @@ impl ProtocolInfo {
         K: Into<String>,
         V: Into<String>,
     {
-        self.metadata.insert(key.into(), value.into());
+        self.make_mut_metadata().insert(key.into(), value.into());
+    }
+    
+    /// 获取可修改的元数据，与其他克隆共享时先复制一份
+    pub fn make_mut_metadata(&mut self) -> &mut std::collections::HashMap<String, String> {
+        std::sync::Arc::make_mut(&mut self.metadata)
     }
     
     /// 检查置信度是否足够高
@@ -247,7 +384,68 @@ impl ProtocolInfo {
     }
 }
 
+/// 自定义协议注册表
+///
+/// 为用户命名的协议分配稳定的小整数ID（按注册顺序从0开始），
+/// 使多个自定义协议在 `ProtocolType::Custom` 之下仍可区分。
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolRegistry {
+    names: Vec<String>,
+}
+
+#[cfg(feature = "std")]
+impl ProtocolRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 注册自定义协议名称，返回分配的ID
+    ///
+    /// 名称为空或已被注册时返回错误。
+    pub fn register<S: Into<String>>(&mut self, name: S) -> Result<u16> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(DetectorError::config_error("自定义协议名称不能为空"));
+        }
+        if self.id(&name).is_some() {
+            return Err(DetectorError::config_error(format!("自定义协议 {} 已注册", name)));
+        }
+        let id = u16::try_from(self.names.len())
+            .map_err(|_| DetectorError::config_error("自定义协议数量超过上限"))?;
+        self.names.push(name);
+        Ok(id)
+    }
+    
+    /// 按名称查找ID
+    pub fn id(&self, name: &str) -> Option<u16> {
+        self.names.iter().position(|n| n == name).map(|id| id as u16)
+    }
+    
+    /// 按ID查找名称
+    pub fn name(&self, id: u16) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+    
+    /// 已注册的协议数量
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    
+    /// 是否没有注册任何协议
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    
+    /// 按ID顺序列出所有名称
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+}
+
 /// 协议升级路径
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UpgradePath {
     /// 源协议
@@ -263,6 +461,7 @@ pub struct UpgradePath {
 }
 
 /// 升级方法
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UpgradeMethod {
     /// HTTP升级头
@@ -279,6 +478,7 @@ pub enum UpgradeMethod {
     Custom(String),
 }
 
+#[cfg(feature = "std")]
 impl UpgradePath {
     /// 创建新的升级路径
     pub fn new(from: ProtocolType, to: ProtocolType, method: UpgradeMethod) -> Self {
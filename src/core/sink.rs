@@ -0,0 +1,73 @@
+//! 探测结果输出模块
+//!
+//! 定义探测结果的输出接口，用于把探测结果投递到事件总线等下游系统。
+
+use crate::core::detector::DetectionResult;
+use crate::core::protocol::ProtocolType;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+/// 探测结果输出接口
+///
+/// 每次探测成功后调用，实现方不应阻塞探测流程。
+pub trait DetectionSink: Send + Sync {
+    /// 输出一条探测结果
+    fn emit(&self, result: &DetectionResult);
+}
+
+/// 通道输出：把探测结果发送到 `mpsc` 通道
+#[derive(Debug)]
+pub struct ChannelSink {
+    sender: Sender<DetectionResult>,
+}
+
+impl ChannelSink {
+    /// 创建通道输出
+    pub fn new(sender: Sender<DetectionResult>) -> Self {
+        Self { sender }
+    }
+}
+
+impl DetectionSink for ChannelSink {
+    fn emit(&self, result: &DetectionResult) {
+        // 接收端已关闭时静默丢弃
+        let _ = self.sender.send(result.clone());
+    }
+}
+
+/// 计数输出：统计探测成功次数
+#[derive(Debug, Default)]
+pub struct CountingSink {
+    total: AtomicU64,
+    by_protocol: Mutex<HashMap<ProtocolType, u64>>,
+}
+
+impl CountingSink {
+    /// 创建计数输出
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// 获取总次数
+    pub fn count(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+    
+    /// 获取指定协议的次数
+    pub fn count_for(&self, protocol: ProtocolType) -> u64 {
+        self.by_protocol.lock()
+            .map(|counts| counts.get(&protocol).copied().unwrap_or(0))
+            .unwrap_or(0)
+    }
+}
+
+impl DetectionSink for CountingSink {
+    fn emit(&self, result: &DetectionResult) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut counts) = self.by_protocol.lock() {
+            *counts.entry(result.protocol_type()).or_insert(0) += 1;
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! 静态协议集探测
+//!
+//! 协议集合在编译期确定时使用：按固定顺序逐个调用被动检测，
+//! 不经过 `dyn ProtocolProbe` 分发，也不构造 `DetectionResult`。
+
+use crate::core::protocol::ProtocolType;
+use crate::probe::PassiveProbe;
+
+/// 默认最小置信度（与被动探测器阈值一致）
+const DEFAULT_MIN_CONFIDENCE: f32 = 0.7;
+
+/// 固定协议集合的探测器
+///
+/// `N` 为协议数量，循环次数在编译期确定。只覆盖被动探测支持的协议，
+/// 适合协议集合已知的嵌入式或高吞吐场景。
+pub struct StaticDetector<const N: usize> {
+    protocols: [ProtocolType; N],
+    probe: PassiveProbe,
+    min_confidence: f32,
+}
+
+impl<const N: usize> StaticDetector<N> {
+    /// 创建探测器，置信度相同时按数组顺序取靠前的协议
+    pub fn new(protocols: [ProtocolType; N]) -> Self {
+        Self {
+            protocols,
+            probe: PassiveProbe::new(),
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+        }
+    }
+    
+    /// 设置最小置信度
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+    
+    /// 获取协议集合
+    pub fn protocols(&self) -> &[ProtocolType; N] {
+        &self.protocols
+    }
+    
+    /// 探测协议，返回置信度最高的 (协议, 置信度)，未达到阈值时返回 `None`
+    pub fn detect(&self, data: &[u8]) -> Option<(ProtocolType, f32)> {
+        let mut best: Option<(ProtocolType, f32)> = None;
+        for &protocol in &self.protocols {
+            if let Some(confidence) = self.probe.detect_protocol(protocol, data) {
+                if best.is_none_or(|(_, best_confidence)| confidence > best_confidence) {
+                    best = Some((protocol, confidence));
+                }
+            }
+        }
+        best.filter(|&(_, confidence)| confidence >= self.min_confidence)
+    }
+}
+
+impl<const N: usize> std::fmt::Debug for StaticDetector<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StaticDetector")
+            .field("protocols", &self.protocols)
+            .field("min_confidence", &self.min_confidence)
+            .finish()
+    }
+}
@@ -122,6 +122,68 @@ impl TlsExtensionType {
     }
 }
 
+/// SNI扩展类型
+const EXT_SERVER_NAME: u16 = 0x0000;
+
+/// ALPN扩展类型
+const EXT_ALPN: u16 = 0x0010;
+
+/// supported_versions扩展类型
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// key_share扩展类型
+const EXT_KEY_SHARE: u16 = 0x0033;
+
+/// encrypted_client_hello扩展类型
+const EXT_ENCRYPTED_CLIENT_HELLO: u16 = 0xfe0d;
+
+/// 判断是否为GREASE保留值（RFC 8701，形如 `0x?a?a` 且高低字节相同）
+pub(crate) fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// 扩展遍历的最大数量，防止畸形长度字段导致过长的解析
+const MAX_EXTENSIONS: usize = 128;
+
+/// SNI主机名的最大长度（DNS名称上限）
+const MAX_HOST_NAME_LEN: usize = 253;
+
+/// server_name列表中host_name条目的类型
+const SERVER_NAME_TYPE_HOST: u8 = 0x00;
+
+/// 浏览器ClientHello通常提供的最少密码套件数
+const MIN_BROWSER_CIPHER_SUITES: usize = 5;
+
+/// ClientHello 关键字段
+struct ClientHelloFields<'a> {
+    /// legacy_version字段
+    legacy_version: u16,
+    /// 密码套件列表（每项2字节）
+    cipher_suites: &'a [u8],
+    /// 扩展数据（可能不完整）
+    extensions: &'a [u8],
+}
+
+/// TLS版本号对应的名称，未知版本返回 `None`
+pub fn tls_version_name(version: u16) -> Option<&'static str> {
+    match version {
+        0x0300 => Some("SSL 3.0"),
+        0x0301 => Some("TLS 1.0"),
+        0x0302 => Some("TLS 1.1"),
+        0x0303 => Some("TLS 1.2"),
+        0x0304 => Some("TLS 1.3"),
+        _ => None,
+    }
+}
+
+/// 把2字节大端列表解析为u16列表并去除GREASE值
+fn u16_list_without_grease(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .filter(|&value| !is_grease(value))
+        .collect()
+}
+
 /// ALPN协议检测结果
 #[derive(Debug, Clone)]
 pub struct AlpnDetectionResult {
@@ -131,6 +193,23 @@ pub struct AlpnDetectionResult {
     pub primary_protocol: Option<ProtocolType>,
     /// 置信度
     pub confidence: f32,
+    /// ClientHello中的SNI主机名
+    pub sni: Option<String>,
+    /// ClientHello的legacy_version字段（TLS 1.3客户端固定为0x0303）
+    pub legacy_version: u16,
+    /// supported_versions扩展提供的版本（已去除GREASE），没有该扩展时为空
+    pub supported_versions: Vec<u16>,
+    /// 提供的密码套件（已去除GREASE）
+    pub cipher_suites: Vec<u16>,
+}
+
+impl AlpnDetectionResult {
+    /// 客户端支持的最高TLS版本
+    ///
+    /// 带supported_versions扩展时以扩展为准，否则为legacy_version。
+    pub fn max_version(&self) -> u16 {
+        self.supported_versions.iter().copied().max().unwrap_or(self.legacy_version)
+    }
 }
 
 /// TLS ALPN检测器
@@ -167,7 +246,166 @@ impl TlsAlpnDetector {
             return None;
         }
 
-        // 检查是否为TLS记录
+        let handshake_data = self.client_hello_handshake(data)?;
+
+        // 解析ClientHello消息
+        self.parse_client_hello_alpn(handshake_data)
+    }
+
+    /// 检查ClientHello是否像扫描器/机器人发出的
+    ///
+    /// 保守的启发式判断，以下特征至少命中两项时返回 `true`：
+    /// - 缺少SNI扩展
+    /// - 密码套件少于 `MIN_BROWSER_CIPHER_SUITES` 个
+    /// - 常见扩展（ALPN、supported_versions、key_share）缺失两个及以上
+    ///
+    /// 浏览器和主流TLS库通常不会同时出现这些特征；非ClientHello数据返回 `false`。
+    pub fn is_scanner_like(&self, data: &[u8]) -> bool {
+        let fields = match self.client_hello_handshake(data).and_then(|h| self.parse_client_hello(h)) {
+            Some(fields) => fields,
+            None => return false,
+        };
+
+        let extension_types = self.extension_types(fields.extensions);
+        let has = |ext: u16| extension_types.contains(&ext);
+
+        let missing_common = [EXT_ALPN, EXT_SUPPORTED_VERSIONS, EXT_KEY_SHARE]
+            .iter()
+            .filter(|&&ext| !has(ext))
+            .count();
+
+        let indicators = [
+            !has(EXT_SERVER_NAME),
+            fields.cipher_suites.len() / 2 < MIN_BROWSER_CIPHER_SUITES,
+            missing_common >= 2,
+        ];
+
+        indicators.iter().filter(|&&hit| hit).count() >= 2
+    }
+
+    /// 获取ClientHello扩展的线上顺序（已去除GREASE值）
+    ///
+    /// 扩展顺序本身即是客户端指纹；非ClientHello数据返回 `None`。
+    pub fn extension_order(&self, data: &[u8]) -> Option<Vec<u16>> {
+        let fields = self.client_hello_handshake(data).and_then(|h| self.parse_client_hello(h))?;
+        Some(
+            self.extension_types(fields.extensions)
+                .into_iter()
+                .filter(|&ext| !is_grease(ext))
+                .collect()
+        )
+    }
+
+    /// 检查ClientHello扩展顺序是否像被随机打乱
+    ///
+    /// Chromium系客户端会发送GREASE扩展，打乱前SNI固定为首个真实扩展；
+    /// 带GREASE但SNI不在首位时视为打乱。该判断是保守的启发式，SNI恰好被排到首位时会漏判。
+    pub fn extensions_shuffled(&self, data: &[u8]) -> bool {
+        let fields = match self.client_hello_handshake(data).and_then(|h| self.parse_client_hello(h)) {
+            Some(fields) => fields,
+            None => return false,
+        };
+
+        let types = self.extension_types(fields.extensions);
+        if !types.iter().any(|&ext| is_grease(ext)) {
+            return false;
+        }
+
+        let first_real = types.iter().find(|&&ext| !is_grease(ext));
+        types.contains(&EXT_SERVER_NAME) && first_real != Some(&EXT_SERVER_NAME)
+    }
+
+    /// 检查ClientHello是否携带 `encrypted_client_hello` 扩展（ECH）
+    ///
+    /// 带ECH时明文中的SNI属于外层ClientHello，可能只是掩护用的公共名称；非ClientHello数据返回 `false`。
+    pub fn has_ech(&self, data: &[u8]) -> bool {
+        self.has_extension(data, EXT_ENCRYPTED_CLIENT_HELLO)
+    }
+
+    /// 检查ClientHello是否携带SNI扩展
+    pub fn has_sni(&self, data: &[u8]) -> bool {
+        self.has_extension(data, EXT_SERVER_NAME)
+    }
+
+    /// 获取ClientHello提供的最高TLS版本（见 [`AlpnDetectionResult::max_version`]）
+    ///
+    /// 不要求ALPN扩展，可用于在探测层拒绝只支持TLS 1.0/1.1的客户端；非ClientHello数据返回 `None`。
+    pub fn client_hello_max_version(&self, data: &[u8]) -> Option<u16> {
+        let fields = self.client_hello_handshake(data).and_then(|h| self.parse_client_hello(h))?;
+        Some(self.supported_versions(fields.extensions).into_iter().max().unwrap_or(fields.legacy_version))
+    }
+    
+    /// 提取ClientHello中SNI扩展的host_name
+    ///
+    /// 数据被截断、扩展格式错误或主机名不是合法ASCII时返回 `None`。
+    pub fn extract_sni(&self, data: &[u8]) -> Option<String> {
+        let fields = self.client_hello_handshake(data).and_then(|h| self.parse_client_hello(h))?;
+        self.server_name(fields.extensions)
+    }
+    
+    /// 在扩展数据中查找指定扩展的内容（扩展被截断时返回 `None`）
+    fn find_extension<'a>(&self, extensions_data: &'a [u8], extension: u16) -> Option<&'a [u8]> {
+        let mut pos = 0;
+        
+        for _ in 0..MAX_EXTENSIONS {
+            let header = extensions_data.get(pos..pos + 4)?;
+            let extension_type = u16::from_be_bytes([header[0], header[1]]);
+            let extension_length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            pos += 4;
+            
+            let body = extensions_data.get(pos..pos + extension_length)?;
+            if extension_type == extension {
+                return Some(body);
+            }
+            pos += extension_length;
+        }
+        
+        None
+    }
+    
+    /// 解析server_name扩展中的host_name条目
+    fn server_name(&self, extensions_data: &[u8]) -> Option<String> {
+        let body = self.find_extension(extensions_data, EXT_SERVER_NAME)?;
+        let list_length = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+        let list = body.get(2..2 + list_length)?;
+        
+        let mut pos = 0;
+        while pos + 3 <= list.len() {
+            let name_type = list[pos];
+            let name_length = u16::from_be_bytes([list[pos + 1], list[pos + 2]]) as usize;
+            pos += 3;
+            
+            let name = list.get(pos..pos + name_length)?;
+            if name_type == SERVER_NAME_TYPE_HOST {
+                let valid = !name.is_empty()
+                    && name.len() <= MAX_HOST_NAME_LEN
+                    && name.iter().all(|b| b.is_ascii_graphic());
+                return valid.then(|| String::from_utf8_lossy(name).into_owned());
+            }
+            pos += name_length;
+        }
+        
+        None
+    }
+    
+    /// 解析supported_versions扩展中的版本列表（已去除GREASE）
+    fn supported_versions(&self, extensions_data: &[u8]) -> Vec<u16> {
+        let Some(body) = self.find_extension(extensions_data, EXT_SUPPORTED_VERSIONS) else {
+            return Vec::new();
+        };
+        let list_length = body.first().copied().unwrap_or(0) as usize;
+        body.get(1..1 + list_length).map(u16_list_without_grease).unwrap_or_default()
+    }
+    
+    /// 检查ClientHello是否携带指定扩展
+    fn has_extension(&self, data: &[u8], extension: u16) -> bool {
+        self.client_hello_handshake(data)
+            .and_then(|h| self.parse_client_hello(h))
+            .is_some_and(|fields| self.extension_types(fields.extensions).contains(&extension))
+    }
+
+    /// 从TLS记录中取出ClientHello握手消息
+    fn client_hello_handshake<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
         if data.len() < 5 {
             return None;
         }
@@ -196,16 +434,32 @@ impl TlsAlpnDetector {
             return None;
         }
 
-        // 解析ClientHello消息
-        self.parse_client_hello_alpn(available_data)
+        Some(available_data)
     }
 
     /// 解析ClientHello消息中的ALPN扩展
     fn parse_client_hello_alpn(&self, handshake_data: &[u8]) -> Option<AlpnDetectionResult> {
+        let fields = self.parse_client_hello(handshake_data)?;
+        if fields.extensions.is_empty() {
+            return None;
+        }
+
+        let mut result = self.parse_alpn_extensions(fields.extensions)?;
+        result.sni = self.server_name(fields.extensions);
+        result.legacy_version = fields.legacy_version;
+        result.supported_versions = self.supported_versions(fields.extensions);
+        result.cipher_suites = u16_list_without_grease(fields.cipher_suites);
+        Some(result)
+    }
+
+    /// 解析ClientHello消息的密码套件长度和扩展数据
+    fn parse_client_hello<'a>(&self, handshake_data: &'a [u8]) -> Option<ClientHelloFields<'a>> {
         if handshake_data.len() < 12 {
             return None;
         }
 
+        let legacy_version = u16::from_be_bytes([handshake_data[4], handshake_data[5]]);
+        
         // 跳过握手类型(1) + 长度(3) + 版本(2) + 随机数(32)
         let mut pos = 1 + 3 + 2 + 32;
         if handshake_data.len() < pos {
@@ -228,6 +482,7 @@ impl TlsAlpnDetector {
             return None;
         }
         let cipher_suites_len = u16::from_be_bytes([handshake_data[pos], handshake_data[pos + 1]]) as usize;
+        let cipher_suites = handshake_data.get(pos + 2..pos + 2 + cipher_suites_len).unwrap_or_default();
         pos += 2 + cipher_suites_len;
         if handshake_data.len() < pos {
             return None;
@@ -243,9 +498,13 @@ impl TlsAlpnDetector {
             return None;
         }
 
-        // 解析扩展
+        // 没有扩展字段（旧式ClientHello）
         if handshake_data.len() < pos + 2 {
-            return None;
+            return Some(ClientHelloFields {
+                legacy_version,
+                cipher_suites,
+                extensions: &[],
+            });
         }
         let extensions_length = u16::from_be_bytes([handshake_data[pos], handshake_data[pos + 1]]) as usize;
         pos += 2;
@@ -256,13 +515,27 @@ impl TlsAlpnDetector {
         } else {
             extensions_length
         };
-        
-        if available_extensions_length == 0 {
-            return None;
+
+        Some(ClientHelloFields {
+            legacy_version,
+            cipher_suites,
+            extensions: &handshake_data[pos..pos + available_extensions_length],
+        })
+    }
+
+    /// 列出扩展数据中的所有扩展类型
+    fn extension_types(&self, extensions_data: &[u8]) -> Vec<u16> {
+        let mut types = Vec::new();
+        let mut pos = 0;
+
+        while pos + 4 <= extensions_data.len() {
+            let extension_type = u16::from_be_bytes([extensions_data[pos], extensions_data[pos + 1]]);
+            let extension_length = u16::from_be_bytes([extensions_data[pos + 2], extensions_data[pos + 3]]) as usize;
+            types.push(extension_type);
+            pos += 4 + extension_length;
         }
 
-        let extensions_data = &handshake_data[pos..pos + available_extensions_length];
-        self.parse_alpn_extensions(extensions_data)
+        types
     }
 
     /// 解析ALPN扩展
@@ -280,7 +553,7 @@ impl TlsAlpnDetector {
             }
 
             // 检查是否为ALPN扩展
-            if extension_type == 0x0010 {
+            if extension_type == EXT_ALPN {
                 let alpn_data = &extensions_data[pos..pos + extension_length];
                 if let Some(protocols) = self.parse_alpn_list(alpn_data) {
                     alpn_protocols = protocols;
@@ -303,6 +576,10 @@ impl TlsAlpnDetector {
             protocols: alpn_protocols,
             primary_protocol,
             confidence,
+            sni: None,
+            legacy_version: 0,
+            supported_versions: Vec::new(),
+            cipher_suites: Vec::new(),
         })
     }
 
@@ -384,18 +661,25 @@ impl TlsAlpnDetector {
 
     /// 创建协议信息
     pub fn create_protocol_info(&self, result: AlpnDetectionResult) -> Option<ProtocolInfo> {
-        if let Some(primary_protocol) = result.primary_protocol {
-            let mut info = ProtocolInfo::new(primary_protocol, result.confidence);
-            info.add_metadata("alpn_protocols", &result.protocols.join(","));
-            info.add_metadata("detection_method", "tls_alpn");
-            Some(info)
+        let mut info = if let Some(primary_protocol) = result.primary_protocol {
+            ProtocolInfo::new(primary_protocol, result.confidence)
         } else {
             // 如果没有确定的主要协议，返回TLS协议信息
-            let mut info = ProtocolInfo::new(ProtocolType::TLS, 0.7);
-            info.add_metadata("alpn_protocols", &result.protocols.join(","));
-            info.add_metadata("detection_method", "tls_alpn");
-            Some(info)
+            ProtocolInfo::new(ProtocolType::TLS, 0.7)
+        };
+        info.add_metadata("alpn_protocols", &result.protocols.join(","));
+        info.add_metadata("detection_method", "tls_alpn");
+        let max_version = result.max_version();
+        let version = tls_version_name(max_version).map_or_else(|| format!("0x{:04x}", max_version), str::to_string);
+        info.add_metadata("tls_version", version);
+        if !result.cipher_suites.is_empty() {
+            let suites: Vec<String> = result.cipher_suites.iter().map(|suite| format!("0x{:04x}", suite)).collect();
+            info.add_metadata("tls_cipher_suites", suites.join(","));
+        }
+        if let Some(sni) = result.sni {
+            info.add_metadata("tls_sni", sni);
         }
+        Some(info)
     }
 }
 
@@ -480,4 +764,195 @@ mod tests {
         let result = detector.detect_alpn(&tls_no_alpn_data);
         assert!(result.is_none());
     }
-}
\ No newline at end of file
+
+    /// 构造ClientHello记录
+    fn build_client_hello(cipher_suites: &[u16], extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x42; 32]); // 随机数
+        body.push(0x00); // 会话ID长度
+        body.extend_from_slice(&((cipher_suites.len() * 2) as u16).to_be_bytes());
+        for suite in cipher_suites {
+            body.extend_from_slice(&suite.to_be_bytes());
+        }
+        body.extend_from_slice(&[0x01, 0x00]); // 压缩方法
+
+        let mut ext_data = Vec::new();
+        for (ext_type, data) in extensions {
+            ext_data.extend_from_slice(&ext_type.to_be_bytes());
+            ext_data.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            ext_data.extend_from_slice(data);
+        }
+        if !extensions.is_empty() {
+            body.extend_from_slice(&(ext_data.len() as u16).to_be_bytes());
+            body.extend_from_slice(&ext_data);
+        }
+
+        let mut handshake = vec![0x01];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&body);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn test_browser_like_hello_is_not_scanner() {
+        let detector = TlsAlpnDetector::new();
+        let cipher_suites = [
+            0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+        ];
+        let extensions = vec![
+            (0x0000, b"\x00\x0e\x00\x00\x0bexample.com".to_vec()), // SNI
+            (0x0010, b"\x00\x0c\x02h2\x08http/1.1".to_vec()),       // ALPN
+            (0x002b, vec![0x04, 0x03, 0x04, 0x03, 0x03]),             // supported_versions
+            (0x0033, vec![0x00, 0x02, 0x00, 0x1d]),                   // key_share
+        ];
+        let hello = build_client_hello(&cipher_suites, &extensions);
+
+        assert!(!detector.is_scanner_like(&hello));
+        // 共享的ClientHello解析器仍能提取ALPN
+        assert_eq!(detector.detect_alpn(&hello).unwrap().primary_protocol, Some(ProtocolType::HTTP2));
+    }
+
+    #[test]
+    fn test_minimal_hello_is_scanner_like() {
+        let detector = TlsAlpnDetector::new();
+
+        // 无扩展、只有两个密码套件
+        let hello = build_client_hello(&[0x002f, 0x0035], &[]);
+        assert!(detector.is_scanner_like(&hello));
+
+        // 只缺少SNI不足以判定为扫描器
+        let cipher_suites = [0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc030];
+        let extensions = vec![
+            (0x0010, b"\x00\x03\x02h2".to_vec()),
+            (0x002b, vec![0x02, 0x03, 0x04]),
+            (0x0033, vec![0x00, 0x02, 0x00, 0x1d]),
+        ];
+        let hello = build_client_hello(&cipher_suites, &extensions);
+        assert!(!detector.is_scanner_like(&hello));
+
+        // 非TLS数据
+        assert!(!detector.is_scanner_like(b"GET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_ech_extension_detected() {
+        let detector = TlsAlpnDetector::new();
+        let cipher_suites = [0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f];
+        let extensions = vec![
+            (0x0000, b"\x00\x13\x00\x00\x10public.example.com".to_vec()), // 外层SNI
+            (0x0010, b"\x00\x03\x02h2".to_vec()),
+            (0xfe0d, vec![0x00, 0x00, 0x01, 0x00, 0x01, 0x2a, 0x00, 0x00, 0x00, 0x00]),
+        ];
+        let hello = build_client_hello(&cipher_suites, &extensions);
+
+        assert!(detector.has_ech(&hello));
+        assert!(detector.has_sni(&hello));
+
+        let plain = build_client_hello(&cipher_suites, &extensions[..2]);
+        assert!(!detector.has_ech(&plain));
+        assert!(!detector.has_ech(b"GET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_extract_sni() {
+        let detector = TlsAlpnDetector::new();
+        let cipher_suites = [0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f];
+        let extensions = vec![
+            (0x0000, b"\x00\x0e\x00\x00\x0bexample.com".to_vec()),
+            (0x0010, b"\x00\x03\x02h2".to_vec()),
+        ];
+        let hello = build_client_hello(&cipher_suites, &extensions);
+        
+        assert_eq!(detector.extract_sni(&hello), Some("example.com".to_string()));
+        assert_eq!(detector.detect_alpn(&hello).unwrap().sni, Some("example.com".to_string()));
+        
+        // 截断在任意位置都不会panic
+        for len in 0..hello.len() {
+            let _ = detector.extract_sni(&hello[..len]);
+        }
+        
+        // host_name长度字段越界
+        let broken = vec![(0x0000, b"\x00\x0e\x00\xff\xffexample.com".to_vec())];
+        assert_eq!(detector.extract_sni(&build_client_hello(&cipher_suites, &broken)), None);
+        
+        // 没有SNI扩展
+        assert_eq!(detector.extract_sni(&build_client_hello(&cipher_suites, &extensions[1..])), None);
+    }
+    
+    #[test]
+    fn test_extension_order_strips_grease() {
+        let detector = TlsAlpnDetector::new();
+        let cipher_suites = [0x4a4a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f];
+
+        // 打乱前的Chromium扩展顺序：GREASE开头和结尾，SNI为首个真实扩展
+        let extensions = vec![
+            (0x2a2a, vec![]),
+            (0x0000, b"\x00\x0e\x00\x00\x0bexample.com".to_vec()),
+            (0x0017, vec![]),
+            (0xff01, vec![0x00]),
+            (0x000a, vec![0x00, 0x04, 0x3a, 0x3a, 0x00, 0x1d]),
+            (0x0010, b"\x00\x0c\x02h2\x08http/1.1".to_vec()),
+            (0x002b, vec![0x04, 0x8a, 0x8a, 0x03, 0x04]),
+            (0x0033, vec![0x00, 0x02, 0x00, 0x1d]),
+            (0x1a1a, vec![0x00]),
+        ];
+        let hello = build_client_hello(&cipher_suites, &extensions);
+        assert_eq!(
+            detector.extension_order(&hello),
+            Some(vec![0x0000, 0x0017, 0xff01, 0x000a, 0x0010, 0x002b, 0x0033])
+        );
+        assert!(!detector.extensions_shuffled(&hello));
+
+        // 打乱后的顺序按线上顺序原样返回
+        let mut shuffled = extensions.clone();
+        shuffled.swap(1, 5);
+        let hello = build_client_hello(&cipher_suites, &shuffled);
+        assert_eq!(
+            detector.extension_order(&hello),
+            Some(vec![0x0010, 0x0017, 0xff01, 0x000a, 0x0000, 0x002b, 0x0033])
+        );
+        assert!(detector.extensions_shuffled(&hello));
+
+        // 无GREASE的客户端不做判断
+        let plain: Vec<_> = shuffled.into_iter().filter(|(ext, _)| !is_grease(*ext)).collect();
+        assert!(!detector.extensions_shuffled(&build_client_hello(&cipher_suites[1..], &plain)));
+
+        assert_eq!(detector.extension_order(b"GET / HTTP/1.1\r\n\r\n"), None);
+    }
+    #[test]
+    fn test_tls_versions_and_cipher_suites() {
+        let detector = TlsAlpnDetector::new();
+        let extensions = vec![
+            (0x0000, b"\x00\x0e\x00\x00\x0bexample.com".to_vec()), // SNI
+            (0x0010, b"\x00\x03\x02h2".to_vec()),                    // ALPN
+            (0x002b, vec![0x06, 0x3a, 0x3a, 0x03, 0x04, 0x03, 0x03]), // supported_versions（含GREASE）
+        ];
+        let hello = build_client_hello(&[0x0a0a, 0x1301, 0xc02f], &extensions);
+
+        // TLS 1.3：legacy_version固定为TLS 1.2，真实版本在supported_versions中
+        let result = detector.detect_alpn(&hello).unwrap();
+        assert_eq!(result.legacy_version, 0x0303);
+        assert_eq!(result.supported_versions, vec![0x0304, 0x0303]);
+        assert_eq!(result.cipher_suites, vec![0x1301, 0xc02f]);
+        assert_eq!(result.max_version(), 0x0304);
+        assert_eq!(detector.client_hello_max_version(&hello), Some(0x0304));
+
+        let info = detector.create_protocol_info(result).unwrap();
+        assert_eq!(info.metadata.get("tls_version").map(String::as_str), Some("TLS 1.3"));
+        assert_eq!(info.metadata.get("tls_cipher_suites").map(String::as_str), Some("0x1301,0xc02f"));
+
+        // 没有supported_versions扩展的旧客户端以legacy_version为准
+        let mut legacy = build_client_hello(&[0x002f, 0x0035], &extensions[..2]);
+        legacy[9..11].copy_from_slice(&[0x03, 0x01]);
+        let result = detector.detect_alpn(&legacy).unwrap();
+        assert!(result.supported_versions.is_empty());
+        assert_eq!(result.max_version(), 0x0301);
+        assert_eq!(tls_version_name(result.max_version()), Some("TLS 1.0"));
+        assert_eq!(detector.client_hello_max_version(&build_client_hello(&[0x002f], &[])), Some(0x0303));
+        assert_eq!(detector.client_hello_max_version(b"GET / HTTP/1.1\r\n\r\n"), None);
+    }
+}
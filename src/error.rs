@@ -2,6 +2,7 @@
 //!
 //! 定义PSI-Detector框架中使用的所有错误类型。
 
+use crate::core::protocol::ProtocolType;
 use std::fmt;
 use thiserror::Error;
 
@@ -12,6 +13,8 @@ pub type Result<T> = std::result::Result<T, DetectorError>;
 #[derive(Error, Debug)]
 pub enum DetectorError {
     /// 需要更多数据进行探测
+    ///
+    /// 携带的是完成探测所需的数据总字节数，而非还差的字节数。
     #[error("Need more data for detection, minimum required: {0} bytes")]
     NeedMoreData(usize),
     
@@ -27,6 +30,24 @@ pub enum DetectorError {
     #[error("No protocol detected: {0}")]
     NoProtocolDetected(String),
     
+    /// 最佳结果的置信度低于要求的阈值
+    #[error("Best guess {protocol} has confidence {confidence:.2}, below threshold {threshold:.2}")]
+    LowConfidence {
+        /// 最佳猜测的协议
+        protocol: ProtocolType,
+        /// 最佳猜测的置信度
+        confidence: f32,
+        /// 要求的最低置信度
+        threshold: f32,
+    },
+    
+    /// 多个协议都达到阈值且置信度过于接近，无法可靠地选出一个
+    #[error("Ambiguous detection: {}", format_candidates(candidates))]
+    Ambiguous {
+        /// 难以区分的协议及其置信度，按置信度降序排列
+        candidates: Vec<(ProtocolType, f32)>,
+    },
+    
     /// 协议探测失败
     #[error("Protocol detection failed: {reason}")]
     DetectionFailed {
@@ -175,6 +196,8 @@ impl DetectorError {
         matches!(
             self,
             Self::NeedMoreData(_)
+                | Self::LowConfidence { .. }
+                | Self::Ambiguous { .. }
                 | Self::DetectionFailed { .. }
                 | Self::Timeout { .. }
                 | Self::NetworkError { .. }
@@ -206,11 +229,21 @@ impl DetectorError {
             Self::BufferError { .. } => 1012,
             #[cfg(feature = "simd-accel")]
             Self::SimdError { .. } => 1013,
+            Self::LowConfidence { .. } => 1014,
+            Self::Ambiguous { .. } => 1015,
             Self::InternalError { .. } => 1999,
         }
     }
 }
 
+/// 歧义候选的展示形式，例如 `HTTP/1.1 (0.90), WebSocket (0.88)`
+fn format_candidates(candidates: &[(ProtocolType, f32)]) -> String {
+    candidates.iter()
+        .map(|(protocol, confidence)| format!("{} ({:.2})", protocol, confidence))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// 从anyhow::Error转换
 impl From<anyhow::Error> for DetectorError {
     fn from(err: anyhow::Error) -> Self {
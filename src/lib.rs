@@ -15,12 +15,17 @@
 //!
 //! 创建探测器实例，启用需要探测的协议，然后对数据进行探测。
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 #![warn(clippy::all)]
 #![allow(clippy::module_inception)]
 
+// 关闭 `std` 特性时只编译协议类型和魔法包检测（见 `core::magic`）
+extern crate alloc;
+
 // 核心模块
 pub mod core;
+#[cfg(feature = "std")]
 pub mod error;
 
 // 工具模块
@@ -30,25 +35,37 @@ pub mod utils;
 #[cfg(feature = "simd-accel")]
 pub mod simd;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "std")]
 pub mod upgrade;
+#[cfg(feature = "std")]
 pub mod stream;
 
 // 构造器
+#[cfg(feature = "std")]
 pub mod builder;
+#[cfg(feature = "std")]
 pub mod probe;
 
 // 重新导出核心类型
+#[cfg(feature = "std")]
 pub use crate::core::{
     detector::{
-        ProtocolDetector, DetectionResult, ProtocolAgent, Agent, AgentConfig, 
+        ProtocolDetector, DetectionResult, ProtocolAgent, Agent, AgentConfig, AgentConfigBuilder,
         Role, LoadBalancerConfig, LoadBalanceStrategy, Transport
     },
-    protocol::{ProtocolType, ProtocolInfo},
+    protocol::ProtocolInfo,
 };
+pub use crate::core::protocol::ProtocolType;
 
+#[cfg(feature = "std")]
 pub use crate::error::{DetectorError, Result};
-pub use crate::builder::DetectorBuilder;
-// pub use crate::stream::UnifiedStream;  // 暂时注释，等待实现
+#[cfg(feature = "std")]
+pub use crate::builder::{DetectorBuilder, Preset};
+#[cfg(feature = "std")]
+pub use crate::stream::UnifiedStream;
 // pub use crate::upgrade::UpgradePipeline;  // 暂时注释，等待实现
 
 // 尤里主题支持
@@ -0,0 +1,76 @@
+//! 指标导出
+//!
+//! 把探测统计渲染为Prometheus文本格式，并提供可序列化的代理指标快照。
+
+use crate::core::detector::{DetectionStats, Role};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// 代理指标快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// 实例ID
+    pub instance_id: String,
+    /// 代理角色
+    pub role: Role,
+    /// 活跃连接数
+    pub active_connections: usize,
+    /// 处理的总请求数
+    pub total_requests: u64,
+    /// 成功的协议升级数
+    pub successful_upgrades: u64,
+    /// 失败的协议升级数
+    pub failed_upgrades: u64,
+    /// 被动探测统计
+    pub detection: DetectionStats,
+}
+
+impl MetricsSnapshot {
+    /// 渲染为Prometheus文本格式
+    pub fn render_prometheus(&self) -> String {
+        let mut out = render_prometheus(&self.detection);
+        write_metric(&mut out, "psi_detector_active_connections", "gauge", "当前活跃连接数", self.active_connections);
+        write_metric(&mut out, "psi_detector_requests_total", "counter", "代理处理的总请求数", self.total_requests);
+        write_metric(&mut out, "psi_detector_upgrades_successful_total", "counter", "成功的协议升级数", self.successful_upgrades);
+        write_metric(&mut out, "psi_detector_upgrades_failed_total", "counter", "失败的协议升级数", self.failed_upgrades);
+        out
+    }
+}
+
+/// 把探测统计渲染为Prometheus文本格式
+///
+/// 各协议的计数按协议名排序输出，便于比对。
+pub fn render_prometheus(stats: &DetectionStats) -> String {
+    let mut out = String::new();
+    write_metric(&mut out, "psi_detector_detections_total", "counter", "总探测次数", stats.total_detections);
+    write_metric(&mut out, "psi_detector_detections_failed_total", "counter", "失败探测次数", stats.failed_detections);
+    write_metric(&mut out, "psi_detector_detection_success_rate", "gauge", "探测成功率", stats.success_rate());
+    write_metric(
+        &mut out,
+        "psi_detector_detection_duration_seconds_avg",
+        "gauge",
+        "平均探测耗时（秒）",
+        stats.avg_detection_time.as_secs_f64(),
+    );
+    write_metric(&mut out, "psi_detector_cache_hits_total", "counter", "探测结果缓存命中次数", stats.cache_hits);
+    write_metric(&mut out, "psi_detector_cache_misses_total", "counter", "探测结果缓存未命中次数", stats.cache_misses);
+    
+    let mut protocols: Vec<(String, u64)> = stats.protocol_counts.iter()
+        .map(|(protocol, &count)| (format!("{:?}", protocol), count))
+        .collect();
+    protocols.sort();
+    
+    let _ = writeln!(out, "# HELP psi_detector_protocol_detections_total 各协议的成功探测次数");
+    let _ = writeln!(out, "# TYPE psi_detector_protocol_detections_total counter");
+    for (protocol, count) in protocols {
+        let _ = writeln!(out, "psi_detector_protocol_detections_total{{protocol=\"{}\"}} {}", protocol, count);
+    }
+    out
+}
+
+/// 写入单个无标签指标及其HELP/TYPE行
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
@@ -4,6 +4,7 @@
 
 use crate::core::{ProtocolType, DetectionResult, ProtocolInfo};
 use crate::core::detector::DetectionMethod;
+use crate::core::probe::{ProbeContext, ProtocolProbe};
 use crate::error::{Result, DetectorError};
 use super::{ProbeEngine, ProbeType};
 use std::collections::HashMap;
@@ -329,6 +330,38 @@ impl ProbeEngine for ActiveProbe {
     }
 }
 
+// 实现 ProtocolProbe trait，注册到探测器后结果按 `DetectionMethod::Active` 钳制置信度
+impl ProtocolProbe for ActiveProbe {
+    fn name(&self) -> &'static str {
+        "ActiveProbe"
+    }
+    
+    fn supported_protocols(&self) -> Vec<ProtocolType> {
+        vec![
+            ProtocolType::HTTP1_1,
+            ProtocolType::HTTP2,
+            ProtocolType::QUIC,
+            ProtocolType::WebSocket,
+        ]
+    }
+    
+    fn probe(&self, data: &[u8], context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+        match ProbeEngine::probe(self, data) {
+            Ok(result) if context.is_protocol_enabled(result.protocol_type()) => Ok(Some(result.protocol_info)),
+            Ok(_) | Err(DetectorError::DetectionFailed { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    
+    fn needs_more_data(&self, _data: &[u8]) -> bool {
+        false
+    }
+    
+    fn detection_method(&self) -> DetectionMethod {
+        DetectionMethod::Active
+    }
+}
+
 impl Default for ActiveProbe {
     fn default() -> Self {
         Self::new()
@@ -4,10 +4,26 @@
 
 use crate::core::{ProtocolType, DetectionResult, ProtocolInfo};
 use crate::core::detector::DetectionMethod;
+use crate::core::magic::MagicDetector;
+use crate::core::probe::{ProbeContext, ProtocolProbe};
 use crate::error::{Result, DetectorError};
+use crate::utils::features::shannon_entropy;
 use super::{ProbeEngine, ProbeType};
 use std::collections::HashMap;
 
+/// obfs4客户端握手最小长度（32字节代表元 + 85字节最小填充 + 16字节标记 + 16字节MAC）
+const OBFS4_MIN_HANDSHAKE_LEN: usize = 149;
+/// obfs4握手最大长度
+const OBFS4_MAX_HANDSHAKE_LEN: usize = 8192;
+/// 判定为近似最大熵的比例（相对样本长度可达到的最大熵）
+const NEAR_MAX_ENTROPY_RATIO: f32 = 0.85;
+/// 混淆传输的启发式置信度
+const OBFUSCATED_CONFIDENCE: f32 = 0.5;
+/// 判定为加密数据的香农熵下限（比特/字节）
+const ENCRYPTED_ENTROPY_THRESHOLD: f64 = 7.5;
+/// 无法归类的加密数据的启发式置信度
+const ENCRYPTED_CONFIDENCE: f32 = 0.4;
+
 /// 启发式探测器
 pub struct HeuristicProbe {
     /// 最小样本大小
@@ -18,6 +34,8 @@ pub struct HeuristicProbe {
     deep_analysis: bool,
     /// 字节频率阈值
     frequency_threshold: f32,
+    /// 用于排除已知协议的魔数检测器
+    magic: MagicDetector,
 }
 
 impl HeuristicProbe {
@@ -28,6 +46,7 @@ impl HeuristicProbe {
             window_size: 256,
             deep_analysis: false,
             frequency_threshold: 0.1,
+            magic: MagicDetector::new(),
         }
     }
     
@@ -294,6 +313,42 @@ impl HeuristicProbe {
             None
         }
     }
+    
+    /// 混淆传输启发式分析
+    ///
+    /// obfs4等传输没有握手魔数，首个报文是长度落在固定区间内的近似均匀随机字节。
+    /// 这只是统计推断（压缩或加密的未知协议同样可能命中），因此置信度保持在较低水平。
+    fn heuristic_obfuscated(&self, data: &[u8], window: &[u8], analysis: &PatternAnalysis) -> Option<f32> {
+        if !(OBFS4_MIN_HANDSHAKE_LEN..=OBFS4_MAX_HANDSHAKE_LEN).contains(&data.len()) {
+            return None;
+        }
+        
+        // 样本越短可达到的最大熵越低，按样本长度归一化
+        let max_entropy = (window.len().min(256) as f32).log2();
+        if analysis.randomness_score * 8.0 / max_entropy < NEAR_MAX_ENTROPY_RATIO {
+            return None;
+        }
+        
+        // 均匀随机字节中可打印字符约占 95/256
+        if !(0.25..=0.5).contains(&analysis.ascii_ratio) {
+            return None;
+        }
+        
+        // 只看精确魔数，首字节启发式对随机数据过于宽松
+        if !self.magic.deep_detect(data).is_empty() {
+            return None;
+        }
+        
+        Some(OBFUSCATED_CONFIDENCE)
+    }
+    
+    /// 加密数据启发式分析
+    ///
+    /// 熵按完整探测数据计算：分析窗口过短时可达到的最大熵低于阈值。
+    /// 带有已知魔数的数据（如TLS记录）不视为未知加密流。
+    fn likely_encrypted(&self, data: &[u8], entropy: f64) -> bool {
+        entropy > ENCRYPTED_ENTROPY_THRESHOLD && self.magic.deep_detect(data).is_empty()
+    }
 }
 
 /// 模式分析结果
@@ -328,9 +383,14 @@ impl ProbeEngine for HeuristicProbe {
         let mut best_protocol = ProtocolType::Unknown;
         let mut best_confidence = 0.0;
         let mut metadata = HashMap::new();
+        let entropy = shannon_entropy(data);
+        let likely_encrypted = self.likely_encrypted(data, entropy);
         
         // 记录分析结果
-        metadata.insert("entropy".to_string(), analysis.randomness_score.to_string());
+        metadata.insert("entropy".to_string(), format!("{:.3}", entropy));
+        if likely_encrypted {
+            metadata.insert("likely_encrypted".to_string(), "true".to_string());
+        }
         metadata.insert("ascii_ratio".to_string(), analysis.ascii_ratio.to_string());
         metadata.insert("structure_score".to_string(), analysis.structure_score.to_string());
         metadata.insert("repetition_score".to_string(), analysis.repetition_score.to_string());
@@ -341,6 +401,7 @@ impl ProbeEngine for HeuristicProbe {
             ("quic", self.heuristic_quic(analysis_data, &analysis)),
             ("binary", self.heuristic_binary(analysis_data, &analysis)),
         ];
+        let any_matched = heuristics.iter().any(|(_, confidence)| confidence.is_some());
         
         for (name, confidence_opt) in heuristics {
             if let Some(confidence) = confidence_opt {
@@ -375,13 +436,28 @@ impl ProbeEngine for HeuristicProbe {
             }
         }
         
+        // 没有任何已知协议特征时才考虑混淆传输
+        if !any_matched {
+            if let Some(confidence) = self.heuristic_obfuscated(data, analysis_data, &analysis) {
+                metadata.insert("obfuscated_heuristic_confidence".to_string(), confidence.to_string());
+                best_protocol = ProtocolType::Obfuscated;
+                best_confidence = confidence;
+            } else if likely_encrypted {
+                // 高熵但无法归类：标记为未知加密流，与低熵的无效数据区分
+                best_confidence = ENCRYPTED_CONFIDENCE;
+            }
+        }
+        
         if best_confidence < 0.3 {
             return Err(DetectorError::detection_failed(
                 "Heuristic analysis confidence too low"
             ));
         }
         
-        let protocol_info = ProtocolInfo::new(best_protocol, best_confidence);
+        let mut protocol_info = ProtocolInfo::new(best_protocol, best_confidence);
+        for (key, value) in metadata {
+            protocol_info.add_metadata(key, value);
+        }
         
         Ok(DetectionResult::new(
             protocol_info,
@@ -400,6 +476,43 @@ impl ProbeEngine for HeuristicProbe {
     }
 }
 
+// 实现 ProtocolProbe trait，注册到探测器后结果按 `DetectionMethod::Heuristic` 钳制置信度
+impl ProtocolProbe for HeuristicProbe {
+    fn name(&self) -> &'static str {
+        "HeuristicProbe"
+    }
+    
+    fn supported_protocols(&self) -> Vec<ProtocolType> {
+        vec![
+            ProtocolType::HTTP1_1,
+            ProtocolType::HTTP2,
+            ProtocolType::QUIC,
+            ProtocolType::GRPC,
+            ProtocolType::Obfuscated,
+        ]
+    }
+    
+    fn probe(&self, data: &[u8], context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+        match ProbeEngine::probe(self, data) {
+            Ok(result) if context.is_protocol_enabled(result.protocol_type()) => Ok(Some(result.protocol_info)),
+            Ok(_) | Err(DetectorError::DetectionFailed { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    
+    fn priority(&self) -> u8 {
+        30 // 启发式结果可靠性较低
+    }
+    
+    fn needs_more_data(&self, data: &[u8]) -> bool {
+        data.len() < self.min_sample_size
+    }
+    
+    fn detection_method(&self) -> DetectionMethod {
+        DetectionMethod::Heuristic
+    }
+}
+
 impl Default for HeuristicProbe {
     fn default() -> Self {
         Self::new()
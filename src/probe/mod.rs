@@ -10,8 +10,10 @@ pub use passive::PassiveProbe;
 // pub use active::ActiveProbe;  // 暂时禁用主动探测
 pub use heuristic::HeuristicProbe;
 
-use crate::core::{ProtocolType, DetectionResult};
+use crate::core::{ProtocolType, DetectionResult, ProbeConfig};
 use crate::error::{Result, DetectorError};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
 
 /// 探测引擎trait
 pub trait ProbeEngine {
@@ -23,10 +25,18 @@ pub trait ProbeEngine {
     
     /// 是否需要更多数据
     fn needs_more_data(&self, data: &[u8]) -> bool;
+    
+    /// 单次探测的时间预算
+    ///
+    /// 返回 `None` 时使用聚合器的默认预算。探测是同步执行的，
+    /// 超出预算的结果会被聚合器丢弃并记为超时错误。
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 /// 探测类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ProbeType {
     /// 被动探测
     Passive,
@@ -36,53 +46,200 @@ pub enum ProbeType {
     Heuristic,
 }
 
+/// 单个探测引擎的失败记录
+#[derive(Debug)]
+pub struct EngineError {
+    /// 引擎在聚合器中的添加顺序
+    pub index: usize,
+    /// 引擎类型
+    pub probe_type: ProbeType,
+    /// 引擎返回的错误（超出时间预算时为 `Timeout`）
+    pub error: DetectorError,
+}
+
+/// 聚合报告
+///
+/// 各引擎相互隔离：某个引擎出错不会影响其他引擎，错误记录在 `errors` 中。
+/// 需要更多数据（`NeedMoreData`）的引擎既不算结果也不算错误。
+#[derive(Debug, Default)]
+pub struct AggregationReport {
+    /// 成功的探测结果，按引擎添加顺序排列
+    pub results: Vec<DetectionResult>,
+    /// 失败的引擎
+    pub errors: Vec<EngineError>,
+}
+
+impl AggregationReport {
+    /// 是否有引擎失败
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
 /// 探测结果聚合器
+///
+/// 各引擎的置信度先乘以其类型的权重，再按协议合并：
+/// 指向同一协议的多个结果按 `1 - Π(1 - c)` 相互加强。
 pub struct ProbeAggregator {
     engines: Vec<Box<dyn ProbeEngine>>,
+    weights: HashMap<ProbeType, f32>,
+    engine_timeout: Option<Duration>,
+}
+
+/// 同一协议的合并状态
+struct ProtocolVote {
+    /// 所有支持结果都不成立的概率
+    miss: f32,
+    /// 加权置信度最高的结果
+    best: DetectionResult,
+    best_weighted: f32,
+    contributors: Vec<(String, f32)>,
 }
 
 impl ProbeAggregator {
-    /// 创建新的聚合器
+    /// 创建新的聚合器（所有引擎权重为1）
     pub fn new() -> Self {
         Self {
             engines: Vec::new(),
+            weights: HashMap::new(),
+            engine_timeout: None,
+        }
+    }
+    
+    /// 使用探测配置中的引擎权重创建聚合器
+    pub fn with_config(config: &ProbeConfig) -> Self {
+        Self {
+            engines: Vec::new(),
+            weights: config.probe_weights.clone(),
+            engine_timeout: None,
         }
     }
     
+    /// 设置某类探测引擎的置信度权重
+    pub fn with_weight(mut self, probe_type: ProbeType, weight: f32) -> Self {
+        self.weights.insert(probe_type, weight.max(0.0));
+        self
+    }
+    
+    /// 设置引擎的默认时间预算，引擎自身的 [`ProbeEngine::timeout`] 优先
+    pub fn with_engine_timeout(mut self, timeout: Duration) -> Self {
+        self.engine_timeout = Some(timeout);
+        self
+    }
+    
+    /// 获取探测引擎类型的权重
+    pub fn weight(&self, probe_type: ProbeType) -> f32 {
+        self.weights.get(&probe_type).copied().unwrap_or(1.0)
+    }
+    
     /// 添加探测引擎
     pub fn add_engine(&mut self, engine: Box<dyn ProbeEngine>) {
         self.engines.push(engine);
     }
     
-    /// 执行所有探测引擎并聚合结果
-    pub fn probe_all(&self, data: &[u8]) -> Result<Vec<DetectionResult>> {
+    /// 执行所有探测引擎，收集成功结果和各引擎的错误
+    pub fn probe_all(&self, data: &[u8]) -> AggregationReport {
+        let (results, errors) = self.run_engines(data);
+        AggregationReport {
+            results: results.into_iter().map(|(_, result)| result).collect(),
+            errors,
+        }
+    }
+    
+    /// 依次执行各引擎，返回带引擎类型的成功结果和失败记录
+    fn run_engines(&self, data: &[u8]) -> (Vec<(ProbeType, DetectionResult)>, Vec<EngineError>) {
         let mut results = Vec::new();
+        let mut errors = Vec::new();
         
-        for engine in &self.engines {
-            match engine.probe(data) {
-                Ok(result) => results.push(result),
+        for (index, engine) in self.engines.iter().enumerate() {
+            let probe_type = engine.probe_type();
+            let timeout = engine.timeout().or(self.engine_timeout);
+            let start = Instant::now();
+            let outcome = engine.probe(data).and_then(|result| match timeout {
+                Some(timeout) if start.elapsed() > timeout => {
+                    Err(DetectorError::timeout(timeout.as_millis() as u64))
+                }
+                _ => Ok(result),
+            });
+            
+            match outcome {
+                Ok(result) => results.push((probe_type, result)),
                 Err(DetectorError::NeedMoreData(_)) => continue,
-                Err(e) => return Err(e),
+                Err(error) => errors.push(EngineError { index, probe_type, error }),
             }
         }
         
-        Ok(results)
+        (results, errors)
     }
     
     /// 获取最佳探测结果
+    ///
+    /// 返回合并置信度最高的协议；结果的置信度为合并值，
+    /// `contributing_probes` 列出支持该协议的各引擎及其加权置信度。
+    /// 出错的引擎被跳过；没有任何结果时返回第一个引擎错误。
     pub fn best_result(&self, data: &[u8]) -> Result<DetectionResult> {
-        let results = self.probe_all(data)?;
-        
-        if results.is_empty() {
-            return Err(DetectorError::detection_failed("No probe results available"));
+        let (best, mut report) = self.best_result_with_report(data);
+        match best {
+            Some(best) => Ok(best),
+            None if report.has_errors() => Err(report.errors.swap_remove(0).error),
+            None => Err(DetectorError::detection_failed("No probe results available")),
         }
+    }
+    
+    /// 获取最佳探测结果，同时返回聚合报告
+    ///
+    /// 没有任何引擎给出结果时第一个值为 `None`。
+    pub fn best_result_with_report(&self, data: &[u8]) -> (Option<DetectionResult>, AggregationReport) {
+        let (results, errors) = self.run_engines(data);
+        let mut votes: BTreeMap<ProtocolType, ProtocolVote> = BTreeMap::new();
         
-        // 选择置信度最高的结果
-        let best = results.into_iter()
-            .max_by(|a, b| a.confidence().partial_cmp(&b.confidence()).unwrap())
-            .unwrap();
+        for (probe_type, result) in &results {
+            if result.confidence().is_nan() {
+                continue;
+            }
             
-        Ok(best)
+            let result = result.clone();
+            let weighted = (result.confidence() * self.weight(*probe_type)).clamp(0.0, 1.0);
+            let contributor = (result.detector_name.clone(), weighted);
+            match votes.get_mut(&result.protocol_type()) {
+                Some(vote) => {
+                    vote.miss *= 1.0 - weighted;
+                    vote.contributors.push(contributor);
+                    if weighted > vote.best_weighted {
+                        vote.best = result;
+                        vote.best_weighted = weighted;
+                    }
+                }
+                None => {
+                    votes.insert(result.protocol_type(), ProtocolVote {
+                        miss: 1.0 - weighted,
+                        best: result,
+                        best_weighted: weighted,
+                        contributors: vec![contributor],
+                    });
+                }
+            }
+        }
+        
+        // 合并置信度相同时取单个结果更可信的协议
+        let best = votes.into_values()
+            .max_by(|a, b| {
+                (1.0 - a.miss).total_cmp(&(1.0 - b.miss))
+                    .then_with(|| a.best_weighted.total_cmp(&b.best_weighted))
+            })
+            .map(|vote| {
+                let mut contributors = vote.contributors;
+                contributors.sort_by(|a, b| b.1.total_cmp(&a.1));
+                let mut best = vote.best.with_contributing_probes(contributors);
+                best.protocol_info.confidence = 1.0 - vote.miss;
+                best
+            });
+        
+        let report = AggregationReport {
+            results: results.into_iter().map(|(_, result)| result).collect(),
+            errors,
+        };
+        (best, report)
     }
 }
 
@@ -5,15 +5,252 @@
 use crate::core::{ProtocolType, DetectionResult, ProtocolInfo};
 use crate::core::detector::DetectionMethod;
 use crate::core::probe::{ProtocolProbe, ProbeContext};
+use crate::core::tls_alpn::TlsAlpnDetector;
 use crate::error::{Result, DetectorError};
+use crate::utils::{grpc, http, http2, mqtt, starttls, websocket};
+use crate::utils::grpc::GrpcVariant;
+use crate::utils::framing::read_quic_varint;
 use super::{ProbeEngine, ProbeType};
 
+/// 文本协议校验时检查的前缀长度
+const TEXT_PREFIX_LEN: usize = 32;
+
+/// 文本协议前缀中可打印字符的最低比例
+const MIN_PRINTABLE_RATIO: f32 = 0.9;
+
+/// HTTP/0.9简单请求的置信度（没有版本号，只能依据请求行形状判断）
+const HTTP09_CONFIDENCE: f32 = 0.8;
+
+/// HTTP/3 SETTINGS帧类型
+const H3_FRAME_SETTINGS: u64 = 0x04;
+
+/// SETTINGS_H3_DATAGRAM
+const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+
+/// SETTINGS_ENABLE_WEBTRANSPORT
+const SETTINGS_ENABLE_WEBTRANSPORT: u64 = 0x2b603742;
+
+/// TLS记录头长度
+const TLS_RECORD_HEADER_LEN: usize = 5;
+
+/// TLS记录负载的最大长度（2^14 + 2048，RFC 5246 §6.2.3）
+const TLS_MAX_RECORD_LEN: usize = 16384 + 2048;
+
+/// PostgreSQL SSLRequest（长度8 + 请求码80877103）
+const PG_SSL_REQUEST: [u8; 8] = [0x00, 0x00, 0x00, 0x08, 0x04, 0xd2, 0x16, 0x2f];
+
+/// STARTTLS类命令行（不含CRLF）的最大长度，IMAP标签一般很短
+const STARTTLS_MAX_LINE_LEN: usize = 64;
+
+/// `:protocol` 伪头部与其取值之间允许的最大字节数（QPACK字面量的长度前缀等）
+const CONNECT_PROTOCOL_WINDOW: usize = 8;
+
+/// MySQL SSLRequest 包负载长度
+const MYSQL_SSL_REQUEST_LEN: usize = 32;
+
+/// MySQL 能力标志 CLIENT_PROTOCOL_41
+const MYSQL_CLIENT_PROTOCOL_41: u32 = 0x0200;
+
+/// MySQL 能力标志 CLIENT_SSL
+const MYSQL_CLIENT_SSL: u32 = 0x0800;
+
+/// Thrift分帧传输允许的最大帧长度（与官方实现的默认值一致）
+const THRIFT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Thrift binary协议版本标识（严格模式）
+const THRIFT_BINARY_VERSION: [u8; 2] = [0x80, 0x01];
+
+/// Thrift compact协议标识
+const THRIFT_COMPACT_ID: u8 = 0x82;
+
+/// Avro对象容器文件头
+const AVRO_OBJECT_MAGIC: &[u8; 4] = b"Obj\x01";
+
+/// Avro socket传输单个缓冲区的最大长度
+const AVRO_MAX_BUFFER_LEN: usize = 16 * 1024 * 1024;
+
+/// Avro握手中MD5协议指纹的长度
+const AVRO_HASH_LEN: usize = 16;
+
+/// Memcached二进制协议的请求/响应头长度
+const MEMCACHED_HEADER_LEN: usize = 24;
+
+/// Memcached二进制协议请求魔数
+const MEMCACHED_REQUEST_MAGIC: u8 = 0x80;
+
+/// Memcached二进制协议响应魔数
+const MEMCACHED_RESPONSE_MAGIC: u8 = 0x81;
+
+/// Memcached键的最大长度
+const MEMCACHED_MAX_KEY_LEN: usize = 250;
+
+/// Memcached二进制报文体的最大长度（服务端默认的单个条目上限）
+const MEMCACHED_MAX_BODY_LEN: u32 = 1024 * 1024;
+
+/// ClickHouse原生协议Hello包类型
+const CLICKHOUSE_PACKET_HELLO: u8 = 0x00;
+
+/// ClickHouse原生协议修订号的合理范围
+const CLICKHOUSE_REVISION_RANGE: std::ops::RangeInclusive<u64> = 54_000..=59_999;
+
+/// ClickHouse Hello中客户端/服务端名称的最大长度
+const CLICKHOUSE_MAX_NAME_LEN: u64 = 64;
+
+/// OpenVPN报文头：操作码(高5位) + key_id(低3位) + 8字节会话ID
+const OPENVPN_HEADER_LEN: usize = 9;
+
+/// OpenVPN TCP长度前缀允许的最大报文长度
+const OPENVPN_MAX_PACKET_LEN: usize = 1600;
+
+/// OpenVPN确认数组的最大长度
+const OPENVPN_MAX_ACKS: usize = 8;
+
+/// tls-auth可用的HMAC长度（SHA1、SHA256、SHA512）
+const OPENVPN_TLS_AUTH_HMAC_LENS: [usize; 3] = [20, 32, 64];
+
+/// tls-crypt认证标签长度
+const OPENVPN_TLS_CRYPT_TAG_LEN: usize = 32;
+
+/// 重放保护时间戳下限（2015-01-01），用于过滤随机数据
+const OPENVPN_MIN_TIMESTAMP: u32 = 1_420_070_400;
+
+/// ISAKMP报文头长度
+const IKE_HEADER_LEN: usize = 28;
+
+/// NAT-T（UDP 4500）在IKE报文前插入的non-ESP标记
+const IKE_NON_ESP_MARKER: [u8; 4] = [0x00; 4];
+
+/// TLS记录头的校验结果
+#[derive(Debug)]
+enum TlsRecord {
+    /// 缓冲区包含完整记录
+    Complete {
+        /// 内容类型
+        content_type: u8,
+    },
+    /// 记录头合法但负载尚未收全，值为完整记录所需的总字节数
+    Truncated(usize),
+}
+
+/// OpenVPN硬重置报文
+#[derive(Debug)]
+struct OpenVpnReset {
+    /// 操作码名称
+    opcode: &'static str,
+    /// `tcp` 或 `udp`
+    transport: &'static str,
+    /// 控制通道保护方式：`none`、`tls-auth` 或 `tls-crypt`
+    protection: &'static str,
+}
+
+/// ISAKMP报文头
+#[derive(Debug)]
+struct IkeHeader {
+    /// 主版本号（1或2）
+    version: u8,
+    /// 交换类型名称
+    exchange: &'static str,
+    /// 是否为协商的首个报文（响应方SPI为0）
+    initial: bool,
+    /// 是否带NAT-T的non-ESP标记
+    nat_t: bool,
+}
+
+/// Memcached请求
+#[derive(Debug)]
+struct MemcachedRequest {
+    /// `text` 或 `binary`
+    protocol: &'static str,
+    /// 文本协议的命令名，或二进制协议的操作码（十六进制）
+    command: String,
+}
+
+/// ClickHouse Hello包
+#[derive(Debug)]
+struct ClickHouseHello<'a> {
+    /// 客户端或服务端名称
+    name: &'a str,
+    /// 协议修订号
+    revision: u64,
+}
+
+/// Thrift消息头
+#[derive(Debug)]
+struct ThriftHeader<'a> {
+    /// `binary` 或 `compact`
+    protocol: &'static str,
+    /// 消息类型：1=call 2=reply 3=exception 4=oneway
+    message_type: u8,
+    /// 方法名
+    method: &'a str,
+}
+
+/// WebTransport 相关信号
+#[derive(Debug, Default)]
+struct WebTransportSignals {
+    /// SETTINGS中启用了WebTransport
+    enable_webtransport: bool,
+    /// SETTINGS中启用了HTTP Datagram
+    h3_datagram: bool,
+    /// 扩展CONNECT请求携带 `:protocol: webtransport`
+    connect_protocol: bool,
+    /// 声明使用Capsule协议
+    capsule_protocol: bool,
+}
+
+/// 固定协议的检测函数，返回置信度
+type FixedDetectFn = fn(&PassiveProbe, &[u8]) -> Option<f32>;
+
+/// 文本协议的检测函数，同时返回所属协议
+type TextDetectFn = fn(&PassiveProbe, &[u8]) -> Option<(ProtocolType, f32)>;
+
+/// 被动检测函数
+#[derive(Clone, Copy)]
+enum Detector {
+    Fixed(ProtocolType, FixedDetectFn),
+    Text(TextDetectFn),
+}
+
+/// 按优先级排列的检测函数，置信度相同时靠前的协议胜出
+const DETECTORS: &[Detector] = &[
+    Detector::Fixed(ProtocolType::HTTP3, PassiveProbe::detect_http3),
+    Detector::Fixed(ProtocolType::QUIC, PassiveProbe::detect_quic),
+    Detector::Fixed(ProtocolType::HTTP2, PassiveProbe::detect_http2),
+    Detector::Fixed(ProtocolType::GRPC, PassiveProbe::detect_grpc),
+    Detector::Fixed(ProtocolType::HTTP1_1, PassiveProbe::detect_http1),
+    Detector::Fixed(ProtocolType::HTTP1_0, PassiveProbe::detect_http09),
+    Detector::Fixed(ProtocolType::TLS, PassiveProbe::detect_tls),
+    Detector::Fixed(ProtocolType::SSH, PassiveProbe::detect_ssh),
+    Detector::Fixed(ProtocolType::WebSocket, PassiveProbe::detect_websocket),
+    Detector::Fixed(ProtocolType::IRC, PassiveProbe::detect_irc),
+    Detector::Fixed(ProtocolType::XMPP, PassiveProbe::detect_xmpp),
+    Detector::Fixed(ProtocolType::Protobuf, PassiveProbe::detect_length_prefixed_protobuf),
+    Detector::Fixed(ProtocolType::VNC, PassiveProbe::detect_rfb),
+    Detector::Fixed(ProtocolType::RDP, PassiveProbe::detect_rdp),
+    Detector::Fixed(ProtocolType::WebTransport, PassiveProbe::detect_webtransport),
+    Detector::Fixed(ProtocolType::PostgreSQL, PassiveProbe::detect_postgres_ssl_request),
+    Detector::Fixed(ProtocolType::MySQL, PassiveProbe::detect_mysql_ssl_request),
+    Detector::Text(|_, data| starttls_command(data).map(|protocol| (protocol, 0.95))),
+    Detector::Text(PassiveProbe::detect_text_session),
+    Detector::Fixed(ProtocolType::Thrift, PassiveProbe::detect_thrift),
+    Detector::Fixed(ProtocolType::Avro, PassiveProbe::detect_avro),
+    Detector::Fixed(ProtocolType::Memcached, PassiveProbe::detect_memcached),
+    Detector::Fixed(ProtocolType::ClickHouse, PassiveProbe::detect_clickhouse),
+    Detector::Fixed(ProtocolType::OpenVPN, PassiveProbe::detect_openvpn),
+    Detector::Fixed(ProtocolType::IKE, PassiveProbe::detect_ike),
+    Detector::Fixed(ProtocolType::MQTT, PassiveProbe::detect_mqtt),
+];
+
 /// 被动探测器
 pub struct PassiveProbe {
     /// 最小数据要求
     min_data_size: usize,
     /// 置信度阈值
     confidence_threshold: f32,
+    /// 是否在文本协议探测前校验可打印ASCII前缀
+    text_validation: bool,
+    /// TLS ClientHello 解析器
+    tls_alpn_detector: TlsAlpnDetector,
 }
 
 impl PassiveProbe {
@@ -22,6 +259,8 @@ impl PassiveProbe {
         Self {
             min_data_size: 16,
             confidence_threshold: 0.7,
+            text_validation: true,
+            tls_alpn_detector: TlsAlpnDetector::new(),
         }
     }
     
@@ -37,42 +276,261 @@ impl PassiveProbe {
         self
     }
     
+    /// 设置是否启用文本协议前缀校验
+    ///
+    /// 默认启用。关闭后文本协议探测器将不再检查前缀是否为可打印ASCII。
+    pub fn with_text_validation(mut self, enabled: bool) -> Self {
+        self.text_validation = enabled;
+        self
+    }
+    
+    /// 检查数据前缀是否像文本协议
+    ///
+    /// 统计前32字节中可打印ASCII（含 `\r`、`\n`、`\t`）的比例，
+    /// 低于阈值时认为是二进制数据，文本协议探测器应跳过。
+    fn is_text_like(&self, data: &[u8]) -> bool {
+        if !self.text_validation {
+            return true;
+        }
+        
+        let prefix = &data[..data.len().min(TEXT_PREFIX_LEN)];
+        if prefix.is_empty() {
+            return false;
+        }
+        
+        let printable = prefix.iter()
+            .filter(|&&b| matches!(b, 0x20..=0x7e | b'\r' | b'\n' | b'\t'))
+            .count();
+        
+        printable as f32 / prefix.len() as f32 >= MIN_PRINTABLE_RATIO
+    }
+    
+    /// 检查首行（请求行/状态行）是否为合法的可打印ASCII
+    fn has_printable_first_line(&self, data: &[u8]) -> bool {
+        if !self.text_validation {
+            return true;
+        }
+        
+        let line_end = data.iter()
+            .position(|&b| b == b'\r' || b == b'\n')
+            .unwrap_or(data.len());
+        
+        data[..line_end.min(TEXT_PREFIX_LEN)].iter().all(|&b| matches!(b, 0x20..=0x7e))
+    }
+    
     /// 检测HTTP/1.1协议
+    ///
+    /// 容忍请求行前的空白和空行，方法不区分大小写；这类不规范的请求要求请求行带有
+    /// `HTTP/1.x` 版本（避免误判Memcached等小写文本命令），并降低置信度。
     fn detect_http1(&self, data: &[u8]) -> Option<f32> {
-        if data.len() < 8 {
+        let trimmed = http::trim_leading_blank(data);
+        if trimmed.len() < 8 {
+            return None;
+        }
+        
+        // 文本协议：前缀必须是可打印ASCII
+        if !self.is_text_like(trimmed) || !self.has_printable_first_line(trimmed) {
+            return None;
+        }
+        
+        // HTTP/0.9简单请求交给 `detect_http09`
+        if http::is_http09_request(trimmed) {
             return None;
         }
         
         // 检查HTTP方法
-        let methods = [b"GET ", b"POST", b"PUT ", b"HEAD", b"DELE"];
-        for method in &methods {
-            if data.starts_with(*method) {
+        if let Some(method) = http::request_method(trimmed) {
+            if trimmed.len() == data.len() && trimmed.starts_with(method.as_bytes()) {
                 return Some(0.9);
             }
+            return self.http1_version(trimmed).map(|_| 0.85);
         }
         
         // 检查HTTP响应
-        if data.starts_with(b"HTTP/1.") {
+        if trimmed.starts_with(b"HTTP/1.") {
             return Some(0.95);
         }
         
         None
     }
     
+    /// 检测HTTP/0.9简单请求（`GET <path>` 后直接换行，没有协议版本）
+    fn detect_http09(&self, data: &[u8]) -> Option<f32> {
+        http::is_http09_request(data).then_some(HTTP09_CONFIDENCE)
+    }
+    
+    /// 从首行提取HTTP/1.x版本号
+    fn http1_version(&self, data: &[u8]) -> Option<&'static str> {
+        let line_end = data.iter()
+            .position(|&b| b == b'\r' || b == b'\n')
+            .unwrap_or(data.len());
+        let first_line = &data[..line_end];
+        
+        if self.fast_search(first_line, b"HTTP/1.0") {
+            Some("1.0")
+        } else if self.fast_search(first_line, b"HTTP/1.1") {
+            Some("1.1")
+        } else {
+            None
+        }
+    }
+    
+    /// 构建协议信息，为部分协议附带版本或特征信息
+    fn build_protocol_info(&self, protocol: ProtocolType, confidence: f32, data: &[u8]) -> ProtocolInfo {
+        let mut info = ProtocolInfo::new(protocol, confidence);
+        match protocol {
+            ProtocolType::HTTP1_1 => {
+                if let Some(version) = self.http1_version(data) {
+                    info = info.with_version(version);
+                }
+                if let Some(encoding) = http::response_content_encoding(data) {
+                    encoding.annotate(&mut info);
+                }
+                http::annotate_interim(&mut info, data);
+            }
+            ProtocolType::HTTP1_0 if http::is_http09_request(data) => {
+                info = info.with_version("0.9");
+            }
+            ProtocolType::QUIC => {
+                if let Some(version) = self.quic_version(data) {
+                    info = info.with_version(version);
+                }
+                if let Some(versions) = self.parse_quic_version_negotiation(data) {
+                    let list: Vec<String> = versions.iter().map(|v| format!("0x{:08x}", v)).collect();
+                    info.add_metadata("quic_versions", format!("[{}]", list.join(", ")));
+                }
+            }
+            ProtocolType::HTTP2 => {
+                if !data.starts_with(http2::CONNECTION_PREFACE) && http2::midstream_frame_chain(data) >= 2 {
+                    info.add_metadata("http2_mode", "midstream");
+                }
+                if let Some(anomaly) = http2::detect_anomaly(data) {
+                    anomaly.annotate(&mut info);
+                }
+            }
+            ProtocolType::GRPC => {
+                let variant = grpc::content_type_variant(data)
+                    .or_else(|| data.starts_with(http2::CONNECTION_PREFACE).then_some(GrpcVariant::H2));
+                if let Some(variant) = variant {
+                    info.add_metadata("grpc_variant", variant.as_str());
+                }
+            }
+            ProtocolType::TLS => {
+                info.add_metadata(
+                    "tls_scanner_hint",
+                    self.tls_alpn_detector.is_scanner_like(data).to_string(),
+                );
+                // 带ECH时明文SNI只是外层名称，按SNI路由可能不可靠
+                let ech_present = self.tls_alpn_detector.has_ech(data);
+                info.add_metadata("ech_present", ech_present.to_string());
+                if ech_present && self.tls_alpn_detector.has_sni(data) {
+                    info.add_metadata("sni_is_outer", "true");
+                }
+            }
+            ProtocolType::PostgreSQL if is_postgres_ssl_request(data) => {
+                info.add_metadata("db_ssl_upgrade", "pending");
+            }
+            ProtocolType::MySQL if self.is_mysql_ssl_request(data) => {
+                info.add_metadata("db_ssl_upgrade", "pending");
+            }
+            ProtocolType::SMTP | ProtocolType::IMAP | ProtocolType::POP3 | ProtocolType::FTP
+                if starttls_command(data) == Some(protocol) =>
+            {
+                info.add_metadata("starttls_command", "true");
+            }
+            ProtocolType::SMTP | ProtocolType::IMAP | ProtocolType::FTP => {
+                let offered = starttls::classify(data)
+                    .filter(|session| session.protocol == protocol)
+                    .and_then(|session| session.starttls_offered);
+                if let Some(offered) = offered {
+                    info.add_metadata("starttls_offered", offered.to_string());
+                }
+            }
+            ProtocolType::Thrift => {
+                if let Some(header) = self.parse_thrift_header(data) {
+                    let message_type = match header.message_type {
+                        1 => "call",
+                        2 => "reply",
+                        3 => "exception",
+                        _ => "oneway",
+                    };
+                    info.add_metadata("thrift_protocol", header.protocol);
+                    info.add_metadata("thrift_message_type", message_type);
+                    info.add_metadata("thrift_method", header.method);
+                }
+            }
+            ProtocolType::WebSocket => {
+                if let Some(close) = websocket::parse_close_frame(data) {
+                    close.annotate(&mut info);
+                }
+            }
+            ProtocolType::Memcached => {
+                if let Some(request) = self.parse_memcached(data) {
+                    info.add_metadata("memcached_protocol", request.protocol);
+                    info.add_metadata("memcached_command", request.command);
+                }
+            }
+            ProtocolType::ClickHouse => {
+                if let Some(hello) = self.parse_clickhouse_hello(data) {
+                    info.add_metadata("clickhouse_client", hello.name);
+                    info.add_metadata("clickhouse_revision", hello.revision.to_string());
+                }
+            }
+            ProtocolType::OpenVPN => {
+                if let Some(reset) = self.parse_openvpn(data) {
+                    info.add_metadata("openvpn_opcode", reset.opcode);
+                    info.add_metadata("openvpn_protection", reset.protection);
+                    info.add_metadata("transport", reset.transport);
+                }
+            }
+            ProtocolType::MQTT => {
+                if let mqtt::ConnectCheck::Valid(header) = mqtt::check_connect(data) {
+                    info = info.with_version(header.version());
+                    header.annotate(&mut info);
+                }
+            }
+            ProtocolType::IKE => {
+                if let Some(header) = self.parse_ike(data) {
+                    info.add_metadata("ike_version", header.version.to_string());
+                    info.add_metadata("ike_exchange", header.exchange);
+                    info.add_metadata("ike_initial", header.initial.to_string());
+                    info.add_metadata("transport", if header.nat_t { "udp-nat-t" } else { "udp" });
+                }
+            }
+            ProtocolType::Avro => {
+                let framing = if data.starts_with(AVRO_OBJECT_MAGIC) { "object_container" } else { "rpc_handshake" };
+                info.add_metadata("avro_framing", framing);
+            }
+            ProtocolType::WebTransport => {
+                let signals = self.webtransport_signals(data);
+                info.add_metadata("webtransport_enabled", signals.enable_webtransport.to_string());
+                info.add_metadata("h3_datagram", signals.h3_datagram.to_string());
+                info.add_metadata("connect_protocol", signals.connect_protocol.to_string());
+                info.add_metadata("capsule_protocol", signals.capsule_protocol.to_string());
+            }
+            _ => {}
+        }
+        info
+    }
+    
     /// 检测HTTP/2协议
     fn detect_http2(&self, data: &[u8]) -> Option<f32> {
-        if data.len() < 24 {
-            return None;
-        }
-        
         // HTTP/2连接前言
-        const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
-        if data.starts_with(HTTP2_PREFACE) {
+        if data.starts_with(http2::CONNECTION_PREFACE) {
             return Some(1.0);
         }
         
+        // 中途接入：没有前言，按连续自洽的帧数给出置信度
+        match http2::midstream_frame_chain(data) {
+            0 => {}
+            1 => return Some(0.8),
+            2 => return Some(0.85),
+            3 => return Some(0.9),
+            _ => return Some(0.95),
+        }
+        
         // 检查HTTP/2帧格式
-        if data.len() >= 9 {
+        if data.len() >= 24 {
             let frame_type = data[3];
             // SETTINGS帧 (0x4) 或 HEADERS帧 (0x1)
             if frame_type == 0x4 || frame_type == 0x1 {
@@ -99,8 +557,16 @@ impl PassiveProbe {
             // 检查已知的QUIC版本
             match version {
                 0x00000001 => return Some(0.95), // QUIC v1
+                0x6b3343cf => return Some(0.95), // QUIC v2
                 0xff00001d => return Some(0.9),  // Draft-29
-                0 => return Some(0.7),           // 版本协商
+                0 => {
+                    // 版本协商：能解析出版本列表时置信度更高
+                    return if self.parse_quic_version_negotiation(data).is_some() {
+                        Some(0.85)
+                    } else {
+                        Some(0.7)
+                    };
+                }
                 _ => {}
             }
         }
@@ -108,8 +574,49 @@ impl PassiveProbe {
         None
     }
     
+    /// 解析QUIC版本协商包，返回服务端提供的版本列表
+    ///
+    /// 格式：首字节 + 版本(0) + DCID长度/DCID + SCID长度/SCID + 若干4字节版本号。
+    fn parse_quic_version_negotiation(&self, data: &[u8]) -> Option<Vec<u32>> {
+        if data.len() < 7 || data[0] & 0x80 == 0 || data[1..5] != [0, 0, 0, 0] {
+            return None;
+        }
+        
+        let dcid_len = data[5] as usize;
+        let scid_offset = 6 + dcid_len;
+        let scid_len = *data.get(scid_offset)? as usize;
+        let versions = data.get(scid_offset + 1 + scid_len..)?;
+        
+        if versions.is_empty() || versions.len() % 4 != 0 {
+            return None;
+        }
+        
+        Some(versions.chunks_exact(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect())
+    }
+    
+    /// 从QUIC长包头提取版本名称
+    fn quic_version(&self, data: &[u8]) -> Option<&'static str> {
+        if data.len() < 5 || data[0] & 0x80 == 0 {
+            return None;
+        }
+        
+        match u32::from_be_bytes([data[1], data[2], data[3], data[4]]) {
+            0x00000001 => Some("1"),
+            0x6b3343cf => Some("2"),
+            0xff00001d => Some("draft-29"),
+            _ => None,
+        }
+    }
+    
     /// 检测HTTP/3协议 (优化版)
     fn detect_http3(&self, data: &[u8]) -> Option<f32> {
+        // 版本协商包不携带应用数据，不可能是HTTP/3
+        if data.len() >= 5 && data[1..5] == [0, 0, 0, 0] {
+            return None;
+        }
+        
         // HTTP/3基于QUIC，先检查QUIC
         if let Some(quic_confidence) = self.detect_quic(data) {
             if quic_confidence > 0.7 {
@@ -158,6 +665,9 @@ impl PassiveProbe {
     
     /// 检测gRPC协议 (优化版)
     fn detect_grpc(&self, data: &[u8]) -> Option<f32> {
+        if let Some(confidence) = self.detect_grpc_web_http1(data) {
+            return Some(confidence);
+        }
         if data.len() < 16 {
             return None;
         }
@@ -207,6 +717,29 @@ impl PassiveProbe {
         }
     }
     
+    /// 检测HTTP/1.x报文中的gRPC-Web（浏览器流量）
+    ///
+    /// 依据 `content-type` 判断，已收到消息体时校验第一个消息帧头（文本模式先做base64解码）。
+    /// 置信度高于普通HTTP/1.x响应，使gRPC-Web优先于HTTP/1.1。
+    fn detect_grpc_web_http1(&self, data: &[u8]) -> Option<f32> {
+        let data = http::trim_leading_blank(data);
+        if http::request_method(data).is_none() && !data.starts_with(b"HTTP/1.") {
+            return None;
+        }
+        
+        let headers = http::header_block(data).unwrap_or(data);
+        let content_type = http::header_value(headers, "content-type")?;
+        let variant = grpc::content_type_variant(content_type.as_bytes())?;
+        let body = http::body_slice(data).unwrap_or_default();
+        let framed = match variant {
+            GrpcVariant::Web => grpc::is_frame_header(body),
+            GrpcVariant::Text => grpc::is_web_text_frame(body),
+            // 原生gRPC只能运行在HTTP/2上
+            GrpcVariant::H2 => return None,
+        };
+        (framed != Some(false)).then_some(0.98)
+    }
+    
     /// 快速字节序列搜索 (Boyer-Moore 简化版)
     #[inline]
     fn fast_search(&self, haystack: &[u8], needle: &[u8]) -> bool {
@@ -247,19 +780,22 @@ impl PassiveProbe {
             return None;
         }
         
-        // 首先检查是否明确是 HTTP 请求/响应
-        let is_http_like = self.fast_search(data, b"HTTP/") || 
-                          self.fast_search(data, b"GET ") ||
-                          self.fast_search(data, b"POST ");
+        // 首先检查是否明确是 HTTP 请求/响应（仅对文本前缀的数据）
+        let is_http_like = self.is_text_like(data) && (
+            self.fast_search(data, b"HTTP/") || 
+            self.fast_search(data, b"GET ") ||
+            self.fast_search(data, b"POST ")
+        );
         
         if is_http_like {
-            // 快速检查 WebSocket 升级头部
-            let has_upgrade_websocket = self.fast_search(data, b"Upgrade: websocket") ||
-                                       self.fast_search(data, b"upgrade: websocket");
+            // 只在头部中查找升级头，避免正文内容造成误判
+            let headers = http::header_block(data).unwrap_or(data);
+            let has_upgrade_websocket = self.fast_search(headers, b"Upgrade: websocket") ||
+                                       self.fast_search(headers, b"upgrade: websocket");
             
             if has_upgrade_websocket {
                 // 检查是否是握手响应
-                if self.fast_search(data, b"HTTP/1.1 101") {
+                if self.fast_search(headers, b"HTTP/1.1 101") {
                     return Some(0.98);
                 }
                 // 普通握手请求应该优先识别为 HTTP1_1，降低 WebSocket 置信度
@@ -270,6 +806,11 @@ impl PassiveProbe {
             return None;
         }
         
+        // 状态码和原因都通过校验的关闭帧
+        if websocket::parse_close_frame(data).is_some() {
+            return Some(0.85);
+        }
+        
         // 检查 WebSocket 帧格式 (数据帧) - 更严格的检查
         if data.len() >= 2 {
             let first_byte = data[0];
@@ -293,15 +834,16 @@ impl PassiveProbe {
         None
     }
     
-    /// 检测TLS协议
-    fn detect_tls(&self, data: &[u8]) -> Option<f32> {
-        if data.len() < 5 {
-            return None;
-        }
-        
-        let content_type = data[0];
-        let version_major = data[1];
-        let version_minor = data[2];
+    /// 解析TLS记录头
+    ///
+    /// 内容类型、版本或长度字段不合法时返回 `None`；
+    /// 记录头合法但负载被截断（如ClientHello跨越两个TCP分段）时返回 `Truncated`。
+    fn parse_tls_record(&self, data: &[u8]) -> Option<TlsRecord> {
+        let header = data.get(..TLS_RECORD_HEADER_LEN)?;
+        let content_type = header[0];
+        let version_major = header[1];
+        let version_minor = header[2];
+        let length = u16::from_be_bytes([header[3], header[4]]) as usize;
         
         // TLS内容类型检查
         let valid_content_type = matches!(content_type, 0x14 | 0x15 | 0x16 | 0x17);
@@ -316,15 +858,33 @@ impl PassiveProbe {
             _ => false,
         };
         
-        if valid_content_type && valid_version {
-            // ClientHello (0x16) 有最高置信度
-            if content_type == 0x16 {
-                Some(0.95)
-            } else {
-                Some(0.8)
-            }
+        if !valid_content_type || !valid_version || !(1..=TLS_MAX_RECORD_LEN).contains(&length) {
+            return None;
+        }
+        
+        let total = TLS_RECORD_HEADER_LEN + length;
+        if data.len() < total {
+            Some(TlsRecord::Truncated(total))
         } else {
-            None
+            Some(TlsRecord::Complete { content_type })
+        }
+    }
+    
+    /// 检测TLS协议，只对完整记录给出结论
+    fn detect_tls(&self, data: &[u8]) -> Option<f32> {
+        match self.parse_tls_record(data)? {
+            // ClientHello (0x16) 有最高置信度
+            TlsRecord::Complete { content_type: 0x16 } => Some(0.95),
+            TlsRecord::Complete { .. } => Some(0.8),
+            TlsRecord::Truncated(_) => None,
+        }
+    }
+    
+    /// TLS记录被截断时返回完整记录所需的总字节数
+    fn truncated_tls_record(&self, data: &[u8]) -> Option<usize> {
+        match self.parse_tls_record(data)? {
+            TlsRecord::Truncated(total) => Some(total),
+            TlsRecord::Complete { .. } => None,
         }
     }
     
@@ -368,73 +928,825 @@ impl PassiveProbe {
         
         None
     }
-}
-
-impl ProbeEngine for PassiveProbe {
-    fn probe(&self, data: &[u8]) -> Result<DetectionResult> {
-        if data.len() < self.min_data_size {
-            return Err(DetectorError::NeedMoreData(self.min_data_size));
+    
+    /// 检测VNC协议（RFB ProtocolVersion握手，如 `RFB 003.008\n`）
+    fn detect_rfb(&self, data: &[u8]) -> Option<f32> {
+        if data.len() < 12 || !data.starts_with(b"RFB ") {
+            return None;
         }
         
-        let mut best_protocol = ProtocolType::Unknown;
-        let mut best_confidence = 0.0;
+        let version = &data[4..12];
+        let valid = version[..3].iter().all(u8::is_ascii_digit)
+            && version[3] == b'.'
+            && version[4..7].iter().all(u8::is_ascii_digit)
+            && version[7] == b'\n';
         
-        // 尝试各种协议检测 (按优先级排序)
-        // 使用栈分配的数组来减少堆分配
-        let mut detections = [(ProtocolType::Unknown, 0.0); 8];
-        let mut detection_count = 0;
+        if valid {
+            Some(0.98)
+        } else {
+            None
+        }
+    }
+    
+    /// 检测RDP协议（X.224 Connection Request）
+    ///
+    /// TPKT头 `03 00` + 2字节长度，随后是COTP CR PDU（长度指示 + `0xE0`）。
+    fn detect_rdp(&self, data: &[u8]) -> Option<f32> {
+        if data.len() < 11 || data[0] != 0x03 || data[1] != 0x00 {
+            return None;
+        }
         
-        // 按优先级检测协议
-        if let Some(confidence) = self.detect_http3(data) {
-            detections[detection_count] = (ProtocolType::HTTP3, confidence);
-            detection_count += 1;
+        // TPKT长度包含4字节头部，且不能超过已有数据
+        let tpkt_length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        if tpkt_length < 11 || tpkt_length > data.len() {
+            return None;
         }
-        if let Some(confidence) = self.detect_quic(data) {
-            detections[detection_count] = (ProtocolType::QUIC, confidence);
-            detection_count += 1;
+        
+        // COTP长度指示不含自身字节，必须与TPKT长度一致
+        let length_indicator = data[4] as usize;
+        if length_indicator + 5 != tpkt_length || data[5] & 0xf0 != 0xe0 {
+            return None;
         }
-        if let Some(confidence) = self.detect_http2(data) {
-            detections[detection_count] = (ProtocolType::HTTP2, confidence);
-            detection_count += 1;
+        
+        if self.fast_search(&data[..tpkt_length], b"Cookie: mstshash=") {
+            Some(0.98)
+        } else {
+            Some(0.95)
         }
-        if let Some(confidence) = self.detect_grpc(data) {
-            detections[detection_count] = (ProtocolType::GRPC, confidence);
-            detection_count += 1;
+    }
+    
+    /// 依次运行 [`DETECTORS`]，返回允许的协议中置信度最高的一个（没有命中时为 `Unknown`）
+    fn best_detection<F>(&self, data: &[u8], allowed: F) -> (ProtocolType, f32)
+    where
+        F: Fn(ProtocolType) -> bool,
+    {
+        let mut best = (ProtocolType::Unknown, 0.0);
+        for detector in DETECTORS {
+            let detection = match *detector {
+                Detector::Fixed(protocol, detect) => detect(self, data).map(|confidence| (protocol, confidence)),
+                Detector::Text(detect) => detect(self, data),
+            };
+            if let Some((protocol, confidence)) = detection {
+                if confidence > best.1 && allowed(protocol) {
+                    best = (protocol, confidence);
+                }
+            }
         }
-        if let Some(confidence) = self.detect_http1(data) {
-            detections[detection_count] = (ProtocolType::HTTP1_1, confidence);
-            detection_count += 1;
+        best
+    }
+    
+    /// 识别SMTP、IMAP、FTP明文阶段的命令和响应（问候语、能力列表等）
+    fn detect_text_session(&self, data: &[u8]) -> Option<(ProtocolType, f32)> {
+        starttls::classify(data).map(|session| (session.protocol, session.confidence))
+    }
+    
+    /// 检测PostgreSQL SSLRequest
+    fn detect_postgres_ssl_request(&self, data: &[u8]) -> Option<f32> {
+        if is_postgres_ssl_request(data) {
+            Some(0.99)
+        } else {
+            None
         }
-        if let Some(confidence) = self.detect_tls(data) {
-            detections[detection_count] = (ProtocolType::TLS, confidence);
-            detection_count += 1;
+    }
+    
+    /// 检查是否为MySQL SSLRequest
+    ///
+    /// 客户端响应服务端握手时仅发送32字节的能力声明（置位CLIENT_SSL，
+    /// 序号为1，末尾23字节填充为0），随后开始TLS握手。
+    fn is_mysql_ssl_request(&self, data: &[u8]) -> bool {
+        if data.len() < MYSQL_SSL_REQUEST_LEN + 4 {
+            return false;
         }
-        if let Some(confidence) = self.detect_ssh(data) {
-            detections[detection_count] = (ProtocolType::SSH, confidence);
-            detection_count += 1;
+        
+        let payload_len = u32::from_le_bytes([data[0], data[1], data[2], 0]) as usize;
+        if payload_len != MYSQL_SSL_REQUEST_LEN || data[3] != 1 {
+            return false;
         }
-        if let Some(confidence) = self.detect_websocket(data) {
-            detections[detection_count] = (ProtocolType::WebSocket, confidence);
-            detection_count += 1;
+        
+        let capabilities = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        capabilities & MYSQL_CLIENT_SSL != 0
+            && capabilities & MYSQL_CLIENT_PROTOCOL_41 != 0
+            && data[13..MYSQL_SSL_REQUEST_LEN + 4].iter().all(|&b| b == 0)
+    }
+    
+    /// 检测MySQL SSLRequest
+    fn detect_mysql_ssl_request(&self, data: &[u8]) -> Option<f32> {
+        if self.is_mysql_ssl_request(data) {
+            Some(0.95)
+        } else {
+            None
         }
+    }
+    
+    /// 收集WebTransport相关信号
+    ///
+    /// 无状态时无法完整解码HTTP/3帧，因此查找SETTINGS帧中的启用标志，
+    /// 以及扩展CONNECT和Capsule协议的文本特征。
+    fn webtransport_signals(&self, data: &[u8]) -> WebTransportSignals {
+        let mut signals = WebTransportSignals::default();
         
-        // 找到最佳匹配
-        for i in 0..detection_count {
-            let (protocol, confidence) = detections[i];
-            if confidence > best_confidence {
-                best_confidence = confidence;
-                best_protocol = protocol;
+        for (offset, _) in data.iter().enumerate().filter(|(_, &b)| b as u64 == H3_FRAME_SETTINGS) {
+            if let Some(settings) = self.parse_h3_settings(&data[offset..]) {
+                for (id, value) in settings {
+                    match id {
+                        SETTINGS_ENABLE_WEBTRANSPORT if value != 0 => signals.enable_webtransport = true,
+                        SETTINGS_H3_DATAGRAM if value != 0 => signals.h3_datagram = true,
+                        _ => {}
+                    }
+                }
+                break;
             }
         }
         
+        signals.connect_protocol = self.has_webtransport_connect_protocol(data);
+        signals.capsule_protocol = self.fast_search(data, b"capsule-protocol: ?1")
+            || self.fast_search(data, b"Capsule-Protocol: ?1");
+        signals
+    }
+    
+    /// 检查扩展CONNECT的 `:protocol` 伪头部是否取值为 `webtransport`
+    ///
+    /// 只认伪头部上下文，正文或URL中出现的 `webtransport` 字样不算。
+    fn has_webtransport_connect_protocol(&self, data: &[u8]) -> bool {
+        const PSEUDO_HEADER: &[u8] = b":protocol";
+        data.windows(PSEUDO_HEADER.len())
+            .enumerate()
+            .filter(|(_, window)| *window == PSEUDO_HEADER)
+            .any(|(pos, _)| {
+                let start = pos + PSEUDO_HEADER.len();
+                let end = (start + CONNECT_PROTOCOL_WINDOW + b"webtransport".len()).min(data.len());
+                self.fast_search(&data[start..end], b"webtransport")
+            })
+    }
+    
+    /// 解析以SETTINGS帧开头的数据，返回设置项列表
+    fn parse_h3_settings(&self, data: &[u8]) -> Option<Vec<(u64, u64)>> {
+        let (frame_type, type_len) = read_quic_varint(data)?;
+        if frame_type != H3_FRAME_SETTINGS {
+            return None;
+        }
+        
+        let (length, length_len) = read_quic_varint(&data[type_len..])?;
+        let start = type_len + length_len;
+        let payload = data.get(start..start.checked_add(usize::try_from(length).ok()?)?)?;
+        if payload.is_empty() {
+            return None;
+        }
+        
+        let mut settings = Vec::new();
+        let mut offset = 0;
+        while offset < payload.len() {
+            let (id, id_len) = read_quic_varint(&payload[offset..])?;
+            offset += id_len;
+            let (value, value_len) = read_quic_varint(&payload[offset..])?;
+            offset += value_len;
+            settings.push((id, value));
+        }
+        
+        Some(settings)
+    }
+    
+    /// 检测WebTransport及HTTP Datagram
+    ///
+    /// 扩展CONNECT只在同时看到SETTINGS_ENABLE_WEBTRANSPORT时提高置信度。
+    fn detect_webtransport(&self, data: &[u8]) -> Option<f32> {
+        let signals = self.webtransport_signals(data);
+        
+        if signals.enable_webtransport && signals.connect_protocol {
+            Some(0.95)
+        } else if signals.enable_webtransport {
+            Some(0.9)
+        } else if signals.capsule_protocol && signals.h3_datagram {
+            Some(0.75)
+        } else {
+            None
+        }
+    }
+    
+    /// 检测IRC协议
+    fn detect_irc(&self, data: &[u8]) -> Option<f32> {
+        if data.len() < 6 || !self.is_text_like(data) {
+            return None;
+        }
+        
+        // 客户端注册命令
+        const COMMANDS: [&[u8]; 4] = [b"NICK ", b"USER ", b"CAP LS", b"PASS "];
+        
+        let mut matched_lines = 0;
+        for line in data.split(|&b| b == b'\n').take(4) {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            
+            if COMMANDS.iter().any(|cmd| line.starts_with(cmd)) || self.is_irc_numeric_reply(line) {
+                matched_lines += 1;
+            } else {
+                break;
+            }
+        }
+        
+        match matched_lines {
+            0 => None,
+            1 => Some(0.8),
+            _ => Some(0.95),
+        }
+    }
+    
+    /// 检查是否为服务器数字应答，如 `:irc.example.net 001 nick :Welcome`
+    fn is_irc_numeric_reply(&self, line: &[u8]) -> bool {
+        if line.first() != Some(&b':') {
+            return false;
+        }
+        
+        let prefix_end = match line.iter().position(|&b| b == b' ') {
+            Some(pos) if pos > 1 => pos,
+            _ => return false,
+        };
+        
+        let rest = &line[prefix_end + 1..];
+        rest.len() >= 4 && rest[..3].iter().all(u8::is_ascii_digit) && rest[3] == b' '
+    }
+    
+    /// 检测长度前缀的裸Protobuf消息
+    ///
+    /// 格式为4字节大端长度加Protobuf消息体，长度必须与剩余数据完全一致。
+    /// 该格式本身有歧义，置信度随成功解析的顶层字段数增加，最高0.75。
+    fn detect_length_prefixed_protobuf(&self, data: &[u8]) -> Option<f32> {
+        if data.len() < 6 {
+            return None;
+        }
+        
+        let length = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let message = &data[4..];
+        if length == 0 || length != message.len() {
+            return None;
+        }
+        
+        let mut offset = 0;
+        let mut fields = 0;
+        while offset < message.len() {
+            let (tag, tag_len) = read_varint(&message[offset..])?;
+            let field_number = tag >> 3;
+            if field_number == 0 {
+                return None;
+            }
+            offset += tag_len;
+            
+            let value_len = match tag & 0x07 {
+                0 => read_varint(&message[offset..])?.1,
+                1 => 8,
+                2 => {
+                    let (len, len_size) = read_varint(&message[offset..])?;
+                    len_size.checked_add(usize::try_from(len).ok()?)?
+                }
+                3 | 4 => 0,
+                5 => 4,
+                _ => return None,
+            };
+            offset = offset.checked_add(value_len)?;
+            if offset > message.len() {
+                return None;
+            }
+            fields += 1;
+        }
+        
+        Some((0.55 + fields.min(4) as f32 * 0.05).min(0.75))
+    }
+    
+    /// 解析Thrift分帧传输的消息头
+    ///
+    /// 4字节大端帧长度后为binary（`80 01 00 类型`）或compact（`82 类型|版本`）消息头。
+    /// 帧长度必须能容纳解析出的消息头，且不超过 `THRIFT_MAX_FRAME_SIZE`。
+    fn parse_thrift_header<'a>(&self, data: &'a [u8]) -> Option<ThriftHeader<'a>> {
+        let frame_size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        if !(8..=THRIFT_MAX_FRAME_SIZE).contains(&frame_size) {
+            return None;
+        }
+        let frame = &data[4..];
+        
+        let (protocol, message_type, name_start, name_len) = if frame.starts_with(&THRIFT_BINARY_VERSION) {
+            // 版本(2) + 保留(1) + 类型(1) + 名称长度(4) + 名称 + 序号(4)
+            if *frame.get(2)? != 0 {
+                return None;
+            }
+            let name_len = u32::from_be_bytes(frame.get(4..8)?.try_into().ok()?) as usize;
+            ("binary", *frame.get(3)?, 8, name_len)
+        } else if frame.first() == Some(&THRIFT_COMPACT_ID) {
+            // 标识(1) + 类型|版本(1) + 序号(varint) + 名称长度(varint) + 名称
+            let type_and_version = *frame.get(1)?;
+            if type_and_version & 0x1f != 1 {
+                return None;
+            }
+            let (_, seq_len) = read_varint(frame.get(2..)?)?;
+            let (name_len, len_size) = read_varint(frame.get(2 + seq_len..)?)?;
+            ("compact", type_and_version >> 5, 2 + seq_len + len_size, usize::try_from(name_len).ok()?)
+        } else {
+            return None;
+        };
+        
+        if !(1..=4).contains(&message_type) || name_len == 0 || name_len > 256 {
+            return None;
+        }
+        
+        // 消息头必须完整落在帧内
+        let header_len = name_start + name_len + if protocol == "binary" { 4 } else { 0 };
+        if header_len > frame_size {
+            return None;
+        }
+        
+        let name = frame.get(name_start..name_start + name_len)?;
+        if !name.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || b == b':') {
+            return None;
+        }
+        
+        Some(ThriftHeader {
+            protocol,
+            message_type,
+            method: std::str::from_utf8(name).ok()?,
+        })
+    }
+    
+    /// 检测Thrift协议
+    fn detect_thrift(&self, data: &[u8]) -> Option<f32> {
+        match self.parse_thrift_header(data)?.protocol {
+            "binary" => Some(0.9),
+            _ => Some(0.85),
+        }
+    }
+    
+    /// 检测Avro协议
+    ///
+    /// 识别对象容器文件头 `Obj\x01`，以及Avro socket传输上的RPC握手：
+    /// 序号(4) + 缓冲区数(4) + 缓冲区长度(4)，随后是HandshakeRequest
+    /// （clientHash(16) + union{null, string clientProtocol} + serverHash(16) + union{null, map meta}），
+    /// 且要求clientProtocol为JSON字符串。
+    fn detect_avro(&self, data: &[u8]) -> Option<f32> {
+        if data.starts_with(AVRO_OBJECT_MAGIC) {
+            return Some(0.95);
+        }
+        
+        let buffer_count = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+        let buffer_len = u32::from_be_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+        if !(1..=64).contains(&buffer_count) || !(2 * AVRO_HASH_LEN + 2..=AVRO_MAX_BUFFER_LEN).contains(&buffer_len) {
+            return None;
+        }
+        
+        // 只认携带clientProtocol JSON的握手，null分支特征太弱，容易误判
+        let handshake = &data[12..];
+        let mut pos = AVRO_HASH_LEN;
+        if *handshake.get(pos)? != 0x02 {
+            return None;
+        }
+        
+        // Avro字符串长度为zigzag编码的varint
+        let (raw_len, len_size) = read_varint(handshake.get(pos + 1..)?)?;
+        let protocol_len = usize::try_from(raw_len >> 1).ok()?;
+        if raw_len & 1 != 0 || handshake.get(pos + 1 + len_size) != Some(&b'{') {
+            return None;
+        }
+        pos += 1 + len_size + protocol_len;
+        
+        if pos + AVRO_HASH_LEN + 1 > buffer_len {
+            return None;
+        }
+        
+        // 协议JSON可能超出已接收数据，此时无法检查meta
+        match handshake.get(pos + AVRO_HASH_LEN) {
+            Some(0x00 | 0x02) | None => Some(0.9),
+            _ => None,
+        }
+    }
+    
+    /// 解析Memcached请求
+    ///
+    /// 二进制协议校验24字节报文头（魔数、键长、数据类型、报文体长度）；
+    /// 文本协议要求完整的命令行，且参数符合命令语法（键 + 数值参数 + `\r\n`），
+    /// 避免与 `get`、`set` 等常见单词冲突。
+    fn parse_memcached(&self, data: &[u8]) -> Option<MemcachedRequest> {
+        if let Some(opcode) = self.memcached_binary_opcode(data) {
+            return Some(MemcachedRequest {
+                protocol: "binary",
+                command: format!("0x{:02x}", opcode),
+            });
+        }
+        
+        let line_end = data.windows(2).position(|w| w == b"\r\n")?;
+        let line = std::str::from_utf8(&data[..line_end]).ok()?;
+        let mut parts = line.split(' ');
+        let command = parts.next()?;
+        let args: Vec<&str> = parts.collect();
+        
+        let is_key = |key: &&str| {
+            !key.is_empty() && key.len() <= MEMCACHED_MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic())
+        };
+        let is_number = |value: &&str| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit());
+        let is_noreply = |rest: &[&str]| rest.is_empty() || rest == ["noreply"];
+        
+        let valid = match (command, args.as_slice()) {
+            ("get" | "gets", keys) => !keys.is_empty() && keys.iter().all(is_key),
+            ("gat" | "gats", [exptime, keys @ ..]) => is_number(exptime) && !keys.is_empty() && keys.iter().all(is_key),
+            ("set" | "add" | "replace" | "append" | "prepend", [key, flags, exptime, bytes, rest @ ..]) => {
+                is_key(key) && [flags, exptime, bytes].into_iter().all(is_number) && is_noreply(rest)
+            }
+            ("cas", [key, flags, exptime, bytes, unique, rest @ ..]) => {
+                is_key(key) && [flags, exptime, bytes, unique].into_iter().all(is_number) && is_noreply(rest)
+            }
+            ("incr" | "decr" | "touch", [key, value, rest @ ..]) => is_key(key) && is_number(value) && is_noreply(rest),
+            ("delete", [key, rest @ ..]) => is_key(key) && is_noreply(rest),
+            ("stats", args) => args.iter().all(is_key),
+            ("version" | "quit", []) => true,
+            _ => false,
+        };
+        
+        valid.then(|| MemcachedRequest {
+            protocol: "text",
+            command: command.to_string(),
+        })
+    }
+    
+    /// 校验Memcached二进制协议报文头，返回操作码
+    fn memcached_binary_opcode(&self, data: &[u8]) -> Option<u8> {
+        let header = data.get(..MEMCACHED_HEADER_LEN)?;
+        let magic = header[0];
+        let opcode = header[1];
+        let key_len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let extras_len = header[4] as usize;
+        let data_type = header[5];
+        let status = u16::from_be_bytes([header[6], header[7]]);
+        let body_len = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+        
+        let valid_status = match magic {
+            // 请求中该字段为vbucket id
+            MEMCACHED_REQUEST_MAGIC => true,
+            MEMCACHED_RESPONSE_MAGIC => matches!(status, 0x0000..=0x0008 | 0x0081..=0x0086),
+            _ => false,
+        };
+        
+        let valid = valid_status
+            && opcode <= 0x22
+            && data_type == 0
+            && key_len <= MEMCACHED_MAX_KEY_LEN
+            && body_len <= MEMCACHED_MAX_BODY_LEN
+            && (extras_len + key_len) as u32 <= body_len;
+        valid.then_some(opcode)
+    }
+    
+    /// 检测Memcached协议
+    fn detect_memcached(&self, data: &[u8]) -> Option<f32> {
+        match self.parse_memcached(data)?.protocol {
+            "binary" => Some(0.85),
+            _ => Some(0.8),
+        }
+    }
+    
+    /// 解析ClickHouse原生协议的Hello包
+    ///
+    /// 客户端和服务端的Hello结构相同：包类型0 + 名称字符串 + 主版本号 + 次版本号 + 协议修订号，
+    /// 字符串和整数均为变长编码。
+    fn parse_clickhouse_hello<'a>(&self, data: &'a [u8]) -> Option<ClickHouseHello<'a>> {
+        if *data.first()? != CLICKHOUSE_PACKET_HELLO {
+            return None;
+        }
+        
+        let (name_len, len_size) = read_varint(data.get(1..)?)?;
+        if !(1..=CLICKHOUSE_MAX_NAME_LEN).contains(&name_len) {
+            return None;
+        }
+        let mut pos = 1 + len_size;
+        let name = data.get(pos..pos + name_len as usize)?;
+        if !name.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return None;
+        }
+        pos += name.len();
+        
+        let mut fields = [0u64; 3];
+        for field in &mut fields {
+            let (value, size) = read_varint(data.get(pos..)?)?;
+            *field = value;
+            pos += size;
+        }
+        let [major, _minor, revision] = fields;
+        if major > 100 || !CLICKHOUSE_REVISION_RANGE.contains(&revision) {
+            return None;
+        }
+        
+        Some(ClickHouseHello {
+            name: std::str::from_utf8(name).ok()?,
+            revision,
+        })
+    }
+    
+    /// 检测ClickHouse原生协议
+    fn detect_clickhouse(&self, data: &[u8]) -> Option<f32> {
+        let hello = self.parse_clickhouse_hello(data)?;
+        if hello.name.to_ascii_lowercase().contains("clickhouse") {
+            Some(0.9)
+        } else {
+            Some(0.8)
+        }
+    }
+    
+    /// 检测MQTT CONNECT报文
+    ///
+    /// 报文头完整且一致时给出高置信度；报文头尚未收全时只作为低置信度候选。
+    fn detect_mqtt(&self, data: &[u8]) -> Option<f32> {
+        match mqtt::check_connect(data) {
+            mqtt::ConnectCheck::Valid(_) => Some(0.95),
+            mqtt::ConnectCheck::Partial => Some(0.3),
+            mqtt::ConnectCheck::Invalid => None,
+        }
+    }
+    
+    /// 只检测指定协议，返回置信度
+    ///
+    /// 不构造 `ProtocolInfo`，不在被动探测范围内的协议返回 `None`。
+    pub fn detect_protocol(&self, protocol: ProtocolType, data: &[u8]) -> Option<f32> {
+        if data.len() < self.min_data_size && !is_short_upgrade_request(data) {
+            return None;
+        }
+        
+        match protocol {
+            ProtocolType::HTTP1_0 => self.detect_http09(data),
+            ProtocolType::HTTP1_1 => self.detect_http1(data),
+            ProtocolType::HTTP2 => self.detect_http2(data),
+            ProtocolType::HTTP3 => self.detect_http3(data),
+            ProtocolType::QUIC => self.detect_quic(data),
+            ProtocolType::GRPC => self.detect_grpc(data),
+            ProtocolType::WebSocket => self.detect_websocket(data),
+            ProtocolType::TLS => self.detect_tls(data),
+            ProtocolType::SSH => self.detect_ssh(data),
+            ProtocolType::IRC => self.detect_irc(data),
+            ProtocolType::XMPP => self.detect_xmpp(data),
+            ProtocolType::Protobuf => self.detect_length_prefixed_protobuf(data),
+            ProtocolType::VNC => self.detect_rfb(data),
+            ProtocolType::RDP => self.detect_rdp(data),
+            ProtocolType::WebTransport => self.detect_webtransport(data),
+            ProtocolType::PostgreSQL => self.detect_postgres_ssl_request(data),
+            ProtocolType::MySQL => self.detect_mysql_ssl_request(data),
+            ProtocolType::SMTP | ProtocolType::IMAP | ProtocolType::POP3 | ProtocolType::FTP => {
+                if starttls_command(data) == Some(protocol) {
+                    Some(0.95)
+                } else {
+                    self.detect_text_session(data)
+                        .filter(|(session_protocol, _)| *session_protocol == protocol)
+                        .map(|(_, confidence)| confidence)
+                }
+            }
+            ProtocolType::Thrift => self.detect_thrift(data),
+            ProtocolType::Avro => self.detect_avro(data),
+            ProtocolType::Memcached => self.detect_memcached(data),
+            ProtocolType::ClickHouse => self.detect_clickhouse(data),
+            ProtocolType::OpenVPN => self.detect_openvpn(data),
+            ProtocolType::IKE => self.detect_ike(data),
+            ProtocolType::MQTT => self.detect_mqtt(data),
+            _ => None,
+        }
+    }
+    
+    /// 解析OpenVPN会话的首个报文（硬重置）
+    ///
+    /// TCP模式下每个报文前有2字节大端长度。只接受会话开始时的硬重置操作码，
+    /// 并按明文、tls-auth、tls-crypt三种控制通道格式校验后续字段。
+    fn parse_openvpn(&self, data: &[u8]) -> Option<OpenVpnReset> {
+        if let [len_hi, len_lo, packet @ ..] = data {
+            let len = u16::from_be_bytes([*len_hi, *len_lo]) as usize;
+            if (OPENVPN_HEADER_LEN..=OPENVPN_MAX_PACKET_LEN).contains(&len) {
+                let packet = &packet[..len.min(packet.len())];
+                if let Some(reset) = self.parse_openvpn_packet(packet, "tcp") {
+                    return Some(reset);
+                }
+            }
+        }
+        self.parse_openvpn_packet(data, "udp")
+    }
+    
+    /// 解析单个OpenVPN硬重置报文
+    fn parse_openvpn_packet(&self, packet: &[u8], transport: &'static str) -> Option<OpenVpnReset> {
+        let header = packet.get(..OPENVPN_HEADER_LEN)?;
+        let opcode = match header[0] {
+            0x08 => "hard_reset_client_v1",
+            0x10 => "hard_reset_server_v1",
+            0x38 => "hard_reset_client_v2",
+            0x40 => "hard_reset_server_v2",
+            0x50 => "hard_reset_client_v3",
+            _ => return None,
+        };
+        if header[1..].iter().all(|&b| b == 0) {
+            return None;
+        }
+        
+        let rest = &packet[OPENVPN_HEADER_LEN..];
+        // v3仅用于tls-crypt-v2
+        let protection = if opcode != "hard_reset_client_v3" && openvpn_reliable_header_ok(rest) {
+            "none"
+        } else if opcode != "hard_reset_client_v3" && OPENVPN_TLS_AUTH_HMAC_LENS.iter().any(|&hmac_len| {
+            rest.get(hmac_len..)
+                .is_some_and(|rest| openvpn_replay_id_ok(rest) && openvpn_reliable_header_ok(&rest[8..]))
+        }) {
+            "tls-auth"
+        } else if openvpn_replay_id_ok(rest) && rest.len() >= 8 + OPENVPN_TLS_CRYPT_TAG_LEN {
+            "tls-crypt"
+        } else {
+            return None;
+        };
+        
+        Some(OpenVpnReset { opcode, transport, protection })
+    }
+    
+    /// 检测OpenVPN协议
+    fn detect_openvpn(&self, data: &[u8]) -> Option<f32> {
+        match self.parse_openvpn(data)?.protection {
+            "none" => Some(0.9),
+            "tls-auth" => Some(0.85),
+            _ => Some(0.8),
+        }
+    }
+    
+    /// 解析ISAKMP报文头（IKEv1/IKEv2），兼容NAT-T的non-ESP标记
+    ///
+    /// 校验版本、交换类型、下一载荷、标志位和长度字段；
+    /// 响应方SPI为0时必须是协商的首个报文。
+    fn parse_ike(&self, data: &[u8]) -> Option<IkeHeader> {
+        let (message, nat_t) = match data.strip_prefix(&IKE_NON_ESP_MARKER) {
+            Some(message) => (message, true),
+            None => (data, false),
+        };
+        let header = message.get(..IKE_HEADER_LEN)?;
+        if header[..8].iter().all(|&b| b == 0) {
+            return None;
+        }
+        let initial = header[8..16].iter().all(|&b| b == 0);
+        let (next_payload, flags) = (header[16], header[19]);
+        let message_id = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+        let length = u32::from_be_bytes([header[24], header[25], header[26], header[27]]) as usize;
+        
+        let (version, exchange) = match (header[17], header[18]) {
+            (0x10, 2) => (1, "main_mode"),
+            (0x10, 4) => (1, "aggressive_mode"),
+            (0x10, 5) => (1, "informational"),
+            (0x10, 32) => (1, "quick_mode"),
+            (0x20, 34) => (2, "ike_sa_init"),
+            (0x20, 35) => (2, "ike_auth"),
+            (0x20, 36) => (2, "create_child_sa"),
+            (0x20, 37) => (2, "informational"),
+            _ => return None,
+        };
+        
+        let valid = match version {
+            // IKEv1：载荷类型1~21，标志位只有E/C/A
+            1 => (1..=21).contains(&next_payload)
+                && flags & !0x07 == 0
+                && (!initial || matches!(exchange, "main_mode" | "aggressive_mode")),
+            // IKEv2：载荷类型33~54（0表示无载荷），标志位只有I/V/R
+            _ => (next_payload == 0 || (33..=54).contains(&next_payload))
+                && flags & !0x38 == 0
+                && (!initial || (exchange == "ike_sa_init" && flags & 0x28 == 0x08)),
+        };
+        // UDP数据报中长度字段覆盖整个报文，缓冲区可以是其前缀但不能更长
+        if !valid || (initial && message_id != 0) || length < IKE_HEADER_LEN || message.len() > length {
+            return None;
+        }
+        
+        Some(IkeHeader { version, exchange, initial, nat_t })
+    }
+    
+    /// 检测IKE协议
+    fn detect_ike(&self, data: &[u8]) -> Option<f32> {
+        if self.parse_ike(data)?.initial {
+            Some(0.9)
+        } else {
+            Some(0.8)
+        }
+    }
+    
+    /// 检测XMPP协议
+    ///
+    /// 容忍开头空白及可选的 `<?xml ...?>` 声明，要求随后是 `<stream:stream` 元素。
+    fn detect_xmpp(&self, data: &[u8]) -> Option<f32> {
+        if data.len() < 14 || !self.is_text_like(data) {
+            return None;
+        }
+        
+        let mut rest = data.trim_ascii_start();
+        
+        // 跳过可选的XML声明
+        if rest.starts_with(b"<?xml") {
+            let decl_end = rest.windows(2).position(|w| w == b"?>")?;
+            rest = rest[decl_end + 2..].trim_ascii_start();
+        }
+        
+        if !rest.starts_with(b"<stream:stream") {
+            return None;
+        }
+        
+        if self.fast_search(rest, b"jabber:client") || self.fast_search(rest, b"jabber:server") {
+            Some(0.95)
+        } else {
+            Some(0.8)
+        }
+    }
+}
+
+/// 检查是否为PostgreSQL SSLRequest
+///
+/// 服务端回复单字节 `S`/`N` 后，同一连接上即开始TLS握手。
+/// 报文只有8字节，探测器的最小长度检查需放行。
+pub(crate) fn is_postgres_ssl_request(data: &[u8]) -> bool {
+    data.starts_with(&PG_SSL_REQUEST)
+}
+
+/// 检查是否为长度可能低于最小探测长度的升级请求
+///
+/// 包括PostgreSQL SSLRequest、STARTTLS命令行，以及STARTTLS协商前的
+/// `EHLO`、`<tag> CAPABILITY`、`FEAT` 等完整命令行。
+pub(crate) fn is_short_upgrade_request(data: &[u8]) -> bool {
+    is_postgres_ssl_request(data)
+        || starttls_command(data).is_some()
+        || (data.ends_with(b"\n") && starttls::classify(data).is_some())
+}
+
+/// 识别STARTTLS类命令行，返回其所属的文本协议
+///
+/// 仅匹配以CRLF结尾的完整单行命令（不区分大小写）：SMTP `STARTTLS`、
+/// IMAP `<tag> STARTTLS`、FTP `AUTH TLS`/`AUTH SSL`、POP3 `STLS`。
+/// 服务端确认后，同一连接上即开始TLS握手。
+pub(crate) fn starttls_command(data: &[u8]) -> Option<ProtocolType> {
+    let line = data.strip_suffix(b"\r\n")?;
+    if line.len() > STARTTLS_MAX_LINE_LEN || line.iter().any(|&b| b == b'\r' || b == b'\n') {
+        return None;
+    }
+    
+    if line.eq_ignore_ascii_case(b"STARTTLS") {
+        return Some(ProtocolType::SMTP);
+    }
+    if line.eq_ignore_ascii_case(b"STLS") {
+        return Some(ProtocolType::POP3);
+    }
+    if line.eq_ignore_ascii_case(b"AUTH TLS") || line.eq_ignore_ascii_case(b"AUTH SSL") {
+        return Some(ProtocolType::FTP);
+    }
+    
+    // IMAP命令以标签开头
+    let space = line.iter().position(|&b| b == b' ')?;
+    let (tag, command) = (&line[..space], &line[space + 1..]);
+    if starttls::is_imap_tag(tag) && command.eq_ignore_ascii_case(b"STARTTLS") {
+        return Some(ProtocolType::IMAP);
+    }
+    None
+}
+
+/// OpenVPN可靠层头：确认数组（带对端会话ID）+ 报文ID，硬重置的报文ID为0
+fn openvpn_reliable_header_ok(data: &[u8]) -> bool {
+    let Some(&ack_count) = data.first() else { return false };
+    let ack_count = ack_count as usize;
+    if ack_count > OPENVPN_MAX_ACKS {
+        return false;
+    }
+    let pos = 1 + 4 * ack_count + if ack_count > 0 { 8 } else { 0 };
+    data.get(pos..pos + 4) == Some(&[0; 4])
+}
+
+/// tls-auth/tls-crypt的重放保护字段：首个报文ID为1，后跟时间戳
+fn openvpn_replay_id_ok(data: &[u8]) -> bool {
+    match data {
+        [0, 0, 0, 1, t0, t1, t2, t3, ..] => u32::from_be_bytes([*t0, *t1, *t2, *t3]) >= OPENVPN_MIN_TIMESTAMP,
+        _ => false,
+    }
+}
+
+/// 读取Protobuf变长整数，返回 (值, 占用字节数)
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+impl ProbeEngine for PassiveProbe {
+    fn probe(&self, data: &[u8]) -> Result<DetectionResult> {
+        // PostgreSQL SSLRequest 只有8字节，需在长度检查前放行
+        if data.len() < self.min_data_size && !is_short_upgrade_request(data) {
+            return Err(DetectorError::NeedMoreData(self.min_data_size));
+        }
+        
+        let (best_protocol, best_confidence) = self.best_detection(data, |_| true);
+        
         if best_confidence < self.confidence_threshold {
+            if let Some(total) = self.truncated_tls_record(data) {
+                return Err(DetectorError::NeedMoreData(total));
+            }
             return Err(DetectorError::detection_failed(
                 format!("Confidence {} below threshold {}", 
                        best_confidence, self.confidence_threshold)
             ));
         }
         
-        let protocol_info = ProtocolInfo::new(best_protocol, best_confidence);
+        let protocol_info = self.build_protocol_info(best_protocol, best_confidence, data);
         
         Ok(DetectionResult::new(
             protocol_info,
@@ -449,7 +1761,7 @@ impl ProbeEngine for PassiveProbe {
     }
     
     fn needs_more_data(&self, data: &[u8]) -> bool {
-        data.len() < self.min_data_size
+        data.len() < self.min_data_size && !is_short_upgrade_request(data)
     }
 }
 
@@ -467,6 +1779,7 @@ impl ProtocolProbe for PassiveProbe {
     
     fn supported_protocols(&self) -> Vec<ProtocolType> {
         vec![
+            ProtocolType::HTTP1_0,
             ProtocolType::HTTP1_1,
             ProtocolType::HTTP2,
             ProtocolType::HTTP3,
@@ -476,67 +1789,45 @@ impl ProtocolProbe for PassiveProbe {
             ProtocolType::TLS,
             ProtocolType::SSH,
             ProtocolType::UDP,
+            ProtocolType::IRC,
+            ProtocolType::XMPP,
+            ProtocolType::Protobuf,
+            ProtocolType::VNC,
+            ProtocolType::RDP,
+            ProtocolType::WebTransport,
+            ProtocolType::PostgreSQL,
+            ProtocolType::MySQL,
+            ProtocolType::SMTP,
+            ProtocolType::IMAP,
+            ProtocolType::POP3,
+            ProtocolType::FTP,
+            ProtocolType::Thrift,
+            ProtocolType::Avro,
+            ProtocolType::Memcached,
+            ProtocolType::ClickHouse,
+            ProtocolType::OpenVPN,
+            ProtocolType::IKE,
+            ProtocolType::MQTT,
         ]
     }
     
     fn probe(&self, data: &[u8], context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
-        if data.len() < self.min_data_size {
+        if data.len() < self.min_data_size && !is_short_upgrade_request(data) {
             return Ok(None);
         }
         
-        let mut best_protocol = ProtocolType::Unknown;
-        let mut best_confidence = 0.0;
-        
-        // 尝试各种协议检测 (按优先级排序)
-        // 使用栈分配的数组来减少堆分配
-        let mut detections = [(ProtocolType::Unknown, 0.0); 8];
-        let mut detection_count = 0;
-        
-        // 按优先级检测协议
-        if let Some(confidence) = self.detect_http3(data) {
-            detections[detection_count] = (ProtocolType::HTTP3, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_quic(data) {
-            detections[detection_count] = (ProtocolType::QUIC, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_http2(data) {
-            detections[detection_count] = (ProtocolType::HTTP2, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_grpc(data) {
-            detections[detection_count] = (ProtocolType::GRPC, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_http1(data) {
-            detections[detection_count] = (ProtocolType::HTTP1_1, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_tls(data) {
-            detections[detection_count] = (ProtocolType::TLS, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_ssh(data) {
-            detections[detection_count] = (ProtocolType::SSH, confidence);
-            detection_count += 1;
-        }
-        if let Some(confidence) = self.detect_websocket(data) {
-            detections[detection_count] = (ProtocolType::WebSocket, confidence);
-            detection_count += 1;
-        }
-        
-        // 找到最佳匹配
-        for i in 0..detection_count {
-            let (protocol, confidence) = detections[i];
-            if confidence > best_confidence {
-                best_confidence = confidence;
-                best_protocol = protocol;
+        // 记录头合法的截断TLS记录不是否定结论，请求调用方补齐数据
+        if context.is_protocol_enabled(ProtocolType::TLS) {
+            if let Some(total) = self.truncated_tls_record(data) {
+                context.request_more_data(total);
             }
         }
         
+        // 找到最佳匹配（跳过未启用的协议）
+        let (best_protocol, best_confidence) = self.best_detection(data, |protocol| context.is_protocol_enabled(protocol));
+        
         if best_confidence >= self.confidence_threshold {
-            let protocol_info = ProtocolInfo::new(best_protocol, best_confidence);
+            let protocol_info = self.build_protocol_info(best_protocol, best_confidence, data);
             context.add_candidate(protocol_info.clone());
             Ok(Some(protocol_info))
         } else {
@@ -549,6 +1840,6 @@ impl ProtocolProbe for PassiveProbe {
     }
     
     fn needs_more_data(&self, data: &[u8]) -> bool {
-        data.len() < self.min_data_size
+        data.len() < self.min_data_size && !is_short_upgrade_request(data)
     }
 }
\ No newline at end of file
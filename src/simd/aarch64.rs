@@ -40,7 +40,7 @@ impl AArch64SimdDetector {
         }
         
         if needle.len() == 1 {
-            return self.neon_find_byte(haystack, needle[0]);
+            return neon_find_byte(haystack, needle[0]);
         }
         
         // 对于多字节模式，使用优化的NEON实现
@@ -85,58 +85,6 @@ impl AArch64SimdDetector {
         None
     }
     
-    /// 使用NEON查找单个字节（优化版本）
-    #[cfg(target_arch = "aarch64")]
-    unsafe fn neon_find_byte(&self, data: &[u8], byte: u8) -> Option<usize> {
-        if !self.has_neon || data.is_empty() {
-            return None;
-        }
-        
-        let needle = vdupq_n_u8(byte);
-        let mut pos = 0;
-        
-        // 处理16字节对齐的块
-        while pos + 16 <= data.len() {
-            let chunk = vld1q_u8(data.as_ptr().add(pos));
-            let cmp = vceqq_u8(chunk, needle);
-            
-            // 使用更高效的方法检查匹配
-            let mask_low = vget_low_u8(cmp);
-            let mask_high = vget_high_u8(cmp);
-            
-            // 检查低8字节
-            let low_mask = vget_lane_u64(vreinterpret_u64_u8(mask_low), 0);
-            if low_mask != 0 {
-                for i in 0..8 {
-                    if data[pos + i] == byte {
-                        return Some(pos + i);
-                    }
-                }
-            }
-            
-            // 检查高8字节
-            let high_mask = vget_lane_u64(vreinterpret_u64_u8(mask_high), 0);
-            if high_mask != 0 {
-                for i in 8..16 {
-                    if pos + i < data.len() && data[pos + i] == byte {
-                        return Some(pos + i);
-                    }
-                }
-            }
-            
-            pos += 16;
-        }
-        
-        // 处理剩余字节
-        for i in pos..data.len() {
-            if data[i] == byte {
-                return Some(i);
-            }
-        }
-        
-        None
-    }
-    
     /// 快速模式匹配（根据可用指令集选择最佳实现）
     fn fast_pattern_match(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
         #[cfg(target_arch = "aarch64")]
@@ -164,45 +112,68 @@ impl AArch64SimdDetector {
         
         None
     }
+}
+
+/// 使用NEON指令集计算字节出现次数
+#[cfg(target_arch = "aarch64")]
+pub unsafe fn neon_count_bytes(data: &[u8], byte: u8) -> usize {
+    if !std::arch::is_aarch64_feature_detected!("neon") || data.is_empty() {
+        return data.iter().filter(|&&b| b == byte).count();
+    }
     
-    /// 使用NEON进行字节计数（优化版本）
-    #[cfg(target_arch = "aarch64")]
-    unsafe fn neon_count_bytes(&self, data: &[u8], byte: u8) -> usize {
-        if !self.has_neon || data.is_empty() {
-            return data.iter().filter(|&&b| b == byte).count();
+    let needle = vdupq_n_u8(byte);
+    let mut count = 0;
+    let mut pos = 0;
+    
+    // 处理16字节块：匹配的字节为0xFF，右移7位后为1，横向求和即为匹配数
+    while pos + 16 <= data.len() {
+        let chunk = vld1q_u8(data.as_ptr().add(pos));
+        let matches = vshrq_n_u8(vceqq_u8(chunk, needle), 7);
+        count += vaddvq_u8(matches) as usize;
+        pos += 16;
+    }
+    
+    // 处理剩余字节
+    for i in pos..data.len() {
+        if data[i] == byte {
+            count += 1;
         }
-        
-        let needle = vdupq_n_u8(byte);
-        let mut count = 0;
-        let mut pos = 0;
-        
-        // 处理16字节对齐的块
-        while pos + 16 <= data.len() {
-            let chunk = vld1q_u8(data.as_ptr().add(pos));
-            let cmp = vceqq_u8(chunk, needle);
-            
-            // 使用NEON指令直接计算匹配数
-            // 将比较结果转换为计数
-            let low = vget_low_u8(cmp);
-            let high = vget_high_u8(cmp);
-            
-            // 每个匹配的字节为0xFF，计算总和
-            let low_sum = vaddv_u8(vshr_n_u8(low, 7));
-            let high_sum = vaddv_u8(vshr_n_u8(high, 7));
-            
-            count += (low_sum + high_sum) as usize;
-            pos += 16;
+    }
+    
+    count
+}
+
+/// 使用NEON指令集查找字节
+#[cfg(target_arch = "aarch64")]
+pub unsafe fn neon_find_byte(data: &[u8], byte: u8) -> Option<usize> {
+    if !std::arch::is_aarch64_feature_detected!("neon") || data.is_empty() {
+        return data.iter().position(|&b| b == byte);
+    }
+    
+    let needle = vdupq_n_u8(byte);
+    let mut pos = 0;
+    
+    // 处理16字节块：比较结果窄化右移为每字节4位的掩码，首个置位半字节即首个匹配
+    while pos + 16 <= data.len() {
+        let chunk = vld1q_u8(data.as_ptr().add(pos));
+        let cmp = vceqq_u8(chunk, needle);
+        let mask = vget_lane_u64(vreinterpret_u64_u8(vshrn_n_u16(vreinterpretq_u16_u8(cmp), 4)), 0);
+        
+        if mask != 0 {
+            return Some(pos + (mask.trailing_zeros() / 4) as usize);
         }
         
-        // 处理剩余字节
-        for i in pos..data.len() {
-            if data[i] == byte {
-                count += 1;
-            }
+        pos += 16;
+    }
+    
+    // 处理剩余字节
+    for i in pos..data.len() {
+        if data[i] == byte {
+            return Some(i);
         }
-        
-        count
     }
+    
+    None
 }
 
 impl SimdDetector for AArch64SimdDetector {
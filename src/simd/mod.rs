@@ -17,6 +17,13 @@ pub mod detector;
 
 use crate::core::protocol::ProtocolType;
 use crate::error::{DetectorError, Result};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 尚未检测指令集时的缓存值
+const SIMD_SUPPORT_UNKNOWN: u8 = u8::MAX;
+
+/// 缓存的指令集检测结果，首次使用时初始化
+static SIMD_SUPPORT: AtomicU8 = AtomicU8::new(SIMD_SUPPORT_UNKNOWN);
 
 /// SIMD探测结果
 #[derive(Debug, Clone, PartialEq)]
@@ -50,6 +57,26 @@ pub enum SimdInstructionSet {
     WasmSimd,
 }
 
+impl SimdInstructionSet {
+    fn to_u8(self) -> u8 {
+        self as u8
+    }
+    
+    fn from_u8(value: u8) -> Option<Self> {
+        [
+            Self::None,
+            Self::SSE2,
+            Self::SSE41,
+            Self::AVX2,
+            Self::AVX512,
+            Self::NEON,
+            Self::WasmSimd,
+        ]
+        .into_iter()
+        .find(|set| set.to_u8() == value)
+    }
+}
+
 /// SIMD探测器trait
 pub trait SimdDetector: Send + Sync {
     /// 探测HTTP/2协议
@@ -78,36 +105,44 @@ pub trait SimdDetector: Send + Sync {
 }
 
 /// 创建最佳的SIMD探测器
+///
+/// 读取缓存的指令集检测结果，频繁创建探测器时不会重复检测CPU特性。
 pub fn create_best_detector() -> Box<dyn SimdDetector> {
-    #[cfg(target_arch = "x86_64")]
-    {
-        if is_x86_feature_detected!("avx512f") {
-            return Box::new(x86_64::Avx512Detector::new());
-        } else if is_x86_feature_detected!("avx2") {
-            return Box::new(x86_64::Avx2Detector::new());
-        } else if is_x86_feature_detected!("sse2") {
-            return Box::new(x86_64::Sse2Detector::new());
-        }
-    }
-    
-    #[cfg(target_arch = "aarch64")]
-    {
-        if std::arch::is_aarch64_feature_detected!("neon") {
-            return Box::new(aarch64::AArch64SimdDetector::new());
-        }
-    }
-    
-    #[cfg(target_arch = "wasm32")]
-    {
-        return Box::new(wasm32::WasmSimdDetector::new());
+    match detect_simd_support() {
+        #[cfg(target_arch = "x86_64")]
+        SimdInstructionSet::AVX512 => Box::new(x86_64::Avx512Detector::new()),
+        #[cfg(target_arch = "x86_64")]
+        SimdInstructionSet::AVX2 => Box::new(x86_64::Avx2Detector::new()),
+        #[cfg(target_arch = "x86_64")]
+        SimdInstructionSet::SSE2 => Box::new(x86_64::Sse2Detector::new()),
+        #[cfg(target_arch = "aarch64")]
+        SimdInstructionSet::NEON => Box::new(aarch64::AArch64SimdDetector::new()),
+        #[cfg(target_arch = "wasm32")]
+        SimdInstructionSet::WasmSimd => Box::new(wasm32::WasmSimdDetector::new()),
+        // 回退到通用实现
+        _ => Box::new(detector::GenericSimdDetector::new()),
     }
-    
-    // 回退到通用实现
-    Box::new(detector::GenericSimdDetector::new())
 }
 
 /// 检查当前平台的SIMD支持
+///
+/// 结果在首次调用时检测并缓存。
 pub fn detect_simd_support() -> SimdInstructionSet {
+    match SimdInstructionSet::from_u8(SIMD_SUPPORT.load(Ordering::Relaxed)) {
+        Some(instruction_set) => instruction_set,
+        None => force_refresh(),
+    }
+}
+
+/// 重新检测SIMD支持并刷新缓存（主要用于测试）
+pub fn force_refresh() -> SimdInstructionSet {
+    let instruction_set = probe_simd_support();
+    SIMD_SUPPORT.store(instruction_set.to_u8(), Ordering::Relaxed);
+    instruction_set
+}
+
+/// 检测CPU实际支持的SIMD指令集
+fn probe_simd_support() -> SimdInstructionSet {
     #[cfg(target_arch = "x86_64")]
     {
         if is_x86_feature_detected!("avx512f") {
@@ -175,6 +210,15 @@ pub fn simd_count_bytes(data: &[u8], byte: u8) -> usize {
         }
     }
     
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                return aarch64::neon_count_bytes(data, byte);
+            }
+        }
+    }
+    
     // 回退到标准实现
     data.iter().filter(|&&b| b == byte).count()
 }
@@ -194,6 +238,15 @@ pub fn simd_find_byte(data: &[u8], byte: u8) -> Option<usize> {
         }
     }
     
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            unsafe {
+                return aarch64::neon_find_byte(data, byte);
+            }
+        }
+    }
+    
     // 回退到标准实现
     data.iter().position(|&b| b == byte)
 }
\ No newline at end of file
@@ -22,6 +22,8 @@ fn detect_x86_features() -> (bool, bool, bool, bool) {
 /// AVX512 SIMD探测器
 pub struct Avx512Detector {
     instruction_set: SimdInstructionSet,
+    /// 头部特征扫描使用的AVX2匹配例程
+    scanner: X86_64SimdDetector,
 }
 
 impl Avx512Detector {
@@ -29,6 +31,7 @@ impl Avx512Detector {
     pub fn new() -> Self {
         Self {
             instruction_set: SimdInstructionSet::AVX512,
+            scanner: X86_64SimdDetector::new(),
         }
     }
 }
@@ -60,26 +63,20 @@ impl SimdDetector for Avx512Detector {
     }
     
     fn detect_grpc(&self, data: &[u8]) -> Result<SimdDetectionResult> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let result = detector.detect_grpc(data)?;
+        let result = self.scanner.detect_grpc(data)?;
         
         Ok(SimdDetectionResult {
-            protocol: result.protocol,
-            confidence: result.confidence,
-            match_positions: result.match_positions,
             instruction_set: self.instruction_set,
+            ..result
         })
     }
     
     fn detect_websocket(&self, data: &[u8]) -> Result<SimdDetectionResult> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let result = detector.detect_websocket(data)?;
+        let result = self.scanner.detect_websocket(data)?;
         
         Ok(SimdDetectionResult {
-            protocol: result.protocol,
-            confidence: result.confidence,
-            match_positions: result.match_positions,
             instruction_set: self.instruction_set,
+            ..result
         })
     }
     
@@ -96,17 +93,25 @@ impl SimdDetector for Avx512Detector {
     }
     
     fn detect_multiple(&self, data: &[u8], protocols: &[ProtocolType]) -> Result<Vec<SimdDetectionResult>> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let results = detector.detect_multiple(data, protocols)?;
+        let mut results = Vec::new();
         
-        Ok(results.into_iter().map(|result| {
-            SimdDetectionResult {
-                protocol: result.protocol,
-                confidence: result.confidence,
-                match_positions: result.match_positions,
-                instruction_set: self.instruction_set,
+        for &protocol in protocols {
+            let result = match protocol {
+                ProtocolType::HTTP2 => self.detect_http2(data),
+                ProtocolType::QUIC => self.detect_quic(data),
+                ProtocolType::GRPC => self.detect_grpc(data),
+                ProtocolType::WebSocket => self.detect_websocket(data),
+                ProtocolType::TLS => self.detect_tls(data),
+                _ => continue,
+            };
+            
+            if let Ok(detection) = result {
+                results.push(detection);
             }
-        }).collect())
+        }
+        
+        results.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(results)
     }
     
     fn instruction_set(&self) -> SimdInstructionSet {
@@ -121,6 +126,8 @@ impl SimdDetector for Avx512Detector {
 /// AVX2 SIMD探测器
 pub struct Avx2Detector {
     instruction_set: SimdInstructionSet,
+    /// 头部特征扫描使用的AVX2匹配例程
+    scanner: X86_64SimdDetector,
 }
 
 impl Avx2Detector {
@@ -128,6 +135,7 @@ impl Avx2Detector {
     pub fn new() -> Self {
         Self {
             instruction_set: SimdInstructionSet::AVX2,
+            scanner: X86_64SimdDetector::new(),
         }
     }
 }
@@ -158,26 +166,20 @@ impl SimdDetector for Avx2Detector {
     }
     
     fn detect_grpc(&self, data: &[u8]) -> Result<SimdDetectionResult> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let result = detector.detect_grpc(data)?;
+        let result = self.scanner.detect_grpc(data)?;
         
         Ok(SimdDetectionResult {
-            protocol: result.protocol,
-            confidence: result.confidence,
-            match_positions: result.match_positions,
             instruction_set: self.instruction_set,
+            ..result
         })
     }
     
     fn detect_websocket(&self, data: &[u8]) -> Result<SimdDetectionResult> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let result = detector.detect_websocket(data)?;
+        let result = self.scanner.detect_websocket(data)?;
         
         Ok(SimdDetectionResult {
-            protocol: result.protocol,
-            confidence: result.confidence,
-            match_positions: result.match_positions,
             instruction_set: self.instruction_set,
+            ..result
         })
     }
     
@@ -194,17 +196,25 @@ impl SimdDetector for Avx2Detector {
     }
     
     fn detect_multiple(&self, data: &[u8], protocols: &[ProtocolType]) -> Result<Vec<SimdDetectionResult>> {
-        let detector = crate::simd::detector::GenericSimdDetector::new();
-        let results = detector.detect_multiple(data, protocols)?;
+        let mut results = Vec::new();
         
-        Ok(results.into_iter().map(|result| {
-            SimdDetectionResult {
-                protocol: result.protocol,
-                confidence: result.confidence,
-                match_positions: result.match_positions,
-                instruction_set: self.instruction_set,
+        for &protocol in protocols {
+            let result = match protocol {
+                ProtocolType::HTTP2 => self.detect_http2(data),
+                ProtocolType::QUIC => self.detect_quic(data),
+                ProtocolType::GRPC => self.detect_grpc(data),
+                ProtocolType::WebSocket => self.detect_websocket(data),
+                ProtocolType::TLS => self.detect_tls(data),
+                _ => continue,
+            };
+            
+            if let Ok(detection) = result {
+                results.push(detection);
             }
-        }).collect())
+        }
+        
+        results.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(results)
     }
     
     fn instruction_set(&self) -> SimdInstructionSet {
@@ -420,6 +430,63 @@ impl X86_64SimdDetector {
         None
     }
     
+    /// 使用AVX2查找两个字节中任一个首次出现的位置
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn avx2_find_either_byte(&self, data: &[u8], first: u8, second: u8) -> Option<usize> {
+        if !self.has_avx2 || data.is_empty() {
+            return None;
+        }
+        
+        let first_needle = _mm256_set1_epi8(first as i8);
+        let second_needle = _mm256_set1_epi8(second as i8);
+        let mut pos = 0;
+        
+        while pos + 32 <= data.len() {
+            let chunk = _mm256_loadu_si256(data.as_ptr().add(pos) as *const __m256i);
+            let cmp = _mm256_or_si256(
+                _mm256_cmpeq_epi8(chunk, first_needle),
+                _mm256_cmpeq_epi8(chunk, second_needle),
+            );
+            let mask = _mm256_movemask_epi8(cmp) as u32;
+            
+            if mask != 0 {
+                return Some(pos + mask.trailing_zeros() as usize);
+            }
+            
+            pos += 32;
+        }
+        
+        data[pos..]
+            .iter()
+            .position(|&b| b == first || b == second)
+            .map(|i| pos + i)
+    }
+    
+    /// 使用AVX2进行不区分大小写的模式匹配
+    ///
+    /// 同时比较首字节的大小写两种形式定位候选位置，再逐字节确认。
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn avx2_pattern_match_ignore_case(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        if !self.has_avx2 || needle.is_empty() || haystack.len() < needle.len() {
+            return None;
+        }
+        
+        let lower = needle[0].to_ascii_lowercase();
+        let upper = needle[0].to_ascii_uppercase();
+        let last_start = haystack.len() - needle.len();
+        let mut pos = 0;
+        
+        while pos <= last_start {
+            let candidate = pos + self.avx2_find_either_byte(&haystack[pos..=last_start], lower, upper)?;
+            if haystack[candidate..candidate + needle.len()].eq_ignore_ascii_case(needle) {
+                return Some(candidate);
+            }
+            pos = candidate + 1;
+        }
+        
+        None
+    }
+    
     /// 使用SSE4.1进行模式匹配
     #[cfg(target_arch = "x86_64")]
     unsafe fn sse41_pattern_match(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
@@ -508,6 +575,21 @@ impl X86_64SimdDetector {
         self.fallback_pattern_match(haystack, needle)
     }
     
+    /// 不区分大小写的快速模式匹配
+    fn fast_pattern_match_ignore_case(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            if self.has_avx2 {
+                return self.avx2_pattern_match_ignore_case(haystack, needle);
+            }
+        }
+        
+        if needle.is_empty() || haystack.len() < needle.len() {
+            return None;
+        }
+        haystack.windows(needle.len()).position(|window| window.eq_ignore_ascii_case(needle))
+    }
+    
     /// 回退模式匹配实现
     fn fallback_pattern_match(&self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
         if needle.is_empty() || haystack.len() < needle.len() {
@@ -794,21 +876,21 @@ impl SimdDetector for X86_64SimdDetector {
         let mut positions = Vec::new();
         let mut confidence = 0.0;
         
-        // 使用SIMD加速的模式匹配
+        // 使用SIMD加速的模式匹配（头部名称和取值不区分大小写）
         let upgrade_header = b"Upgrade: websocket";
-        if let Some(pos) = self.fast_pattern_match(data, upgrade_header) {
+        if let Some(pos) = self.fast_pattern_match_ignore_case(data, upgrade_header) {
             positions.push(pos);
             confidence += 0.4;
         }
         
         let connection_header = b"Connection: Upgrade";
-        if let Some(pos) = self.fast_pattern_match(data, connection_header) {
+        if let Some(pos) = self.fast_pattern_match_ignore_case(data, connection_header) {
             positions.push(pos);
             confidence += 0.3;
         }
         
         let websocket_key = b"Sec-WebSocket-Key:";
-        if let Some(pos) = self.fast_pattern_match(data, websocket_key) {
+        if let Some(pos) = self.fast_pattern_match_ignore_case(data, websocket_key) {
             positions.push(pos);
             confidence += 0.3;
         }
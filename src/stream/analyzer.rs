@@ -4,6 +4,7 @@
 
 use crate::core::protocol::ProtocolType;
 use crate::error::{DetectorError, Result};
+use crate::utils::features;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
@@ -58,7 +59,7 @@ pub struct PacketSizeDistribution {
     pub std_dev: f64,
     /// 中位数
     pub median: usize,
-    /// 大小直方图
+    /// 大小直方图（键为分桶下界，桶数不超过 `AnalyzerConfig::histogram_bins`）
     pub histogram: HashMap<usize, usize>,
 }
 
@@ -73,8 +74,8 @@ pub struct TimingDistribution {
     pub mean_interval: Duration,
     /// 标准差
     pub std_dev: Duration,
-    /// 间隔直方图
-    pub histogram: HashMap<u64, usize>, // 毫秒为单位
+    /// 间隔直方图（键为分桶下界，毫秒为单位）
+    pub histogram: HashMap<u64, usize>,
 }
 
 /// 字节频率分析
@@ -203,6 +204,8 @@ pub enum AnomalyType {
     PatternAnomaly,
     /// 协议异常
     ProtocolAnomaly,
+    /// 自定义检测器报告的异常
+    Custom(String),
 }
 
 /// 异常严重程度
@@ -254,6 +257,12 @@ pub struct AnalyzerConfig {
     pub history_retention: Duration,
     /// 分析间隔
     pub analysis_interval: Duration,
+    /// 包大小和时间间隔直方图的最大桶数
+    ///
+    /// 在最小值和最大值之间等宽分桶，避免取值分散的长连接使直方图无限增长。
+    pub histogram_bins: usize,
+    /// 协议切换检测时每个窗口包含的数据点数
+    pub protocol_window: usize,
 }
 
 impl Default for AnalyzerConfig {
@@ -267,12 +276,21 @@ impl Default for AnalyzerConfig {
             enable_anomaly_detection: true,
             history_retention: Duration::from_secs(3600), // 1小时
             analysis_interval: Duration::from_secs(10),
+            histogram_bins: 64,
+            protocol_window: 1,
         }
     }
 }
 
+/// 自定义异常检测器
+///
+/// 注册到 [`StreamAnalyzer`] 后与内置的大小、时间、熵值检查一同运行。
+pub trait AnomalyDetector: Send + Sync {
+    /// 根据窗口内的数据点和已提取的特征报告异常
+    fn detect(&self, points: &[DataPoint], features: &StreamFeatures) -> Vec<Anomaly>;
+}
+
 /// 流分析器
-#[derive(Debug)]
 pub struct StreamAnalyzer {
     /// 配置
     config: AnalyzerConfig,
@@ -280,6 +298,19 @@ pub struct StreamAnalyzer {
     history: HashMap<String, StreamHistory>,
     /// 分析统计
     stats: AnalyzerStats,
+    /// 自定义异常检测器
+    anomaly_detectors: Vec<Box<dyn AnomalyDetector>>,
+}
+
+impl std::fmt::Debug for StreamAnalyzer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamAnalyzer")
+            .field("config", &self.config)
+            .field("history", &self.history)
+            .field("stats", &self.stats)
+            .field("anomaly_detectors", &self.anomaly_detectors.len())
+            .finish()
+    }
 }
 
 /// 流历史数据
@@ -293,9 +324,9 @@ struct StreamHistory {
     accumulated_features: StreamFeatures,
 }
 
-/// 数据点
+/// 数据点（只读）
 #[derive(Debug, Clone)]
-struct DataPoint {
+pub struct DataPoint {
     /// 时间戳
     timestamp: Instant,
     /// 数据大小
@@ -304,6 +335,23 @@ struct DataPoint {
     sample: Vec<u8>,
 }
 
+impl DataPoint {
+    /// 数据到达时间
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+    
+    /// 原始数据大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    
+    /// 数据样本（最多前1KB）
+    pub fn sample(&self) -> &[u8] {
+        &self.sample
+    }
+}
+
 /// 分析器统计
 #[derive(Debug, Clone, Default)]
 pub struct AnalyzerStats {
@@ -326,9 +374,15 @@ impl StreamAnalyzer {
             config,
             history: HashMap::new(),
             stats: AnalyzerStats::default(),
+            anomaly_detectors: Vec::new(),
         }
     }
     
+    /// 注册自定义异常检测器
+    pub fn add_anomaly_detector(&mut self, detector: Box<dyn AnomalyDetector>) {
+        self.anomaly_detectors.push(detector);
+    }
+    
     /// 添加数据点
     pub fn add_data_point(&mut self, stream_id: String, data: &[u8]) {
         // 创建数据点（只保存前1KB作为样本）
@@ -367,9 +421,7 @@ impl StreamAnalyzer {
             
             // 检查是否有足够的数据
             if history.data_points.len() < self.config.min_samples {
-                return Err(DetectorError::NeedMoreData(
-                    self.config.min_samples - history.data_points.len()
-                ));
+                return Err(DetectorError::NeedMoreData(self.config.min_samples));
             }
             
             history.data_points.clone()
@@ -477,10 +529,15 @@ impl StreamAnalyzer {
         let median = sorted_sizes[sorted_sizes.len() / 2];
         
         // 构建直方图
-        let mut histogram = HashMap::new();
-        for &size in &sizes {
-            *histogram.entry(size).or_insert(0) += 1;
-        }
+        let histogram = bucketed_histogram(
+            sizes.iter().map(|&size| size as u64),
+            min_size as u64,
+            max_size as u64,
+            self.config.histogram_bins,
+        )
+        .into_iter()
+        .map(|(bucket, count)| (bucket as usize, count))
+        .collect();
         
         PacketSizeDistribution {
             min_size,
@@ -517,11 +574,12 @@ impl StreamAnalyzer {
         let std_dev = Duration::from_nanos((variance_nanos as f64).sqrt() as u64);
         
         // 构建直方图（以毫秒为单位）
-        let mut histogram = HashMap::new();
-        for interval in &intervals {
-            let millis = interval.as_millis() as u64;
-            *histogram.entry(millis).or_insert(0) += 1;
-        }
+        let histogram = bucketed_histogram(
+            intervals.iter().map(|interval| interval.as_millis() as u64),
+            min_interval.as_millis() as u64,
+            max_interval.as_millis() as u64,
+            self.config.histogram_bins,
+        );
         
         TimingDistribution {
             min_interval,
@@ -538,10 +596,11 @@ impl StreamAnalyzer {
         let mut total_bytes = 0;
         
         for data_point in data_points {
-            for &byte in &data_point.sample {
-                frequencies[byte as usize] += 1;
-                total_bytes += 1;
+            let histogram = features::byte_histogram(&data_point.sample);
+            for (total, count) in frequencies.iter_mut().zip(histogram) {
+                *total += count;
             }
+            total_bytes += data_point.sample.len();
         }
         
         if total_bytes == 0 {
@@ -581,21 +640,7 @@ impl StreamAnalyzer {
     /// 计算熵值
     fn calculate_entropy(&self, data_points: &[DataPoint]) -> f64 {
         let byte_freq = self.calculate_byte_frequency(data_points);
-        let total_bytes: usize = byte_freq.frequencies.iter().sum();
-        
-        if total_bytes == 0 {
-            return 0.0;
-        }
-        
-        let mut entropy = 0.0;
-        for &freq in &byte_freq.frequencies {
-            if freq > 0 {
-                let probability = freq as f64 / total_bytes as f64;
-                entropy -= probability * probability.log2();
-            }
-        }
-        
-        entropy
+        features::entropy_from_histogram(&byte_freq.frequencies)
     }
     
     /// 计算压缩率
@@ -757,9 +802,53 @@ impl StreamAnalyzer {
             });
         }
         
+        // 检测协议切换（如STARTTLS）
+        patterns.extend(self.detect_protocol_switches(data_points));
+        
         Ok(patterns)
     }
     
+    /// 检测协议切换
+    ///
+    /// 每 `protocol_window` 个数据点为一个窗口，取窗口内置信度最高的协议，未识别出协议的窗口跳过；
+    /// 相邻两个已识别窗口的协议不同时（如HTTP请求之后出现TLS握手）报告一次切换，
+    /// 元数据 `from_protocol`/`to_protocol` 记录切换前后的协议。
+    fn detect_protocol_switches(&self, data_points: &[DataPoint]) -> Vec<BehaviorPattern> {
+        let mut patterns = Vec::new();
+        let mut previous: Option<(ProtocolType, f64, Instant)> = None;
+        
+        for window in data_points.chunks(self.config.protocol_window.max(1)) {
+            let Some((protocol, confidence)) = self.dominant_protocol(window) else {
+                continue;
+            };
+            
+            if let Some((from, from_confidence, switch_start)) = previous {
+                if from != protocol {
+                    let mut metadata = HashMap::new();
+                    metadata.insert("from_protocol".to_string(), from.to_string());
+                    metadata.insert("to_protocol".to_string(), protocol.to_string());
+                    patterns.push(BehaviorPattern {
+                        pattern_type: PatternType::ProtocolSwitch,
+                        description: format!("Protocol switch detected: {} -> {}", from, protocol),
+                        confidence: from_confidence.min(confidence),
+                        start_time: switch_start,
+                        duration: window[0].timestamp.duration_since(switch_start),
+                        metadata,
+                    });
+                }
+            }
+            previous = Some((protocol, confidence, window[window.len() - 1].timestamp));
+        }
+        
+        patterns
+    }
+    
+    /// 窗口内置信度最高的协议
+    fn dominant_protocol(&self, window: &[DataPoint]) -> Option<(ProtocolType, f64)> {
+        self.extract_protocol_features(window).protocol_confidence.into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+    }
+    
     /// 检测突发模式
     fn detect_burst_pattern(&self, data_points: &[DataPoint]) -> Result<Option<BehaviorPattern>> {
         if data_points.len() < 5 {
@@ -829,6 +918,10 @@ impl StreamAnalyzer {
             });
         }
         
+        for detector in &self.anomaly_detectors {
+            anomalies.extend(detector.detect(data_points, features));
+        }
+        
         Ok(anomalies)
     }
     
@@ -1010,6 +1103,24 @@ impl StreamAnalyzer {
     }
 }
 
+/// 在 `[min, max]` 区间内等宽分桶统计，键为桶下界
+fn bucketed_histogram(
+    values: impl Iterator<Item = u64>,
+    min: u64,
+    max: u64,
+    bins: usize,
+) -> HashMap<u64, usize> {
+    let bins = bins.max(1) as u64;
+    let width = ((max - min) / bins + 1).max(1);
+    
+    let mut histogram = HashMap::new();
+    for value in values {
+        let bucket = min + (value - min) / width * width;
+        *histogram.entry(bucket).or_insert(0) += 1;
+    }
+    histogram
+}
+
 impl Default for StreamAnalyzer {
     fn default() -> Self {
         Self::new(AnalyzerConfig::default())
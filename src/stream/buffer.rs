@@ -3,6 +3,7 @@
 //! 提供高效的流数据缓存和管理功能。
 
 use crate::error::{DetectorError, Result};
+use crate::stream::pool::BufferPool;
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
@@ -23,6 +24,8 @@ pub struct BufferConfig {
     pub auto_cleanup: bool,
     /// 清理间隔
     pub cleanup_interval: Duration,
+    /// 缓冲池大小（保留的空闲缓冲区数量），0表示不使用缓冲池
+    pub pool_size: usize,
 }
 
 impl Default for BufferConfig {
@@ -35,6 +38,7 @@ impl Default for BufferConfig {
             compression_threshold: 4096,      // 4KB
             auto_cleanup: true,
             cleanup_interval: Duration::from_secs(60), // 1分钟
+            pool_size: 0,
         }
     }
 }
@@ -172,6 +176,81 @@ impl DataChunk {
     }
 }
 
+/// 固定容量的环形存储
+///
+/// 写入不会重新分配内存；数据跨越末尾时，只有在请求连续视图时才整理到开头。
+#[derive(Debug)]
+struct RingStorage {
+    /// 底层存储，长度即容量
+    data: Vec<u8>,
+    /// 最老数据的位置
+    head: usize,
+    /// 已缓冲的字节数
+    len: usize,
+}
+
+impl RingStorage {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: vec![0; capacity],
+            head: 0,
+            len: 0,
+        }
+    }
+    
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+    
+    /// 写入数据，空间不足时覆盖最老的数据，返回被丢弃的字节数
+    fn write(&mut self, input: &[u8]) -> usize {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return input.len();
+        }
+        let (skipped, input) = if input.len() > capacity {
+            (input.len() - capacity, &input[input.len() - capacity..])
+        } else {
+            (0, input)
+        };
+        
+        let overflow = (self.len + input.len()).saturating_sub(capacity);
+        self.advance(overflow);
+        
+        let tail = (self.head + self.len) % capacity;
+        let first = input.len().min(capacity - tail);
+        self.data[tail..tail + first].copy_from_slice(&input[..first]);
+        self.data[..input.len() - first].copy_from_slice(&input[first..]);
+        self.len += input.len();
+        
+        skipped + overflow
+    }
+    
+    /// 从最老的数据开始复制最多 `size` 字节
+    fn copy_front(&self, size: usize, out: &mut Vec<u8>) {
+        let size = size.min(self.len);
+        let first = size.min(self.capacity() - self.head);
+        out.extend_from_slice(&self.data[self.head..self.head + first]);
+        out.extend_from_slice(&self.data[..size - first]);
+    }
+    
+    /// 丢弃最老的 `size` 字节
+    fn advance(&mut self, size: usize) {
+        let size = size.min(self.len);
+        self.len -= size;
+        self.head = if self.len == 0 { 0 } else { (self.head + size) % self.capacity() };
+    }
+    
+    /// 返回全部数据的连续视图，数据跨越末尾时先整理到开头
+    fn contiguous(&mut self) -> &[u8] {
+        if self.head + self.len > self.capacity() {
+            self.data.rotate_left(self.head);
+            self.head = 0;
+        }
+        &self.data[self.head..self.head + self.len]
+    }
+}
+
 /// 流缓冲区
 #[derive(Debug)]
 pub struct StreamBuffer {
@@ -187,6 +266,10 @@ pub struct StreamBuffer {
     last_cleanup: Instant,
     /// 统计信息
     stats: BufferStats,
+    /// 缓冲池（释放的数据块会归还到池中）
+    pool: Option<BufferPool>,
+    /// 环形缓冲模式的存储，启用时不使用 `chunks`
+    ring: Option<RingStorage>,
 }
 
 /// 缓冲区统计信息
@@ -214,7 +297,10 @@ pub struct BufferStats {
 
 impl StreamBuffer {
     /// 创建新的流缓冲区
+    ///
+    /// `pool_size` 大于0时创建独立的缓冲池。
     pub fn new(config: BufferConfig) -> Self {
+        let pool = (config.pool_size > 0).then(|| BufferPool::new(config.pool_size));
         Self {
             chunks: VecDeque::new(),
             config,
@@ -222,6 +308,97 @@ impl StreamBuffer {
             next_sequence: 0,
             last_cleanup: Instant::now(),
             stats: BufferStats::default(),
+            pool,
+            ring: None,
+        }
+    }
+    
+    /// 创建固定容量的环形缓冲区
+    ///
+    /// 写入直接复制到预分配的存储中，不随读取次数增长内存，适合高吞吐代理。
+    /// 超出容量时丢弃最老的数据。压缩和过期清理在该模式下不生效。
+    pub fn with_ring_capacity(capacity: usize) -> Self {
+        let config = BufferConfig {
+            max_size: capacity,
+            enable_compression: false,
+            auto_cleanup: false,
+            ..BufferConfig::default()
+        };
+        let mut buffer = Self::new(config);
+        buffer.ring = Some(RingStorage::new(capacity));
+        buffer
+    }
+    
+    /// 是否为环形缓冲模式
+    pub fn is_ring(&self) -> bool {
+        self.ring.is_some()
+    }
+    
+    /// 返回当前全部缓冲数据的连续视图，供探测器直接读取
+    ///
+    /// 环形模式下只有数据跨越存储末尾时才整理；分块模式下多个块会合并为一块。
+    pub fn contiguous_slice(&mut self) -> &[u8] {
+        if self.ring.is_none() {
+            return self.merge_chunks();
+        }
+        self.ring.as_mut().map_or(&[], RingStorage::contiguous)
+    }
+    
+    /// 把所有块解压并合并为一块
+    fn merge_chunks(&mut self) -> &[u8] {
+        if self.chunks.len() > 1 || self.chunks.front().is_some_and(|chunk| chunk.compressed) {
+            let merged = self.peek(usize::MAX);
+            while let Some(chunk) = self.chunks.pop_front() {
+                self.recycle(chunk.data);
+            }
+            self.total_size = merged.len();
+            self.chunks.push_back(DataChunk::new(merged, self.next_sequence));
+            self.next_sequence += 1;
+            self.stats.current_buffered_bytes = self.total_size;
+            self.stats.chunk_count = self.chunks.len();
+        }
+        self.chunks.front().map_or(&[], |chunk| chunk.data.as_slice())
+    }
+    
+    /// 环形模式下写入数据并更新统计
+    fn push_ring(&mut self, data: &[u8]) {
+        let Some(ring) = &mut self.ring else { return };
+        self.stats.total_bytes_received += data.len();
+        self.stats.dropped_bytes += ring.write(data);
+        self.total_size = ring.len;
+        self.stats.current_buffered_bytes = self.total_size;
+        self.stats.max_buffered_bytes = self.stats.max_buffered_bytes.max(self.total_size);
+    }
+    
+    /// 创建使用共享缓冲池的流缓冲区
+    pub fn with_pool(config: BufferConfig, pool: BufferPool) -> Self {
+        let mut buffer = Self::new(BufferConfig { pool_size: 0, ..config });
+        buffer.config.pool_size = pool.max_buffers();
+        buffer.pool = Some(pool);
+        buffer
+    }
+    
+    /// 复制数据并添加，启用缓冲池时从池中取缓冲区
+    pub fn push_slice(&mut self, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.ring.is_some() {
+            self.push_ring(data);
+            return Ok(());
+        }
+        
+        let buffer = match &self.pool {
+            Some(pool) => pool.acquire_from(data),
+            None => data.to_vec(),
+        };
+        self.push(buffer)
+    }
+    
+    /// 将不再使用的数据归还缓冲池
+    fn recycle(&self, data: Vec<u8>) {
+        if let Some(pool) = &self.pool {
+            pool.release(data);
         }
     }
     
@@ -230,6 +407,11 @@ impl StreamBuffer {
         if data.is_empty() {
             return Ok(());
         }
+        if self.ring.is_some() {
+            self.push_ring(&data);
+            self.recycle(data);
+            return Ok(());
+        }
         
         let data_size = data.len();
         self.stats.total_bytes_received += data_size;
@@ -276,6 +458,11 @@ impl StreamBuffer {
     /// 获取数据（不移除）
     pub fn peek(&self, size: usize) -> Vec<u8> {
         let mut result = Vec::new();
+        if let Some(ring) = &self.ring {
+            ring.copy_front(size, &mut result);
+            return result;
+        }
+        
         let mut remaining = size;
         
         for chunk in &self.chunks {
@@ -283,20 +470,21 @@ impl StreamBuffer {
                 break;
             }
             
-            let chunk_data = if chunk.compressed {
+            let take_size = if chunk.compressed {
                 // 需要解压缩才能读取
                 let mut temp_chunk = chunk.clone();
-                if temp_chunk.decompress().is_ok() {
-                    temp_chunk.data
-                } else {
+                if temp_chunk.decompress().is_err() {
                     continue;
                 }
+                let take_size = std::cmp::min(remaining, temp_chunk.data.len());
+                result.extend_from_slice(&temp_chunk.data[..take_size]);
+                take_size
             } else {
-                chunk.data.clone()
+                let take_size = std::cmp::min(remaining, chunk.data.len());
+                result.extend_from_slice(&chunk.data[..take_size]);
+                take_size
             };
             
-            let take_size = std::cmp::min(remaining, chunk_data.len());
-            result.extend_from_slice(&chunk_data[..take_size]);
             remaining -= take_size;
         }
         
@@ -304,8 +492,21 @@ impl StreamBuffer {
     }
     
     /// 获取并移除数据
+    ///
+    /// 启用缓冲池时返回的缓冲区取自池中，使用完毕后可通过 [`BufferPool::release`] 归还。
     pub fn pop(&mut self, size: usize) -> Vec<u8> {
-        let mut result = Vec::new();
+        let mut result = match &self.pool {
+            Some(pool) => pool.acquire(),
+            None => Vec::new(),
+        };
+        if let Some(ring) = &mut self.ring {
+            ring.copy_front(size, &mut result);
+            ring.advance(result.len());
+            self.total_size = ring.len;
+            self.stats.total_bytes_sent += result.len();
+            self.stats.current_buffered_bytes = self.total_size;
+            return result;
+        }
         let mut remaining = size;
         
         while remaining > 0 && !self.chunks.is_empty() {
@@ -325,14 +526,11 @@ impl StreamBuffer {
                 result.extend_from_slice(&chunk.data);
                 remaining -= chunk_size;
                 self.total_size -= chunk.size();
+                self.recycle(chunk.data);
             } else {
-                // 只需要块的一部分
-                result.extend_from_slice(&chunk.data[..remaining]);
-                
-                // 创建剩余数据的新块
-                let remaining_data = chunk.data[remaining..].to_vec();
-                let new_chunk = DataChunk::new(remaining_data, chunk.sequence);
-                self.chunks.push_front(new_chunk);
+                // 只需要块的一部分，剩余数据原地保留在块中
+                result.extend(chunk.data.drain(..remaining));
+                self.chunks.push_front(chunk);
                 
                 self.total_size -= remaining;
                 remaining = 0;
@@ -365,7 +563,7 @@ impl StreamBuffer {
     
     /// 是否为空
     pub fn is_empty(&self) -> bool {
-        self.chunks.is_empty()
+        self.total_size == 0
     }
     
     /// 是否已满
@@ -375,7 +573,12 @@ impl StreamBuffer {
     
     /// 清空缓冲区
     pub fn clear(&mut self) {
-        self.chunks.clear();
+        if let Some(ring) = &mut self.ring {
+            ring.advance(ring.len);
+        }
+        while let Some(chunk) = self.chunks.pop_front() {
+            self.recycle(chunk.data);
+        }
         self.total_size = 0;
         self.stats.current_buffered_bytes = 0;
         self.stats.chunk_count = 0;
@@ -412,6 +615,7 @@ impl StreamBuffer {
                 let chunk = self.chunks.pop_front().unwrap();
                 removed_size += chunk.size();
                 removed_count += 1;
+                self.recycle(chunk.data);
             } else {
                 break;
             }
@@ -450,6 +654,7 @@ impl StreamBuffer {
                 freed_size += chunk.size();
                 self.total_size -= chunk.size();
                 self.stats.dropped_bytes += chunk.size();
+                self.recycle(chunk.data);
             }
         }
         
@@ -471,6 +676,11 @@ impl StreamBuffer {
         &self.stats
     }
     
+    /// 获取缓冲池
+    pub fn pool(&self) -> Option<&BufferPool> {
+        self.pool.as_ref()
+    }
+    
     /// 获取配置
     pub fn config(&self) -> &BufferConfig {
         &self.config
@@ -514,6 +724,14 @@ impl StreamBuffer {
     }
 }
 
+impl Drop for StreamBuffer {
+    fn drop(&mut self) {
+        if self.pool.is_some() {
+            self.clear();
+        }
+    }
+}
+
 impl Default for StreamBuffer {
     fn default() -> Self {
         Self::new(BufferConfig::default())
@@ -0,0 +1,86 @@
+//! tokio-util编解码适配
+//!
+//! 将协议探测器包装为 `tokio_util::codec::Decoder`，用于基于 `FramedRead` 的异步管道。
+
+use crate::core::detector::{DetectionResult, ProtocolDetector};
+use crate::error::{DetectorError, Result};
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+
+/// 协议探测解码器
+///
+/// 缓冲数据直到达到探测器的最小探测大小，识别出协议后产出一次 `DetectionResult`，
+/// 之后不再消费任何字节：剩余数据原样留在缓冲区，可通过 `FramedRead::map_decoder`
+/// 或 `into_parts` 切换到真正的协议编解码器继续处理。
+pub struct DetectionDecoder {
+    detector: Box<dyn ProtocolDetector>,
+    detected: bool,
+}
+
+impl DetectionDecoder {
+    /// 创建新的探测解码器
+    pub fn new(detector: Box<dyn ProtocolDetector>) -> Self {
+        Self {
+            detector,
+            detected: false,
+        }
+    }
+    
+    /// 是否已完成协议识别
+    pub fn is_detected(&self) -> bool {
+        self.detected
+    }
+    
+    /// 对当前缓冲区执行探测
+    ///
+    /// 数据不足或未达到探测上限前未识别出协议时返回 `Ok(None)` 继续等待；
+    /// `at_eof` 为真时不再等待，直接返回探测错误。
+    fn try_detect(&mut self, src: &mut BytesMut, at_eof: bool) -> Result<Option<DetectionResult>> {
+        if self.detected || src.is_empty() {
+            return Ok(None);
+        }
+        if !at_eof && src.len() < self.detector.min_probe_size() {
+            return Ok(None);
+        }
+        
+        match self.detector.detect(src) {
+            Ok(result) => {
+                self.detected = true;
+                Ok(Some(result))
+            }
+            Err(DetectorError::NeedMoreData(needed)) if !at_eof => {
+                src.reserve(needed.saturating_sub(src.len()));
+                Ok(None)
+            }
+            Err(DetectorError::InsufficientData(_) | DetectorError::NoProtocolDetected(_))
+                if !at_eof && src.len() < self.detector.max_probe_size() =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl std::fmt::Debug for DetectionDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DetectionDecoder")
+            .field("detector", &self.detector.name())
+            .field("detected", &self.detected)
+            .finish()
+    }
+}
+
+impl Decoder for DetectionDecoder {
+    type Item = DetectionResult;
+    type Error = DetectorError;
+    
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<DetectionResult>> {
+        self.try_detect(src, false)
+    }
+    
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<DetectionResult>> {
+        // 识别完成后剩余字节属于后续协议，流结束时不视为错误
+        self.try_detect(src, true)
+    }
+}
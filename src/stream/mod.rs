@@ -11,11 +11,23 @@ use std::time::{Duration, Instant};
 pub mod buffer;
 pub mod processor;
 pub mod analyzer;
+pub mod pool;
+pub mod unified;
+#[cfg(feature = "tokio-util")]
+pub mod codec;
+#[cfg(feature = "runtime-tokio")]
+pub mod tokio_io;
 
 // 重导出主要类型
 pub use buffer::{StreamBuffer, BufferConfig};
 pub use processor::{StreamProcessor, ProcessorConfig};
-pub use analyzer::{StreamAnalyzer, AnalysisResult};
+pub use analyzer::{StreamAnalyzer, AnalysisResult, AnomalyDetector, DataPoint};
+pub use pool::{BufferPool, PoolStats};
+pub use unified::UnifiedStream;
+#[cfg(feature = "tokio-util")]
+pub use codec::DetectionDecoder;
+#[cfg(feature = "runtime-tokio")]
+pub use tokio_io::detect_from_async_read;
 
 /// 流状态
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -64,6 +76,8 @@ pub struct StreamMetadata {
     pub packet_count: usize,
     /// 自定义属性
     pub attributes: std::collections::HashMap<String, String>,
+    /// 同一连接反方向的流ID（由 [`StreamManager::correlate`] 设置）
+    pub sibling_id: Option<String>,
 }
 
 impl StreamMetadata {
@@ -80,6 +94,7 @@ impl StreamMetadata {
             total_bytes: 0,
             packet_count: 0,
             attributes: std::collections::HashMap::new(),
+            sibling_id: None,
         }
     }
     
@@ -95,6 +110,12 @@ impl StreamMetadata {
         self
     }
     
+    /// 设置反方向的流ID
+    pub fn with_sibling(mut self, stream_id: String) -> Self {
+        self.sibling_id = Some(stream_id);
+        self
+    }
+    
     /// 添加属性
     pub fn with_attribute(mut self, key: String, value: String) -> Self {
         self.attributes.insert(key, value);
@@ -147,6 +168,17 @@ pub enum StreamEvent {
         /// 时间戳
         timestamp: Instant,
     },
+    /// 协议锁定后识别出的消息边界
+    MessageBoundary {
+        /// 连接级协议
+        protocol: ProtocolType,
+        /// 消息序号（从0开始）
+        index: usize,
+        /// 完整消息数据
+        data: Vec<u8>,
+        /// 时间戳
+        timestamp: Instant,
+    },
     /// 探测失败
     DetectionFailed {
         /// 错误信息
@@ -374,9 +406,48 @@ impl StreamManager {
         self.active_streams.get_mut(stream_id)
     }
     
+    /// 关联同一连接的入站流和出站流
+    ///
+    /// 入站流的源/目标地址为 `source_addr`/`dest_addr`，出站流的地址与之相反。
+    /// 两个方向都存在时互相记录为兄弟流，并返回 `(入站流ID, 出站流ID)`。
+    pub fn correlate(&mut self, source_addr: &str, dest_addr: &str) -> Option<(String, String)> {
+        let inbound = self.find_directional_stream(StreamDirection::Inbound, source_addr, dest_addr)?;
+        let outbound = self.find_directional_stream(StreamDirection::Outbound, dest_addr, source_addr)?;
+        
+        if let Some(metadata) = self.active_streams.get_mut(&inbound) {
+            metadata.sibling_id = Some(outbound.clone());
+        }
+        if let Some(metadata) = self.active_streams.get_mut(&outbound) {
+            metadata.sibling_id = Some(inbound.clone());
+        }
+        Some((inbound, outbound))
+    }
+    
+    /// 获取流的兄弟流元数据
+    pub fn sibling(&self, stream_id: &str) -> Option<&StreamMetadata> {
+        let sibling_id = self.active_streams.get(stream_id)?.sibling_id.as_deref()?;
+        self.active_streams.get(sibling_id)
+    }
+    
+    /// 按方向和地址查找流，多个匹配时取ID最小者以保证结果稳定
+    fn find_directional_stream(&self, direction: StreamDirection, source_addr: &str, dest_addr: &str) -> Option<String> {
+        self.active_streams.values()
+            .filter(|metadata| {
+                metadata.direction == direction
+                    && metadata.source_addr.as_deref() == Some(source_addr)
+                    && metadata.dest_addr.as_deref() == Some(dest_addr)
+            })
+            .map(|metadata| &metadata.stream_id)
+            .min()
+            .cloned()
+    }
+    
     /// 关闭流
     pub fn close_stream(&mut self, stream_id: &str) -> Result<()> {
-        if self.active_streams.remove(stream_id).is_some() {
+        if let Some(metadata) = self.active_streams.remove(stream_id) {
+            if let Some(sibling) = metadata.sibling_id.and_then(|id| self.active_streams.get_mut(&id)) {
+                sibling.sibling_id = None;
+            }
             let event = StreamEvent::StreamClosed {
                 timestamp: Instant::now(),
             };
@@ -0,0 +1,115 @@
+//! 缓冲池模块
+//!
+//! 为流缓冲区提供可复用的 `Vec<u8>`，减少高吞吐场景下每包分配带来的开销。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 缓冲池统计信息
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// 取出缓冲区次数
+    pub acquired: u64,
+    /// 复用已有缓冲区次数
+    pub reused: u64,
+    /// 归还缓冲区次数
+    pub released: u64,
+    /// 因池已满或容量过大而丢弃的缓冲区数
+    pub discarded: u64,
+}
+
+#[derive(Debug, Default)]
+struct PoolInner {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    acquired: AtomicU64,
+    reused: AtomicU64,
+    released: AtomicU64,
+    discarded: AtomicU64,
+}
+
+/// 字节缓冲池
+///
+/// 克隆后共享同一个池，可在多个流之间复用缓冲区。
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+    /// 池中最多保留的缓冲区数量
+    max_buffers: usize,
+    /// 归还时允许保留的最大容量，避免大缓冲区长期占用内存
+    max_buffer_capacity: usize,
+}
+
+impl BufferPool {
+    /// 创建缓冲池
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            inner: Arc::new(PoolInner::default()),
+            max_buffers,
+            max_buffer_capacity: 64 * 1024,
+        }
+    }
+    
+    /// 设置归还时允许保留的最大缓冲区容量
+    pub fn with_max_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.max_buffer_capacity = capacity;
+        self
+    }
+    
+    /// 取出一个空缓冲区，池为空时新分配
+    pub fn acquire(&self) -> Vec<u8> {
+        self.inner.acquired.fetch_add(1, Ordering::Relaxed);
+        match self.inner.buffers.lock().unwrap().pop() {
+            Some(buffer) => {
+                self.inner.reused.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => Vec::new(),
+        }
+    }
+    
+    /// 取出缓冲区并复制数据
+    pub fn acquire_from(&self, data: &[u8]) -> Vec<u8> {
+        let mut buffer = self.acquire();
+        buffer.extend_from_slice(data);
+        buffer
+    }
+    
+    /// 归还缓冲区，内容会被清空
+    pub fn release(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        
+        if buffer.capacity() <= self.max_buffer_capacity {
+            let mut buffers = self.inner.buffers.lock().unwrap();
+            if buffers.len() < self.max_buffers {
+                buffer.clear();
+                buffers.push(buffer);
+                self.inner.released.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        
+        self.inner.discarded.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// 池中当前可用的缓冲区数量
+    pub fn available(&self) -> usize {
+        self.inner.buffers.lock().unwrap().len()
+    }
+    
+    /// 池的最大缓冲区数量
+    pub fn max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+    
+    /// 获取统计信息
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            acquired: self.inner.acquired.load(Ordering::Relaxed),
+            reused: self.inner.reused.load(Ordering::Relaxed),
+            released: self.inner.released.load(Ordering::Relaxed),
+            discarded: self.inner.discarded.load(Ordering::Relaxed),
+        }
+    }
+}
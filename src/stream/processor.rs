@@ -6,7 +6,9 @@ use crate::core::detector::{DetectionConfig, DetectionResult, ProtocolDetector};
 use crate::core::protocol::ProtocolType;
 use crate::error::{DetectorError, Result};
 use crate::stream::buffer::{StreamBuffer, BufferConfig};
+use crate::stream::pool::BufferPool;
 use crate::stream::{StreamEvent, StreamMetadata, StreamState};
+use crate::utils::http;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
@@ -66,18 +68,24 @@ struct StreamContext {
     last_detection: Option<Instant>,
     /// 最后活动时间
     last_activity: Instant,
+    /// 下次探测前缓冲区需要达到的大小（由探测器的 `NeedMoreData` 提示决定）
+    required_size: usize,
+    /// 协议锁定后已切分出的消息数
+    message_count: usize,
 }
 
 impl StreamContext {
-    fn new(metadata: StreamMetadata, buffer_config: BufferConfig) -> Self {
+    fn new(metadata: StreamMetadata, buffer: StreamBuffer) -> Self {
         Self {
             metadata,
             state: StreamState::Initial,
-            buffer: StreamBuffer::new(buffer_config),
+            buffer,
             detection_history: Vec::new(),
             detection_attempts: 0,
             last_detection: None,
             last_activity: Instant::now(),
+            required_size: 0,
+            message_count: 0,
         }
     }
     
@@ -97,7 +105,7 @@ impl StreamContext {
         }
         
         // 检查缓冲区大小
-        if self.buffer.size() < min_size {
+        if self.buffer.size() < min_size.max(self.required_size) {
             return false;
         }
         
@@ -123,6 +131,8 @@ pub struct StreamProcessor {
     detector: Box<dyn ProtocolDetector>,
     /// 处理统计
     stats: ProcessorStats,
+    /// 各流共享的缓冲池
+    pool: Option<BufferPool>,
 }
 
 /// 处理器统计信息
@@ -194,11 +204,13 @@ impl ProcessorStats {
 impl StreamProcessor {
     /// 创建新的流处理器
     pub fn new(config: ProcessorConfig, detector: Box<dyn ProtocolDetector>) -> Self {
+        let pool_size = config.buffer_config.pool_size;
         Self {
             config,
             streams: HashMap::new(),
             detector,
             stats: ProcessorStats::default(),
+            pool: (pool_size > 0).then(|| BufferPool::new(pool_size)),
         }
     }
     
@@ -214,13 +226,29 @@ impl StreamProcessor {
         }
         
         // 创建流上下文
-        let context = StreamContext::new(metadata, self.config.buffer_config.clone());
+        let buffer_config = self.config.buffer_config.clone();
+        let buffer = match &self.pool {
+            Some(pool) => StreamBuffer::with_pool(buffer_config, pool.clone()),
+            None => StreamBuffer::new(buffer_config),
+        };
+        let context = StreamContext::new(metadata, buffer);
         self.streams.insert(stream_id, context);
         
         self.stats.record_new_stream();
         Ok(())
     }
     
+    /// 处理借用的流数据
+    ///
+    /// 启用缓冲池时数据会复制到池中取出的缓冲区，避免调用方每包分配。
+    pub fn process_slice(&mut self, stream_id: &str, data: &[u8]) -> Result<Vec<StreamEvent>> {
+        let buffer = match &self.pool {
+            Some(pool) => pool.acquire_from(data),
+            None => data.to_vec(),
+        };
+        self.process_data(stream_id, buffer)
+    }
+    
     /// 处理流数据
     pub fn process_data(&mut self, stream_id: &str, data: Vec<u8>) -> Result<Vec<StreamEvent>> {
         let mut events = Vec::new();
@@ -316,6 +344,7 @@ impl StreamProcessor {
         match self.detector.detect(&data) {
             Ok(result) => {
                 let detection_time = start_time.elapsed();
+                context.required_size = 0;
                 
                 // 检查置信度
                 if result.protocol_info.confidence >= self.config.detection_config.min_confidence {
@@ -347,6 +376,12 @@ impl StreamProcessor {
                     Ok(None)
                 }
             }
+            Err(DetectorError::NeedMoreData(needed)) => {
+                // 数据不足，等缓冲区累计到所需总字节数后重新探测
+                context.state = StreamState::Initial;
+                context.required_size = needed;
+                Ok(None)
+            }
             Err(err) => {
                 // 探测失败
                 let error_msg = err.to_string();
@@ -361,6 +396,48 @@ impl StreamProcessor {
         }
     }
     
+    /// 切分连接上的下一条消息
+    ///
+    /// 协议锁定后，按协议的报文格式（目前支持HTTP/1.x请求/响应）识别消息边界，
+    /// 从缓冲区取出完整消息并重置逐消息的探测状态，连接级协议保持不变。
+    /// 用于keep-alive和管线化连接；消息不完整或协议不支持分帧时返回 `None`。
+    pub fn next_message(&mut self, stream_id: &str) -> Result<Option<StreamEvent>> {
+        let context = self.streams.get_mut(stream_id)
+            .ok_or_else(|| DetectorError::config_error(
+                format!("Stream not found: {}", stream_id)
+            ))?;
+        
+        let protocol = match context.state {
+            StreamState::Detected(protocol @ (ProtocolType::HTTP1_0 | ProtocolType::HTTP1_1)) => protocol,
+            _ => return Ok(None),
+        };
+        
+        let buffered = context.buffer.peek(context.buffer.size());
+        let Some(message_len) = http::message_len(&buffered) else {
+            return Ok(None);
+        };
+        
+        let data = context.buffer.pop(message_len);
+        
+        // 重置逐消息的探测状态
+        context.detection_attempts = 0;
+        context.last_detection = None;
+        context.required_size = 0;
+        context.message_count += 1;
+        
+        Ok(Some(StreamEvent::MessageBoundary {
+            protocol,
+            index: context.message_count - 1,
+            data,
+            timestamp: Instant::now(),
+        }))
+    }
+    
+    /// 获取流已切分出的消息数
+    pub fn get_message_count(&self, stream_id: &str) -> Option<usize> {
+        self.streams.get(stream_id).map(|ctx| ctx.message_count)
+    }
+    
     /// 获取流状态
     pub fn get_stream_state(&self, stream_id: &str) -> Option<&StreamState> {
         self.streams.get(stream_id).map(|ctx| &ctx.state)
@@ -376,6 +453,12 @@ impl StreamProcessor {
         self.streams.get(stream_id).map(|ctx| ctx.buffer.size())
     }
     
+    /// 获取流在下次探测前还需读取的字节数
+    pub fn get_bytes_needed(&self, stream_id: &str) -> Option<usize> {
+        self.streams.get(stream_id)
+            .map(|ctx| ctx.required_size.saturating_sub(ctx.buffer.size()))
+    }
+    
     /// 获取流探测历史
     pub fn get_detection_history(&self, stream_id: &str) -> Option<&[DetectionResult]> {
         self.streams.get(stream_id).map(|ctx| ctx.detection_history.as_slice())
@@ -427,6 +510,11 @@ impl StreamProcessor {
         &self.stats
     }
     
+    /// 获取共享缓冲池
+    pub fn buffer_pool(&self) -> Option<&BufferPool> {
+        self.pool.as_ref()
+    }
+    
     /// 获取配置
     pub fn config(&self) -> &ProcessorConfig {
         &self.config
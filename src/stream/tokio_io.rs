@@ -0,0 +1,64 @@
+//! tokio异步读取适配
+//!
+//! 从 `AsyncRead` 读取连接开头的数据完成协议探测，并把已读取的字节交还调用方，
+//! 便于在tokio的TCP accept循环中作为前置分流使用。
+
+use crate::core::detector::{DetectionProgress, DetectionResult, IncrementalState, ProtocolDetector};
+use crate::error::{DetectorError, Result};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// 单次读取的缓冲区大小
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// 从异步读取端探测协议
+///
+/// 最多读取 `peek_cap` 字节（同时不超过探测器的 `max_probe_size`），识别出协议后立即返回。
+/// 读取和探测的总耗时受探测器的 `timeout` 限制。
+///
+/// 无论探测成功与否（包括超时、I/O错误和无法识别），都会返回已从 `reader` 中消费的字节，
+/// 调用方应先把它们交给真正的协议处理器或回退处理器。
+pub async fn detect_from_async_read<R: AsyncRead + Unpin>(
+    detector: &dyn ProtocolDetector,
+    reader: &mut R,
+    peek_cap: usize,
+) -> (Result<DetectionResult>, Vec<u8>) {
+    let timeout = detector.timeout();
+    let mut state = IncrementalState::new();
+    let outcome = match tokio::time::timeout(timeout, read_and_detect(detector, reader, peek_cap, &mut state)).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(DetectorError::timeout(timeout.as_millis() as u64)),
+    };
+    (outcome, state.into_buffer())
+}
+
+/// 逐段读取并增量探测，直到得出结论、读满上限或遇到EOF
+///
+/// 读取的字节累积在 `state` 中，出错时同样保留。
+async fn read_and_detect<R: AsyncRead + Unpin>(
+    detector: &dyn ProtocolDetector,
+    reader: &mut R,
+    peek_cap: usize,
+    state: &mut IncrementalState,
+) -> Result<DetectionResult> {
+    let limit = peek_cap.min(detector.max_probe_size());
+    let mut chunk = vec![0u8; READ_CHUNK_SIZE.min(limit.max(1))];
+    
+    while state.buffered().len() < limit {
+        let want = chunk.len().min(limit - state.buffered().len());
+        let n = reader.read(&mut chunk[..want]).await?;
+        if n == 0 {
+            break;
+        }
+        
+        match detector.detect_incremental(state, &chunk[..n])? {
+            DetectionProgress::Detected(result) => return Ok(result),
+            DetectionProgress::Failed => {
+                return Err(DetectorError::NoProtocolDetected("达到探测上限仍未识别协议".to_string()));
+            }
+            DetectionProgress::NeedMore { .. } => {}
+        }
+    }
+    
+    // EOF或读满 `peek_cap`：不再等待，按已有数据给出最终结论
+    detector.detect(state.buffered())
+}
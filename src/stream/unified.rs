@@ -0,0 +1,143 @@
+//! 统一流抽象
+//!
+//! 包装 `Transport`，在探测协议时缓存已读取的字节，探测完成后通过 `std::io::Read`
+//! 先把这些字节重放给使用方，再继续读取传输层，使后续协议处理器看到完整的字节流。
+
+use crate::core::detector::{DetectionProgress, DetectionResult, IncrementalState, ProtocolDetector, Transport};
+use crate::error::{DetectorError, Result};
+use std::io;
+use std::time::Instant;
+
+/// 探测时单次读取的缓冲区大小
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// 统一流：探测协议后透明重放已读取字节的传输层包装
+///
+/// 探测最多读取探测器的 `max_probe_size` 字节。传输层的 `read` 是阻塞调用，
+/// 探测器的 `timeout` 只在两次读取之间检查。
+#[derive(Debug)]
+pub struct UnifiedStream<T: Transport> {
+    transport: T,
+    replay: Vec<u8>,
+    position: usize,
+    detection: Option<DetectionResult>,
+}
+
+impl<T: Transport> UnifiedStream<T> {
+    /// 包装传输层
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            replay: Vec::new(),
+            position: 0,
+            detection: None,
+        }
+    }
+    
+    /// 从传输层读取数据并探测协议
+    ///
+    /// 读取的字节无论探测成功与否都会保留，之后由 `Read` 重放。已有探测结果时直接返回；
+    /// 上一次探测失败时，尚未被读走的字节会作为本次探测的开头数据。
+    pub fn detect(&mut self, detector: &dyn ProtocolDetector) -> Result<DetectionResult> {
+        if let Some(result) = &self.detection {
+            return Ok(result.clone());
+        }
+        
+        let timeout = detector.timeout();
+        let deadline = Instant::now() + timeout;
+        let limit = detector.max_probe_size();
+        let mut state = IncrementalState::new();
+        let mut progress = detector.detect_incremental(&mut state, &self.replay[self.position..])?;
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE.min(limit.max(1))];
+        
+        while !progress.is_finished() && state.buffered().len() < limit {
+            if Instant::now() >= deadline {
+                return Err(DetectorError::timeout(timeout.as_millis() as u64));
+            }
+            
+            let want = chunk.len().min(limit - state.buffered().len());
+            let n = self.transport.read(&mut chunk[..want])?;
+            if n == 0 {
+                break;
+            }
+            self.replay.extend_from_slice(&chunk[..n]);
+            progress = detector.detect_incremental(&mut state, &chunk[..n])?;
+        }
+        
+        let result = match progress {
+            DetectionProgress::Detected(result) => result,
+            DetectionProgress::Failed => {
+                return Err(DetectorError::NoProtocolDetected("达到探测上限仍未识别协议".to_string()));
+            }
+            // EOF或读满上限：按已有数据给出最终结论
+            DetectionProgress::NeedMore { .. } => detector.detect(state.buffered())?,
+        };
+        self.detection = Some(result.clone());
+        Ok(result)
+    }
+    
+    /// 探测结果，尚未探测成功时为 `None`
+    pub fn detection(&self) -> Option<&DetectionResult> {
+        self.detection.as_ref()
+    }
+    
+    /// 尚未被读走的已缓存字节
+    pub fn buffered(&self) -> &[u8] {
+        &self.replay[self.position..]
+    }
+    
+    /// 底层传输层的引用
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+    
+    /// 底层传输层的可变引用
+    ///
+    /// 直接读取传输层会跳过尚未重放的缓存字节。
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+    
+    /// 拆出传输层和尚未被读走的缓存字节
+    pub fn into_parts(mut self) -> (T, Vec<u8>) {
+        let buffered = self.replay.split_off(self.position);
+        (self.transport, buffered)
+    }
+}
+
+impl<T: Transport> io::Read for UnifiedStream<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let buffered = &self.replay[self.position..];
+        if buffered.is_empty() {
+            return self.transport.read(buf).map_err(into_io_error);
+        }
+        
+        let n = buffered.len().min(buf.len());
+        buf[..n].copy_from_slice(&buffered[..n]);
+        self.position += n;
+        if self.position == self.replay.len() {
+            // 重放完毕后释放缓存
+            self.replay = Vec::new();
+            self.position = 0;
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Transport> io::Write for UnifiedStream<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transport.write(buf).map_err(into_io_error)
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 传输层错误转换为I/O错误，原本就是I/O错误的直接取出
+fn into_io_error(err: DetectorError) -> io::Error {
+    match err {
+        DetectorError::IoError(err) => err,
+        err => io::Error::other(err),
+    }
+}
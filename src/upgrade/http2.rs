@@ -0,0 +1,173 @@
+//! HTTP/2协议升级器
+//!
+//! 处理明文HTTP/1.1通过 `Upgrade: h2c` 升级到HTTP/2（RFC 7540 §3.2）。
+
+use crate::core::protocol::{ProtocolType, UpgradePath, UpgradeMethod};
+use crate::error::Result;
+use crate::upgrade::{detect_http_upgrade, ProtocolUpgrader, UpgradeResult};
+use crate::utils::encoding::decode_base64;
+use crate::utils::{http, http2};
+use std::time::{Duration, Instant};
+
+/// SETTINGS参数长度（2字节ID + 4字节值）
+const SETTING_LEN: usize = 6;
+
+/// SETTINGS_MAX_CONCURRENT_STREAMS参数ID
+const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+
+/// h2c升级成功时的响应
+const SWITCHING_PROTOCOLS: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+
+/// HTTP/2升级器
+#[derive(Debug)]
+pub struct Http2Upgrader {
+    name: &'static str,
+    settings: Vec<(u16, u32)>,
+}
+
+impl Http2Upgrader {
+    /// 创建新的HTTP/2升级器，初始SETTINGS帧只声明最大并发流数100
+    pub fn new() -> Self {
+        Self {
+            name: "Http2Upgrader",
+            settings: vec![(SETTINGS_MAX_CONCURRENT_STREAMS, 100)],
+        }
+    }
+    
+    /// 设置初始SETTINGS帧中的参数
+    pub fn with_settings(mut self, settings: Vec<(u16, u32)>) -> Self {
+        self.settings = settings;
+        self
+    }
+    
+    /// 升级成功时服务端应先发送的 `101 Switching Protocols` 响应
+    pub fn switching_protocols_response(&self) -> &'static [u8] {
+        SWITCHING_PROTOCOLS
+    }
+    
+    /// 生成连接前言和初始SETTINGS帧
+    fn create_preface(&self) -> Vec<u8> {
+        let payload_len = self.settings.len() * SETTING_LEN;
+        let mut data = Vec::with_capacity(http2::CONNECTION_PREFACE.len() + http2::FRAME_HEADER_LEN + payload_len);
+        data.extend_from_slice(http2::CONNECTION_PREFACE);
+        data.extend_from_slice(&(payload_len as u32).to_be_bytes()[1..]);
+        data.extend_from_slice(&[http2::FRAME_SETTINGS, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        for &(id, value) in &self.settings {
+            data.extend_from_slice(&id.to_be_bytes());
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data
+    }
+    
+    /// 校验h2c升级请求并解码 `HTTP2-Settings`
+    ///
+    /// 要求 `Upgrade` 包含 `h2c`，`Connection` 同时列出 `Upgrade` 和 `HTTP2-Settings`，
+    /// 且 `HTTP2-Settings` 为base64url编码的SETTINGS帧负载。
+    fn parse_upgrade_request(&self, data: &[u8]) -> std::result::Result<Vec<(u16, u32)>, String> {
+        let headers = http::header_block(data).ok_or("Incomplete HTTP/1.1 request headers")?;
+        
+        let intent = detect_http_upgrade(headers).filter(|intent| !intent.is_response);
+        if !intent.is_some_and(|intent| intent.targets.iter().any(|t| t.name.eq_ignore_ascii_case("h2c"))) {
+            return Err("Missing 'Upgrade: h2c' header".to_string());
+        }
+        
+        let connection = http::header_value(headers, "connection").unwrap_or_default();
+        let has_token = |name: &str| connection.split(',').any(|token| token.trim().eq_ignore_ascii_case(name));
+        if !has_token("upgrade") || !has_token("http2-settings") {
+            return Err("Connection header must list 'Upgrade, HTTP2-Settings'".to_string());
+        }
+        
+        let encoded = http::header_value(headers, "http2-settings").ok_or("Missing 'HTTP2-Settings' header")?;
+        let payload = decode_base64(encoded).map_err(|e| format!("Invalid HTTP2-Settings: {}", e))?;
+        if payload.len() % SETTING_LEN != 0 {
+            return Err(format!("HTTP2-Settings payload length {} is not a multiple of 6", payload.len()));
+        }
+        
+        Ok(payload.chunks_exact(SETTING_LEN)
+            .map(|s| (u16::from_be_bytes([s[0], s[1]]), u32::from_be_bytes([s[2], s[3], s[4], s[5]])))
+            .collect())
+    }
+}
+
+/// 已知SETTINGS参数的元数据键名
+fn setting_name(id: u16) -> Option<&'static str> {
+    match id {
+        0x1 => Some("header_table_size"),
+        0x2 => Some("enable_push"),
+        0x3 => Some("max_concurrent_streams"),
+        0x4 => Some("initial_window_size"),
+        0x5 => Some("max_frame_size"),
+        0x6 => Some("max_header_list_size"),
+        0x8 => Some("enable_connect_protocol"),
+        0x9 => Some("no_rfc7540_priorities"),
+        _ => None,
+    }
+}
+
+impl Default for Http2Upgrader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProtocolUpgrader for Http2Upgrader {
+    fn can_upgrade(&self, from: ProtocolType, to: ProtocolType) -> bool {
+        matches!((from, to), (ProtocolType::HTTP1_1, ProtocolType::HTTP2))
+    }
+    
+    fn upgrade(&self, from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<UpgradeResult> {
+        let start = Instant::now();
+        
+        // 检查前置条件
+        self.check_prerequisites(from, to, data)?;
+        
+        let client_settings = match self.parse_upgrade_request(data) {
+            Ok(settings) => settings,
+            Err(error) => {
+                return Ok(UpgradeResult::failure(to, UpgradeMethod::HttpUpgrade, start.elapsed(), error));
+            }
+        };
+        
+        let mut result = UpgradeResult::success(
+            to,
+            self.create_preface(),
+            UpgradeMethod::HttpUpgrade,
+            start.elapsed(),
+        );
+        result = result.with_metadata("original_protocol".to_string(), format!("{:?}", from));
+        result = result.with_metadata("upgrade_token".to_string(), "h2c".to_string());
+        result = result.with_metadata("response_status".to_string(), "101".to_string());
+        
+        // 未知参数按RFC要求忽略
+        for (id, value) in client_settings {
+            if let Some(name) = setting_name(id) {
+                result = result.with_metadata(format!("client_settings_{}", name), value.to_string());
+            }
+        }
+        
+        Ok(result)
+    }
+    
+    fn supported_upgrades(&self) -> Vec<UpgradePath> {
+        vec![UpgradePath {
+            from: ProtocolType::HTTP1_1,
+            to: ProtocolType::HTTP2,
+            method: UpgradeMethod::HttpUpgrade,
+            required_headers: vec![
+                "Connection".to_string(),
+                "Upgrade".to_string(),
+                "HTTP2-Settings".to_string(),
+            ],
+            optional_headers: vec![],
+        }]
+    }
+    
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    
+    fn estimate_upgrade_time(&self, _from: ProtocolType, _to: ProtocolType) -> Duration {
+        // 101响应与首个请求的响应共用一次往返
+        Duration::from_millis(100)
+    }
+}
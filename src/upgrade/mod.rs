@@ -4,19 +4,128 @@
 
 use crate::core::protocol::{ProtocolType, UpgradePath, UpgradeMethod};
 use crate::error::{DetectorError, Result};
-use std::collections::HashMap;
+use crate::utils::http as http_utils;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 
 pub mod http;
+pub mod http2;
+pub mod quic;
 pub mod websocket;
-// pub mod http2;  // TODO: 实现 HTTP/2 升级器
-// pub mod quic;   // TODO: 实现 QUIC 升级器
 
 // 重导出主要类型
 pub use http::HttpUpgrader;
+pub use http2::Http2Upgrader;
+pub use quic::QuicUpgrader;
 pub use websocket::WebSocketUpgrader;
-// pub use http2::Http2Upgrader;
-// pub use quic::QuicUpgrader;
+
+/// `Upgrade` 头中的单个目标协议
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeTarget {
+    /// 协议名（如 `h2c`、`websocket`、`TLS`）
+    pub name: String,
+    /// 协议版本（如 `TLS/1.2` 中的 `1.2`）
+    pub version: Option<String>,
+}
+
+impl UpgradeTarget {
+    /// 解析 `protocol-name ["/" protocol-version]` 形式的令牌
+    pub fn parse(token: &str) -> Option<Self> {
+        let token = token.trim();
+        if token.is_empty() {
+            return None;
+        }
+        
+        let (name, version) = match token.split_once('/') {
+            Some((name, version)) => (name.trim(), Some(version.trim().to_string())),
+            None => (token, None),
+        };
+        Some(Self { name: name.to_string(), version })
+    }
+    
+    /// 映射到已知协议类型，自定义令牌返回 `None`
+    pub fn protocol_type(&self) -> Option<ProtocolType> {
+        match self.name.to_ascii_lowercase().as_str() {
+            "h2c" => Some(ProtocolType::HTTP2),
+            "websocket" => Some(ProtocolType::WebSocket),
+            // RFC 2817
+            "tls" => Some(ProtocolType::TLS),
+            "http" => match self.version.as_deref() {
+                Some("2" | "2.0") => Some(ProtocolType::HTTP2),
+                Some("1.1") => Some(ProtocolType::HTTP1_1),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for UpgradeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.version {
+            Some(ref version) => write!(f, "{}/{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// HTTP `Upgrade` 意图
+///
+/// 路由可据此把连接分派给对应的升级器。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeIntent {
+    /// 按优先级排列的目标协议
+    pub targets: Vec<UpgradeTarget>,
+    /// 是否为 `101 Switching Protocols` 响应（否则为请求）
+    pub is_response: bool,
+}
+
+impl UpgradeIntent {
+    /// 首选的目标协议
+    pub fn primary(&self) -> Option<&UpgradeTarget> {
+        self.targets.first()
+    }
+    
+    /// 所有目标中已知的协议类型
+    pub fn protocol_types(&self) -> Vec<ProtocolType> {
+        self.targets.iter().filter_map(UpgradeTarget::protocol_type).collect()
+    }
+    
+    /// 以逗号分隔的目标列表，用于 `upgrade_to` 元数据
+    pub fn targets_value(&self) -> String {
+        self.targets.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+    }
+}
+
+/// 解析HTTP/1.x报文中的 `Upgrade` 头
+///
+/// 支持请求和 `101` 响应；其他状态码的响应（如 `426`、通告h2c的 `200`）只是
+/// 声明可升级，并未切换协议，返回 `None`。头部未完整时只检查已收到的完整行。
+pub fn detect_http_upgrade(data: &[u8]) -> Option<UpgradeIntent> {
+    let line_end = data.iter().position(|&b| b == b'\n')?;
+    let first_line = &data[..line_end];
+    let is_response = first_line.starts_with(b"HTTP/1.");
+    if is_response {
+        let status = first_line.split(|&b| b == b' ').nth(1).map(<[u8]>::trim_ascii);
+        if status != Some(b"101".as_slice()) {
+            return None;
+        }
+    } else if !first_line.windows(7).any(|w| w == b"HTTP/1.") {
+        return None;
+    }
+    
+    let end = http_utils::header_end(data)
+        .or_else(|| data.iter().rposition(|&b| b == b'\n').map(|pos| pos + 1))?;
+    let targets: Vec<UpgradeTarget> = http_utils::header_value(&data[..end], "upgrade")?
+        .split(',')
+        .filter_map(UpgradeTarget::parse)
+        .collect();
+    
+    if targets.is_empty() {
+        return None;
+    }
+    Some(UpgradeIntent { targets, is_response })
+}
 
 /// 协议升级器trait
 pub trait ProtocolUpgrader: Send + Sync + std::fmt::Debug {
@@ -37,6 +146,12 @@ pub trait ProtocolUpgrader: Send + Sync + std::fmt::Debug {
         Duration::from_millis(100) // 默认估算
     }
     
+    /// 返回升级器的异步实现，`UpgradeManager::upgrade_async` 会优先等待该实现
+    #[cfg(any(feature = "runtime-tokio", feature = "runtime-async-std"))]
+    fn as_async(&self) -> Option<&dyn AsyncProtocolUpgrader> {
+        None
+    }
+    
     /// 检查升级的前置条件
     fn check_prerequisites(&self, from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<()> {
         if !self.can_upgrade(from, to) {
@@ -61,7 +176,8 @@ pub trait ProtocolUpgrader: Send + Sync + std::fmt::Debug {
 
 /// 异步协议升级器trait
 #[cfg(any(feature = "runtime-tokio", feature = "runtime-async-std"))]
-pub trait AsyncProtocolUpgrader {
+#[async_trait::async_trait]
+pub trait AsyncProtocolUpgrader: Send + Sync {
     /// 异步执行协议升级
     async fn upgrade_async(&self, from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<UpgradeResult>;
     
@@ -175,6 +291,50 @@ impl Default for UpgradeConfig {
     }
 }
 
+/// 延迟直方图保留的最大样本数
+const LATENCY_SAMPLE_CAPACITY: usize = 1024;
+
+/// 升级延迟直方图
+///
+/// 保留最近的 `LATENCY_SAMPLE_CAPACITY` 个样本，按最近排名法计算百分位。
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    samples: Vec<Duration>,
+    next: usize,
+    count: u64,
+}
+
+impl LatencyHistogram {
+    /// 记录一次延迟
+    pub fn record(&mut self, duration: Duration) {
+        if self.samples.len() < LATENCY_SAMPLE_CAPACITY {
+            self.samples.push(duration);
+        } else {
+            self.samples[self.next] = duration;
+        }
+        self.next = (self.next + 1) % LATENCY_SAMPLE_CAPACITY;
+        self.count += 1;
+    }
+    
+    /// 累计记录次数
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    
+    /// 计算百分位延迟，`p` 取值 0.0 - 100.0
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.saturating_sub(1).min(sorted.len() - 1)])
+    }
+}
+
 /// 升级统计信息
 #[derive(Debug, Clone, Default)]
 pub struct UpgradeStats {
@@ -190,6 +350,8 @@ pub struct UpgradeStats {
     pub protocol_upgrades: HashMap<(ProtocolType, ProtocolType), u64>,
     /// 各升级方法使用次数
     pub method_usage: HashMap<UpgradeMethod, u64>,
+    /// 各升级路径的延迟分布
+    pub path_latencies: HashMap<(ProtocolType, ProtocolType), LatencyHistogram>,
 }
 
 impl UpgradeStats {
@@ -216,6 +378,7 @@ impl UpgradeStats {
         // 记录协议升级统计
         let upgrade_pair = (from, result.target_protocol);
         *self.protocol_upgrades.entry(upgrade_pair).or_insert(0) += 1;
+        self.path_latencies.entry(upgrade_pair).or_default().record(result.duration);
         
         // 记录方法使用统计
         *self.method_usage.entry(result.method.clone()).or_insert(0) += 1;
@@ -238,6 +401,22 @@ impl UpgradeStats {
             .map(|(&upgrade_pair, _)| upgrade_pair)
     }
     
+    /// 获取某条升级路径的百分位延迟，`p` 取值 0.0 - 100.0
+    pub fn percentile(&self, from: ProtocolType, to: ProtocolType, p: f64) -> Option<Duration> {
+        self.path_latencies.get(&(from, to))?.percentile(p)
+    }
+    
+    /// 按p95延迟降序返回最慢的 `n` 条升级路径
+    pub fn top_slowest_paths(&self, n: usize) -> Vec<((ProtocolType, ProtocolType), Duration)> {
+        let mut paths: Vec<_> = self.path_latencies
+            .iter()
+            .filter_map(|(&path, histogram)| histogram.percentile(95.0).map(|p95| (path, p95)))
+            .collect();
+        paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        paths.truncate(n);
+        paths
+    }
+    
     /// 重置统计信息
     pub fn reset(&mut self) {
         *self = Self::new();
@@ -291,37 +470,15 @@ impl UpgradeManager {
         }
         
         // 查找合适的升级器
-        let upgrader = self.upgraders
-            .iter()
-            .find(|u| u.can_upgrade(from, to))
-            .ok_or_else(|| DetectorError::upgrade_failed(
-                format!("{:?}", from),
-                format!("{:?}", to),
-                "No upgrader found"
-            ))?;
+        let index = self.upgrader_index(from, to)?;
         
         let start = Instant::now();
         let mut last_error = None;
         
         // 重试逻辑
         for attempt in 0..=self.config.max_retries {
-            match upgrader.upgrade(from, to, data) {
-                Ok(result) => {
-                    let final_result = UpgradeResult {
-                        duration: start.elapsed(),
-                        ..result
-                    };
-                    
-                    // 记录统计
-                    self.stats.record_upgrade(&final_result, from);
-                    
-                    // 缓存结果
-                    if self.config.enable_cache && final_result.success {
-                        self.cache_result(from, to, final_result.clone());
-                    }
-                    
-                    return Ok(final_result);
-                }
+            match self.upgraders[index].upgrade(from, to, data) {
+                Ok(result) => return Ok(self.finish_upgrade(from, to, result, start)),
                 Err(e) => {
                     last_error = Some(e);
                     if attempt < self.config.max_retries {
@@ -332,6 +489,84 @@ impl UpgradeManager {
         }
         
         // 所有重试都失败了
+        Ok(self.fail_upgrade(from, to, start, last_error))
+    }
+    
+    /// 异步执行协议升级
+    ///
+    /// 与 `upgrade` 的缓存、重试和统计行为一致，但重试间隔使用 `tokio::time::sleep`，不会阻塞执行器；
+    /// 升级器通过 [`ProtocolUpgrader::as_async`] 提供异步实现时等待该实现。
+    #[cfg(feature = "runtime-tokio")]
+    pub async fn upgrade_async(&mut self, from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<UpgradeResult> {
+        if self.config.enable_cache {
+            if let Some(cached_result) = self.get_cached_result(from, to) {
+                return Ok(cached_result);
+            }
+        }
+        
+        let index = self.upgrader_index(from, to)?;
+        
+        let start = Instant::now();
+        let mut last_error = None;
+        
+        for attempt in 0..=self.config.max_retries {
+            let upgrader = &self.upgraders[index];
+            let outcome = match upgrader.as_async() {
+                Some(async_upgrader) => async_upgrader.upgrade_async(from, to, data).await,
+                None => upgrader.upgrade(from, to, data),
+            };
+            match outcome {
+                Ok(result) => return Ok(self.finish_upgrade(from, to, result, start)),
+                Err(e) => {
+                    last_error = Some(e);
+                    if attempt < self.config.max_retries {
+                        tokio::time::sleep(self.config.retry_interval).await;
+                    }
+                }
+            }
+        }
+        
+        Ok(self.fail_upgrade(from, to, start, last_error))
+    }
+    
+    /// 查找第一个支持该升级路径的升级器
+    fn upgrader_index(&self, from: ProtocolType, to: ProtocolType) -> Result<usize> {
+        self.upgraders
+            .iter()
+            .position(|u| u.can_upgrade(from, to))
+            .ok_or_else(|| DetectorError::upgrade_failed(
+                format!("{:?}", from),
+                format!("{:?}", to),
+                "No upgrader found"
+            ))
+    }
+    
+    /// 记录成功的升级结果并写入缓存
+    fn finish_upgrade(&mut self, from: ProtocolType, to: ProtocolType, result: UpgradeResult, start: Instant) -> UpgradeResult {
+        let final_result = UpgradeResult {
+            duration: start.elapsed(),
+            ..result
+        };
+        
+        // 记录统计
+        self.stats.record_upgrade(&final_result, from);
+        
+        // 缓存结果
+        if self.config.enable_cache && final_result.success {
+            self.cache_result(from, to, final_result.clone());
+        }
+        
+        final_result
+    }
+    
+    /// 所有重试都失败时生成并记录失败结果
+    fn fail_upgrade(
+        &mut self,
+        from: ProtocolType,
+        to: ProtocolType,
+        start: Instant,
+        last_error: Option<DetectorError>,
+    ) -> UpgradeResult {
         let error_result = UpgradeResult::failure(
             to,
             UpgradeMethod::Direct,
@@ -340,7 +575,7 @@ impl UpgradeManager {
         );
         
         self.stats.record_upgrade(&error_result, from);
-        Ok(error_result)
+        error_result
     }
     
     /// 获取所有支持的升级路径
@@ -351,6 +586,38 @@ impl UpgradeManager {
             .collect()
     }
     
+    /// 规划从 `from` 到 `to` 的升级路径
+    ///
+    /// 按跳数最少搜索已注册升级器支持的路径；源协议与目标相同时返回空路径，不可达时返回 `None`。
+    pub fn plan_upgrade(&self, from: ProtocolType, to: ProtocolType) -> Option<Vec<UpgradePath>> {
+        let paths = self.supported_upgrades();
+        let mut previous: HashMap<ProtocolType, usize> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut route = Vec::new();
+                let mut node = to;
+                while node != from {
+                    let index = previous[&node];
+                    node = paths[index].from;
+                    route.push(paths[index].clone());
+                }
+                route.reverse();
+                return Some(route);
+            }
+            
+            for (index, path) in paths.iter().enumerate() {
+                if path.from == current && path.to != from && !previous.contains_key(&path.to) {
+                    previous.insert(path.to, index);
+                    queue.push_back(path.to);
+                }
+            }
+        }
+        
+        None
+    }
+    
     /// 检查是否支持指定的升级路径
     pub fn can_upgrade(&self, from: ProtocolType, to: ProtocolType) -> bool {
         self.upgraders.iter().any(|u| u.can_upgrade(from, to))
@@ -407,12 +674,11 @@ impl Default for UpgradeManager {
     fn default() -> Self {
         let mut manager = Self::new();
         
-        // 添加默认升级器
+        // 添加默认升级器（按顺序匹配，h2c和HTTP/3迁移优先交给专用升级器）
+        manager.add_upgrader(Box::new(Http2Upgrader::new()));
+        manager.add_upgrader(Box::new(QuicUpgrader::new()));
         manager.add_upgrader(Box::new(HttpUpgrader::new()));
         manager.add_upgrader(Box::new(WebSocketUpgrader::new()));
-        // TODO: 实现 HTTP/2 和 QUIC 升级器
-        // manager.add_upgrader(Box::new(Http2Upgrader::new()));
-        // manager.add_upgrader(Box::new(QuicUpgrader::new()));
         
         manager
     }
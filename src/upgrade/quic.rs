@@ -0,0 +1,307 @@
+//! QUIC协议升级器
+//!
+//! 处理TCP/TLS迁移到QUIC、HTTP/2迁移到HTTP/3的场景，生成客户端的首个Initial包。
+
+use crate::core::protocol::{ProtocolType, UpgradePath, UpgradeMethod};
+use crate::error::{DetectorError, Result};
+use crate::upgrade::{ProtocolUpgrader, UpgradeResult};
+use crate::utils::encoding::encode_hex;
+use std::time::{Duration, Instant};
+
+/// QUIC v1版本号
+const QUIC_VERSION_1: u32 = 0x0000_0001;
+
+/// 连接ID的最大长度（RFC 9000 §17.2）
+const MAX_CONNECTION_ID_LEN: usize = 20;
+
+/// 客户端Initial包所在数据报的最小长度（RFC 9000 §14.1）
+const MIN_INITIAL_DATAGRAM_SIZE: usize = 1200;
+
+/// 2字节变长整数能表示的最大值，Length字段按此编码
+const MAX_TWO_BYTE_VARINT: usize = 0x3fff;
+
+/// CRYPTO帧类型
+const FRAME_CRYPTO: u8 = 0x06;
+
+/// TLS扩展：ALPN
+const EXT_ALPN: u16 = 0x0010;
+
+/// TLS扩展：supported_versions
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// TLS扩展：quic_transport_parameters
+const EXT_QUIC_TRANSPORT_PARAMETERS: u16 = 0x0039;
+
+/// 传输参数：initial_max_stream_data_bidi_local / bidi_remote / uni
+const TP_INITIAL_MAX_STREAM_DATA: [u64; 3] = [0x05, 0x06, 0x07];
+
+/// 传输参数：initial_source_connection_id
+const TP_INITIAL_SOURCE_CONNECTION_ID: u64 = 0x0f;
+
+/// QUIC升级配置
+#[derive(Debug, Clone)]
+pub struct QuicConfig {
+    /// 每个流的初始最大数据量（同时用于双向和单向流）
+    pub initial_max_stream_data: u64,
+    /// ALPN令牌，升级到HTTP/3时必须包含 `h3`
+    pub alpn_protocols: Vec<String>,
+    /// 生成的连接ID长度（不超过20字节）
+    pub connection_id_len: usize,
+    /// Initial包填充后的最小长度（受2字节Length字段限制）
+    pub min_datagram_size: usize,
+}
+
+impl Default for QuicConfig {
+    fn default() -> Self {
+        Self {
+            initial_max_stream_data: 1024 * 1024,
+            alpn_protocols: vec!["h3".to_string()],
+            connection_id_len: 8,
+            min_datagram_size: MIN_INITIAL_DATAGRAM_SIZE,
+        }
+    }
+}
+
+/// QUIC升级器
+///
+/// 生成的Initial包未做包头保护和负载加密，只包含明文CRYPTO帧（ClientHello）和填充，
+/// 用于探测、测试和迁移演练，不能直接发送给真实的QUIC端点。
+#[derive(Debug)]
+pub struct QuicUpgrader {
+    name: &'static str,
+    config: QuicConfig,
+}
+
+impl QuicUpgrader {
+    /// 创建新的QUIC升级器
+    pub fn new() -> Self {
+        Self {
+            name: "QuicUpgrader",
+            config: QuicConfig::default(),
+        }
+    }
+    
+    /// 使用自定义配置创建QUIC升级器
+    pub fn with_config(config: QuicConfig) -> Self {
+        Self {
+            name: "QuicUpgrader",
+            config,
+        }
+    }
+    
+    /// 获取配置的引用
+    pub fn config(&self) -> &QuicConfig {
+        &self.config
+    }
+    
+    /// 获取配置的可变引用
+    pub fn config_mut(&mut self) -> &mut QuicConfig {
+        &mut self.config
+    }
+    
+    /// 生成随机连接ID
+    fn generate_connection_id(&self) -> Vec<u8> {
+        let len = self.config.connection_id_len.min(MAX_CONNECTION_ID_LEN);
+        let mut id = Vec::with_capacity(len);
+        while id.len() < len {
+            id.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+        }
+        id.truncate(len);
+        id
+    }
+    
+    /// 检查ALPN配置：每个令牌1～255字节
+    ///
+    /// 令牌总长度是否放得下由 [`Self::create_initial_packet`] 按2字节Length字段检查。
+    fn check_alpn(&self) -> Result<()> {
+        for protocol in &self.config.alpn_protocols {
+            if protocol.is_empty() || u8::try_from(protocol.len()).is_err() {
+                return Err(DetectorError::config_error(format!(
+                    "ALPN protocol must be 1-255 bytes, got {} bytes", protocol.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    /// 构造携带ALPN和传输参数的最小ClientHello
+    ///
+    /// ALPN令牌需已经过 [`Self::check_alpn`] 检查。
+    fn create_client_hello(&self, source_cid: &[u8]) -> Result<Vec<u8>> {
+        let mut alpn = Vec::new();
+        for protocol in &self.config.alpn_protocols {
+            alpn.push(protocol.len() as u8);
+            alpn.extend_from_slice(protocol.as_bytes());
+        }
+        let alpn_len = u16::try_from(alpn.len())
+            .map_err(|_| DetectorError::config_error("ALPN protocol list exceeds 65535 bytes"))?;
+        let mut alpn_ext = alpn_len.to_be_bytes().to_vec();
+        alpn_ext.extend_from_slice(&alpn);
+        
+        let mut params = Vec::new();
+        for id in TP_INITIAL_MAX_STREAM_DATA {
+            let mut value = Vec::new();
+            put_varint(&mut value, self.config.initial_max_stream_data);
+            put_transport_parameter(&mut params, id, &value);
+        }
+        put_transport_parameter(&mut params, TP_INITIAL_SOURCE_CONNECTION_ID, source_cid);
+        
+        let mut extensions = Vec::new();
+        put_extension(&mut extensions, EXT_SUPPORTED_VERSIONS, &[0x02, 0x03, 0x04]);
+        put_extension(&mut extensions, EXT_ALPN, &alpn_ext);
+        put_extension(&mut extensions, EXT_QUIC_TRANSPORT_PARAMETERS, &params);
+        
+        // legacy_version + random + 空session_id + TLS_AES_128_GCM_SHA256 + 空压缩
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+        body.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+        
+        let mut hello = vec![0x01];
+        hello.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        hello.extend_from_slice(&body);
+        Ok(hello)
+    }
+    
+    /// 构造Initial包：长包头 + 1字节包号 + CRYPTO帧 + PADDING
+    ///
+    /// ClientHello过大、包长超出2字节Length字段时返回配置错误。
+    fn create_initial_packet(&self, dcid: &[u8], scid: &[u8]) -> Result<Vec<u8>> {
+        let hello = self.create_client_hello(scid)?;
+        let mut payload = vec![FRAME_CRYPTO, 0x00];
+        put_varint(&mut payload, hello.len() as u64);
+        payload.extend_from_slice(&hello);
+        
+        // 首字节：长包头 + 固定位 + Initial类型 + 包号长度1
+        let mut packet = vec![0xc0];
+        packet.extend_from_slice(&QUIC_VERSION_1.to_be_bytes());
+        packet.push(dcid.len() as u8);
+        packet.extend_from_slice(dcid);
+        packet.push(scid.len() as u8);
+        packet.extend_from_slice(scid);
+        packet.push(0x00); // 令牌长度
+        
+        // Length字段固定用2字节变长整数编码，便于预先计算填充量
+        let header_len = packet.len() + 2 + 1;
+        let target_len = self.config.min_datagram_size.min(header_len - 1 + MAX_TWO_BYTE_VARINT);
+        let padding = target_len.saturating_sub(header_len + payload.len());
+        payload.resize(payload.len() + padding, 0x00);
+        
+        let length = u16::try_from(1 + payload.len())
+            .ok()
+            .filter(|&length| length as usize <= MAX_TWO_BYTE_VARINT)
+            .ok_or_else(|| DetectorError::config_error(format!(
+                "Initial packet payload of {} bytes does not fit the 2-byte Length field; shorten alpn_protocols",
+                payload.len()
+            )))?;
+        packet.extend_from_slice(&(length | 0x4000).to_be_bytes());
+        packet.push(0x00); // 包号
+        packet.extend_from_slice(&payload);
+        Ok(packet)
+    }
+}
+
+/// 写入QUIC变长整数
+fn put_varint(buf: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0x3f => buf.push(value as u8),
+        0x40..=0x3fff => buf.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3fff_ffff => buf.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => buf.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+/// 写入传输参数（ID和长度均为变长整数）
+fn put_transport_parameter(buf: &mut Vec<u8>, id: u64, value: &[u8]) {
+    put_varint(buf, id);
+    put_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+/// 写入TLS扩展
+fn put_extension(buf: &mut Vec<u8>, ext_type: u16, data: &[u8]) {
+    buf.extend_from_slice(&ext_type.to_be_bytes());
+    buf.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+impl Default for QuicUpgrader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProtocolUpgrader for QuicUpgrader {
+    fn can_upgrade(&self, from: ProtocolType, to: ProtocolType) -> bool {
+        matches!(
+            (from, to),
+            (ProtocolType::TCP | ProtocolType::TLS, ProtocolType::QUIC) | (ProtocolType::HTTP2, ProtocolType::HTTP3)
+        )
+    }
+    
+    fn upgrade(&self, from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<UpgradeResult> {
+        let start = Instant::now();
+        
+        // 检查前置条件
+        self.check_prerequisites(from, to, data)?;
+        self.check_alpn()?;
+        
+        let method = if to == ProtocolType::HTTP3 { UpgradeMethod::Negotiation } else { UpgradeMethod::Direct };
+        if to == ProtocolType::HTTP3 && !self.config.alpn_protocols.iter().any(|p| p == "h3") {
+            return Ok(UpgradeResult::failure(
+                to,
+                method,
+                start.elapsed(),
+                "ALPN protocols must include 'h3' to upgrade to HTTP/3".to_string(),
+            ));
+        }
+        
+        let dcid = self.generate_connection_id();
+        let scid = self.generate_connection_id();
+        let packet = self.create_initial_packet(&dcid, &scid)?;
+        
+        let mut result = UpgradeResult::success(to, packet, method, start.elapsed());
+        result = result.with_metadata("original_protocol".to_string(), format!("{:?}", from));
+        result = result.with_metadata("quic_version".to_string(), "1".to_string());
+        result = result.with_metadata("dcid".to_string(), encode_hex(&dcid));
+        result = result.with_metadata("scid".to_string(), encode_hex(&scid));
+        result = result.with_metadata("alpn".to_string(), self.config.alpn_protocols.join(","));
+        result = result.with_metadata(
+            "initial_max_stream_data".to_string(),
+            self.config.initial_max_stream_data.to_string(),
+        );
+        
+        Ok(result)
+    }
+    
+    fn supported_upgrades(&self) -> Vec<UpgradePath> {
+        vec![
+            UpgradePath::new(ProtocolType::TCP, ProtocolType::QUIC, UpgradeMethod::Direct),
+            UpgradePath::new(ProtocolType::TLS, ProtocolType::QUIC, UpgradeMethod::Direct),
+            UpgradePath {
+                from: ProtocolType::HTTP2,
+                to: ProtocolType::HTTP3,
+                method: UpgradeMethod::Negotiation,
+                required_headers: vec!["Alt-Svc".to_string()],
+                optional_headers: vec![],
+            },
+        ]
+    }
+    
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    
+    fn estimate_upgrade_time(&self, from: ProtocolType, to: ProtocolType) -> Duration {
+        // 迁移到QUIC需要新建UDP连接并重新完成QUIC + TLS 1.3握手，不能复用原连接
+        match (from, to) {
+            (ProtocolType::TCP, ProtocolType::QUIC) => Duration::from_millis(200),
+            (ProtocolType::TLS, ProtocolType::QUIC) => Duration::from_millis(200),
+            // 另需建立HTTP/3控制流并交换SETTINGS
+            (ProtocolType::HTTP2, ProtocolType::HTTP3) => Duration::from_millis(250),
+            _ => Duration::from_millis(100),
+        }
+    }
+}
@@ -5,9 +5,18 @@
 use crate::core::protocol::{ProtocolType, UpgradePath, UpgradeMethod};
 use crate::error::{DetectorError, Result};
 use crate::upgrade::{ProtocolUpgrader, UpgradeResult};
+use crate::utils::digest::sha1;
+use crate::utils::encoding::encode_base64;
+use crate::utils::http;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
 
+/// 计算 `Sec-WebSocket-Accept` 时拼接的GUID（RFC 6455 §1.3）
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// `Sec-WebSocket-Key` 解码后的长度
+const WEBSOCKET_KEY_LEN: usize = 16;
+
 /// WebSocket升级配置
 #[derive(Debug, Clone)]
 pub struct WebSocketConfig {
@@ -75,42 +84,20 @@ impl WebSocketUpgrader {
         &self.config
     }
     
-    /// 生成WebSocket密钥
+    /// 生成WebSocket密钥：16字节随机数的Base64编码（24个字符）
     fn generate_websocket_key(&self) -> String {
         if self.config.use_random_key {
-            // 生成16字节随机数据并进行Base64编码
-            use std::collections::hash_map::DefaultHasher;
-            use std::hash::{Hash, Hasher};
-            
-            let mut hasher = DefaultHasher::new();
-            std::time::SystemTime::now().hash(&mut hasher);
-            let random_data = hasher.finish();
-            
-            // 简化的Base64编码（实际应用中应使用proper Base64库）
-            format!("{}==", random_data)
+            encode_base64(&uuid::Uuid::new_v4().as_bytes()[..WEBSOCKET_KEY_LEN])
         } else {
-            // 使用固定密钥用于测试
+            // 使用RFC 6455示例中的固定密钥用于测试
             "dGhlIHNhbXBsZSBub25jZQ==".to_string()
         }
     }
     
-    /// 计算WebSocket接受密钥
+    /// 计算WebSocket接受密钥：Base64(SHA-1(key + GUID))
     fn calculate_accept_key(&self, key: &str) -> String {
-        // WebSocket协议规定的魔法字符串
-        let magic_string = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
-        let combined = format!("{}{}", key, magic_string);
-        
-        // 在实际实现中，这里应该使用SHA-1哈希和Base64编码
-        // 这里使用简化的实现
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        combined.hash(&mut hasher);
-        let hash = hasher.finish();
-        
-        // 简化的Base64编码模拟
-        format!("{}==", hash)
+        let combined = format!("{}{}", key.trim(), WEBSOCKET_GUID);
+        encode_base64(&sha1(combined.as_bytes()))
     }
     
     /// 创建WebSocket升级请求
@@ -173,9 +160,14 @@ impl WebSocketUpgrader {
         response.into_bytes()
     }
     
+    /// 截取HTTP头部，头部不完整时使用全部数据
+    fn header_section<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        http::header_block(data).unwrap_or(data)
+    }
+    
     /// 解析HTTP请求中的WebSocket密钥
     fn extract_websocket_key(&self, data: &[u8]) -> Option<String> {
-        let data_str = String::from_utf8_lossy(data);
+        let data_str = String::from_utf8_lossy(self.header_section(data));
         
         for line in data_str.lines() {
             if line.to_lowercase().starts_with("sec-websocket-key:") {
@@ -190,7 +182,7 @@ impl WebSocketUpgrader {
     
     /// 验证WebSocket升级请求
     fn validate_websocket_request(&self, data: &[u8]) -> Result<()> {
-        let data_str = String::from_utf8_lossy(data).to_lowercase();
+        let data_str = String::from_utf8_lossy(self.header_section(data)).to_lowercase();
         
         // 检查必需的头部
         if !data_str.contains("upgrade: websocket") {
@@ -230,7 +222,7 @@ impl WebSocketUpgrader {
     
     /// 检查是否是WebSocket升级请求
     fn is_websocket_upgrade_request(&self, data: &[u8]) -> bool {
-        let data_str = String::from_utf8_lossy(data).to_lowercase();
+        let data_str = String::from_utf8_lossy(self.header_section(data)).to_lowercase();
         
         data_str.contains("upgrade: websocket") &&
         data_str.contains("connection: upgrade") &&
@@ -239,7 +231,7 @@ impl WebSocketUpgrader {
     
     /// 检查是否是WebSocket升级响应
     fn is_websocket_upgrade_response(&self, data: &[u8]) -> bool {
-        let data_str = String::from_utf8_lossy(data).to_lowercase();
+        let data_str = String::from_utf8_lossy(self.header_section(data)).to_lowercase();
         
         data_str.contains("101 switching protocols") &&
         data_str.contains("upgrade: websocket") &&
@@ -351,6 +343,22 @@ impl Default for WebSocketUpgrader {
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_websocket_keys_follow_rfc6455() {
+        let upgrader = WebSocketUpgrader::new();
+        
+        // RFC 6455 §1.3 示例
+        assert_eq!(
+            upgrader.calculate_accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        
+        let key = upgrader.generate_websocket_key();
+        assert_eq!(key.len(), 24);
+        assert_eq!(crate::utils::encoding::decode_base64(&key).unwrap().len(), WEBSOCKET_KEY_LEN);
+        assert_ne!(key, upgrader.generate_websocket_key());
+    }
+    
     #[test]
     fn test_websocket_config_default() {
         let config = WebSocketConfig::default();
@@ -449,7 +457,7 @@ impl ProtocolUpgrader for WebSocketUpgrader {
             });
             
             // 从HTTP请求中提取Host和路径
-            let data_str = String::from_utf8_lossy(data);
+            let data_str = String::from_utf8_lossy(self.header_section(data));
             let mut host: Option<&str> = None;
             let mut path: Option<&str> = None;
             
@@ -0,0 +1,100 @@
+//! 调试工具模块
+//!
+//! 提供协议探测结果的对比分析，帮助定位相似数据分类不同的原因。
+
+use crate::core::detector::{ConfidenceMap, DefaultProtocolDetector};
+use crate::core::protocol::{ProtocolInfo, ProtocolType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 单个探测器在两份数据上的结果差异
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeDifference {
+    /// 探测器名称
+    pub probe_name: String,
+    /// 数据A上的结果
+    pub a: Vec<ProtocolInfo>,
+    /// 数据B上的结果
+    pub b: Vec<ProtocolInfo>,
+}
+
+/// 两份数据的探测差异报告
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DetectionDiff {
+    /// 数据A的置信度图
+    pub a: ConfidenceMap,
+    /// 数据B的置信度图
+    pub b: ConfidenceMap,
+    /// 结果不同的探测器
+    pub probe_differences: Vec<ProbeDifference>,
+    /// 各协议的置信度变化（B - A），仅包含有变化的协议
+    pub confidence_deltas: BTreeMap<ProtocolType, f32>,
+}
+
+impl DetectionDiff {
+    /// 两份数据的探测结果是否完全一致
+    pub fn is_identical(&self) -> bool {
+        self.probe_differences.is_empty() && self.confidence_deltas.is_empty()
+    }
+    
+    /// 获取指定探测器的差异
+    pub fn probe(&self, probe_name: &str) -> Option<&ProbeDifference> {
+        self.probe_differences.iter().find(|diff| diff.probe_name == probe_name)
+    }
+}
+
+/// 对比两份数据的探测结果
+///
+/// 分别计算两份数据的置信度图，报告结果不同（协议、版本或置信度）的探测器，
+/// 以及每个协议的置信度变化。
+pub fn diff_detection(detector: &DefaultProtocolDetector, a: &[u8], b: &[u8]) -> DetectionDiff {
+    let map_a = detector.confidence_map(a);
+    let map_b = detector.confidence_map(b);
+    
+    let probe_names: BTreeSet<&String> = map_a.probes.keys().chain(map_b.probes.keys()).collect();
+    let probe_differences = probe_names
+        .into_iter()
+        .filter_map(|name| {
+            let results_a = map_a.probes.get(name).cloned().unwrap_or_default();
+            let results_b = map_b.probes.get(name).cloned().unwrap_or_default();
+            if fired_same(&results_a, &results_b) {
+                None
+            } else {
+                Some(ProbeDifference {
+                    probe_name: name.clone(),
+                    a: results_a,
+                    b: results_b,
+                })
+            }
+        })
+        .collect();
+    
+    let confidences_a = map_a.protocol_confidences();
+    let confidences_b = map_b.protocol_confidences();
+    let protocols: BTreeSet<ProtocolType> = confidences_a.keys().chain(confidences_b.keys()).copied().collect();
+    let confidence_deltas = protocols
+        .into_iter()
+        .filter_map(|protocol| {
+            let delta = confidences_b.get(&protocol).copied().unwrap_or(0.0)
+                - confidences_a.get(&protocol).copied().unwrap_or(0.0);
+            (delta.abs() > f32::EPSILON).then_some((protocol, delta))
+        })
+        .collect();
+    
+    DetectionDiff {
+        a: map_a,
+        b: map_b,
+        probe_differences,
+        confidence_deltas,
+    }
+}
+
+/// 比较两组结果的协议、版本和置信度是否一致
+fn fired_same(a: &[ProtocolInfo], b: &[ProtocolInfo]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(x, y)| {
+            x.protocol_type == y.protocol_type
+                && x.version == y.version
+                && (x.confidence - y.confidence).abs() <= f32::EPSILON
+        })
+}
@@ -0,0 +1,156 @@
+//! 摘要算法
+//!
+//! 协议握手和指纹所需的摘要计算（如WebSocket的 `Sec-WebSocket-Accept`、JA3哈希），
+//! 不用于任何安全相关的校验。
+
+/// SHA-1摘要长度
+pub const SHA1_DIGEST_LEN: usize = 20;
+
+/// SHA-1分组长度
+const SHA1_BLOCK_LEN: usize = 64;
+
+/// MD5摘要长度
+pub const MD5_DIGEST_LEN: usize = 16;
+
+/// SHA-1初始哈希值
+const SHA1_INIT: [u32; 5] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0];
+
+/// MD5初始哈希值
+const MD5_INIT: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+
+/// MD5每步的加法常数（`floor(|sin(i + 1)| * 2^32)`）
+const MD5_K: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+/// MD5每轮的循环左移位数
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// 按消息长度填充到64字节分组（长度字段的字节序由调用方决定）
+fn pad_message(data: &[u8], length_bytes: [u8; 8]) -> Vec<u8> {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % SHA1_BLOCK_LEN != SHA1_BLOCK_LEN - 8 {
+        message.push(0x00);
+    }
+    message.extend_from_slice(&length_bytes);
+    message
+}
+
+/// 计算SHA-1摘要（FIPS 180-4）
+pub fn sha1(data: &[u8]) -> [u8; SHA1_DIGEST_LEN] {
+    let mut state = SHA1_INIT;
+    
+    // 填充：0x80 + 0 + 64位大端消息比特长度
+    let message = pad_message(data, (data.len() as u64).wrapping_mul(8).to_be_bytes());
+    
+    for block in message.chunks_exact(SHA1_BLOCK_LEN) {
+        sha1_compress(&mut state, block);
+    }
+    
+    let mut digest = [0u8; SHA1_DIGEST_LEN];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// 处理一个64字节分组
+fn sha1_compress(state: &mut [u32; 5], block: &[u8]) {
+    let mut w = [0u32; 80];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (i, &word) in w.iter().enumerate() {
+        let (f, k) = match i {
+            0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+            20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+            40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+            _ => (b ^ c ^ d, 0xca62_c1d6),
+        };
+        let temp = a.rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+    
+    for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+        *value = value.wrapping_add(add);
+    }
+}
+
+/// 计算MD5摘要（RFC 1321）
+pub fn md5(data: &[u8]) -> [u8; MD5_DIGEST_LEN] {
+    let mut state = MD5_INIT;
+    
+    // 填充：与SHA-1相同，但比特长度为小端
+    let message = pad_message(data, (data.len() as u64).wrapping_mul(8).to_le_bytes());
+    
+    for block in message.chunks_exact(SHA1_BLOCK_LEN) {
+        md5_compress(&mut state, block);
+    }
+    
+    let mut digest = [0u8; MD5_DIGEST_LEN];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// 处理一个64字节分组
+fn md5_compress(state: &mut [u32; 4], block: &[u8]) {
+    let mut m = [0u32; 16];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    
+    let [mut a, mut b, mut c, mut d] = *state;
+    for (i, &k) in MD5_K.iter().enumerate() {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let shift = MD5_SHIFTS[(i / 16) * 4 + i % 4];
+        let rotated = a
+            .wrapping_add(f)
+            .wrapping_add(k)
+            .wrapping_add(m[g])
+            .rotate_left(shift);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+    
+    for (value, add) in state.iter_mut().zip([a, b, c, d]) {
+        *value = value.wrapping_add(add);
+    }
+}
@@ -0,0 +1,108 @@
+//! 文本编码解析
+//!
+//! 解析十六进制和Base64文本，便于直接探测从抓包工具中复制的报文；
+//! 同时提供对应的编码函数。
+
+use crate::error::{DetectorError, Result};
+
+/// 解析十六进制字符串
+///
+/// 忽略空白和 `:` 分隔符，允许 `0x` 前缀（如Wireshark复制的 `16 03 01` 或 `16:03:01`）。
+pub fn decode_hex(input: &str) -> Result<Vec<u8>> {
+    let trimmed = input.trim();
+    let trimmed = trimmed.strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .unwrap_or(trimmed);
+    
+    let digits: Vec<u8> = trimmed.bytes()
+        .filter(|b| !b.is_ascii_whitespace() && *b != b':')
+        .collect();
+    
+    if !digits.len().is_multiple_of(2) {
+        return Err(DetectorError::config_error(
+            format!("十六进制字符串长度为奇数: {} 个字符", digits.len())
+        ));
+    }
+    
+    digits.chunks_exact(2)
+        .map(|pair| Ok(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+        .collect()
+}
+
+/// 编码为小写十六进制字符串（无分隔符）
+pub fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 编码为标准Base64字符串（带 `=` 填充）
+pub fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let buffer = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(buffer >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// 解析Base64字符串
+///
+/// 支持标准和URL安全字母表，忽略空白，填充 `=` 可省略。
+pub fn decode_base64(input: &str) -> Result<Vec<u8>> {
+    let symbols: Vec<u8> = input.bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    
+    let data_len = symbols.iter().rposition(|&b| b != b'=').map_or(0, |i| i + 1);
+    let (data, padding) = symbols.split_at(data_len);
+    if padding.len() > 2 || data_len % 4 == 1 {
+        return Err(DetectorError::config_error("Base64字符串长度或填充无效"));
+    }
+    
+    let mut output = Vec::with_capacity(data_len * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &symbol in data {
+        buffer = buffer << 6 | base64_value(symbol)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    
+    Ok(output)
+}
+
+fn hex_value(digit: u8) -> Result<u8> {
+    match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => Err(DetectorError::config_error(
+            format!("无效的十六进制字符: {:?}", digit as char)
+        )),
+    }
+}
+
+fn base64_value(symbol: u8) -> Result<u8> {
+    match symbol {
+        b'A'..=b'Z' => Ok(symbol - b'A'),
+        b'a'..=b'z' => Ok(symbol - b'a' + 26),
+        b'0'..=b'9' => Ok(symbol - b'0' + 52),
+        b'+' | b'-' => Ok(62),
+        b'/' | b'_' => Ok(63),
+        _ => Err(DetectorError::config_error(
+            format!("无效的Base64字符: {:?}", symbol as char)
+        )),
+    }
+}
@@ -0,0 +1,234 @@
+//! 探测结果导出模块
+//!
+//! 将探测结果以NDJSON或CSV格式流式写出，便于导入数据分析工具。
+//! 时间戳和耗时统一以毫秒表示。
+
+use crate::core::detector::{DetectionMethod, DetectionResult};
+use crate::core::protocol::ProtocolType;
+use crate::error::{DetectorError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 导出记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    /// 记录时间（Unix毫秒时间戳）
+    pub timestamp_ms: u64,
+    /// 流标识（如五元组）
+    pub flow_key: Option<String>,
+    /// 协议类型
+    pub protocol: ProtocolType,
+    /// 协议版本
+    pub version: Option<String>,
+    /// 置信度
+    pub confidence: f32,
+    /// 探测耗时（毫秒）
+    pub detection_time_ms: f64,
+    /// 探测方法
+    pub detection_method: DetectionMethod,
+    /// 探测器名称
+    pub detector_name: String,
+    /// 协议元数据
+    pub metadata: HashMap<String, String>,
+}
+
+impl DetectionRecord {
+    /// 从探测结果创建记录，时间戳取当前时间
+    pub fn new(result: &DetectionResult, flow_key: Option<&str>) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        
+        Self {
+            timestamp_ms,
+            flow_key: flow_key.map(str::to_string),
+            protocol: result.protocol_info.protocol_type,
+            version: result.protocol_info.version.clone(),
+            confidence: result.protocol_info.confidence,
+            detection_time_ms: duration_to_millis(result.detection_time),
+            detection_method: result.detection_method,
+            detector_name: result.detector_name.clone(),
+            metadata: (*result.protocol_info.metadata).clone(),
+        }
+    }
+    
+    /// 设置时间戳（Unix毫秒）
+    pub fn with_timestamp_ms(mut self, timestamp_ms: u64) -> Self {
+        self.timestamp_ms = timestamp_ms;
+        self
+    }
+}
+
+/// 将时长转换为毫秒（保留小数）
+fn duration_to_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// NDJSON写出器：每条探测结果一行JSON
+#[derive(Debug)]
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    /// 创建NDJSON写出器
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+    
+    /// 写出一条探测结果
+    pub fn write_result(&mut self, result: &DetectionResult, flow_key: Option<&str>) -> Result<()> {
+        self.write_record(&DetectionRecord::new(result, flow_key))
+    }
+    
+    /// 写出一条记录
+    pub fn write_record(&mut self, record: &DetectionRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)
+            .map_err(|e| DetectorError::internal_error(format!("序列化探测结果失败: {}", e)))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+    
+    /// 刷新底层输出
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+    
+    /// 取回底层输出
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// CSV列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+    /// 记录时间（Unix毫秒）
+    Timestamp,
+    /// 流标识
+    FlowKey,
+    /// 协议类型
+    Protocol,
+    /// 协议版本
+    Version,
+    /// 置信度
+    Confidence,
+    /// 探测耗时（毫秒）
+    DetectionTimeMs,
+    /// 探测方法
+    DetectionMethod,
+    /// 探测器名称
+    DetectorName,
+}
+
+impl CsvColumn {
+    /// 默认列（全部列）
+    pub fn all() -> Vec<CsvColumn> {
+        vec![
+            Self::Timestamp,
+            Self::FlowKey,
+            Self::Protocol,
+            Self::Version,
+            Self::Confidence,
+            Self::DetectionTimeMs,
+            Self::DetectionMethod,
+            Self::DetectorName,
+        ]
+    }
+    
+    /// 列名
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "timestamp_ms",
+            Self::FlowKey => "flow_key",
+            Self::Protocol => "protocol",
+            Self::Version => "version",
+            Self::Confidence => "confidence",
+            Self::DetectionTimeMs => "detection_time_ms",
+            Self::DetectionMethod => "detection_method",
+            Self::DetectorName => "detector_name",
+        }
+    }
+    
+    fn value(&self, record: &DetectionRecord) -> String {
+        match self {
+            Self::Timestamp => record.timestamp_ms.to_string(),
+            Self::FlowKey => record.flow_key.clone().unwrap_or_default(),
+            Self::Protocol => format!("{:?}", record.protocol),
+            Self::Version => record.version.clone().unwrap_or_default(),
+            Self::Confidence => record.confidence.to_string(),
+            Self::DetectionTimeMs => record.detection_time_ms.to_string(),
+            Self::DetectionMethod => format!("{:?}", record.detection_method),
+            Self::DetectorName => record.detector_name.clone(),
+        }
+    }
+}
+
+/// CSV写出器，首条记录前自动写出表头
+#[derive(Debug)]
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    columns: Vec<CsvColumn>,
+    header_written: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// 创建包含全部列的CSV写出器
+    pub fn new(writer: W) -> Self {
+        Self::with_columns(writer, CsvColumn::all())
+    }
+    
+    /// 创建指定列的CSV写出器
+    pub fn with_columns(writer: W, columns: Vec<CsvColumn>) -> Self {
+        Self {
+            writer,
+            columns,
+            header_written: false,
+        }
+    }
+    
+    /// 写出一条探测结果
+    pub fn write_result(&mut self, result: &DetectionResult, flow_key: Option<&str>) -> Result<()> {
+        self.write_record(&DetectionRecord::new(result, flow_key))
+    }
+    
+    /// 写出一条记录
+    pub fn write_record(&mut self, record: &DetectionRecord) -> Result<()> {
+        if !self.header_written {
+            let header: Vec<&str> = self.columns.iter().map(CsvColumn::header).collect();
+            writeln!(self.writer, "{}", header.join(","))?;
+            self.header_written = true;
+        }
+        
+        let row: Vec<String> = self.columns
+            .iter()
+            .map(|column| escape_csv(&column.value(record)))
+            .collect();
+        writeln!(self.writer, "{}", row.join(","))?;
+        Ok(())
+    }
+    
+    /// 刷新底层输出
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+    
+    /// 取回底层输出
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// 按RFC 4180转义CSV字段
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
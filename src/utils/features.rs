@@ -0,0 +1,97 @@
+//! 字节特征计算
+//!
+//! 提供与流分析器相同的熵、可打印比例和字节直方图计算，可直接用于任意缓冲区。
+//! 启用 `simd-accel` 特性时使用便于编译器向量化的分块实现。
+
+/// 统计字节直方图
+pub fn byte_histogram(data: &[u8]) -> [usize; 256] {
+    #[cfg(feature = "simd-accel")]
+    {
+        // 四张表交替计数，消除相邻相同字节造成的写依赖
+        let mut tables = [[0usize; 256]; 4];
+        let mut chunks = data.chunks_exact(4);
+        for chunk in &mut chunks {
+            tables[0][chunk[0] as usize] += 1;
+            tables[1][chunk[1] as usize] += 1;
+            tables[2][chunk[2] as usize] += 1;
+            tables[3][chunk[3] as usize] += 1;
+        }
+        for &byte in chunks.remainder() {
+            tables[0][byte as usize] += 1;
+        }
+        
+        let mut histogram = [0usize; 256];
+        for (i, count) in histogram.iter_mut().enumerate() {
+            *count = tables[0][i] + tables[1][i] + tables[2][i] + tables[3][i];
+        }
+        histogram
+    }
+    
+    #[cfg(not(feature = "simd-accel"))]
+    {
+        let mut histogram = [0usize; 256];
+        for &byte in data {
+            histogram[byte as usize] += 1;
+        }
+        histogram
+    }
+}
+
+/// 根据字节直方图计算香农熵（比特/字节，范围0~8）
+pub fn entropy_from_histogram(histogram: &[usize; 256]) -> f64 {
+    let total: usize = histogram.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    
+    let total = total as f64;
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / total;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// 计算香农熵（比特/字节，范围0~8）
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    entropy_from_histogram(&byte_histogram(data))
+}
+
+/// 计算可打印ASCII字节（含 `\r`、`\n`、`\t`）的比例
+pub fn printable_ratio(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    
+    #[cfg(feature = "simd-accel")]
+    let printable = {
+        let mut chunks = data.chunks_exact(16);
+        let mut count = 0usize;
+        for chunk in &mut chunks {
+            // 无分支累加，便于编译器生成向量比较
+            count += chunk.iter().map(|&b| is_printable(b) as usize).sum::<usize>();
+        }
+        count + chunks.remainder().iter().filter(|&&b| is_printable(b)).count()
+    };
+    
+    #[cfg(not(feature = "simd-accel"))]
+    let printable = data.iter().filter(|&&b| is_printable(b)).count();
+    
+    printable as f64 / data.len() as f64
+}
+
+/// 基于熵估算的压缩率（0~1，越小越容易压缩）
+pub fn estimated_compression_ratio(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 1.0;
+    }
+    shannon_entropy(data) / 8.0
+}
+
+#[inline]
+fn is_printable(byte: u8) -> bool {
+    matches!(byte, 0x20..=0x7e | b'\r' | b'\n' | b'\t')
+}
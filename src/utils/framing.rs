@@ -0,0 +1,242 @@
+//! 报文分帧辅助函数
+//!
+//! 根据协议自身的长度字段判断缓冲区中的首个报文是否完整。
+
+use crate::core::protocol::ProtocolType;
+use crate::utils::http2;
+
+/// TLS记录头长度
+const TLS_RECORD_HEADER_LEN: usize = 5;
+
+/// MySQL报文头长度（3字节负载长度 + 1字节序号）
+const MYSQL_HEADER_LEN: usize = 4;
+
+/// Memcached二进制协议报文头长度
+const MEMCACHED_BINARY_HEADER_LEN: usize = 24;
+
+/// 分帧协议允许的最大报文长度，超过时视为不是长度字段
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// 首个报文的分帧状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFraming {
+    /// 缓冲区已包含完整的首个报文
+    Complete,
+    /// 首个报文被截断，值为完整报文所需的总字节数
+    Truncated(usize),
+    /// 协议没有长度字段，或数据不符合该协议的分帧格式
+    Unframed,
+}
+
+impl MessageFraming {
+    /// 按协议的长度字段判断首个报文的分帧状态
+    ///
+    /// 报文头本身不完整时，`Truncated` 给出已知的最小报文头长度。
+    pub fn of(protocol: ProtocolType, data: &[u8]) -> Self {
+        let required = match protocol {
+            ProtocolType::TLS => tls_record_len(data),
+            ProtocolType::HTTP2 | ProtocolType::GRPC => http2_frame_len(data),
+            ProtocolType::QUIC | ProtocolType::HTTP3 => quic_long_header_len(data),
+            ProtocolType::WebSocket => websocket_frame_len(data),
+            ProtocolType::Thrift => thrift_frame_len(data),
+            ProtocolType::MySQL => mysql_packet_len(data),
+            ProtocolType::PostgreSQL => postgres_message_len(data),
+            ProtocolType::MQTT => mqtt_packet_len(data),
+            ProtocolType::Memcached => memcached_binary_len(data),
+            _ => None,
+        };
+        
+        match required {
+            Some(len) if len > data.len() => Self::Truncated(len),
+            Some(_) => Self::Complete,
+            None => Self::Unframed,
+        }
+    }
+    
+    /// 是否已包含完整报文
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Complete)
+    }
+    
+    /// 已看到数据占完整首个报文的比例，无法分帧或已完整时为1
+    pub fn completeness(&self, seen: usize) -> f32 {
+        match self {
+            Self::Truncated(needed) if *needed > 0 => (seen as f32 / *needed as f32).min(1.0),
+            _ => 1.0,
+        }
+    }
+    
+    /// 被截断时返回完整报文所需的总字节数
+    pub fn needed(&self) -> Option<usize> {
+        match self {
+            Self::Truncated(needed) => Some(*needed),
+            _ => None,
+        }
+    }
+}
+
+/// 读取QUIC变长整数，返回 (值, 占用字节数)
+pub fn read_quic_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = 1usize << (first >> 6);
+    let bytes = data.get(..len)?;
+    
+    let mut value = (first & 0x3f) as u64;
+    for &byte in &bytes[1..] {
+        value = (value << 8) | byte as u64;
+    }
+    Some((value, len))
+}
+
+/// 首个报文长度字段不超过上限时返回报文总长度
+fn bounded(header_len: usize, payload_len: usize) -> Option<usize> {
+    (payload_len <= MAX_FRAME_LEN).then_some(header_len + payload_len)
+}
+
+/// TLS记录：内容类型(1) + 版本(2) + 长度(2)
+fn tls_record_len(data: &[u8]) -> Option<usize> {
+    match data {
+        [0x14..=0x17, 0x03, _, len_hi, len_lo, ..] => {
+            bounded(TLS_RECORD_HEADER_LEN, u16::from_be_bytes([*len_hi, *len_lo]) as usize)
+        }
+        [0x14..=0x17] | [0x14..=0x17, 0x03, ..] => Some(TLS_RECORD_HEADER_LEN),
+        _ => None,
+    }
+}
+
+/// HTTP/2首帧（跳过连接前言）
+fn http2_frame_len(data: &[u8]) -> Option<usize> {
+    let preface = http2::CONNECTION_PREFACE;
+    let offset = if data.starts_with(preface) {
+        preface.len()
+    } else if preface.starts_with(data) {
+        return Some(preface.len() + http2::FRAME_HEADER_LEN);
+    } else {
+        0
+    };
+    
+    match http2::parse_frame_header(&data[offset..]) {
+        Some(header) => bounded(offset + http2::FRAME_HEADER_LEN, header.length as usize),
+        None => Some(offset + http2::FRAME_HEADER_LEN),
+    }
+}
+
+/// QUIC长包头（Initial / 0-RTT / Handshake）的 Length 字段
+///
+/// 短包头、版本协商和Retry包没有长度字段。
+fn quic_long_header_len(data: &[u8]) -> Option<usize> {
+    let first = *data.first()?;
+    if first & 0x80 == 0 {
+        return None;
+    }
+    
+    let version = data.get(1..5).map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]));
+    let packet_type = (first & 0x30) >> 4;
+    if version == Some(0) || packet_type == 3 {
+        return None;
+    }
+    
+    // 按字段逐个前进，字段不完整时至少需要再读一个字节
+    let mut pos = 5;
+    let Some(&dcid_len) = data.get(pos) else { return Some(pos + 1) };
+    pos += 1 + dcid_len as usize;
+    let Some(&scid_len) = data.get(pos) else { return Some(pos + 1) };
+    pos += 1 + scid_len as usize;
+    
+    if packet_type == 0 {
+        let Some((token_len, size)) = data.get(pos..).and_then(read_quic_varint) else { return Some(pos + 1) };
+        pos += size + usize::try_from(token_len).ok()?;
+    }
+    
+    let Some((length, size)) = data.get(pos..).and_then(read_quic_varint) else { return Some(pos + 1) };
+    bounded(pos + size, usize::try_from(length).ok()?)
+}
+
+/// WebSocket数据帧或控制帧
+fn websocket_frame_len(data: &[u8]) -> Option<usize> {
+    let first = *data.first()?;
+    let opcode = first & 0x0f;
+    let valid = match opcode {
+        // 数据帧允许RSV1（permessage-deflate）
+        0x0..=0x2 => first & 0x30 == 0,
+        // 控制帧必须设置FIN且不能压缩
+        0x8..=0xa => first & 0x70 == 0 && first & 0x80 != 0,
+        _ => false,
+    };
+    if !valid {
+        return None;
+    }
+    
+    let Some(&second) = data.get(1) else { return Some(2) };
+    let mask_len = if second & 0x80 != 0 { 4 } else { 0 };
+    let (header_len, payload_len) = match second & 0x7f {
+        126 => {
+            let Some(len) = data.get(2..4) else { return Some(4 + mask_len) };
+            (4, u16::from_be_bytes([len[0], len[1]]) as usize)
+        }
+        127 => {
+            let Some(len) = data.get(2..10) else { return Some(10 + mask_len) };
+            (10, usize::try_from(u64::from_be_bytes(len.try_into().ok()?)).ok()?)
+        }
+        len => (2, len as usize),
+    };
+    bounded(header_len + mask_len, payload_len)
+}
+
+/// Thrift分帧传输：4字节大端帧长度
+fn thrift_frame_len(data: &[u8]) -> Option<usize> {
+    let Some(len) = data.get(..4) else { return Some(4) };
+    let frame_len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if frame_len == 0 {
+        return None;
+    }
+    bounded(4, frame_len)
+}
+
+/// MySQL报文：3字节小端负载长度 + 序号
+fn mysql_packet_len(data: &[u8]) -> Option<usize> {
+    let Some(header) = data.get(..MYSQL_HEADER_LEN) else { return Some(MYSQL_HEADER_LEN) };
+    Some(MYSQL_HEADER_LEN + u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize)
+}
+
+/// PostgreSQL报文：启动包以长度开头，其余报文前有1字节类型
+///
+/// 长度字段包含自身的4字节。
+fn postgres_message_len(data: &[u8]) -> Option<usize> {
+    let offset = match data.first()? {
+        0x00 => 0,
+        tag if tag.is_ascii_alphabetic() => 1,
+        _ => return None,
+    };
+    let Some(len) = data.get(offset..offset + 4) else { return Some(offset + 4) };
+    let message_len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    if message_len < 4 {
+        return None;
+    }
+    bounded(offset, message_len)
+}
+
+/// MQTT报文：固定头(1) + 剩余长度（最多4字节的变长整数）
+fn mqtt_packet_len(data: &[u8]) -> Option<usize> {
+    let mut remaining = 0usize;
+    for i in 0..4 {
+        let Some(&byte) = data.get(1 + i) else { return Some(2 + i) };
+        remaining |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some(2 + i + remaining);
+        }
+    }
+    None
+}
+
+/// Memcached二进制协议：24字节报文头 + 报文体
+///
+/// 文本协议没有长度前缀。
+fn memcached_binary_len(data: &[u8]) -> Option<usize> {
+    if !matches!(data.first()?, 0x80 | 0x81) {
+        return None;
+    }
+    let Some(header) = data.get(..MEMCACHED_BINARY_HEADER_LEN) else { return Some(MEMCACHED_BINARY_HEADER_LEN) };
+    let body_len = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    bounded(MEMCACHED_BINARY_HEADER_LEN, body_len)
+}
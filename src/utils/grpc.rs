@@ -0,0 +1,108 @@
+//! gRPC传输变体辅助函数
+//!
+//! 按 `content-type` 区分原生gRPC（`application/grpc`、`application/grpc+proto`，运行在HTTP/2上）、
+//! gRPC-Web（`application/grpc-web`）和gRPC-Web文本模式（`application/grpc-web-text`，消息帧经base64编码），
+//! 并校验消息帧头。
+
+/// gRPC内容类型前缀
+pub const CONTENT_TYPE: &[u8] = b"application/grpc";
+
+/// 消息帧头长度：1字节标志 + 4字节大端长度
+pub const FRAME_HEADER_LEN: usize = 5;
+
+/// 单条消息的最大长度（超过时不视为gRPC帧）
+pub const MAX_MESSAGE_LEN: u32 = 16 * 1024 * 1024;
+
+/// gRPC传输变体
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcVariant {
+    /// 原生gRPC（HTTP/2）
+    H2,
+    /// gRPC-Web二进制模式
+    Web,
+    /// gRPC-Web文本模式（base64）
+    Text,
+}
+
+impl GrpcVariant {
+    /// 元数据 `grpc_variant` 的取值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::H2 => "h2",
+            Self::Web => "web",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// 数据中第一个 `application/grpc*` 内容类型对应的变体
+///
+/// 大小写不敏感；`application/grpc-` 之后不是 `web`/`web-text` 时返回 `None`。
+pub fn content_type_variant(data: &[u8]) -> Option<GrpcVariant> {
+    let start = data.windows(CONTENT_TYPE.len())
+        .position(|window| window.eq_ignore_ascii_case(CONTENT_TYPE))?;
+    let rest = &data[start + CONTENT_TYPE.len()..];
+    
+    let Some(suffix) = rest.strip_prefix(b"-") else {
+        return Some(GrpcVariant::H2);
+    };
+    if starts_with_ignore_case(suffix, b"web-text") {
+        Some(GrpcVariant::Text)
+    } else if starts_with_ignore_case(suffix, b"web") {
+        Some(GrpcVariant::Web)
+    } else {
+        None
+    }
+}
+
+/// 检查gRPC消息帧头：标志只允许压缩位（0x01）和gRPC-Web的trailer位（0x80），长度不超过上限
+///
+/// 不足5字节时返回 `None`。
+pub fn is_frame_header(data: &[u8]) -> Option<bool> {
+    let header = data.get(..FRAME_HEADER_LEN)?;
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+    Some(header[0] & !0x81 == 0 && length <= MAX_MESSAGE_LEN)
+}
+
+/// 检查gRPC-Web文本模式的消息体：base64字符，解码后以合法的帧头开始
+///
+/// 不足8个字符时返回 `None`。
+pub fn is_web_text_frame(body: &[u8]) -> Option<bool> {
+    if !body.iter().all(|&b| base64_value(b).is_some() || b == b'=') {
+        return Some(false);
+    }
+    
+    // 帧头5字节对应前8个字符，空消息的编码在第8个字符处即有填充
+    let mut decoded = [0u8; 6];
+    let mut len = 0;
+    for chunk in body.get(..8)?.chunks(4) {
+        let sextets = chunk.iter().take_while(|&&c| c != b'=').count();
+        let bits = chunk[..sextets].iter()
+            .fold(0u32, |bits, &c| bits << 6 | base64_value(c).unwrap_or(0) as u32)
+            << (6 * (4 - sextets));
+        let bytes = sextets.saturating_sub(1);
+        decoded[len..len + bytes].copy_from_slice(&bits.to_be_bytes()[1..1 + bytes]);
+        len += bytes;
+        if sextets < 4 {
+            break;
+        }
+    }
+    is_frame_header(&decoded[..len])
+}
+
+/// 标准base64字母表中字符的值
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// 不区分大小写的前缀比较
+fn starts_with_ignore_case(data: &[u8], prefix: &[u8]) -> bool {
+    data.len() >= prefix.len() && data[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
@@ -0,0 +1,266 @@
+//! HTTP报文辅助函数
+//!
+//! 集中处理HTTP/1.x头部边界的定位，避免各模块重复扫描。
+
+use crate::core::protocol::ProtocolInfo;
+
+/// 识别的HTTP请求方法
+pub const REQUEST_METHODS: [&str; 9] = [
+    "GET", "POST", "PUT", "HEAD", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+
+/// 跳过请求行之前的空白和空行
+///
+/// 部分客户端会在请求行前多发CRLF或空格（RFC 9112 §2.2 建议服务端忽略）。
+pub fn trim_leading_blank(data: &[u8]) -> &[u8] {
+    let start = data.iter()
+        .position(|&b| !matches!(b, b'\r' | b'\n' | b' ' | b'\t'))
+        .unwrap_or(data.len());
+    &data[start..]
+}
+
+/// 识别开头的请求方法（不区分大小写，方法后必须紧跟空格），返回规范的大写形式
+pub fn request_method(data: &[u8]) -> Option<&'static str> {
+    REQUEST_METHODS.iter().copied().find(|method| {
+        data.len() > method.len()
+            && data[..method.len()].eq_ignore_ascii_case(method.as_bytes())
+            && data[method.len()] == b' '
+    })
+}
+
+/// 判断是否为HTTP/0.9简单请求：`GET <path>` 后直接换行，没有协议版本
+///
+/// 允许请求行前的空白，方法不区分大小写；请求行必须已以换行结束。
+pub fn is_http09_request(data: &[u8]) -> bool {
+    let data = trim_leading_blank(data);
+    if request_method(data) != Some("GET") {
+        return false;
+    }
+    
+    let Some(line_end) = data.iter().position(|&b| b == b'\n') else {
+        return false;
+    };
+    let target = data[4..line_end].strip_suffix(b"\r").unwrap_or(&data[4..line_end]);
+    target.first() == Some(&b'/') && target.iter().all(|&b| matches!(b, 0x21..=0x7e))
+}
+
+/// 查找HTTP头部结束位置
+///
+/// 返回紧随空行（`\r\n\r\n` 或 `\n\n`）之后的偏移量，即正文起始位置；
+/// 头部尚未完整接收时返回 `None`。
+pub fn header_end(data: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(offset) = data[pos..].iter().position(|&b| b == b'\n') {
+        let line_end = pos + offset + 1;
+        match data.get(line_end..) {
+            Some([b'\n', ..]) => return Some(line_end + 1),
+            Some([b'\r', b'\n', ..]) => return Some(line_end + 2),
+            _ => pos = line_end,
+        }
+    }
+    None
+}
+
+/// 获取HTTP头部块（含结束空行）
+pub fn header_block(data: &[u8]) -> Option<&[u8]> {
+    header_end(data).map(|end| &data[..end])
+}
+
+/// 获取HTTP正文部分
+///
+/// 头部尚未完整时返回 `None`，头部完整但无正文时返回空切片。
+pub fn body_slice(data: &[u8]) -> Option<&[u8]> {
+    header_end(data).map(|end| &data[end..])
+}
+
+/// 解析头部块中的 `Content-Length`
+pub fn content_length(headers: &[u8]) -> Option<usize> {
+    header_value(headers, "content-length")?.trim().parse().ok()
+}
+
+/// 计算首个完整HTTP/1.x报文的长度（头部 + 正文）
+///
+/// 支持 `Content-Length` 和 `Transfer-Encoding: chunked`。没有正文长度信息时，
+/// 请求视为无正文；响应除1xx/204/304外无法确定边界，返回 `None`。
+/// 报文尚未完整接收时同样返回 `None`。
+pub fn message_len(data: &[u8]) -> Option<usize> {
+    let end = header_end(data)?;
+    let headers = &data[..end];
+    
+    let body_len = if header_value(headers, "transfer-encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"))
+    {
+        chunked_body_len(&data[end..])?
+    } else if let Some(length) = content_length(headers) {
+        length
+    } else if headers.starts_with(b"HTTP/") {
+        // 响应只有无正文的状态码才能确定边界
+        let status = headers.get(9..12)?;
+        if status[0] == b'1' || status == b"204" || status == b"304" {
+            0
+        } else {
+            return None;
+        }
+    } else {
+        0
+    };
+    
+    let total = end.checked_add(body_len)?;
+    (total <= data.len()).then_some(total)
+}
+
+/// 查找头部字段值（字段名不区分大小写），跳过首行
+pub fn header_value<'a>(headers: &'a [u8], name: &str) -> Option<&'a str> {
+    headers
+        .split(|&b| b == b'\n')
+        .skip(1)
+        .filter_map(|line| std::str::from_utf8(line).ok())
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+}
+
+/// 识别HTTP/1.x临时响应
+///
+/// `100 Continue` 返回 `continue`，`103 Early Hints` 返回 `early_hints`，其余状态码返回 `None`。
+pub fn interim_response(data: &[u8]) -> Option<&'static str> {
+    if !data.starts_with(b"HTTP/1.") {
+        return None;
+    }
+    
+    let line_end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    match data[..line_end].split(|&b| b == b' ').nth(1).map(<[u8]>::trim_ascii)? {
+        b"100" => Some("continue"),
+        b"103" => Some("early_hints"),
+        _ => None,
+    }
+}
+
+/// 判断请求是否携带 `Expect: 100-continue`（头部可以尚未接收完整）
+pub fn expects_continue(data: &[u8]) -> bool {
+    if data.starts_with(b"HTTP/") {
+        return false;
+    }
+    
+    let end = header_end(data).unwrap_or(data.len());
+    header_value(&data[..end], "expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue"))
+}
+
+/// 写入 `http_interim` 和 `expects_continue` 元数据
+///
+/// 两者都会改变代理的缓冲方式：前者之后还会有最终响应，后者要求先转发请求头再等待正文。
+pub fn annotate_interim(info: &mut ProtocolInfo, data: &[u8]) {
+    if let Some(interim) = interim_response(data) {
+        info.add_metadata("http_interim", interim);
+    }
+    if expects_continue(data) {
+        info.add_metadata("expects_continue", "true");
+    }
+}
+
+/// 计算chunked正文长度（含结束块和尾部字段）
+fn chunked_body_len(body: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let line_len = body[pos..].iter().position(|&b| b == b'\n')?;
+        let line = std::str::from_utf8(&body[pos..pos + line_len]).ok()?;
+        let size_str = line.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_str, 16).ok()?;
+        pos += line_len + 1;
+        
+        if size == 0 {
+            // 结束块之后是可选的尾部字段和空行
+            return match body.get(pos..)? {
+                [b'\n', ..] => Some(pos + 1),
+                [b'\r', b'\n', ..] => Some(pos + 2),
+                rest => header_end(rest).map(|trailer| pos + trailer),
+            };
+        }
+        
+        pos = pos.checked_add(size)?;
+        match body.get(pos..)? {
+            [b'\n', ..] => pos += 1,
+            [b'\r', b'\n', ..] => pos += 2,
+            _ => return None,
+        }
+    }
+}
+
+/// 根据正文魔数识别压缩格式
+///
+/// 可识别 `gzip`（`1f 8b`）和 `zstd`（`28 b5 2f fd`）；brotli没有魔数，无法识别。
+pub fn sniff_body_encoding(body: &[u8]) -> Option<&'static str> {
+    if body.starts_with(&[0x1f, 0x8b]) {
+        Some("gzip")
+    } else if body.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some("zstd")
+    } else {
+        None
+    }
+}
+
+/// 判断 `Content-Type` 是否为压缩文件类型
+fn is_archive_content_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    matches!(
+        media_type.as_str(),
+        "application/gzip" | "application/x-gzip" | "application/zstd" | "application/x-zstd"
+    )
+}
+
+/// HTTP响应的内容编码信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentEncoding {
+    /// 声明的编码（小写），未声明时为 `identity`
+    pub declared: String,
+    /// 声明与正文魔数是否不符，正文尚未到达时为 `None`
+    pub mismatch: Option<bool>,
+}
+
+impl ContentEncoding {
+    /// 写入 `content_encoding` 和 `content_encoding_mismatch` 元数据
+    pub fn annotate(&self, info: &mut ProtocolInfo) {
+        info.add_metadata("content_encoding", self.declared.as_str());
+        if let Some(mismatch) = self.mismatch {
+            info.add_metadata("content_encoding_mismatch", mismatch.to_string());
+        }
+    }
+}
+
+/// 解析HTTP响应的 `Content-Encoding`，并在正文已到达时用魔数交叉校验
+///
+/// 叠加的编码（如 `gzip, br`）按最后列出的编码校验，它是最外层的编码；
+/// 未声明编码但 `Content-Type` 本身是压缩文件（如 `application/gzip`）时不算不符。
+/// 数据不是响应或头部不完整时返回 `None`。
+pub fn response_content_encoding(data: &[u8]) -> Option<ContentEncoding> {
+    if !data.starts_with(b"HTTP/") {
+        return None;
+    }
+    
+    let end = header_end(data)?;
+    let headers = &data[..end];
+    let declared = header_value(headers, "content-encoding")
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_else(|| "identity".to_string());
+    let outermost = declared
+        .rsplit(',')
+        .map(str::trim)
+        .find(|coding| !coding.is_empty())
+        .unwrap_or("identity");
+    
+    let body = &data[end..];
+    let mismatch = (!body.is_empty()).then(|| {
+        match (outermost, sniff_body_encoding(body)) {
+            ("gzip" | "x-gzip", sniffed) => sniffed != Some("gzip"),
+            ("zstd", sniffed) => sniffed != Some("zstd"),
+            // 正文本身就是压缩文件，魔数属于内容而非传输编码
+            ("identity", _) if header_value(headers, "content-type").is_some_and(is_archive_content_type) => false,
+            // 未声明压缩但正文带有压缩魔数
+            ("identity", sniffed) => sniffed.is_some(),
+            _ => false,
+        }
+    });
+    
+    Some(ContentEncoding { declared, mismatch })
+}
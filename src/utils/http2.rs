@@ -0,0 +1,240 @@
+//! HTTP/2帧辅助函数
+//!
+//! 按帧头遍历HTTP/2字节流，并识别常见的滥用模式。
+
+use crate::core::protocol::ProtocolInfo;
+
+/// HTTP/2连接前言
+pub const CONNECTION_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// 帧头长度
+pub const FRAME_HEADER_LEN: usize = 9;
+
+/// SETTINGS_MAX_FRAME_SIZE的初始值（RFC 9113 §6.5.2）
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 16_384;
+
+/// HEADERS帧类型
+pub const FRAME_HEADERS: u8 = 0x1;
+
+/// SETTINGS帧类型
+pub const FRAME_SETTINGS: u8 = 0x4;
+
+/// PUSH_PROMISE帧类型
+pub const FRAME_PUSH_PROMISE: u8 = 0x5;
+
+/// CONTINUATION帧类型
+pub const FRAME_CONTINUATION: u8 = 0x9;
+
+/// HEADERS / CONTINUATION帧的END_HEADERS标志
+pub const FLAG_END_HEADERS: u8 = 0x4;
+
+/// SETTINGS帧的ACK标志
+pub const FLAG_ACK: u8 = 0x1;
+
+/// SETTINGS_MAX_FRAME_SIZE参数ID
+pub const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+/// SETTINGS_MAX_FRAME_SIZE允许的最大值（2^24 - 1）
+pub const MAX_ALLOWED_FRAME_SIZE: u32 = 0xff_ffff;
+
+/// 未结束头部块的连续CONTINUATION帧数达到该值时视为洪泛
+const CONTINUATION_FLOOD_THRESHOLD: usize = 16;
+
+/// 非ACK的SETTINGS帧数达到该值时视为洪泛
+const SETTINGS_FLOOD_THRESHOLD: usize = 8;
+
+/// HTTP/2帧头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// 负载长度
+    pub length: u32,
+    /// 帧类型
+    pub frame_type: u8,
+    /// 标志位
+    pub flags: u8,
+    /// 流ID（已去除保留位）
+    pub stream_id: u32,
+}
+
+/// 解析帧头，数据不足9字节时返回 `None`
+pub fn parse_frame_header(data: &[u8]) -> Option<FrameHeader> {
+    let header = data.get(..FRAME_HEADER_LEN)?;
+    Some(FrameHeader {
+        length: u32::from_be_bytes([0, header[0], header[1], header[2]]),
+        frame_type: header[3],
+        flags: header[4],
+        stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+    })
+}
+
+/// 遍历数据中的帧头（自动跳过连接前言）
+///
+/// 最后一帧的负载可能不完整；帧头本身不完整时停止。
+pub fn frame_headers(data: &[u8]) -> impl Iterator<Item = FrameHeader> + '_ {
+    frames(data).map(|(header, _)| header)
+}
+
+/// 遍历帧头及已收到的负载（负载可能被截断）
+fn frames(data: &[u8]) -> impl Iterator<Item = (FrameHeader, &[u8])> + '_ {
+    let mut pos = if data.starts_with(CONNECTION_PREFACE) { CONNECTION_PREFACE.len() } else { 0 };
+    std::iter::from_fn(move || {
+        let header = parse_frame_header(data.get(pos..)?)?;
+        let start = pos + FRAME_HEADER_LEN;
+        let end = start.saturating_add(header.length as usize);
+        let payload = &data[start..end.min(data.len())];
+        pos = end;
+        Some((header, payload))
+    })
+}
+
+/// 从SETTINGS帧负载中读取SETTINGS_MAX_FRAME_SIZE，超出允许范围的值忽略
+fn settings_max_frame_size(payload: &[u8]) -> Option<u32> {
+    payload.chunks_exact(6)
+        .filter(|entry| u16::from_be_bytes([entry[0], entry[1]]) == SETTINGS_MAX_FRAME_SIZE)
+        .map(|entry| u32::from_be_bytes([entry[2], entry[3], entry[4], entry[5]]))
+        .rfind(|size| (DEFAULT_MAX_FRAME_SIZE..=MAX_ALLOWED_FRAME_SIZE).contains(size))
+}
+
+impl FrameHeader {
+    /// 按RFC 9113检查帧类型、长度与流ID是否自洽
+    ///
+    /// 未知的扩展帧类型视为无效，以免把任意二进制数据当作HTTP/2。
+    /// 对端可通过SETTINGS_MAX_FRAME_SIZE调大帧长度上限（如gRPC），因此不以默认上限判断。
+    pub fn is_well_formed(&self) -> bool {
+        let len = self.length;
+        let on_stream = self.stream_id != 0;
+        match self.frame_type {
+            // DATA / HEADERS / CONTINUATION
+            0x0 | FRAME_HEADERS | FRAME_CONTINUATION => on_stream,
+            // PRIORITY
+            0x2 => on_stream && len == 5,
+            // RST_STREAM
+            0x3 => on_stream && len == 4,
+            FRAME_SETTINGS if self.flags & FLAG_ACK != 0 => !on_stream && len == 0,
+            FRAME_SETTINGS => !on_stream && len.is_multiple_of(6),
+            FRAME_PUSH_PROMISE => on_stream && len >= 4,
+            // PING
+            0x6 => !on_stream && len == 8,
+            // GOAWAY
+            0x7 => !on_stream && len >= 8,
+            // WINDOW_UPDATE
+            0x8 => len == 4,
+            _ => false,
+        }
+    }
+    
+    /// 是否开启了尚未结束的头部块（其后必须紧跟同一流的CONTINUATION）
+    fn opens_header_block(&self) -> bool {
+        matches!(self.frame_type, FRAME_HEADERS | FRAME_PUSH_PROMISE | FRAME_CONTINUATION)
+            && self.flags & FLAG_END_HEADERS == 0
+    }
+}
+
+/// 从偏移0开始统计连续且自洽的完整帧数（用于中途接入的连接，不要求连接前言）
+///
+/// 未结束的头部块之后必须是同一流的CONTINUATION；遇到不合法的帧头时停止计数，
+/// 末尾不完整的帧不计入。
+pub fn midstream_frame_chain(data: &[u8]) -> usize {
+    let mut pos = 0;
+    let mut chain = 0;
+    let mut open_block: Option<u32> = None;
+    
+    while let Some(header) = data.get(pos..).and_then(parse_frame_header) {
+        if !header.is_well_formed() {
+            break;
+        }
+        
+        let continues_block = header.frame_type == FRAME_CONTINUATION
+            && open_block == Some(header.stream_id);
+        if open_block.is_some() != continues_block {
+            break;
+        }
+        
+        let end = pos + FRAME_HEADER_LEN + header.length as usize;
+        if end > data.len() {
+            break;
+        }
+        
+        open_block = header.opens_header_block().then_some(header.stream_id);
+        chain += 1;
+        pos = end;
+    }
+    
+    chain
+}
+
+/// HTTP/2帧序列异常
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Http2Anomaly {
+    /// 大量CONTINUATION帧且始终未设置END_HEADERS
+    ContinuationFlood,
+    /// 帧长度超过 `DEFAULT_MAX_FRAME_SIZE`
+    OversizedFrame,
+    /// 大量非ACK的SETTINGS帧
+    SettingsFlood,
+}
+
+impl Http2Anomaly {
+    /// 元数据中使用的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Http2Anomaly::ContinuationFlood => "continuation_flood",
+            Http2Anomaly::OversizedFrame => "oversized_frame",
+            Http2Anomaly::SettingsFlood => "settings_flood",
+        }
+    }
+    
+    /// 写入 `http2_anomaly` 元数据
+    pub fn annotate(&self, info: &mut ProtocolInfo) {
+        info.add_metadata("http2_anomaly", self.as_str());
+    }
+}
+
+/// 检查帧序列中的滥用模式，返回最先发现的异常
+///
+/// 帧长度上限取默认值，或同一缓冲区中此前SETTINGS帧声明的SETTINGS_MAX_FRAME_SIZE。
+/// 对端的SETTINGS在另一方向时，请使用 [`detect_anomaly_with_max_frame_size`] 传入已知上限。
+pub fn detect_anomaly(data: &[u8]) -> Option<Http2Anomaly> {
+    detect_anomaly_with_max_frame_size(data, DEFAULT_MAX_FRAME_SIZE)
+}
+
+/// 以给定的初始帧长度上限检查帧序列中的滥用模式
+pub fn detect_anomaly_with_max_frame_size(data: &[u8], max_frame_size: u32) -> Option<Http2Anomaly> {
+    let mut max_frame_size = max_frame_size;
+    let mut continuation_run = 0;
+    let mut settings_frames = 0;
+    
+    for (header, payload) in frames(data) {
+        if header.length > max_frame_size {
+            return Some(Http2Anomaly::OversizedFrame);
+        }
+        
+        if header.frame_type == FRAME_SETTINGS && header.flags & FLAG_ACK == 0 {
+            if let Some(size) = settings_max_frame_size(payload) {
+                max_frame_size = max_frame_size.max(size);
+            }
+        }
+        
+        match header.frame_type {
+            FRAME_CONTINUATION if header.flags & FLAG_END_HEADERS == 0 => {
+                continuation_run += 1;
+                if continuation_run >= CONTINUATION_FLOOD_THRESHOLD {
+                    return Some(Http2Anomaly::ContinuationFlood);
+                }
+            }
+            FRAME_SETTINGS if header.flags & FLAG_ACK == 0 => {
+                settings_frames += 1;
+                if settings_frames >= SETTINGS_FLOOD_THRESHOLD {
+                    return Some(Http2Anomaly::SettingsFlood);
+                }
+            }
+            _ => {}
+        }
+        
+        if header.frame_type != FRAME_CONTINUATION || header.flags & FLAG_END_HEADERS != 0 {
+            continuation_run = 0;
+        }
+    }
+    
+    None
+}
@@ -2,6 +2,33 @@
 //!
 //! 提供各种实用工具和辅助功能。
 
+#[cfg(feature = "std")]
+pub mod debug;
+#[cfg(feature = "std")]
+pub mod digest;
+#[cfg(feature = "std")]
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod features;
+#[cfg(feature = "std")]
+pub mod framing;
+pub mod grpc;
+#[cfg(feature = "std")]
+pub mod http;
+#[cfg(feature = "std")]
+pub mod http2;
+#[cfg(feature = "std")]
 pub mod logger;
+pub mod mqtt;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod starttls;
+#[cfg(feature = "std")]
+pub mod tune;
+#[cfg(feature = "std")]
+pub mod websocket;
 
+#[cfg(feature = "std")]
 pub use logger::*;
\ No newline at end of file
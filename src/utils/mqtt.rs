@@ -0,0 +1,148 @@
+//! MQTT报文辅助函数
+//!
+//! 校验客户端发送的首个CONNECT报文的固定头和可变头。
+
+#[cfg(feature = "std")]
+use crate::core::protocol::ProtocolInfo;
+
+/// CONNECT报文固定头首字节（类型1，标志位必须为0）
+pub const PACKET_CONNECT: u8 = 0x10;
+
+/// 剩余长度变长整数的最大字节数
+pub const MAX_REMAINING_LENGTH_BYTES: usize = 4;
+
+/// CONNECT可变头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectHeader {
+    /// 协议名：`MQTT` 或 `MQIsdp`
+    pub protocol_name: &'static str,
+    /// 协议级别：3=v3.1 4=v3.1.1 5=v5.0
+    pub level: u8,
+    /// 报文声明的剩余长度
+    pub remaining_length: usize,
+    /// 固定头 + 可变头（不含v5属性）的字节数
+    pub header_len: usize,
+}
+
+impl ConnectHeader {
+    /// 协议版本号
+    pub fn version(&self) -> &'static str {
+        match self.level {
+            3 => "3.1",
+            4 => "3.1.1",
+            _ => "5.0",
+        }
+    }
+    
+    /// 写入 `mqtt_protocol_name` 和 `mqtt_level` 元数据
+    #[cfg(feature = "std")]
+    pub fn annotate(&self, info: &mut ProtocolInfo) {
+        info.add_metadata("mqtt_protocol_name", self.protocol_name);
+        info.add_metadata("mqtt_level", self.level.to_string());
+    }
+}
+
+/// CONNECT报文的校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectCheck {
+    /// 固定头和可变头完整且相互一致
+    Valid(ConnectHeader),
+    /// 已有字节与CONNECT一致，但报文头尚未接收完整
+    Partial,
+    /// 不是CONNECT报文
+    Invalid,
+}
+
+/// 校验从偏移0开始的MQTT CONNECT报文头
+///
+/// 依次检查报文类型、剩余长度变长整数、协议名、协议级别和连接标志，
+/// 协议名与级别必须匹配（`MQIsdp` 只用于3，`MQTT` 只用于4和5）。
+pub fn check_connect(data: &[u8]) -> ConnectCheck {
+    match parse_connect(data) {
+        Ok(header) => ConnectCheck::Valid(header),
+        Err(true) => ConnectCheck::Partial,
+        Err(false) => ConnectCheck::Invalid,
+    }
+}
+
+/// 校验完整的CONNECT报文头，返回固定头加可变头的长度
+pub fn connect_header_len(data: &[u8]) -> Option<usize> {
+    match check_connect(data) {
+        ConnectCheck::Valid(header) => Some(header.header_len),
+        _ => None,
+    }
+}
+
+/// 解析CONNECT报文头，失败时 `Err(true)` 表示数据不足
+fn parse_connect(data: &[u8]) -> core::result::Result<ConnectHeader, bool> {
+    if *data.first().ok_or(true)? != PACKET_CONNECT {
+        return Err(false);
+    }
+    
+    let mut remaining_length = 0usize;
+    let mut pos = 1;
+    loop {
+        let byte = *data.get(pos).ok_or(true)?;
+        remaining_length |= ((byte & 0x7f) as usize) << (7 * (pos - 1));
+        pos += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if pos > MAX_REMAINING_LENGTH_BYTES {
+            return Err(false);
+        }
+    }
+    let fixed_header_len = pos;
+    
+    let name_len = data.get(pos..pos + 2).ok_or(true)?;
+    let (protocol_name, levels): (&'static str, &[u8]) = match u16::from_be_bytes([name_len[0], name_len[1]]) {
+        4 => ("MQTT", &[4, 5]),
+        6 => ("MQIsdp", &[3]),
+        _ => return Err(false),
+    };
+    pos += 2;
+    
+    // 逐字节比较，截断的协议名只要是合法前缀就视为数据不足
+    let available = &data[pos..data.len().min(pos + protocol_name.len())];
+    if !protocol_name.as_bytes().starts_with(available) {
+        return Err(false);
+    }
+    pos += protocol_name.len();
+    
+    let level = *data.get(pos).ok_or(true)?;
+    if !levels.contains(&level) {
+        return Err(false);
+    }
+    pos += 1;
+    
+    let flags = *data.get(pos).ok_or(true)?;
+    let will = flags & 0x04 != 0;
+    let will_qos = (flags >> 3) & 0x03;
+    let will_retain = flags & 0x20 != 0;
+    let valid_flags = flags & 0x01 == 0
+        && will_qos != 3
+        && (will || (will_qos == 0 && !will_retain))
+        // v5之前密码标志要求同时设置用户名标志
+        && (level == 5 || flags & 0x40 == 0 || flags & 0x80 != 0);
+    if !valid_flags {
+        return Err(false);
+    }
+    pos += 1;
+    
+    // 保持连接时间
+    data.get(pos..pos + 2).ok_or(true)?;
+    pos += 2;
+    
+    // 可变头之后至少还有客户端标识符的2字节长度（v5另有属性长度）
+    let min_remaining = pos - fixed_header_len + if level == 5 { 3 } else { 2 };
+    if remaining_length < min_remaining {
+        return Err(false);
+    }
+    
+    Ok(ConnectHeader {
+        protocol_name,
+        level,
+        remaining_length,
+        header_len: pos,
+    })
+}
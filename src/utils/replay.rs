@@ -0,0 +1,271 @@
+//! 探测会话录制与回放
+//!
+//! 录制每次探测的输入、探测器配置和结果（NDJSON），之后用同一份输入重新探测并比对结果，
+//! 用于复现线上误判和检查回归。
+
+use crate::core::detector::{DetectionResult, ProtocolDetector};
+use crate::core::protocol::ProtocolType;
+use crate::error::{DetectorError, Result};
+use crate::utils::encoding::{decode_hex, encode_hex};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 回放时置信度允许的误差
+const CONFIDENCE_EPSILON: f32 = 1e-4;
+
+/// 录制时的探测器配置快照
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    /// 探测器名称
+    pub detector_name: String,
+    /// 支持的协议
+    pub supported_protocols: Vec<ProtocolType>,
+    /// 最小探测大小
+    pub min_probe_size: usize,
+    /// 最大探测大小
+    pub max_probe_size: usize,
+}
+
+impl ConfigSnapshot {
+    /// 从探测器获取配置快照，协议列表排序后保存
+    pub fn of(detector: &dyn ProtocolDetector) -> Self {
+        let mut supported_protocols = detector.supported_protocols();
+        supported_protocols.sort();
+        supported_protocols.dedup();
+        
+        Self {
+            detector_name: detector.name().to_string(),
+            supported_protocols,
+            min_probe_size: detector.min_probe_size(),
+            max_probe_size: detector.max_probe_size(),
+        }
+    }
+}
+
+/// 单次探测的结果摘要
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedOutcome {
+    /// 探测到的协议，失败时为 `None`
+    pub protocol: Option<ProtocolType>,
+    /// 置信度，失败时为0
+    pub confidence: f32,
+    /// 错误信息
+    pub error: Option<String>,
+}
+
+impl RecordedOutcome {
+    /// 从探测结果创建摘要
+    pub fn from_result(result: &Result<DetectionResult>) -> Self {
+        match result {
+            Ok(detection) => Self {
+                protocol: Some(detection.protocol_type()),
+                confidence: detection.confidence(),
+                error: None,
+            },
+            Err(err) => Self {
+                protocol: None,
+                confidence: 0.0,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+    
+    /// 协议、置信度和成败是否一致（不比较错误文本）
+    pub fn matches(&self, other: &RecordedOutcome) -> bool {
+        self.protocol == other.protocol
+            && self.error.is_some() == other.error.is_some()
+            && (self.confidence - other.confidence).abs() <= CONFIDENCE_EPSILON
+    }
+}
+
+/// 录制条目（NDJSON中的一行）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedDetection {
+    /// 输入数据（十六进制）
+    pub input: String,
+    /// 探测器配置快照
+    pub config: ConfigSnapshot,
+    /// 探测结果
+    pub outcome: RecordedOutcome,
+}
+
+/// 探测录制器
+///
+/// 包装任意探测器，透明转发探测调用并把每次探测写入NDJSON。
+/// 写入失败不影响探测结果。
+pub struct DetectionRecorder<D, W = BufWriter<File>> {
+    detector: D,
+    config: ConfigSnapshot,
+    writer: Mutex<W>,
+}
+
+impl<D: ProtocolDetector> DetectionRecorder<D> {
+    /// 创建录制到指定文件的录制器（覆盖已有文件）
+    pub fn create(path: impl AsRef<Path>, detector: D) -> Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file), detector))
+    }
+}
+
+impl<D: ProtocolDetector, W: Write + Send> DetectionRecorder<D, W> {
+    /// 创建录制到指定输出的录制器
+    pub fn new(writer: W, detector: D) -> Self {
+        let config = ConfigSnapshot::of(&detector);
+        Self {
+            detector,
+            config,
+            writer: Mutex::new(writer),
+        }
+    }
+    
+    /// 获取被包装的探测器
+    pub fn detector(&self) -> &D {
+        &self.detector
+    }
+    
+    /// 刷新底层输出
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self.writer.lock()
+            .map_err(|_| DetectorError::internal_error("录制器锁已中毒"))?;
+        writer.flush()?;
+        Ok(())
+    }
+    
+    /// 刷新并取回底层输出
+    pub fn into_inner(self) -> Result<W> {
+        let mut writer = self.writer.into_inner()
+            .map_err(|_| DetectorError::internal_error("录制器锁已中毒"))?;
+        writer.flush()?;
+        Ok(writer)
+    }
+    
+    /// 写入一条录制条目
+    fn record(&self, data: &[u8], result: &Result<DetectionResult>) -> Result<()> {
+        let entry = RecordedDetection {
+            input: encode_hex(data),
+            config: self.config.clone(),
+            outcome: RecordedOutcome::from_result(result),
+        };
+        
+        let mut writer = self.writer.lock()
+            .map_err(|_| DetectorError::internal_error("录制器锁已中毒"))?;
+        serde_json::to_writer(&mut *writer, &entry)
+            .map_err(|e| DetectorError::internal_error(format!("序列化录制条目失败: {}", e)))?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+impl<D, W> std::fmt::Debug for DetectionRecorder<D, W>
+where
+    D: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DetectionRecorder")
+            .field("detector", &self.detector)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<D: ProtocolDetector, W: Write + Send> ProtocolDetector for DetectionRecorder<D, W> {
+    fn detect(&self, data: &[u8]) -> Result<DetectionResult> {
+        let result = self.detector.detect(data);
+        // 录制失败只影响调试数据，不改变探测结果
+        let _ = self.record(data, &result);
+        result
+    }
+    
+    fn min_probe_size(&self) -> usize {
+        self.detector.min_probe_size()
+    }
+    
+    fn max_probe_size(&self) -> usize {
+        self.detector.max_probe_size()
+    }
+    
+    fn timeout(&self) -> std::time::Duration {
+        self.detector.timeout()
+    }
+    
+    fn supported_protocols(&self) -> Vec<ProtocolType> {
+        self.detector.supported_protocols()
+    }
+    
+    fn name(&self) -> &str {
+        self.detector.name()
+    }
+}
+
+/// 回放差异
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDiff {
+    /// 录制文件中的行号（从1开始）
+    pub line: usize,
+    /// 输入长度
+    pub input_len: usize,
+    /// 录制时的结果
+    pub expected: RecordedOutcome,
+    /// 回放得到的结果
+    pub actual: RecordedOutcome,
+}
+
+/// 回放报告
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    /// 回放的条目数
+    pub total: usize,
+    /// 结果不一致的条目
+    pub diffs: Vec<ReplayDiff>,
+    /// 录制配置与当前探测器配置不同的条目数
+    pub config_changed: usize,
+}
+
+impl ReplayReport {
+    /// 是否没有回归
+    pub fn is_clean(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+/// 用指定探测器回放录制文件并比对结果
+pub fn replay(path: impl AsRef<Path>, detector: &dyn ProtocolDetector) -> Result<ReplayReport> {
+    let file = File::open(path)?;
+    replay_from(BufReader::new(file), detector)
+}
+
+/// 从任意输入回放录制数据，空行会被跳过
+pub fn replay_from<R: BufRead>(reader: R, detector: &dyn ProtocolDetector) -> Result<ReplayReport> {
+    let current = ConfigSnapshot::of(detector);
+    let mut report = ReplayReport::default();
+    
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let entry: RecordedDetection = serde_json::from_str(&line)
+            .map_err(|e| DetectorError::config_error(format!("第 {} 行录制条目无效: {}", index + 1, e)))?;
+        let input = decode_hex(&entry.input)?;
+        let actual = RecordedOutcome::from_result(&detector.detect(&input));
+        
+        report.total += 1;
+        if entry.config != current {
+            report.config_changed += 1;
+        }
+        if !entry.outcome.matches(&actual) {
+            report.diffs.push(ReplayDiff {
+                line: index + 1,
+                input_len: input.len(),
+                expected: entry.outcome,
+                actual,
+            });
+        }
+    }
+    
+    Ok(report)
+}
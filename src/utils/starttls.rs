@@ -0,0 +1,191 @@
+//! STARTTLS类文本协议辅助函数
+//!
+//! 识别SMTP、IMAP、FTP明文阶段的命令和响应（问候语、`EHLO`、`CAPABILITY`、`FEAT`），
+//! 并判断服务端的能力列表中是否提供STARTTLS升级。
+
+use crate::core::protocol::ProtocolType;
+
+/// 文本协议单行的最大长度（RFC 5321 §4.5.3.1.5 的回复行上限）
+pub const MAX_LINE_LEN: usize = 512;
+
+/// 明文阶段的识别结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Session {
+    /// 协议类型
+    pub protocol: ProtocolType,
+    /// 置信度
+    pub confidence: f32,
+    /// 服务端能力列表是否提供STARTTLS（`AUTH TLS`）；尚未看到能力列表时为 `None`
+    pub starttls_offered: Option<bool>,
+}
+
+impl Session {
+    fn new(protocol: ProtocolType, confidence: f32, starttls_offered: Option<bool>) -> Self {
+        Self { protocol, confidence, starttls_offered }
+    }
+}
+
+/// 识别SMTP、IMAP、FTP的明文命令或响应
+///
+/// 首行必须已以换行结束且为可打印ASCII。
+pub fn classify(data: &[u8]) -> Option<Session> {
+    let line = complete_lines(data).next()?;
+    if line.len() > MAX_LINE_LEN || !line.iter().all(|&b| matches!(b, 0x20..=0x7e | b'\t')) {
+        return None;
+    }
+    
+    smtp_session(data, line)
+        .or_else(|| imap_session(line))
+        .or_else(|| ftp_session(data, line))
+}
+
+/// 服务端 `220` 问候语所属的协议，依据响应码之后的文本判断
+///
+/// 文本提到 `SMTP`（含 `ESMTP`）为SMTP，提到 `FTP` 为FTP，无法区分时返回 `None`。
+pub fn greeting_protocol(data: &[u8]) -> Option<ProtocolType> {
+    let text = reply_text(first_line(data), b"220")?;
+    if contains_ignore_case(text, b"SMTP") {
+        Some(ProtocolType::SMTP)
+    } else if contains_ignore_case(text, b"FTP") {
+        Some(ProtocolType::FTP)
+    } else {
+        None
+    }
+}
+
+/// 魔法字节校验：`220` 问候语提到SMTP
+pub fn smtp_greeting_len(data: &[u8]) -> Option<usize> {
+    (greeting_protocol(data) == Some(ProtocolType::SMTP)).then(|| first_line(data).len())
+}
+
+/// 魔法字节校验：`220` 问候语没有提到SMTP（FTP或无法区分）
+pub fn ftp_greeting_len(data: &[u8]) -> Option<usize> {
+    (greeting_protocol(data) != Some(ProtocolType::SMTP)).then(|| first_line(data).len())
+}
+
+/// 检查IMAP标签：不含特殊字符的可见ASCII
+pub fn is_imap_tag(tag: &[u8]) -> bool {
+    !tag.is_empty() && tag.iter().all(|&b| b.is_ascii_graphic() && !b"+(){%*\"\\".contains(&b))
+}
+
+/// SMTP：问候语、`EHLO`/`HELO` 命令或多行 `250` 扩展列表
+fn smtp_session(data: &[u8], line: &[u8]) -> Option<Session> {
+    if greeting_protocol(line) == Some(ProtocolType::SMTP) {
+        return Some(Session::new(ProtocolType::SMTP, 0.9, smtp_starttls(data)));
+    }
+    
+    for command in [&b"EHLO "[..], b"HELO "] {
+        if line.len() > command.len()
+            && line[..command.len()].eq_ignore_ascii_case(command)
+            && line[command.len()..].iter().all(u8::is_ascii_graphic)
+        {
+            return Some(Session::new(ProtocolType::SMTP, 0.9, None));
+        }
+    }
+    
+    // EHLO回复：以 `250-` 开头，之后每行都是 `250` 续行或末行
+    if line.starts_with(b"250-") && complete_lines(data).nth(1).is_some()
+        && complete_lines(data).all(|line| reply_text(line, b"250").is_some())
+    {
+        return Some(Session::new(ProtocolType::SMTP, 0.9, smtp_starttls(data)));
+    }
+    None
+}
+
+/// 在 `250` 扩展列表中查找STARTTLS，没有扩展列表时返回 `None`
+fn smtp_starttls(data: &[u8]) -> Option<bool> {
+    let mut extensions = complete_lines(data)
+        .filter(|line| line.starts_with(b"250"))
+        .filter_map(|line| reply_text(line, b"250"))
+        .peekable();
+    extensions.peek()?;
+    Some(extensions.any(|text| first_token(text).eq_ignore_ascii_case(b"STARTTLS")))
+}
+
+/// IMAP：带能力列表的问候语、`* CAPABILITY` 响应或 `<tag> CAPABILITY` 命令
+fn imap_session(line: &[u8]) -> Option<Session> {
+    let untagged = line.strip_prefix(b"* OK ").or_else(|| line.strip_prefix(b"* PREAUTH "));
+    if let Some(text) = untagged {
+        if let Some(capabilities) = text.strip_prefix(b"[CAPABILITY ") {
+            return Some(Session::new(ProtocolType::IMAP, 0.9, Some(has_starttls_capability(capabilities))));
+        }
+        if contains_ignore_case(text, b"IMAP") {
+            return Some(Session::new(ProtocolType::IMAP, 0.85, None));
+        }
+        return None;
+    }
+    
+    if let Some(capabilities) = line.strip_prefix(b"* CAPABILITY ") {
+        return Some(Session::new(ProtocolType::IMAP, 0.9, Some(has_starttls_capability(capabilities))));
+    }
+    
+    let space = line.iter().position(|&b| b == b' ')?;
+    let (tag, command) = (&line[..space], &line[space + 1..]);
+    (is_imap_tag(tag) && command.eq_ignore_ascii_case(b"CAPABILITY"))
+        .then(|| Session::new(ProtocolType::IMAP, 0.85, None))
+}
+
+/// IMAP能力列表（到 `]` 为止）中是否包含 `STARTTLS`
+fn has_starttls_capability(capabilities: &[u8]) -> bool {
+    let end = capabilities.iter().position(|&b| b == b']').unwrap_or(capabilities.len());
+    capabilities[..end].split(|&b| b == b' ')
+        .any(|token| token.eq_ignore_ascii_case(b"STARTTLS"))
+}
+
+/// FTP：问候语、`FEAT` 命令或 `211` 特性列表（RFC 2389）
+fn ftp_session(data: &[u8], line: &[u8]) -> Option<Session> {
+    if greeting_protocol(line) == Some(ProtocolType::FTP) {
+        return Some(Session::new(ProtocolType::FTP, 0.9, None));
+    }
+    
+    if line.eq_ignore_ascii_case(b"FEAT") {
+        return Some(Session::new(ProtocolType::FTP, 0.85, None));
+    }
+    
+    // 特性列表：`211-` 开头，特性行以空格开头，以 `211 ` 结束
+    if line.starts_with(b"211-") {
+        let mut features = complete_lines(data).skip(1).take_while(|line| line.starts_with(b" ")).peekable();
+        features.peek()?;
+        let auth_tls = features.any(|feature| {
+            let feature = feature.trim_ascii();
+            feature.len() >= 8 && feature[..8].eq_ignore_ascii_case(b"AUTH TLS")
+        });
+        return Some(Session::new(ProtocolType::FTP, 0.9, Some(auth_tls)));
+    }
+    None
+}
+
+/// 第一行（不含行尾CRLF/LF），尚未收到换行时为全部数据
+fn first_line(data: &[u8]) -> &[u8] {
+    let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    data[..end].strip_suffix(b"\r").unwrap_or(&data[..end])
+}
+
+/// 已以换行结束的各行（不含行尾CRLF/LF）
+fn complete_lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let end = data.iter().rposition(|&b| b == b'\n').map_or(0, |pos| pos + 1);
+    data[..end].split_inclusive(|&b| b == b'\n').map(|line| {
+        let line = &line[..line.len() - 1];
+        line.strip_suffix(b"\r").unwrap_or(line)
+    })
+}
+
+/// 回复行 `<code> <text>` 或 `<code>-<text>` 中的文本部分
+fn reply_text<'a>(line: &'a [u8], code: &[u8]) -> Option<&'a [u8]> {
+    let rest = line.strip_prefix(code)?;
+    match rest.first() {
+        Some(b' ' | b'-') => Some(&rest[1..]),
+        None => Some(rest),
+        _ => None,
+    }
+}
+
+/// 文本的第一个空格分隔的单词
+fn first_token(text: &[u8]) -> &[u8] {
+    text.split(|&b| b == b' ').next().unwrap_or(text)
+}
+
+/// 不区分大小写的子串查找
+fn contains_ignore_case(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window.eq_ignore_ascii_case(needle))
+}
@@ -0,0 +1,65 @@
+//! 特征置信度调优
+//!
+//! 离线工具：根据带标签的样本集估计每个魔法包特征的精确率，给出校准后的置信度建议。
+
+use crate::core::magic::MagicSignature;
+use crate::core::protocol::ProtocolType;
+use std::collections::HashMap;
+
+/// 特征在样本集上的命中统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SignatureHits {
+    /// 命中且标签一致的样本数
+    pub true_positives: usize,
+    /// 命中但标签不一致的样本数
+    pub false_positives: usize,
+}
+
+impl SignatureHits {
+    /// 统计单个特征在样本集上的命中情况
+    pub fn measure(signature: &MagicSignature, corpus: &[(Vec<u8>, ProtocolType)]) -> Self {
+        let mut hits = Self::default();
+        for (data, label) in corpus {
+            if !signature.matches(data) {
+                continue;
+            }
+            if *label == signature.protocol {
+                hits.true_positives += 1;
+            } else {
+                hits.false_positives += 1;
+            }
+        }
+        hits
+    }
+    
+    /// 命中总数
+    pub fn total(&self) -> usize {
+        self.true_positives + self.false_positives
+    }
+    
+    /// 拉普拉斯平滑后的精确率，样本较少时不会给出 1.0
+    pub fn smoothed_precision(&self) -> f32 {
+        (self.true_positives as f32 + 1.0) / (self.total() as f32 + 2.0)
+    }
+}
+
+/// 根据样本集建议各特征的置信度
+///
+/// 返回以特征描述为键的建议值；在样本集中从未命中的特征保留原置信度。
+pub fn suggest_confidences(
+    signatures: &[MagicSignature],
+    corpus: &[(Vec<u8>, ProtocolType)],
+) -> HashMap<String, f32> {
+    signatures
+        .iter()
+        .map(|signature| {
+            let hits = SignatureHits::measure(signature, corpus);
+            let confidence = if hits.total() == 0 {
+                signature.confidence
+            } else {
+                hits.smoothed_precision().clamp(0.0, 1.0)
+            };
+            (signature.description.clone(), confidence)
+        })
+        .collect()
+}
@@ -0,0 +1,76 @@
+//! WebSocket帧辅助函数
+//!
+//! 解析连接中途出现的WebSocket控制帧。
+
+use crate::core::protocol::ProtocolInfo;
+
+/// 关闭帧操作码
+pub const OPCODE_CLOSE: u8 = 0x8;
+
+/// 控制帧负载长度上限（RFC 6455 §5.5）
+pub const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// WebSocket关闭帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// 关闭状态码
+    pub code: u16,
+    /// 关闭原因（UTF-8，可能为空）
+    pub reason: String,
+}
+
+impl CloseFrame {
+    /// 写入 `ws_close_code` 和 `ws_close_reason` 元数据
+    pub fn annotate(&self, info: &mut ProtocolInfo) {
+        info.add_metadata("ws_close_code", self.code.to_string());
+        info.add_metadata("ws_close_reason", self.reason.as_str());
+    }
+}
+
+/// 判断关闭状态码是否允许出现在帧中
+///
+/// 1004-1006和1015为保留值，只能由端点在本地使用；3000-4999留给库和应用。
+pub fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+/// 解析从偏移0开始的关闭帧，支持客户端发送的掩码帧
+///
+/// 帧不完整、不是关闭帧、状态码不允许或原因不是合法UTF-8时返回 `None`；
+/// 不带状态码的空关闭帧同样返回 `None`。
+pub fn parse_close_frame(data: &[u8]) -> Option<CloseFrame> {
+    let (&first, rest) = data.split_first()?;
+    let (&second, rest) = rest.split_first()?;
+    
+    // 控制帧必须设置FIN且不能分片，RSV位必须为0
+    if first != 0x80 | OPCODE_CLOSE {
+        return None;
+    }
+    
+    let payload_len = usize::from(second & 0x7f);
+    if !(2..=MAX_CONTROL_PAYLOAD_LEN).contains(&payload_len) {
+        return None;
+    }
+    
+    let (mask, rest) = if second & 0x80 != 0 {
+        let (mask, rest) = rest.split_at_checked(4)?;
+        (Some(mask), rest)
+    } else {
+        (None, rest)
+    };
+    
+    let mut payload = rest.get(..payload_len)?.to_vec();
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    
+    let code = u16::from_be_bytes([payload[0], payload[1]]);
+    if !is_valid_close_code(code) {
+        return None;
+    }
+    let reason = String::from_utf8(payload.split_off(2)).ok()?;
+    
+    Some(CloseFrame { code, reason })
+}
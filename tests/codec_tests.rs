@@ -0,0 +1,34 @@
+//! 编解码适配测试
+#![cfg(feature = "tokio-util")]
+
+use futures::StreamExt;
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::core::ProtocolType;
+use psi_detector::stream::DetectionDecoder;
+use tokio_util::codec::FramedRead;
+
+#[test]
+fn test_detection_decoder_with_chunked_input() {
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .build()
+        .unwrap();
+    let request = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: psi-test\r\nAccept: */*\r\n\r\n";
+    let (head, tail) = request.split_at(10);
+    let reader = tokio_test::io::Builder::new()
+        .read(head)
+        .read(tail)
+        .build();
+    let mut framed = FramedRead::new(reader, DetectionDecoder::new(Box::new(detector)));
+    
+    tokio_test::block_on(async {
+        let result = framed.next().await.unwrap().unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+        assert!(framed.decoder().is_detected());
+        
+        // 探测不消费字节，完整请求留给后续编解码器
+        assert_eq!(&framed.read_buffer()[..], &request[..]);
+        assert!(framed.next().await.is_none());
+    });
+}
@@ -1,9 +1,73 @@
 //! 核心探测器模块测试
 
 use psi_detector::core::detector::*;
+use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
 use psi_detector::core::protocol::{ProtocolType, ProtocolInfo};
 use std::time::Duration;
 
+/// 以固定置信度报告同一协议的测试探测器
+struct FixedProbe {
+    name: &'static str,
+    protocols: Vec<ProtocolType>,
+    result: ProtocolType,
+    confidence: f32,
+    priority: u8,
+    /// 只在数据包含该片段（不区分大小写）时报告结果
+    trigger: Option<&'static [u8]>,
+}
+
+impl FixedProbe {
+    fn new(name: &'static str, result: ProtocolType, confidence: f32) -> Self {
+        Self { name, protocols: vec![result], result, confidence, priority: 50, trigger: None }
+    }
+    
+    fn with_protocols(mut self, protocols: Vec<ProtocolType>) -> Self {
+        self.protocols = protocols;
+        self
+    }
+    
+    fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+    
+    fn when_contains(mut self, trigger: &'static [u8]) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+}
+
+impl ProtocolProbe for FixedProbe {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    
+    fn supported_protocols(&self) -> Vec<ProtocolType> {
+        self.protocols.clone()
+    }
+    
+    fn probe(&self, data: &[u8], _context: &mut ProbeContext) -> psi_detector::error::Result<Option<ProtocolInfo>> {
+        if let Some(trigger) = self.trigger {
+            if !data.windows(trigger.len()).any(|w| w.eq_ignore_ascii_case(trigger)) {
+                return Ok(None);
+            }
+        }
+        
+        // 直接写入置信度，保留NaN等非法值
+        let mut info = ProtocolInfo::new(self.result, 0.0);
+        info.confidence = self.confidence;
+        Ok(Some(info))
+    }
+    
+    fn priority(&self) -> u8 {
+        self.priority
+    }
+    
+    fn needs_more_data(&self, _data: &[u8]) -> bool {
+        false
+    }
+}
+
 #[test]
 fn test_detection_result() {
     let protocol_info = ProtocolInfo::new(ProtocolType::HTTP2, 0.95);
@@ -100,4 +164,1931 @@ fn test_detection_stats_single_failure() {
     assert_eq!(stats.failed_detections, 1);
     assert_eq!(stats.success_rate(), 0.0);
     assert_eq!(stats.most_common_protocol(), None);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_probe_aggregator_limit_candidates_dedupes_and_caps() {
+    use psi_detector::core::probe::{ProbeAggregator, ProbeConfig};
+    
+    let config = ProbeConfig {
+        max_candidates: 3,
+        ..ProbeConfig::default()
+    };
+    let aggregator = ProbeAggregator::new(config);
+    
+    let protocols = [
+        ProtocolType::HTTP1_1,
+        ProtocolType::HTTP2,
+        ProtocolType::TLS,
+        ProtocolType::SSH,
+        ProtocolType::QUIC,
+    ];
+    
+    // 每个协议由同一探测器重复报告多次，置信度各不相同
+    let mut candidates = Vec::new();
+    for (i, &protocol) in protocols.iter().enumerate() {
+        for step in 0..10 {
+            let confidence = 0.5 + i as f32 * 0.05 + step as f32 * 0.01;
+            candidates.push(("overlap".to_string(), ProtocolInfo::new(protocol, confidence)));
+        }
+    }
+    
+    let limited = aggregator.limit_candidates(candidates);
+    
+    assert_eq!(limited.len(), 3);
+    assert_eq!(limited[0].protocol_type, ProtocolType::QUIC);
+    assert_eq!(limited[1].protocol_type, ProtocolType::SSH);
+    assert_eq!(limited[2].protocol_type, ProtocolType::TLS);
+    // 相同 (协议, 探测器) 只保留最高置信度
+    assert!((limited[0].confidence - 0.79).abs() < 1e-5);
+    
+    // 不同探测器报告的相同协议不会被合并
+    let limited = aggregator.limit_candidates(vec![
+        ("a".to_string(), ProtocolInfo::new(ProtocolType::HTTP2, 0.8)),
+        ("b".to_string(), ProtocolInfo::new(ProtocolType::HTTP2, 0.7)),
+        ("a".to_string(), ProtocolInfo::new(ProtocolType::HTTP2, 0.6)),
+    ]);
+    assert_eq!(limited.len(), 2);
+    assert_eq!(limited[0].confidence, 0.8);
+}
+
+#[test]
+fn test_probe_aggregator_rank_candidates_truncates_in_order() {
+    use psi_detector::core::probe::{ProbeAggregator, ProbeConfig};
+    
+    let aggregator = ProbeAggregator::new(ProbeConfig {
+        max_candidates: 4,
+        ..ProbeConfig::default()
+    });
+    
+    let protocols = [
+        ProtocolType::HTTP1_1,
+        ProtocolType::HTTP2,
+        ProtocolType::TLS,
+        ProtocolType::SSH,
+        ProtocolType::QUIC,
+        ProtocolType::MQTT,
+        ProtocolType::Redis,
+        ProtocolType::DNS,
+    ];
+    
+    // 8个探测器各报告一个结果，多于候选上限；两两置信度相同
+    let candidates: Vec<(String, ProtocolInfo)> = protocols.iter().enumerate()
+        .map(|(i, &protocol)| (format!("probe-{}", i), ProtocolInfo::new(protocol, 0.5 + (i / 2) as f32 * 0.1)))
+        .collect();
+    let ranked = aggregator.rank_candidates(candidates, |name| {
+        if name.ends_with(['0', '2', '4', '6']) { 10 } else { 0 }
+    });
+    
+    // 只保留置信度最高的4个，置信度相同时高优先级探测器在前
+    let order: Vec<ProtocolType> = ranked.iter().map(|info| info.protocol_type).collect();
+    assert_eq!(order, vec![ProtocolType::Redis, ProtocolType::DNS, ProtocolType::QUIC, ProtocolType::MQTT]);
+}
+
+#[test]
+fn test_detect_with_many_overlapping_probes() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    
+    struct OverlapProbe {
+        name: &'static str,
+        confidence: f32,
+    }
+    
+    impl ProtocolProbe for OverlapProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::HTTP2]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            Ok(Some(ProtocolInfo::new(ProtocolType::HTTP2, self.confidence)))
+        }
+    }
+    
+    let mut builder = DetectorBuilder::new()
+        .enable_http2()
+        .with_min_confidence(0.5)
+        .with_max_candidates(4);
+    for i in 0..32 {
+        builder = builder.add_custom_probe(Box::new(OverlapProbe {
+            name: Box::leak(format!("overlap-{}", i).into_boxed_str()),
+            confidence: 0.5 + (i % 8) as f32 * 0.05,
+        }));
+    }
+    let detector = builder.build().expect("构建探测器失败");
+    
+    let data = vec![0x42u8; 64];
+    let result = detector.detect(&data).expect("应检测到HTTP/2");
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert!((result.confidence() - 0.85).abs() < 1e-5);
+}
+
+#[test]
+fn test_builder_rejects_zero_max_candidates() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let result = DetectorBuilder::new()
+        .enable_http()
+        .with_max_candidates(0)
+        .build();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_detect_aggregates_need_more_data_hints() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::{DetectorError, Result};
+    
+    struct NeedMoreProbe {
+        name: &'static str,
+        protocol: ProtocolType,
+        needed: usize,
+    }
+    
+    impl ProtocolProbe for NeedMoreProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![self.protocol]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            Err(DetectorError::NeedMoreData(self.needed))
+        }
+    }
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .add_custom_probe(Box::new(NeedMoreProbe {
+            name: "tls-need-more",
+            protocol: ProtocolType::TLS,
+            needed: 100,
+        }))
+        .add_custom_probe(Box::new(NeedMoreProbe {
+            name: "http-need-more",
+            protocol: ProtocolType::HTTP1_1,
+            needed: 20,
+        }))
+        .build()
+        .expect("构建探测器失败");
+    
+    let data = vec![0x42u8; 64];
+    match detector.detect(&data) {
+        Err(DetectorError::NeedMoreData(needed)) => assert_eq!(needed, 100),
+        other => panic!("期望 NeedMoreData(100)，实际为 {:?}", other),
+    }
+}
+
+#[test]
+fn test_detect_runs_each_probe_at_most_once() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    
+    struct CountingProbe {
+        name: &'static str,
+        protocols: Vec<ProtocolType>,
+        calls: Arc<AtomicUsize>,
+    }
+    
+    impl ProtocolProbe for CountingProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            self.protocols.clone()
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        }
+    }
+    
+    let build = |run_global: bool, shared: &Arc<AtomicUsize>, generic: &Arc<AtomicUsize>| {
+        let builder = DetectorBuilder::new()
+            .enable_http()
+            .enable_tls()
+            .enable_ssh()
+            // 覆盖多个启用协议的探测器，在按协议探测阶段会被多次选中
+            .add_custom_probe(Box::new(CountingProbe {
+                name: "shared-probe",
+                protocols: vec![ProtocolType::HTTP1_1, ProtocolType::TLS, ProtocolType::SSH],
+                calls: shared.clone(),
+            }))
+            // 不声明具体协议的通用探测器，只会在全局阶段运行
+            .add_custom_probe(Box::new(CountingProbe {
+                name: "generic-probe",
+                protocols: Vec::new(),
+                calls: generic.clone(),
+            }));
+        let builder = if run_global { builder.enable_global_probes() } else { builder.disable_global_probes() };
+        builder.build().expect("构建探测器失败")
+    };
+    
+    let data = vec![0x42u8; 64];
+    
+    let shared = Arc::new(AtomicUsize::new(0));
+    let generic = Arc::new(AtomicUsize::new(0));
+    let detector = build(true, &shared, &generic);
+    let _ = detector.detect(&data);
+    assert_eq!(shared.load(Ordering::SeqCst), 1);
+    assert_eq!(generic.load(Ordering::SeqCst), 1);
+    
+    let shared = Arc::new(AtomicUsize::new(0));
+    let generic = Arc::new(AtomicUsize::new(0));
+    let detector = build(false, &shared, &generic);
+    let _ = detector.detect(&data);
+    assert_eq!(shared.load(Ordering::SeqCst), 1);
+    assert_eq!(generic.load(Ordering::SeqCst), 0);
+    
+    // 默认不运行全局补充扫描，支持启用协议的探测器仍会运行
+    assert!(!psi_detector::core::probe::ProbeConfig::default().run_global_probes);
+    let shared = Arc::new(AtomicUsize::new(0));
+    let generic = Arc::new(AtomicUsize::new(0));
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .add_custom_probe(Box::new(CountingProbe {
+            name: "shared-probe",
+            protocols: vec![ProtocolType::HTTP1_1],
+            calls: shared.clone(),
+        }))
+        .add_custom_probe(Box::new(CountingProbe {
+            name: "generic-probe",
+            protocols: Vec::new(),
+            calls: generic.clone(),
+        }))
+        .build()
+        .expect("构建探测器失败");
+    let _ = detector.detect(&data);
+    assert_eq!(shared.load(Ordering::SeqCst), 1);
+    assert_eq!(generic.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_detect_with_context_correlation_id_and_deadline() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::error::DetectorError;
+    use std::time::Instant;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .build()
+        .expect("构建探测器失败");
+    let request = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    
+    let ctx = DetectionContext::new()
+        .with_correlation_id("req-42")
+        .with_deadline(Instant::now() + Duration::from_secs(5));
+    let result = detector.detect_with_context(request, &ctx).expect("应检测到HTTP");
+    assert_eq!(
+        result.protocol_info.metadata.get("correlation_id").map(String::as_str),
+        Some("req-42")
+    );
+    
+    // 已过期的截止时间优先于配置超时
+    let ctx = DetectionContext::new().with_deadline(Instant::now());
+    let err = detector.detect_with_context(request, &ctx).unwrap_err();
+    assert!(matches!(err, DetectorError::Timeout { .. }));
+}
+
+#[test]
+fn test_channel_sink_receives_concurrent_detections() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::sink::{ChannelSink, CountingSink, DetectionSink};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::thread;
+    
+    let (sender, receiver) = mpsc::channel();
+    let detector = Arc::new(
+        DetectorBuilder::new()
+            .enable_http()
+            .with_sink(Arc::new(ChannelSink::new(sender)))
+            .build()
+            .expect("构建探测器失败"),
+    );
+    
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            let detector = detector.clone();
+            thread::spawn(move || {
+                let request = format!("GET /item/{} HTTP/1.1\r\nHost: example.com\r\n\r\n", i);
+                detector.detect(request.as_bytes()).expect("应检测到HTTP");
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    
+    // 探测失败不会输出结果
+    assert!(detector.detect(&[0u8; 32]).is_err());
+    
+    let received: Vec<DetectionResult> = receiver.try_iter().collect();
+    assert_eq!(received.len(), 4);
+    assert!(received.iter().all(|r| r.protocol_type() == ProtocolType::HTTP1_1));
+    
+    let counter = CountingSink::new();
+    counter.emit(&received[0]);
+    assert_eq!(counter.count(), 1);
+    assert_eq!(counter.count_for(ProtocolType::HTTP1_1), 1);
+}
+
+#[test]
+fn test_recommended_initial_read_scales_with_protocols() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeConfig, ProbeRegistry};
+    
+    let http_only = DetectorBuilder::new()
+        .enable_http()
+        .build()
+        .expect("构建探测器失败");
+    let with_tls = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .build()
+        .expect("构建探测器失败");
+    let with_quic = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .enable_quic()
+        .build()
+        .expect("构建探测器失败");
+    
+    assert!(http_only.recommended_initial_read() >= http_only.absolute_min());
+    assert!(with_tls.recommended_initial_read() > http_only.recommended_initial_read());
+    assert!(with_quic.recommended_initial_read() > with_tls.recommended_initial_read());
+    
+    // QUIC推荐1200字节，超过最大探测大小时截断到上限
+    let capped = DefaultProtocolDetector::new(
+        ProbeRegistry::new(),
+        ProbeConfig::default(),
+        DetectionConfig::new().with_max_probe_size(512),
+        vec![ProtocolType::HTTP1_1, ProtocolType::QUIC],
+    )
+    .expect("构建探测器失败");
+    assert_eq!(capped.recommended_initial_read(), 512);
+    
+    // 最小探测大小超过所有协议推荐值时以其为下限
+    let floored = DefaultProtocolDetector::new(
+        ProbeRegistry::new(),
+        ProbeConfig::default(),
+        DetectionConfig::new().with_min_probe_size(100),
+        vec![ProtocolType::HTTP1_1],
+    )
+    .expect("构建探测器失败");
+    assert_eq!(floored.recommended_initial_read(), 100);
+}
+
+#[test]
+fn test_transport_metadata_distinguishes_quic_from_tcp_tls() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_tls()
+        .enable_quic()
+        .build()
+        .expect("构建探测器失败");
+    
+    // QUIC v1 Initial：长包头 + 8字节DCID + 空SCID + 空Token + 长度 + CRYPTO帧(ClientHello)
+    let mut quic_initial = vec![0xc3, 0x00, 0x00, 0x00, 0x01, 0x08];
+    quic_initial.extend_from_slice(&[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08]);
+    quic_initial.extend_from_slice(&[0x00, 0x00, 0x44, 0x9e, 0x00, 0x00, 0x00, 0x02]);
+    quic_initial.extend_from_slice(&[0x06, 0x00, 0x40, 0xf1, 0x01, 0x00, 0x00, 0xed, 0x03, 0x03]);
+    quic_initial.resize(1200, 0x00);
+    
+    let result = detector.detect(&quic_initial).expect("应检测到QUIC");
+    assert!(matches!(result.protocol_type(), ProtocolType::QUIC | ProtocolType::HTTP3));
+    assert_eq!(
+        result.protocol_info.metadata.get("transport").map(String::as_str),
+        Some("udp-quic")
+    );
+    
+    // TCP上的TLS记录：Handshake记录头 + ClientHello
+    let mut tls_record = vec![0x16, 0x03, 0x01, 0x00, 0x2f, 0x01, 0x00, 0x00, 0x2b, 0x03, 0x03];
+    tls_record.extend_from_slice(&[0x11; 32]);
+    tls_record.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+    
+    let result = detector.detect(&tls_record).expect("应检测到TLS");
+    assert_eq!(result.protocol_type(), ProtocolType::TLS);
+    assert_eq!(
+        result.protocol_info.metadata.get("transport").map(String::as_str),
+        Some("tcp-tls")
+    );
+}
+
+#[test]
+fn test_confidence_bounds_clamp_before_threshold() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    
+    struct FixedHeuristicProbe {
+        confidence: f32,
+    }
+    
+    impl ProtocolProbe for FixedHeuristicProbe {
+        fn name(&self) -> &'static str {
+            "fixed-heuristic"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::Custom]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            Ok(Some(ProtocolInfo::new(ProtocolType::Custom, self.confidence)))
+        }
+        
+        fn needs_more_data(&self, _data: &[u8]) -> bool {
+            false
+        }
+        
+        fn detection_method(&self) -> DetectionMethod {
+            DetectionMethod::Heuristic
+        }
+    }
+    
+    let build = |confidence: f32, bounds: Option<(f32, f32)>| {
+        let builder = DetectorBuilder::new()
+            .enable_custom()
+            .add_custom_probe(Box::new(FixedHeuristicProbe { confidence }));
+        let builder = match bounds {
+            Some((floor, ceil)) => builder.with_confidence_bounds(DetectionMethod::Heuristic, floor, ceil),
+            None => builder,
+        };
+        builder.build().expect("构建探测器失败")
+    };
+    
+    let data = vec![0x42u8; 64];
+    
+    // 未设置区间时按原始置信度
+    let result = build(0.95, None).detect(&data).expect("应检测到自定义协议");
+    assert_eq!(result.protocol_type(), ProtocolType::Custom);
+    assert!((result.confidence() - 0.95).abs() < f32::EPSILON);
+    
+    // 上限0.6低于默认阈值0.8，结果被拒绝
+    assert!(build(0.95, Some((0.0, 0.6))).detect(&data).is_err());
+    
+    // 上限高于阈值时结果按上限输出
+    let result = build(0.95, Some((0.0, 0.85))).detect(&data).expect("应检测到自定义协议");
+    assert!((result.confidence() - 0.85).abs() < f32::EPSILON);
+    
+    // 下限可将低置信度结果抬过阈值
+    let result = build(0.5, Some((0.82, 1.0))).detect(&data).expect("下限应生效");
+    assert!((result.confidence() - 0.82).abs() < f32::EPSILON);
+    
+    // 区间上下限颠倒时构建失败
+    let invalid = DetectorBuilder::new()
+        .enable_custom()
+        .with_confidence_bounds(DetectionMethod::Heuristic, 0.7, 0.3)
+        .build();
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn test_confidence_bounds_apply_to_builtin_heuristic_probe() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::ProtocolProbe;
+    use psi_detector::probe::HeuristicProbe;
+    
+    assert_eq!(ProtocolProbe::detection_method(&HeuristicProbe::new()), DetectionMethod::Heuristic);
+    
+    let build = |bounds: Option<(f32, f32)>| {
+        let builder = DetectorBuilder::new()
+            .enable_http()
+            .enable_http2()
+            .with_min_confidence(0.3)
+            .add_custom_probe(Box::new(HeuristicProbe::new()));
+        let builder = match bounds {
+            Some((floor, ceil)) => builder.with_confidence_bounds(DetectionMethod::Heuristic, floor, ceil),
+            None => builder,
+        };
+        builder.build().expect("构建探测器失败")
+    };
+    
+    // 没有请求行，只有启发式探测器能识别
+    let data = b"Host: example.com\r\nUser-Agent: curl/8.0\r\nContent-Length: 0\r\nAccept: */*\r\n\r\n";
+    let uncapped = build(None).detect(data).expect("启发式探测应有结果");
+    assert!(uncapped.confidence() > 0.6);
+    
+    let capped = build(Some((0.0, 0.6))).detect(data).expect("启发式探测应有结果");
+    assert!((capped.confidence() - 0.6).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_early_data_lowers_confidence() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .build()
+        .expect("构建探测器失败");
+    let request = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: test\r\n\r\n";
+    
+    let normal = detector
+        .detect_with_context(request, &DetectionContext::new())
+        .expect("应检测到HTTP");
+    let early = detector
+        .detect_with_context(request, &DetectionContext::new().with_early_data(true))
+        .expect("早期数据仍应检测到HTTP");
+    
+    assert_eq!(early.protocol_type(), normal.protocol_type());
+    assert!(early.confidence() < normal.confidence());
+    assert_eq!(early.protocol_info.metadata.get("early_data").map(String::as_str), Some("true"));
+    assert!(!normal.protocol_info.metadata.contains_key("early_data"));
+}
+
+#[test]
+fn test_http_response_content_encoding_metadata() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .build()
+        .expect("构建探测器失败");
+    
+    let mut gzip_response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Encoding: gzip\r\nContent-Length: 20\r\n\r\n".to_vec();
+    gzip_response.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03]);
+    
+    let result = detector.detect(&gzip_response).expect("应检测到HTTP响应");
+    let metadata = &result.protocol_info.metadata;
+    assert_eq!(metadata.get("content_encoding").map(String::as_str), Some("gzip"));
+    assert_eq!(metadata.get("content_encoding_mismatch").map(String::as_str), Some("false"));
+    
+    // 声明gzip但正文是明文
+    let plain_body = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 5\r\n\r\nhello";
+    let result = detector.detect(plain_body).expect("应检测到HTTP响应");
+    assert_eq!(
+        result.protocol_info.metadata.get("content_encoding_mismatch").map(String::as_str),
+        Some("true")
+    );
+    
+    // 未声明编码、正文未到达
+    let headers_only = b"HTTP/1.1 204 No Content\r\nServer: test\r\n\r\n";
+    let result = detector.detect(headers_only).expect("应检测到HTTP响应");
+    assert_eq!(
+        result.protocol_info.metadata.get("content_encoding").map(String::as_str),
+        Some("identity")
+    );
+    assert!(!result.protocol_info.metadata.contains_key("content_encoding_mismatch"));
+}
+
+#[test]
+fn test_best_match_is_independent_of_protocol_order() {
+    use psi_detector::core::probe::{MatchMode, ProbeConfig, ProbeRegistry};
+    
+    let build = |order: &[ProtocolType], mode: MatchMode, with_generic: bool| {
+        let mut registry = ProbeRegistry::new();
+        registry.register_probe(ProtocolType::TLS, Box::new(
+            FixedProbe::new("tls-probe", ProtocolType::TLS, 0.9).with_priority(60)
+        ));
+        registry.register_probe(ProtocolType::SSH, Box::new(
+            FixedProbe::new("ssh-probe", ProtocolType::SSH, 0.9).with_priority(70)
+        ));
+        if with_generic {
+            // 只在全局阶段运行，首个匹配模式下会被高置信度结果跳过
+            registry.register_global_probe(Box::new(
+                FixedProbe::new("generic-probe", ProtocolType::HTTP1_1, 0.93)
+                    .with_protocols(Vec::new())
+                    .with_priority(10)
+            ));
+        }
+        
+        let probe_config = ProbeConfig {
+            match_mode: mode,
+            run_global_probes: true,
+            ..ProbeConfig::default()
+        };
+        DefaultProtocolDetector::new(registry, probe_config, DetectionConfig::default(), order.to_vec())
+            .expect("构建探测器失败")
+    };
+    
+    let (tls, ssh, http) = (ProtocolType::TLS, ProtocolType::SSH, ProtocolType::HTTP1_1);
+    let orders = [
+        [tls, ssh, http],
+        [tls, http, ssh],
+        [ssh, tls, http],
+        [ssh, http, tls],
+        [http, tls, ssh],
+        [http, ssh, tls],
+    ];
+    let data = vec![0x42u8; 64];
+    
+    for order in &orders {
+        // 最佳匹配总会运行全局探测器并选出最高置信度
+        let result = build(order, MatchMode::BestMatch, true).detect(&data).unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1, "顺序 {:?}", order);
+        assert!((result.confidence() - 0.93).abs() < f32::EPSILON);
+        
+        // 置信度相同时按优先级裁决
+        let result = build(order, MatchMode::BestMatch, false).detect(&data).unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::SSH, "顺序 {:?}", order);
+        
+        // 首个匹配模式找到高置信度结果后跳过全局探测器
+        let result = build(order, MatchMode::FirstMatch, true).detect(&data).unwrap();
+        assert_ne!(result.protocol_type(), ProtocolType::HTTP1_1);
+    }
+}
+
+#[test]
+fn test_detect_hex_and_base64_client_hello() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_tls()
+        .build()
+        .expect("构建探测器失败");
+    
+    // Wireshark风格的十六进制ClientHello
+    let hex = "16 03 01 00 2f 01 00 00 2b 03 03
+               11 11 11 11 11 11 11 11 11 11 11 11 11 11 11 11
+               11 11 11 11 11 11 11 11 11 11 11 11 11 11 11 11
+               00 00 02 13 01 01 00";
+    let result = detector.detect_hex(hex).expect("应检测到TLS");
+    assert_eq!(result.protocol_type(), ProtocolType::TLS);
+    
+    let result = detector.detect_hex(&hex.split_whitespace().collect::<Vec<_>>().join(":")).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::TLS);
+    
+    let b64 = "FgMBAC8BAAArAwMREREREREREREREREREREREREREREREREREREREREREQAAAhMBAQA=";
+    let result = detector.detect_base64(b64).expect("应检测到TLS");
+    assert_eq!(result.protocol_type(), ProtocolType::TLS);
+    
+    assert!(detector.detect_hex("16 03 0").is_err());
+    assert!(detector.detect_hex("16 03 zz").is_err());
+    assert!(detector.detect_base64("Fg*BAA==").is_err());
+    assert!(detector.detect_base64("F===").is_err());
+}
+
+#[test]
+fn test_profiling_records_time_per_probe() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    use std::sync::{Arc, Mutex};
+    
+    struct RecordingProbe {
+        name: &'static str,
+        ran: Arc<Mutex<Vec<&'static str>>>,
+    }
+    
+    impl ProtocolProbe for RecordingProbe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            Vec::new()
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            self.ran.lock().unwrap().push(self.name);
+            std::thread::sleep(Duration::from_millis(1));
+            Ok(None)
+        }
+    }
+    
+    let build = |profiling: bool, ran: &Arc<Mutex<Vec<&'static str>>>| {
+        DetectorBuilder::new()
+            .enable_http()
+            .enable_ssh()
+            .add_custom_probe(Box::new(RecordingProbe { name: "slow-a", ran: ran.clone() }))
+            .add_custom_probe(Box::new(RecordingProbe { name: "slow-b", ran: ran.clone() }))
+            .enable_global_probes()
+            .with_profiling(profiling)
+            .build()
+            .expect("构建探测器失败")
+    };
+    let data = vec![0x42u8; 64];
+    
+    let ran = Arc::new(Mutex::new(Vec::new()));
+    let detector = build(true, &ran);
+    assert!(detector.last_probe_timings().is_none());
+    let _ = detector.detect(&data);
+    
+    let timings = detector.last_probe_timings().expect("应记录耗时");
+    let ran = ran.lock().unwrap();
+    assert_eq!(ran.len(), 2);
+    for name in ran.iter() {
+        assert!(timings.get(name).unwrap() >= Duration::from_millis(1), "{} 缺少耗时", name);
+    }
+    assert!(timings.get("MagicBytesDetector").is_some());
+    assert!(timings.probes_total() <= timings.total);
+    assert!(matches!(timings.slowest(), Some(("slow-a" | "slow-b", _))));
+    
+    // 关闭时不记录
+    let detector = build(false, &Arc::new(Mutex::new(Vec::new())));
+    let _ = detector.detect(&data);
+    assert!(detector.last_probe_timings().is_none());
+}
+
+#[test]
+fn test_disabled_protocols_are_not_reported() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .build()
+        .expect("构建探测器失败");
+    
+    // 魔法包过滤器与构造器启用的协议一致
+    let mut magic_enabled = detector.magic_detector().enabled_protocols().unwrap().to_vec();
+    magic_enabled.sort();
+    let mut enabled = detector.enabled_protocols().to_vec();
+    enabled.sort();
+    assert_eq!(magic_enabled, enabled);
+    
+    let ftp_banner = b"220 ProFTPD 1.3.5 Server ready.\r\n";
+    assert!(detector.magic_detector().deep_detect(ftp_banner).is_empty());
+    assert!(detector.detect(ftp_banner).is_err());
+    
+    // 探测器写入上下文的候选结果同样不能绕过启用列表
+    for data in [
+        &b"NICK alice\r\nUSER alice 0 * :Alice Liddell\r\n"[..],
+        b"SSH-2.0-OpenSSH_8.9p1 Ubuntu-3\r\n",
+    ] {
+        if let Ok(result) = detector.detect(data) {
+            assert!(enabled.contains(&result.protocol_type()), "报告了未启用的协议 {:?}", result.protocol_type());
+        }
+    }
+}
+
+#[test]
+fn test_detect_bidirectional_combines_evidence() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    
+    /// 自定义RPC：请求以 `ca fe 01` 开头、响应以 `ca fe 02` 开头，单向证据都较弱
+    struct WeakRpcProbe;
+    
+    impl ProtocolProbe for WeakRpcProbe {
+        fn name(&self) -> &'static str {
+            "weak-rpc"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::Custom]
+        }
+        
+        fn probe(&self, data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            if data.starts_with(&[0xca, 0xfe]) && matches!(data.get(2), Some(0x01 | 0x02)) {
+                Ok(Some(ProtocolInfo::new(ProtocolType::Custom, 0.6)))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_custom()
+        .add_custom_probe(Box::new(WeakRpcProbe))
+        .build()
+        .expect("构建探测器失败");
+    
+    let mut request = vec![0xca, 0xfe, 0x01];
+    request.resize(64, 0x7f);
+    let mut response = vec![0xca, 0xfe, 0x02];
+    response.resize(64, 0x7f);
+    
+    // 单向置信度都低于阈值
+    assert!(detector.detect(&request).is_err());
+    assert!(detector.detect(&response).is_err());
+    
+    // 双向一致时合并为 1 - 0.4 * 0.4 = 0.84
+    let result = detector.detect_bidirectional(&request, &response).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::Custom);
+    assert!((result.confidence() - 0.84).abs() < 1e-4);
+    assert_eq!(result.protocol_info.metadata.get("bidirectional").map(String::as_str), Some("agree"));
+    
+    // 只有一个方向的弱证据时仍不足以确认
+    assert!(detector.detect_bidirectional(&request, &[0x00; 64]).is_err());
+    
+    // HTTP请求加响应得到比任一方向更高的置信度
+    let c2s = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    let s2c = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+    let result = detector.detect_bidirectional(c2s, s2c).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert!(result.confidence() > detector.confidence_map(c2s).protocol_confidences()[&ProtocolType::HTTP1_1]);
+    assert!(result.confidence() > detector.confidence_map(s2c).protocol_confidences()[&ProtocolType::HTTP1_1]);
+    assert_eq!(result.protocol_info.metadata.get("bidirectional").map(String::as_str), Some("agree"));
+    
+    assert!(detector.detect_bidirectional(&[], &[]).is_err());
+}
+
+#[test]
+fn test_detection_result_clone_shares_metadata() {
+    let mut protocol_info = ProtocolInfo::new(ProtocolType::TLS, 0.9);
+    protocol_info.add_metadata("sni", "example.com");
+    let original = DetectionResult::new(
+        protocol_info,
+        Duration::from_millis(1),
+        DetectionMethod::Passive,
+        "test_detector".to_string(),
+    );
+    
+    // 克隆共享同一张元数据表
+    let mut copy = original.clone();
+    assert!(std::sync::Arc::ptr_eq(&original.protocol_info.metadata, &copy.protocol_info.metadata));
+    
+    // 修改时复制，原结果不受影响
+    copy.make_mut_metadata().insert("alpn".to_string(), "h2".to_string());
+    assert!(!std::sync::Arc::ptr_eq(&original.protocol_info.metadata, &copy.protocol_info.metadata));
+    assert!(!original.protocol_info.metadata.contains_key("alpn"));
+    assert_eq!(copy.protocol_info.metadata.get("alpn").map(String::as_str), Some("h2"));
+    assert_eq!(copy.protocol_info.metadata.get("sni").map(String::as_str), Some("example.com"));
+    
+    // 唯一持有者修改时不再复制
+    let before = std::sync::Arc::as_ptr(&copy.protocol_info.metadata);
+    copy.make_mut_metadata().insert("version".to_string(), "1.3".to_string());
+    assert_eq!(before, std::sync::Arc::as_ptr(&copy.protocol_info.metadata));
+}
+
+#[test]
+fn test_early_data_discounts_magic_candidate_once() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    
+    // 低置信度探测结果，使第三阶段深度魔法包检测不再运行，只剩快速检测的候选
+    struct LowProbe;
+    
+    impl ProtocolProbe for LowProbe {
+        fn name(&self) -> &'static str {
+            "low-ftp"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::FTP]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            Ok(Some(ProtocolInfo::new(ProtocolType::FTP, 0.3)))
+        }
+    }
+    
+    let detector = DetectorBuilder::new()
+        .enable_ftp()
+        .with_min_confidence(0.5)
+        .add_custom_probe(Box::new(LowProbe))
+        .build()
+        .expect("构建探测器失败");
+    
+    let mut banner = b"220 ProFTPD Server ready.\r\n".to_vec();
+    banner.resize(64, b' ');
+    
+    let normal = detector
+        .detect_with_context(&banner, &DetectionContext::new())
+        .expect("应检测到FTP");
+    let early = detector
+        .detect_with_context(&banner, &DetectionContext::new().with_early_data(true))
+        .expect("早期数据仍应检测到FTP");
+    
+    assert_eq!(normal.protocol_type(), ProtocolType::FTP);
+    assert_eq!(early.protocol_type(), ProtocolType::FTP);
+    assert!((early.confidence() - normal.confidence() * 0.9).abs() < 1e-5);
+}
+
+#[test]
+fn test_nan_confidence_from_custom_probe_is_ignored() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{MatchMode, ProbeAggregator, ProbeConfig};
+    
+    let detector = DetectorBuilder::new()
+        .enable_custom()
+        .with_match_mode(MatchMode::BestMatch)
+        .add_custom_probe(Box::new(FixedProbe::new("nan", ProtocolType::Custom, f32::NAN)))
+        .add_custom_probe(Box::new(FixedProbe::new("valid", ProtocolType::Custom, 0.9)))
+        .build()
+        .expect("构建探测器失败");
+    
+    let result = detector.detect(&[0x42u8; 64]).expect("有效结果不应被NaN掩盖");
+    assert!((result.confidence() - 0.9).abs() < f32::EPSILON);
+    
+    let aggregator = ProbeAggregator::new(ProbeConfig::default());
+    assert!(aggregator.aggregate(vec![ProtocolInfo {
+        confidence: f32::NAN,
+        ..ProtocolInfo::new(ProtocolType::Custom, 0.0)
+    }]).is_none());
+}
+
+#[test]
+fn test_agent_config_builder_defaults_and_validation() {
+    use psi_detector::core::probe::ProbeConfig;
+    
+    let server = AgentConfig::builder()
+        .instance_id("edge-1")
+        .load_balancer(LoadBalancerConfig {
+            is_load_balancer: true,
+            backend_instances: vec!["backend-1".to_string()],
+            strategy: LoadBalanceStrategy::RoundRobin,
+        })
+        .build()
+        .expect("服务器配置应有效");
+    assert_eq!(server.role, Role::Server);
+    assert_eq!(server.instance_id, "edge-1");
+    assert!(server.enable_upgrade);
+    assert_eq!(
+        server.enabled_protocols,
+        vec![ProtocolType::HTTP1_1, ProtocolType::HTTP2, ProtocolType::TLS]
+    );
+    assert!(server.load_balancer_config.is_some());
+    
+    let client = AgentConfig::builder()
+        .role(Role::Client)
+        .enabled_protocols(vec![ProtocolType::HTTP2, ProtocolType::QUIC])
+        .enable_upgrade(false)
+        .detection_config(DetectionConfig::new().with_min_confidence(0.9))
+        .probe_config(ProbeConfig { max_candidates: 4, ..ProbeConfig::default() })
+        .build()
+        .expect("客户端配置应有效");
+    assert_eq!(client.role, Role::Client);
+    assert!(!client.instance_id.is_empty());
+    assert!(!client.enable_upgrade);
+    assert_eq!(client.detection_config.min_confidence, 0.9);
+    assert_eq!(client.probe_config.max_candidates, 4);
+    assert!(client.load_balancer_config.is_none());
+    
+    // 客户端角色不能配置负载均衡，协议列表不能为空
+    let err = AgentConfig::builder()
+        .role(Role::Client)
+        .load_balancer(server.load_balancer_config.clone().unwrap())
+        .build()
+        .unwrap_err();
+    assert!(err.is_config_error());
+    assert!(AgentConfig::builder().enabled_protocols(Vec::new()).build().is_err());
+}
+
+#[test]
+fn test_detection_result_combine_votes_by_confidence() {
+    let vote = |protocol, confidence, node: &str| {
+        let mut info = ProtocolInfo::new(protocol, confidence);
+        info.add_feature(format!("seen-by-{}", node));
+        DetectionResult::new(info, Duration::from_micros(100), DetectionMethod::Passive, node.to_string())
+    };
+    
+    let results = [
+        vote(ProtocolType::HTTP1_1, 0.8, "node-a"),
+        vote(ProtocolType::TLS, 0.95, "node-b"),
+        vote(ProtocolType::HTTP1_1, 0.7, "node-c"),
+    ];
+    let consensus = DetectionResult::combine(&results);
+    
+    assert_eq!(consensus.protocol_type(), ProtocolType::HTTP1_1);
+    assert!((consensus.confidence() - 0.75).abs() < 1e-5);
+    assert!(consensus.protocol_info.has_feature("seen-by-node-a"));
+    assert!(consensus.protocol_info.has_feature("seen-by-node-c"));
+    assert!(!consensus.protocol_info.has_feature("seen-by-node-b"));
+    assert_eq!(consensus.protocol_info.metadata.get("agreement").map(String::as_str), Some("0.667"));
+    assert_eq!(consensus.protocol_info.metadata.get("voters").map(String::as_str), Some("3"));
+    
+    // 平局时结果与输入顺序无关
+    let tied = [vote(ProtocolType::TLS, 0.8, "node-a"), vote(ProtocolType::HTTP2, 0.8, "node-b")];
+    let reversed = [tied[1].clone(), tied[0].clone()];
+    assert_eq!(
+        DetectionResult::combine(&tied).protocol_type(),
+        DetectionResult::combine(&reversed).protocol_type()
+    );
+    
+    assert_eq!(DetectionResult::combine(&[]).protocol_type(), ProtocolType::Unknown);
+}
+
+#[test]
+fn test_agent_fallback_protocol_on_garbage_input() {
+    use psi_detector::builder::DetectorBuilder;
+    use std::sync::Arc;
+    
+    let garbage: Vec<u8> = (0..64u8).map(|i| i.wrapping_mul(37) ^ 0x5a).collect();
+    let detector = || -> Arc<dyn ProtocolDetector> {
+        Arc::new(DetectorBuilder::new().enable_http().build().expect("构建探测器失败"))
+    };
+    
+    // 未配置兜底协议时仍返回错误
+    let strict = Agent::new(AgentConfig::default(), detector(), None);
+    assert!(strict.detect(&garbage).is_err());
+    
+    let config = AgentConfig::builder()
+        .fallback_protocol(ProtocolType::HTTP1_1)
+        .build()
+        .unwrap();
+    let agent = Agent::new(config, detector(), None);
+    let result = agent.detect(&garbage).expect("应回退到HTTP/1.1");
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert_eq!(result.confidence(), 0.0);
+    assert_eq!(result.detector_name, "Fallback");
+    assert_eq!(result.protocol_info.metadata.get("fallback").map(String::as_str), Some("true"));
+    
+    // 数据不足不属于无匹配，不回退
+    assert!(agent.detect(b"GET").is_err());
+}
+
+#[test]
+fn test_detect_vectored_split_http2_preface() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_http2()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    // 连接前言跨越两个分片，后跟一个空的SETTINGS帧
+    let mut tail = b"\r\n\r\nSM\r\n\r\n".to_vec();
+    tail.extend_from_slice(&[0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let slices: [&[u8]; 2] = [b"PRI * HTTP/2.0", &tail];
+    let result = detector.detect_vectored(&slices).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert_eq!(result.protocol_type(), detector.detect(&slices.concat()).unwrap().protocol_type());
+    
+    // 只靠前缀即可确定的协议走边界窗口
+    let slices: [&[u8]; 3] = [b"SS", b"H-2.0-OpenSSH_9.6", b"\r\n"];
+    assert_eq!(detector.detect_vectored(&slices).unwrap().protocol_type(), ProtocolType::SSH);
+    
+    // 空分片被忽略
+    let slices: [&[u8]; 2] = [b"", b"SSH-2.0-OpenSSH_9.6\r\n"];
+    assert_eq!(detector.detect_vectored(&slices).unwrap().protocol_type(), ProtocolType::SSH);
+}
+
+#[test]
+fn test_pipeline_order_controls_simd_stage() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{DetectionStage, ProbeContext, ProtocolProbe};
+    use psi_detector::error::Result;
+    
+    struct SimdStageProbe;
+    
+    impl ProtocolProbe for SimdStageProbe {
+        fn name(&self) -> &'static str {
+            "SimdStageProbe"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::SSH]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            Ok(None)
+        }
+        
+        fn needs_more_data(&self, _data: &[u8]) -> bool {
+            false
+        }
+        
+        fn detection_method(&self) -> DetectionMethod {
+            DetectionMethod::SimdAccelerated
+        }
+    }
+    
+    let build = |pipeline: Option<Vec<DetectionStage>>| {
+        let mut builder = DetectorBuilder::new()
+            .enable_ssh()
+            .add_custom_probe(Box::new(SimdStageProbe))
+            .enable_global_probes()
+            .with_profiling(true);
+        if let Some(pipeline) = pipeline {
+            builder = builder.with_pipeline(pipeline);
+        }
+        builder.build().unwrap()
+    };
+    let data = b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n";
+    
+    // 默认顺序：魔法包高置信度命中后不再运行SIMD阶段
+    assert_eq!(DetectionStage::default_pipeline()[0], DetectionStage::Magic);
+    let detector = build(None);
+    assert_eq!(detector.detect(data).unwrap().protocol_type(), ProtocolType::SSH);
+    let timings = detector.last_probe_timings().unwrap();
+    assert!(timings.get("MagicBytesDetector").is_some());
+    assert!(timings.get("SimdStageProbe").is_none());
+    
+    // SIMD阶段提前后总会运行
+    let detector = build(Some(vec![DetectionStage::Simd, DetectionStage::Magic, DetectionStage::Passive]));
+    assert_eq!(detector.detect(data).unwrap().protocol_type(), ProtocolType::SSH);
+    assert!(detector.last_probe_timings().unwrap().get("SimdStageProbe").is_some());
+    
+    // 空流水线和重复阶段在构建时拒绝
+    assert!(DetectorBuilder::new().enable_ssh().with_pipeline(Vec::new()).build().is_err());
+    let duplicated = vec![DetectionStage::Magic, DetectionStage::Magic];
+    assert!(DetectorBuilder::new().enable_ssh().with_pipeline(duplicated).build().is_err());
+}
+
+#[test]
+fn test_classify_prefix_bytes_examined() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_http2()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    // SSH只需检查 "SSH-" 四个字节
+    let outcome = detector.classify_prefix(b"SSH-2.0-OpenSSH_9.6\r\n");
+    assert_eq!(outcome.protocol, ProtocolType::SSH);
+    assert_eq!(outcome.bytes_examined, 4);
+    
+    // HTTP/2需要完整的24字节连接前言
+    let mut data = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    let outcome = detector.classify_prefix(&data);
+    assert_eq!(outcome.protocol, ProtocolType::HTTP2);
+    assert_eq!(outcome.bytes_examined, 24);
+    assert!(outcome.confidence >= 0.9);
+    
+    // 无法识别时返回Unknown
+    let outcome = detector.classify_prefix(&[0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(outcome.protocol, ProtocolType::Unknown);
+    assert_eq!(outcome.confidence, 0.0);
+}
+
+#[test]
+fn test_detect_incremental_accumulates_chunks() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::error::{DetectorError, Result};
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    let mut state = IncrementalState::new();
+    match detector.detect_incremental(&mut state, b"SS").unwrap() {
+        DetectionProgress::NeedMore { min_additional } => {
+            assert_eq!(min_additional, detector.min_probe_size() - 2);
+        }
+        other => panic!("unexpected progress: {:?}", other),
+    }
+    
+    let progress = detector.detect_incremental(&mut state, b"H-2.0-OpenSSH_9.6\r\n").unwrap();
+    match &progress {
+        DetectionProgress::Detected(result) => assert_eq!(result.protocol_type(), ProtocolType::SSH),
+        other => panic!("unexpected progress: {:?}", other),
+    }
+    assert!(state.is_finished());
+    
+    // 得出结论后不再追加数据
+    let buffered = state.buffered().len();
+    assert!(detector.detect_incremental(&mut state, b"more").unwrap().is_finished());
+    assert_eq!(state.buffered().len(), buffered);
+    
+    // 缓冲区写满仍无法识别时返回Failed
+    #[derive(Debug)]
+    struct NeverDetector;
+    
+    impl ProtocolDetector for NeverDetector {
+        fn detect(&self, _data: &[u8]) -> Result<DetectionResult> {
+            Err(DetectorError::NoProtocolDetected("never".to_string()))
+        }
+        
+        fn min_probe_size(&self) -> usize {
+            4
+        }
+        
+        fn max_probe_size(&self) -> usize {
+            8
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            Vec::new()
+        }
+        
+        fn name(&self) -> &str {
+            "NeverDetector"
+        }
+    }
+    
+    let mut state = IncrementalState::new();
+    assert!(matches!(
+        NeverDetector.detect_incremental(&mut state, &[0u8; 5]).unwrap(),
+        DetectionProgress::NeedMore { min_additional: 1 }
+    ));
+    assert!(matches!(
+        NeverDetector.detect_incremental(&mut state, &[0u8; 16]).unwrap(),
+        DetectionProgress::Failed
+    ));
+    assert_eq!(state.buffered().len(), 8);
+}
+
+#[test]
+fn test_effective_confidence_reflects_completeness() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_http2()
+        .build()
+        .unwrap();
+    
+    // 仅有连接前言：首个帧尚未到达
+    let preface = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    let partial = detector.detect(&preface).unwrap();
+    assert_eq!(partial.protocol_type(), ProtocolType::HTTP2);
+    assert!(partial.completeness < 1.0);
+    
+    // 连接前言 + SETTINGS + WINDOW_UPDATE + DATA，共200字节
+    let mut full = preface.clone();
+    full.extend_from_slice(&[0x00, 0x00, 0x0c, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    full.extend_from_slice(&[0x00, 0x03, 0x00, 0x00, 0x00, 0x64, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00]);
+    full.extend_from_slice(&[0x00, 0x00, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x01]);
+    let data_len = 200 - full.len() - 9;
+    full.extend_from_slice(&[0x00, 0x00, data_len as u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    full.resize(200, 0);
+    let complete = detector.detect(&full).unwrap();
+    assert_eq!(complete.protocol_type(), ProtocolType::HTTP2);
+    assert_eq!(complete.completeness, 1.0);
+    
+    assert!(partial.effective_confidence() < complete.effective_confidence());
+    assert!(partial.effective_confidence() <= partial.confidence());
+}
+
+#[test]
+fn test_static_detector_matches_dynamic() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::StaticDetector;
+    
+    let protocols = [ProtocolType::HTTP1_1, ProtocolType::HTTP2, ProtocolType::TLS, ProtocolType::SSH];
+    let fixed = StaticDetector::new(protocols);
+    let dynamic: Box<dyn ProtocolDetector> = Box::new(
+        DetectorBuilder::new()
+            .enable_http()
+            .enable_http2()
+            .enable_tls()
+            .enable_ssh()
+            .build()
+            .unwrap(),
+    );
+    
+    let mut tls = vec![0x16, 0x03, 0x01, 0x00, 0x2d, 0x01, 0x00, 0x00, 0x29, 0x03, 0x03];
+    tls.extend_from_slice(&[0x11; 32]);
+    tls.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+    let samples: Vec<(&[u8], Option<ProtocolType>)> = vec![
+        (b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n", Some(ProtocolType::HTTP1_1)),
+        (b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x00\x04\x00\x00\x00\x00\x00", Some(ProtocolType::HTTP2)),
+        (&tls, Some(ProtocolType::TLS)),
+        (b"SSH-2.0-OpenSSH_9.6\r\n", Some(ProtocolType::SSH)),
+        (&[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c], None),
+    ];
+    
+    for (data, expected) in samples {
+        let static_protocol = fixed.detect(data).map(|(protocol, _)| protocol);
+        let dynamic_protocol = dynamic.detect(data).ok().map(|result| result.protocol_type());
+        assert_eq!(static_protocol, expected);
+        assert_eq!(static_protocol, dynamic_protocol);
+    }
+}
+
+#[test]
+fn test_register_custom_protocols_reports_name() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::magic::CustomSignatureBuilder;
+    
+    let acme = CustomSignatureBuilder::new(ProtocolType::Custom, "ACME RPC")
+        .with_magic_string("ACME")
+        .build();
+    let game = CustomSignatureBuilder::new(ProtocolType::Custom, "Game handshake")
+        .with_magic_bytes(vec![0xca, 0xfe, 0x01])
+        .with_confidence(0.95)
+        .build();
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .register_custom_protocol("acme-rpc", acme.clone())
+        .unwrap()
+        .register_custom_protocol("game", game)
+        .unwrap()
+        .build()
+        .unwrap();
+    
+    let mut data = b"ACME/1 hello".to_vec();
+    data.resize(32, 0);
+    let result = detector.detect(&data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::Custom);
+    assert_eq!(result.custom_name(), Some("acme-rpc"));
+    
+    let mut data = vec![0xca, 0xfe, 0x01];
+    data.resize(32, 0x20);
+    let result = detector.detect(&data).unwrap();
+    assert_eq!(result.custom_name(), Some("game"));
+    
+    let registry = detector.custom_protocols();
+    assert_eq!(registry.id("acme-rpc"), Some(0));
+    assert_eq!(registry.name(1), Some("game"));
+    
+    // 名称冲突在注册时报错
+    let duplicate = DetectorBuilder::new()
+        .register_custom_protocol("acme-rpc", acme.clone())
+        .unwrap()
+        .register_custom_protocol("acme-rpc", acme);
+    assert!(duplicate.is_err());
+}
+
+#[test]
+fn test_detection_result_lists_contributing_probes() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    for data in [&b"SSH-2.0-OpenSSH_9.6\r\n"[..], b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n"] {
+        let result = detector.detect(data).unwrap();
+        assert!(!result.contributing_probes.is_empty());
+        assert!(result.contributing_probes.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+}
+
+#[test]
+fn test_detect_via_peek_leaves_bytes_readable() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::error::Result;
+    
+    /// 内存中的传输层，`peek` 只复制不消费
+    struct MemoryTransport {
+        inbound: Vec<u8>,
+        position: usize,
+    }
+    
+    impl Transport for MemoryTransport {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let remaining = &self.inbound[self.position..];
+            let n = remaining.len().min(buf.len());
+            buf[..n].copy_from_slice(&remaining[..n]);
+            self.position += n;
+            Ok(n)
+        }
+        
+        fn write(&mut self, data: &[u8]) -> Result<usize> {
+            Ok(data.len())
+        }
+        
+        fn peek(&self, size: usize) -> Result<Vec<u8>> {
+            let remaining = &self.inbound[self.position..];
+            Ok(remaining[..remaining.len().min(size)].to_vec())
+        }
+        
+        fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+        
+        fn transport_type(&self) -> &str {
+            "memory"
+        }
+    }
+    
+    let agent = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build_agent()
+        .unwrap();
+    
+    let payload = b"SSH-2.0-OpenSSH_9.6\r\nkey exchange follows".to_vec();
+    let mut transport = MemoryTransport { inbound: payload.clone(), position: 0 };
+    
+    let result = agent.detect_via_peek(&transport, 21).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::SSH);
+    
+    // 升级后的处理器仍能读到全部字节
+    let mut buf = vec![0u8; payload.len()];
+    assert_eq!(transport.read(&mut buf).unwrap(), payload.len());
+    assert_eq!(buf, payload);
+    
+    // 没有数据时返回错误
+    assert!(agent.detect_via_peek(&transport, 21).is_err());
+}
+
+#[test]
+fn test_protocol_priority_breaks_confidence_ties() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    // 按升级头识别WebSocket握手的启发式探测器
+    let heuristic = || {
+        FixedProbe::new("UpgradeHeuristic", ProtocolType::WebSocket, 0.75).when_contains(b"upgrade: websocket")
+    };
+    
+    let request = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+    
+    // 把HTTP/1.1的魔法包和被动探测结果压到与启发式相同的0.75
+    let build = |priority: &[ProtocolType]| {
+        DetectorBuilder::new()
+            .enable_http()
+            .enable_websocket()
+            .with_min_confidence(0.7)
+            .with_confidence_bounds(DetectionMethod::SimdAccelerated, 0.0, 0.75)
+            .with_confidence_bounds(DetectionMethod::Passive, 0.0, 0.75)
+            .add_custom_probe(Box::new(heuristic()))
+            .with_protocol_priority(priority)
+            .build()
+            .unwrap()
+    };
+    
+    for _ in 0..8 {
+        let result = build(&[ProtocolType::WebSocket]).detect(request).unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::WebSocket);
+        assert!((result.confidence() - 0.75).abs() < 1e-6);
+        
+        let result = build(&[ProtocolType::HTTP1_1, ProtocolType::WebSocket]).detect(request).unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    }
+}
+
+#[test]
+fn test_magic_signatures_json_round_trip() {
+    use psi_detector::core::magic::{CustomSignatureBuilder, MagicDetector, MagicSignature};
+    
+    let signature = CustomSignatureBuilder::new(ProtocolType::Custom, "ACME framed RPC")
+        .with_magic_bytes(vec![0xac, 0x4d, 0x45, 0x00, 0xff])
+        .with_offset(2)
+        .with_match_length(3)
+        .with_confidence(0.85)
+        .case_insensitive()
+        .build()
+        .with_custom_name("acme-rpc");
+    
+    let mut source = MagicDetector::new();
+    source.add_signature(signature);
+    let json = source.export_signatures_to_json().unwrap();
+    assert!(json.contains("\"ac4d4500ff\""));
+    
+    let exported: Vec<MagicSignature> = serde_json::from_str(&json).unwrap();
+    let restored = exported.iter().find(|s| s.description == "ACME framed RPC").unwrap();
+    assert_eq!(restored.magic_bytes, vec![0xac, 0x4d, 0x45, 0x00, 0xff]);
+    assert_eq!(restored.offset, 2);
+    assert_eq!(restored.match_length, 3);
+    assert!(!restored.case_sensitive);
+    assert_eq!(restored.custom_name.as_deref(), Some("acme-rpc"));
+    // 带结构校验的内置特征不导出
+    assert!(exported.iter().all(|s| s.protocol != ProtocolType::MQTT));
+    
+    let mut target = MagicDetector::new();
+    let only_acme = serde_json::to_string(&[restored]).unwrap();
+    assert_eq!(target.load_signatures_from_json(&only_acme).unwrap(), 1);
+    let info = target.deep_detect(&[0x01, 0x02, 0xac, 0x6d, 0x65, 0x99])
+        .into_iter()
+        .find(|info| info.protocol_type == ProtocolType::Custom)
+        .expect("导入的特征应生效");
+    assert_eq!(info.custom_name.as_deref(), Some("acme-rpc"));
+    
+    // 非法特征整体拒绝
+    let invalid = r#"[{"protocol":"Custom","magic_bytes":"abcd","offset":0,"match_length":3,"confidence":0.9,"description":"bad","case_sensitive":true}]"#;
+    assert!(target.load_signatures_from_json(invalid).is_err());
+    assert!(target.load_signatures_from_json("not json").is_err());
+}
+
+#[test]
+fn test_consistent_hash_remaps_only_removed_backend_keys() {
+    let backends: Vec<String> = (1..=5).map(|i| format!("backend-{}", i)).collect();
+    let balancer = LoadBalancer::new(LoadBalancerConfig {
+        is_load_balancer: true,
+        backend_instances: backends.clone(),
+        strategy: LoadBalanceStrategy::ConsistentHash,
+    });
+    
+    let keys: Vec<String> = (0..2000).map(|i| format!("10.0.{}.{}:443", i / 256, i % 256)).collect();
+    let before: Vec<String> = keys.iter()
+        .map(|key| balancer.select_backend_for_key(key).expect("应选中后端"))
+        .collect();
+    
+    // 同一标识映射稳定，且所有后端都分到了客户端
+    assert_eq!(balancer.select_backend_for_key(&keys[0]), Some(before[0].clone()));
+    for backend in &backends {
+        let share = before.iter().filter(|selected| *selected == backend).count();
+        assert!(share > 2000 / 5 / 2, "{} 只分到 {} 个客户端", backend, share);
+    }
+    
+    assert!(balancer.remove_backend("backend-3").unwrap());
+    assert!(!balancer.remove_backend("backend-3").unwrap());
+    let mut moved = 0;
+    for (key, previous) in keys.iter().zip(&before) {
+        let current = balancer.select_backend_for_key(key).unwrap();
+        if &current != previous {
+            // 只有原先落在被移除后端上的客户端才会迁移
+            assert_eq!(previous, "backend-3");
+            moved += 1;
+        } else {
+            assert_ne!(current, "backend-3");
+        }
+    }
+    // 约 1/N 的客户端被重新分配
+    assert!(moved > 2000 / 10 && moved < 2000 * 3 / 10, "迁移了 {} 个客户端", moved);
+    
+    // 加回后恢复原有映射
+    balancer.add_backend("backend-3", 1).unwrap();
+    for (key, previous) in keys.iter().zip(&before) {
+        assert_eq!(&balancer.select_backend_for_key(key).unwrap(), previous);
+    }
+}
+
+#[test]
+fn test_detection_result_reports_alternatives() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::MatchMode;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http2()
+        .enable_grpc()
+        .with_match_mode(MatchMode::BestMatch)
+        .build()
+        .unwrap();
+    
+    // 携带gRPC content-type的HTTP/2连接同时匹配gRPC和HTTP/2
+    let mut data = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    data.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(b"\x00\x00\x20\x01\x04\x00\x00\x00\x01application/grpc content-type te trailers");
+    let result = detector.detect(&data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::GRPC);
+    assert!(result.alternatives.iter().any(|&(protocol, _)| protocol == ProtocolType::HTTP2));
+    assert!(result.alternatives.iter().all(|&(protocol, _)| protocol != ProtocolType::GRPC));
+    
+    let json = serde_json::to_string(&result).unwrap();
+    let restored: DetectionResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.alternatives, result.alternatives);
+    
+    // 旧版本序列化的数据没有该字段
+    let mut legacy: serde_json::Value = serde_json::from_str(&json).unwrap();
+    legacy.as_object_mut().unwrap().remove("alternatives");
+    let restored: DetectionResult = serde_json::from_value(legacy).unwrap();
+    assert!(restored.alternatives.is_empty());
+}
+
+#[test]
+fn test_hard_deadline_returns_timeout_error() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::error::{DetectorError, Result};
+    
+    /// 耗时超过截止时间后仍给出低置信度猜测的探测器
+    struct SlowProbe;
+    
+    impl ProtocolProbe for SlowProbe {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::Custom]
+        }
+        
+        fn probe(&self, _data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            std::thread::sleep(Duration::from_millis(30));
+            Ok(Some(ProtocolInfo::new(ProtocolType::Custom, 0.8)))
+        }
+    }
+    
+    let build = |hard: bool| {
+        let builder = DetectorBuilder::new()
+            .enable_custom()
+            .with_timeout(Duration::from_millis(10))
+            .add_custom_probe(Box::new(SlowProbe));
+        let builder = if hard { builder.with_hard_deadline(Duration::from_millis(10)) } else { builder };
+        builder.build().unwrap()
+    };
+    let data = [0x42u8; 64];
+    
+    // 普通超时只停止后续探测，仍返回已有结果
+    assert_eq!(build(false).detect(&data).unwrap().protocol_type(), ProtocolType::Custom);
+    
+    // 硬性截止时间明确报告超时
+    match build(true).detect(&data) {
+        Err(DetectorError::Timeout { timeout_ms }) => assert_eq!(timeout_ms, 10),
+        other => panic!("应返回超时错误: {:?}", other),
+    }
+    
+    let err = DetectorBuilder::new()
+        .enable_custom()
+        .with_hard_deadline(Duration::ZERO)
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, DetectorError::ConfigError { .. }));
+}
+
+#[test]
+fn test_http_request_info_exposed_on_http1_detection() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::fingerprint::HttpRequestInfo;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .build()
+        .unwrap();
+    
+    let data = b"POST /api/v1/orders?dry_run=1 HTTP/1.1\r\nHost: shop.example.com:8080\r\nContent-Length: 0\r\n\r\n";
+    let result = detector.detect(data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert_eq!(result.http_request(), Some(HttpRequestInfo {
+        method: "POST".to_string(),
+        path: "/api/v1/orders?dry_run=1".to_string(),
+        host: Some("shop.example.com:8080".to_string()),
+        version: "1.1".to_string(),
+    }));
+    
+    // Host头尚未完整接收
+    let partial = HttpRequestInfo::parse(b"GET / HTTP/1.0\r\nHost: exam").unwrap();
+    assert_eq!((partial.method.as_str(), partial.version.as_str(), partial.host), ("GET", "1.0", None));
+    
+    assert!(HttpRequestInfo::parse(b"GET / HTTP/1.1").is_err());
+    assert!(HttpRequestInfo::parse(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n").is_err());
+    assert!(HttpRequestInfo::parse(b"SSH-2.0-OpenSSH_9.6\r\n").is_err());
+}
+
+#[test]
+fn test_protocol_min_bytes_allows_short_prefix_detection() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::error::DetectorError;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .enable_tls()
+        .with_protocol_min_bytes(ProtocolType::SSH, 4)
+        .build()
+        .unwrap();
+    assert_eq!(detector.absolute_min(), 4);
+    assert_eq!(detector.protocol_min_bytes(ProtocolType::TLS), 16);
+    
+    // SSH从前缀即可识别
+    assert_eq!(detector.detect(b"SSH-").unwrap().protocol_type(), ProtocolType::SSH);
+    
+    // 其余协议仍需达到全局最少字节数
+    assert!(matches!(detector.detect(b"GET / HTTP"), Err(DetectorError::NeedMoreData(16))));
+    assert!(matches!(detector.detect(b"\x16\x03\x01\x02\x00\x01"), Err(DetectorError::NeedMoreData(16))));
+    assert!(matches!(detector.detect(b"SS"), Err(DetectorError::InsufficientData(_))));
+    
+    // 增量探测在短读取后即可得出SSH结论
+    let mut state = IncrementalState::new();
+    assert!(matches!(
+        detector.detect_incremental(&mut state, b"SSH-").unwrap(),
+        DetectionProgress::Detected(ref result) if result.protocol_type() == ProtocolType::SSH
+    ));
+}
+
+#[test]
+fn test_build_agent_with_role_configures_agent_fluently() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::upgrade::{HttpUpgrader, ProtocolUpgrader};
+    use std::sync::Arc;
+    
+    let upgrader: Arc<dyn ProtocolUpgrader> = Arc::new(HttpUpgrader::new());
+    let agent = DetectorBuilder::new()
+        .with_instance_id("edge-1".to_string())
+        .enable_http()
+        .enable_tls()
+        .with_load_balancer_config(LoadBalancerConfig {
+            is_load_balancer: true,
+            backend_instances: vec!["a".to_string(), "b".to_string()],
+            strategy: LoadBalanceStrategy::RoundRobin,
+        })
+        .with_upgrader(upgrader)
+        .build_agent_with_role(Role::Client)
+        .unwrap();
+    
+    // 在 `with_instance_id` 之后启用的协议同样写入Agent配置
+    let config = agent.config();
+    assert_eq!(config.role, Role::Client);
+    assert_eq!(config.instance_id, "edge-1");
+    assert!(config.enabled_protocols.contains(&ProtocolType::HTTP1_1));
+    assert!(config.enabled_protocols.contains(&ProtocolType::TLS));
+    assert!(agent.upgrader().is_some());
+    assert!(agent.select_backend().is_some());
+    
+    // 禁用协议升级时不使用升级器
+    let agent = DetectorBuilder::new()
+        .enable_http()
+        .with_upgrader(Arc::new(HttpUpgrader::new()))
+        .with_role(Role::Server)
+        .disable_protocol_upgrade()
+        .build_agent()
+        .unwrap();
+    assert!(agent.upgrader().is_none());
+}
+
+#[test]
+fn test_agent_rejects_results_below_min_confidence() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::error::DetectorError;
+    use std::sync::Arc;
+    
+    // 内部探测器接受低置信度的首字节启发式结果
+    let detector = || -> Arc<dyn ProtocolDetector> {
+        Arc::new(DetectorBuilder::new().enable_http().with_min_confidence(0.1).build().unwrap())
+    };
+    let data = b"Hello there this is text\r\n";
+    assert!(detector().detect(data).unwrap().confidence() < 0.7);
+    
+    let agent = Agent::new(AgentConfig::default(), detector(), None);
+    match agent.detect(data) {
+        Err(DetectorError::LowConfidence { protocol, confidence, threshold }) => {
+            assert_eq!(protocol, ProtocolType::HTTP1_1);
+            assert!(confidence < threshold);
+            assert_eq!(threshold, 0.7);
+        }
+        other => panic!("应返回LowConfidence错误: {:?}", other),
+    }
+    assert!(agent.detect(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").is_ok());
+    
+    let config = AgentConfig::builder()
+        .return_low_confidence_as_unknown(true)
+        .build()
+        .unwrap();
+    let agent = Agent::new(config, detector(), None);
+    let result = agent.detect(data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::Unknown);
+    assert_eq!(result.protocol_info.metadata.get("best_guess").map(String::as_str), Some("HTTP/1.1"));
+    
+    // 配置了兜底协议时低置信度结果同样回退
+    let config = AgentConfig::builder()
+        .fallback_protocol(ProtocolType::TLS)
+        .build()
+        .unwrap();
+    let agent = Agent::new(config, detector(), None);
+    assert_eq!(agent.detect(data).unwrap().protocol_type(), ProtocolType::TLS);
+}
+
+#[test]
+fn test_detection_cache_returns_cached_high_confidence_results() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::cache::DetectionCache;
+    use psi_detector::ProtocolAgent;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .with_min_confidence(0.1)
+        .with_detection_cache(16)
+        .build()
+        .unwrap();
+    let request = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    
+    let first = detector.detect(request).unwrap();
+    assert_ne!(first.detection_method, DetectionMethod::Cached);
+    let second = detector.detect(request).unwrap();
+    assert_eq!(second.detection_method, DetectionMethod::Cached);
+    assert_eq!(second.protocol_type(), first.protocol_type());
+    assert_eq!(detector.cache_counters(), Some((1, 1)));
+    
+    // 命中时写入本次调用的关联ID，早期数据不查询缓存
+    let correlated = detector
+        .detect_with_context(request, &DetectionContext::new().with_correlation_id("req-7"))
+        .unwrap();
+    assert_eq!(correlated.detection_method, DetectionMethod::Cached);
+    assert_eq!(correlated.protocol_info.metadata.get("correlation_id").map(String::as_str), Some("req-7"));
+    let early = detector
+        .detect_with_context(request, &DetectionContext::new().with_early_data(true))
+        .unwrap();
+    assert_ne!(early.detection_method, DetectionMethod::Cached);
+    
+    // 低置信度的猜测不写入缓存
+    let guess = b"Hello there this is text\r\n";
+    assert!(detector.detect(guess).unwrap().confidence() < 0.9);
+    assert_ne!(detector.detect(guess).unwrap().detection_method, DetectionMethod::Cached);
+    assert_eq!(detector.detection_cache().map(DetectionCache::len), Some(1));
+    
+    // Agent统计缓存命中和未命中
+    let agent = DetectorBuilder::new()
+        .enable_ssh()
+        .with_detection_cache(16)
+        .build_agent()
+        .unwrap();
+    agent.detect(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+    agent.detect(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+    let stats = agent.state().unwrap().detection_stats;
+    assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+    assert_eq!(stats.cache_hit_rate(), 0.5);
+    
+    assert!(DetectorBuilder::new().enable_http().with_detection_cache(0).build().is_err());
+}
+
+#[test]
+fn test_detection_cache_hits_reannotate_request_metadata() {
+    use psi_detector::builder::DetectorBuilder;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .with_detection_cache(16)
+        .build()
+        .unwrap();
+    
+    // 两个请求的前64字节相同，路径和Host在之后才不同
+    let prefix = format!("GET /{}", "a".repeat(70));
+    let first = format!("{}/one HTTP/1.1\r\nHost: one.example\r\n\r\n", prefix);
+    let second = format!("{}/two HTTP/1.1\r\nHost: two.example\r\n\r\n", prefix);
+    
+    let result = detector.detect(first.as_bytes()).unwrap();
+    assert_ne!(result.detection_method, DetectionMethod::Cached);
+    let result = detector.detect(second.as_bytes()).unwrap();
+    assert_eq!(result.detection_method, DetectionMethod::Cached);
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    
+    let metadata = &result.protocol_info.metadata;
+    assert_eq!(metadata.get("http_path").map(String::as_str), Some(format!("{}/two", &prefix[4..]).as_str()));
+    assert_eq!(metadata.get("http_host").map(String::as_str), Some("two.example"));
+    assert_eq!(metadata.get("http_method").map(String::as_str), Some("GET"));
+}
+
+#[test]
+fn test_detection_cache_evicts_least_recently_used() {
+    use psi_detector::core::cache::DetectionCache;
+    
+    let result = |protocol| {
+        DetectionResult::new(ProtocolInfo::new(protocol, 0.95), Duration::ZERO, DetectionMethod::Passive, "test".to_string())
+    };
+    let cache = DetectionCache::new(2).with_key_bytes(4);
+    cache.insert(b"SSH-2.0-a", &result(ProtocolType::SSH));
+    cache.insert(b"GET /", &result(ProtocolType::HTTP1_1));
+    
+    // 前缀相同的数据共享结论，元数据不进入缓存
+    let mut tagged = result(ProtocolType::SSH);
+    tagged.protocol_info.add_metadata("ssh_software", "a");
+    cache.insert(b"SSH-2.0-a", &tagged);
+    let hit = cache.get(b"SSH-2.0-b").unwrap();
+    assert_eq!(hit.protocol_type(), ProtocolType::SSH);
+    assert!(hit.protocol_info.metadata.is_empty());
+    cache.insert(b"\x16\x03\x01\x00", &result(ProtocolType::TLS));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(b"GET /").is_none());
+    assert_eq!(cache.get(b"SSH-").map(|r| r.protocol_type()), Some(ProtocolType::SSH));
+    assert_eq!(cache.get(b"\x16\x03\x01\x00\x05").map(|r| r.protocol_type()), Some(ProtocolType::TLS));
+    assert_eq!((cache.hits(), cache.misses()), (3, 1));
+}
+
+#[test]
+fn test_ambiguity_margin_fails_closed_on_near_ties() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::probe::{MatchMode, ProbeAggregator, ProbeConfig};
+    use psi_detector::error::DetectorError;
+    
+    // 升级头启发式给出的WebSocket置信度略低于被压到0.75的HTTP/1.1
+    let heuristic = || {
+        FixedProbe::new("UpgradeHeuristic", ProtocolType::WebSocket, 0.72).when_contains(b"upgrade: websocket")
+    };
+    
+    let request = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+    let build = |margin: f32| {
+        DetectorBuilder::new()
+            .enable_http()
+            .enable_websocket()
+            .with_min_confidence(0.7)
+            .with_match_mode(MatchMode::BestMatch)
+            .with_confidence_bounds(DetectionMethod::SimdAccelerated, 0.0, 0.75)
+            .with_confidence_bounds(DetectionMethod::Passive, 0.0, 0.75)
+            .add_custom_probe(Box::new(heuristic()))
+            .with_ambiguity_margin(margin)
+            .build()
+            .unwrap()
+    };
+    
+    // 默认不判定歧义，总是选出最佳结果
+    let result = build(0.0).detect(request).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    
+    match build(0.05).detect(request) {
+        Err(err @ DetectorError::Ambiguous { .. }) => {
+            assert_eq!(err.error_code(), 1015);
+            let DetectorError::Ambiguous { candidates } = err else { unreachable!() };
+            let protocols: Vec<ProtocolType> = candidates.iter().map(|(protocol, _)| *protocol).collect();
+            assert_eq!(protocols, vec![ProtocolType::HTTP1_1, ProtocolType::WebSocket]);
+            assert!(candidates.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        }
+        other => panic!("应返回Ambiguous错误: {:?}", other),
+    }
+    
+    // 差距超过阈值或只有一个协议时正常返回
+    assert_eq!(build(0.01).detect(request).unwrap().protocol_type(), ProtocolType::HTTP1_1);
+    let plain = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    assert_eq!(build(0.05).detect(plain).unwrap().protocol_type(), ProtocolType::HTTP1_1);
+    
+    // 低于最低置信度的候选不参与判定
+    let aggregator = ProbeAggregator::new(ProbeConfig { ambiguity_margin: 0.2, ..ProbeConfig::default() });
+    let candidates = vec![
+        ProtocolInfo::new(ProtocolType::TLS, 0.9),
+        ProtocolInfo::new(ProtocolType::TLS, 0.85),
+        ProtocolInfo::new(ProtocolType::QUIC, 0.75),
+    ];
+    assert!(aggregator.ambiguous_candidates(&candidates).is_none());
+    let candidates = [candidates, vec![ProtocolInfo::new(ProtocolType::SSH, 0.8)]].concat();
+    assert_eq!(
+        aggregator.ambiguous_candidates(&candidates),
+        Some(vec![(ProtocolType::TLS, 0.9), (ProtocolType::SSH, 0.8)])
+    );
+}
@@ -0,0 +1,75 @@
+//! 指纹模块测试
+
+use psi_detector::core::fingerprint::{Ja3Fingerprinter, Ja3Kind};
+
+/// 构造握手记录
+fn handshake_record(handshake_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut handshake = vec![handshake_type];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(body);
+    
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+/// 编码扩展列表
+fn encode_extensions(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (extension_type, extension_data) in extensions {
+        data.extend_from_slice(&extension_type.to_be_bytes());
+        data.extend_from_slice(&(extension_data.len() as u16).to_be_bytes());
+        data.extend_from_slice(extension_data);
+    }
+    let mut encoded = (data.len() as u16).to_be_bytes().to_vec();
+    encoded.extend_from_slice(&data);
+    encoded
+}
+
+#[test]
+fn test_ja3_client_hello_skips_grease() {
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]);
+    body.push(0x00);
+    // 密码套件：GREASE、TLS_AES_128_GCM_SHA256、TLS_AES_256_GCM_SHA384
+    body.extend_from_slice(&[0x00, 0x06, 0x0a, 0x0a, 0x13, 0x01, 0x13, 0x02]);
+    body.extend_from_slice(&[0x01, 0x00]);
+    body.extend_from_slice(&encode_extensions(&[
+        (0x1a1a, Vec::new()),
+        (0x0000, b"\x00\x0e\x00\x00\x0bexample.com".to_vec()),
+        (0x000a, vec![0x00, 0x06, 0x2a, 0x2a, 0x00, 0x1d, 0x00, 0x17]),
+        (0x000b, vec![0x01, 0x00]),
+    ]));
+    
+    let result = Ja3Fingerprinter::new().fingerprint(&handshake_record(0x01, &body)).unwrap();
+    assert_eq!(result.kind, Ja3Kind::Client);
+    assert_eq!(result.raw, "771,4865-4866,0-10-11,29-23,0");
+    assert_eq!(result.hash, "38eaca597c62da4c9db8cfad482f14ad");
+}
+
+#[test]
+fn test_ja3s_server_hello() {
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x24; 32]);
+    body.push(0x00);
+    body.extend_from_slice(&[0x13, 0x01, 0x00]);
+    body.extend_from_slice(&encode_extensions(&[(0x002b, vec![0x03, 0x04])]));
+    
+    let result = Ja3Fingerprinter::new().fingerprint(&handshake_record(0x02, &body)).unwrap();
+    assert_eq!(result.kind, Ja3Kind::Server);
+    assert_eq!(result.raw, "771,4865,43");
+    assert_eq!(result.hash, "cce84e7a8b742462e40afb585a3e3ccc");
+}
+
+#[test]
+fn test_ja3_rejects_invalid_input() {
+    let fingerprinter = Ja3Fingerprinter::new();
+    assert!(fingerprinter.fingerprint(b"GET / HTTP/1.1\r\n\r\n").is_err());
+    assert!(fingerprinter.fingerprint(&[0x16, 0x03, 0x01]).is_err());
+    
+    // 握手消息被截断
+    let mut record = handshake_record(0x01, &[0x03, 0x03, 0x00]);
+    record.truncate(record.len() - 1);
+    assert!(fingerprinter.fingerprint(&record).is_err());
+}
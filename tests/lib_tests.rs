@@ -17,4 +17,91 @@ fn test_basic_detector_creation() {
 fn test_yuri_theme() {
     let _detector = yuri::psychic_detection();
     let _upgrade = yuri::mind_control();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_enable_websocket_only_enables_websocket() {
+    let detector = DetectorBuilder::new().enable_websocket().build().unwrap();
+    assert_eq!(detector.enabled_protocols(), &[ProtocolType::WebSocket]);
+}
+
+#[test]
+fn test_enable_all_covers_new_protocols() {
+    let detector = DetectorBuilder::new().enable_all().build().unwrap();
+    let enabled = detector.enabled_protocols();
+    for protocol in [
+        ProtocolType::IRC,
+        ProtocolType::XMPP,
+        ProtocolType::VNC,
+        ProtocolType::RDP,
+        ProtocolType::WebTransport,
+        ProtocolType::PostgreSQL,
+        ProtocolType::MySQL,
+        ProtocolType::SMTP,
+        ProtocolType::IMAP,
+        ProtocolType::POP3,
+        ProtocolType::FTP,
+        ProtocolType::Thrift,
+        ProtocolType::Avro,
+        ProtocolType::Memcached,
+        ProtocolType::ClickHouse,
+        ProtocolType::OpenVPN,
+        ProtocolType::IKE,
+    ] {
+        assert!(enabled.contains(&protocol), "enable_all 应包含 {:?}", protocol);
+    }
+    
+    // 有歧义的格式需显式启用
+    assert!(!enabled.contains(&ProtocolType::Protobuf));
+}
+
+#[test]
+fn test_builder_presets() {
+    use psi_detector::core::probe::{DetectionStage, MatchMode};
+    use std::time::Duration;
+    
+    let edge = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .preset(Preset::EdgeProxy)
+        .build()
+        .unwrap();
+    assert_eq!(edge.probe_config().pipeline, vec![DetectionStage::Magic, DetectionStage::Passive]);
+    assert_eq!(edge.probe_config().match_mode, MatchMode::FirstMatch);
+    assert_eq!(edge.probe_config().max_probe_time, Duration::from_millis(20));
+    assert_eq!(edge.detection_config().min_confidence, 0.9);
+    assert!(!edge.detection_config().enable_heuristic);
+    assert_eq!(
+        edge.detect(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap().protocol_type(),
+        ProtocolType::HTTP1_1
+    );
+    
+    let deep = DetectorBuilder::new()
+        .enable_http2()
+        .enable_grpc()
+        .preset(Preset::DeepInspection)
+        .build()
+        .unwrap();
+    assert_eq!(deep.probe_config().match_mode, MatchMode::BestMatch);
+    assert!(deep.probe_config().run_global_probes);
+    assert!(deep.detection_config().enable_heuristic);
+    assert_eq!(deep.probe_config().buffer_size, 64 * 1024);
+    
+    // 深度检测会填充备选协议
+    let mut data = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    data.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(b"\x00\x00\x20\x01\x04\x00\x00\x00\x01application/grpc content-type te trailers");
+    let result = deep.detect(&data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::GRPC);
+    assert!(result.alternatives.iter().any(|&(protocol, _)| protocol == ProtocolType::HTTP2));
+    
+    // 预设之后的链式调用覆盖单项配置
+    let custom = DetectorBuilder::new()
+        .enable_http()
+        .preset(Preset::EdgeProxy)
+        .with_min_confidence(0.75)
+        .build()
+        .unwrap();
+    assert_eq!(custom.detection_config().min_confidence, 0.75);
+    assert_eq!(custom.probe_config().max_probe_time, Duration::from_millis(20));
+}
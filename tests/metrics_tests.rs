@@ -0,0 +1,59 @@
+//! 指标导出测试
+#![cfg(feature = "metrics")]
+
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::core::detector::DetectionStats;
+use psi_detector::core::ProtocolType;
+use psi_detector::metrics::{render_prometheus, MetricsSnapshot};
+use psi_detector::ProtocolAgent;
+use std::time::Duration;
+
+#[test]
+fn test_render_prometheus_lines() {
+    let mut stats = DetectionStats::new();
+    stats.record_success(ProtocolType::HTTP1_1, Duration::from_millis(2));
+    stats.record_success(ProtocolType::HTTP1_1, Duration::from_millis(4));
+    stats.record_success(ProtocolType::SSH, Duration::from_millis(3));
+    stats.record_failure(Duration::from_millis(3));
+    
+    let text = render_prometheus(&stats);
+    let lines: Vec<&str> = text.lines().collect();
+    assert!(lines.contains(&"psi_detector_detections_total 4"));
+    assert!(lines.contains(&"psi_detector_detection_success_rate 0.75"));
+    assert!(lines.contains(&"# TYPE psi_detector_detections_total counter"));
+    
+    let http = lines.iter().position(|l| *l == "psi_detector_protocol_detections_total{protocol=\"HTTP1_1\"} 2").unwrap();
+    let ssh = lines.iter().position(|l| *l == "psi_detector_protocol_detections_total{protocol=\"SSH\"} 1").unwrap();
+    assert!(http < ssh);
+    
+    // 每个样本行都是 `名称[标签] 数值`
+    for line in lines.iter().filter(|l| !l.starts_with('#')) {
+        let (_, value) = line.rsplit_once(' ').unwrap();
+        assert!(value.parse::<f64>().is_ok(), "{}", line);
+    }
+}
+
+#[test]
+fn test_agent_metrics_snapshot_tracks_detections() {
+    let agent = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build_agent()
+        .unwrap();
+    
+    agent.detect(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+    agent.detect(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+    assert!(agent.detect(&[0u8; 4]).is_err());
+    
+    let snapshot = agent.metrics_snapshot().unwrap();
+    assert_eq!(snapshot.total_requests, 3);
+    assert_eq!(snapshot.detection.total_detections, 3);
+    assert_eq!(snapshot.detection.successful_detections, 2);
+    assert_eq!(snapshot.detection.protocol_counts.get(&ProtocolType::SSH), Some(&1));
+    assert!(snapshot.render_prometheus().contains("psi_detector_requests_total 3\n"));
+    
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored: MetricsSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.instance_id, snapshot.instance_id);
+    assert_eq!(restored.detection.successful_detections, 2);
+}
@@ -0,0 +1,1180 @@
+//! 探测引擎模块测试
+
+use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+use psi_detector::core::protocol::ProtocolType;
+use psi_detector::probe::{HeuristicProbe, PassiveProbe, ProbeEngine};
+
+#[test]
+fn test_binary_buffer_with_embedded_get_is_not_text() {
+    let probe = PassiveProbe::new();
+    
+    // 二进制数据中间夹带 "GET " 和 WebSocket 升级头
+    let mut data = vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x01, 0x02, 0x03];
+    data.extend_from_slice(&[0x90, 0x91, 0x92, 0x93, 0xa0, 0xa1, 0xa2, 0xa3]);
+    data.extend_from_slice(b"GET / HTTP/1.1\r\nUpgrade: websocket\r\n\r\n");
+    data.extend_from_slice(&[0xff, 0xfe, 0xfd, 0xfc]);
+    
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &data, &mut context).unwrap();
+    if let Some(info) = result {
+        assert_ne!(info.protocol_type, ProtocolType::HTTP1_1);
+        assert_ne!(info.protocol_type, ProtocolType::WebSocket);
+    }
+    
+    assert!(ProbeEngine::probe(&probe, &data).is_err());
+}
+
+#[test]
+fn test_text_validation_keeps_real_http() {
+    let probe = PassiveProbe::new();
+    let data = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    
+    let result = ProbeEngine::probe(&probe, data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+}
+
+#[test]
+fn test_text_validation_can_be_disabled() {
+    let probe = PassiveProbe::new().with_text_validation(false);
+    
+    let mut data = vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x01, 0x02, 0x03];
+    data.extend_from_slice(b"GET / HTTP/1.1\r\nUpgrade: websocket\r\n\r\n");
+    
+    let result = ProbeEngine::probe(&probe, &data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::WebSocket);
+}
+
+#[test]
+fn test_irc_registration_detected() {
+    let probe = PassiveProbe::new();
+    let mut context = ProbeContext::new();
+    let data = b"CAP LS 302\r\nNICK alice\r\nUSER alice 0 * :Alice Liddell\r\n";
+    
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到IRC");
+    assert_eq!(info.protocol_type, ProtocolType::IRC);
+    assert!(info.confidence >= 0.9);
+}
+
+#[test]
+fn test_irc_numeric_reply_detected() {
+    let probe = PassiveProbe::new();
+    let mut context = ProbeContext::new();
+    let data = b":irc.libera.chat 001 alice :Welcome to the Libera.Chat Internet Relay Chat Network alice\r\n";
+    
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到IRC");
+    assert_eq!(info.protocol_type, ProtocolType::IRC);
+}
+
+#[test]
+fn test_xmpp_stream_header_detected() {
+    let probe = PassiveProbe::new();
+    let data = b"<?xml version='1.0'?>\n  <stream:stream to='example.com' xmlns='jabber:client' \
+xmlns:stream='http://etherx.jabber.org/streams' version='1.0'>";
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到XMPP");
+    assert_eq!(info.protocol_type, ProtocolType::XMPP);
+    assert!(info.confidence >= 0.9);
+    
+    // 无XML声明的流头同样可以识别
+    let data = b"<stream:stream xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams'>";
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到XMPP");
+    assert_eq!(info.protocol_type, ProtocolType::XMPP);
+}
+
+#[test]
+fn test_plain_xml_is_not_xmpp() {
+    let probe = PassiveProbe::new();
+    let mut context = ProbeContext::new();
+    let data = b"<?xml version=\"1.0\"?><rss version=\"2.0\"><channel></channel></rss>";
+    
+    let result = ProtocolProbe::probe(&probe, data, &mut context).unwrap();
+    assert!(result.is_none());
+}
+
+/// 构造长度前缀的Protobuf消息：
+/// field 1 = 150 (varint), field 2 = "testing", field 3 = 1.0f32 (fixed32), field 4 = 1 (varint)
+fn length_prefixed_protobuf() -> Vec<u8> {
+    let message: &[u8] = &[
+        0x08, 0x96, 0x01,
+        0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g',
+        0x1d, 0x00, 0x00, 0x80, 0x3f,
+        0x20, 0x01,
+    ];
+    let mut data = (message.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(message);
+    data
+}
+
+#[test]
+fn test_length_prefixed_protobuf_detected() {
+    let probe = PassiveProbe::new();
+    let mut context = ProbeContext::new();
+    let data = length_prefixed_protobuf();
+    
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到Protobuf");
+    assert_eq!(info.protocol_type, ProtocolType::Protobuf);
+    assert!(info.confidence <= 0.75);
+}
+
+#[test]
+fn test_protobuf_length_must_match_buffer() {
+    let probe = PassiveProbe::new();
+    
+    // 长度字段比实际消息多一个字节
+    let mut data = length_prefixed_protobuf();
+    data[3] += 1;
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &data, &mut context).unwrap();
+    assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::Protobuf));
+    
+    // 字段号为0的消息不是合法Protobuf
+    let mut data = length_prefixed_protobuf();
+    data[4] = 0x00;
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &data, &mut context).unwrap();
+    assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::Protobuf));
+}
+
+#[test]
+fn test_vnc_protocol_version_detected() {
+    // RFB 握手只有12字节
+    let probe = PassiveProbe::new().with_min_data_size(12);
+    let mut context = ProbeContext::new();
+    
+    let info = ProtocolProbe::probe(&probe, b"RFB 003.008\n", &mut context)
+        .unwrap()
+        .expect("应检测到VNC");
+    assert_eq!(info.protocol_type, ProtocolType::VNC);
+    
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, b"RFB version 3", &mut context).unwrap();
+    assert!(result.is_none());
+}
+
+#[test]
+fn test_rdp_connection_request_detected() {
+    let probe = PassiveProbe::new();
+    
+    // TPKT + X.224 CR + RDP_NEG_REQ
+    let data: &[u8] = &[
+        0x03, 0x00, 0x00, 0x13,
+        0x0e, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x08, 0x00, 0x03, 0x00, 0x00, 0x00,
+    ];
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到RDP");
+    assert_eq!(info.protocol_type, ProtocolType::RDP);
+    assert!(info.confidence >= 0.95);
+    
+    // COTP长度指示与TPKT长度不一致时不认为是RDP
+    let mut broken = data.to_vec();
+    broken[4] = 0x0a;
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &broken, &mut context).unwrap();
+    assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::RDP));
+}
+
+#[test]
+fn test_quic_v2_initial_detected() {
+    let probe = PassiveProbe::new();
+    
+    // 长包头 + QUIC v2 版本号 + 8字节DCID + 空SCID，载荷避开HTTP/3帧特征
+    let mut data = vec![0xd0, 0x6b, 0x33, 0x43, 0xcf, 0x08];
+    data.extend_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    data.push(0x00);
+    data.extend_from_slice(&[0xaa; 48]);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到QUIC");
+    assert_eq!(info.protocol_type, ProtocolType::QUIC);
+    assert_eq!(info.version.as_deref(), Some("2"));
+}
+
+#[test]
+fn test_quic_version_negotiation_lists_versions() {
+    let probe = PassiveProbe::new();
+    
+    // 版本协商包：版本字段为0，随后列出支持的版本
+    let mut data = vec![0x80, 0x00, 0x00, 0x00, 0x00, 0x08];
+    data.extend_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    data.push(0x08);
+    data.extend_from_slice(&[0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff, 0x10]);
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+    data.extend_from_slice(&[0x6b, 0x33, 0x43, 0xcf]);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到QUIC");
+    assert_eq!(info.protocol_type, ProtocolType::QUIC);
+    assert_eq!(
+        info.metadata.get("quic_versions").map(String::as_str),
+        Some("[0x00000001, 0x6b3343cf]")
+    );
+}
+
+#[test]
+fn test_webtransport_settings_frame_detected() {
+    let probe = PassiveProbe::new();
+    
+    // HTTP/3 控制流：流类型(0x00) + SETTINGS帧
+    let data: &[u8] = &[
+        0x00,                               // 控制流
+        0x04, 0x0e,                         // SETTINGS，长度14
+        0x33, 0x01,                         // SETTINGS_H3_DATAGRAM = 1
+        0xab, 0x60, 0x37, 0x42, 0x01,       // SETTINGS_ENABLE_WEBTRANSPORT = 1
+        0x06, 0x44, 0x00,                   // SETTINGS_MAX_FIELD_SECTION_SIZE = 1024
+        0x01, 0x00,                         // SETTINGS_QPACK_MAX_TABLE_CAPACITY = 0
+        0x07, 0x00,                         // SETTINGS_QPACK_BLOCKED_STREAMS = 0
+    ];
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到WebTransport");
+    assert_eq!(info.protocol_type, ProtocolType::WebTransport);
+    assert_eq!(info.metadata.get("webtransport_enabled").map(String::as_str), Some("true"));
+    assert_eq!(info.metadata.get("h3_datagram").map(String::as_str), Some("true"));
+}
+
+#[test]
+fn test_webtransport_word_in_http_body_is_not_webtransport() {
+    let probe = PassiveProbe::new();
+    let data = b"POST /notes HTTP/1.1\r\nHost: example.com\r\nContent-Length: 33\r\n\r\n\
+                 We should try webtransport later.";
+    
+    // 只启用WebTransport，避免HTTP结果掩盖误判
+    let mut context = ProbeContext::new();
+    context.enabled_protocols = Some(vec![ProtocolType::WebTransport]);
+    assert!(ProtocolProbe::probe(&probe, data, &mut context).unwrap().is_none());
+    
+    // 只有 `:protocol` 伪头部、没有SETTINGS启用标志时也不视为WebTransport
+    let connect_only = b"\x01\x20:method CONNECT :protocol webtransport :path /chat padding";
+    let mut context = ProbeContext::new();
+    context.enabled_protocols = Some(vec![ProtocolType::WebTransport]);
+    let result = ProtocolProbe::probe(&probe, connect_only, &mut context).unwrap();
+    assert!(result.is_none_or(|info| info.protocol_type != ProtocolType::WebTransport));
+}
+
+#[test]
+fn test_postgres_ssl_request_detected() {
+    // SSLRequest 只有8字节，默认最小长度不应拦截
+    let probe = PassiveProbe::new();
+    let data: &[u8] = &[0x00, 0x00, 0x00, 0x08, 0x04, 0xd2, 0x16, 0x2f];
+    
+    assert!(!ProtocolProbe::needs_more_data(&probe, data));
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, data, &mut context)
+        .unwrap()
+        .expect("应检测到PostgreSQL SSLRequest");
+    assert_eq!(info.protocol_type, ProtocolType::PostgreSQL);
+    assert_eq!(info.metadata.get("db_ssl_upgrade").map(String::as_str), Some("pending"));
+    
+    // 其他请求码（如CancelRequest）不是SSLRequest
+    let cancel: &[u8] = &[0x00, 0x00, 0x00, 0x08, 0x04, 0xd2, 0x16, 0x2e];
+    let mut context = ProbeContext::new();
+    assert!(ProtocolProbe::probe(&probe, cancel, &mut context).unwrap().is_none());
+}
+
+#[test]
+fn test_mysql_ssl_request_detected() {
+    let probe = PassiveProbe::new();
+    
+    // 3字节长度(32) + 序号1 + 能力标志(含CLIENT_SSL) + 最大包长 + 字符集 + 23字节填充
+    let mut data = vec![0x20, 0x00, 0x00, 0x01];
+    data.extend_from_slice(&0x000f_aa85u32.to_le_bytes());
+    data.extend_from_slice(&0x0100_0000u32.to_le_bytes());
+    data.push(0x21);
+    data.extend_from_slice(&[0u8; 23]);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到MySQL SSLRequest");
+    assert_eq!(info.protocol_type, ProtocolType::MySQL);
+    assert_eq!(info.metadata.get("db_ssl_upgrade").map(String::as_str), Some("pending"));
+    
+    // 未置位CLIENT_SSL时不是SSL升级请求
+    data[5] &= !0x08;
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &data, &mut context).unwrap();
+    assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::MySQL));
+}
+
+#[test]
+fn test_thrift_framed_messages_detected() {
+    let probe = PassiveProbe::new();
+    
+    // 帧长度 + binary消息头（80 01 00 CALL）+ 方法名 + 序号 + STOP
+    let mut message = vec![0x80, 0x01, 0x00, 0x01];
+    message.extend_from_slice(&7u32.to_be_bytes());
+    message.extend_from_slice(b"getUser");
+    message.extend_from_slice(&1u32.to_be_bytes());
+    message.push(0x00);
+    let mut data = (message.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(&message);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到Thrift");
+    assert_eq!(info.protocol_type, ProtocolType::Thrift);
+    assert_eq!(info.metadata.get("thrift_protocol").map(String::as_str), Some("binary"));
+    assert_eq!(info.metadata.get("thrift_message_type").map(String::as_str), Some("call"));
+    assert_eq!(info.metadata.get("thrift_method").map(String::as_str), Some("getUser"));
+    
+    // compact协议：82 + (REPLY << 5 | 版本1) + 序号varint + 名称长度varint + 名称
+    let mut message = vec![0x82, 0x41, 0x05, 0x04];
+    message.extend_from_slice(b"ping");
+    message.push(0x00);
+    let mut data = (message.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(&message);
+    data.resize(16, 0x00);
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context).unwrap().unwrap();
+    assert_eq!(info.protocol_type, ProtocolType::Thrift);
+    assert_eq!(info.metadata.get("thrift_protocol").map(String::as_str), Some("compact"));
+    assert_eq!(info.metadata.get("thrift_message_type").map(String::as_str), Some("reply"));
+    
+    // 帧长度容纳不下消息头时不是Thrift
+    let mut bogus = 6u32.to_be_bytes().to_vec();
+    bogus.extend_from_slice(&[0x80, 0x01, 0x00, 0x01]);
+    bogus.extend_from_slice(&7u32.to_be_bytes());
+    bogus.extend_from_slice(b"getUser");
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &bogus, &mut context).unwrap();
+    assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::Thrift));
+}
+
+#[test]
+fn test_avro_handshake_detected() {
+    let probe = PassiveProbe::new();
+    let protocol_json = br#"{"protocol":"Mail","namespace":"example.proto"}"#;
+    
+    // HandshakeRequest: clientHash + union(string) + serverHash + union(null)
+    let mut handshake = vec![0x11; 16];
+    handshake.push(0x02);
+    handshake.push((protocol_json.len() * 2) as u8);
+    handshake.extend_from_slice(protocol_json);
+    handshake.extend_from_slice(&[0x22; 16]);
+    handshake.push(0x00);
+    
+    // socket传输帧：序号 + 缓冲区数 + 缓冲区长度
+    let mut data = 1u32.to_be_bytes().to_vec();
+    data.extend_from_slice(&1u32.to_be_bytes());
+    data.extend_from_slice(&(handshake.len() as u32).to_be_bytes());
+    data.extend_from_slice(&handshake);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &data, &mut context)
+        .unwrap()
+        .expect("应检测到Avro握手");
+    assert_eq!(info.protocol_type, ProtocolType::Avro);
+    assert_eq!(info.metadata.get("avro_framing").map(String::as_str), Some("rpc_handshake"));
+    
+    let mut container = b"Obj\x01".to_vec();
+    container.extend_from_slice(&[0x04; 28]);
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &container, &mut context).unwrap().unwrap();
+    assert_eq!(info.protocol_type, ProtocolType::Avro);
+    assert_eq!(info.metadata.get("avro_framing").map(String::as_str), Some("object_container"));
+}
+
+#[test]
+fn test_avro_rejects_handshake_without_protocol_json() {
+    let probe = PassiveProbe::new();
+    
+    // 全零缓冲区和随机字节都不应被当作Avro握手
+    let zeros = vec![0u8; 64];
+    let mut seed = 0x2545_f491u32;
+    let random: Vec<u8> = (0..64)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+    
+    // clientProtocol为null分支的握手同样不再识别
+    let mut null_protocol = 1u32.to_be_bytes().to_vec();
+    null_protocol.extend_from_slice(&1u32.to_be_bytes());
+    null_protocol.extend_from_slice(&34u32.to_be_bytes());
+    null_protocol.extend_from_slice(&[0x11; 16]);
+    null_protocol.push(0x00);
+    null_protocol.extend_from_slice(&[0x22; 16]);
+    null_protocol.push(0x00);
+    
+    for data in [zeros, random, null_protocol] {
+        let mut context = ProbeContext::new();
+        let result = ProtocolProbe::probe(&probe, &data, &mut context).unwrap();
+        assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::Avro));
+    }
+}
+
+#[test]
+fn test_starttls_command_lines_detected() {
+    let probe = PassiveProbe::new();
+    let commands: [(&[u8], ProtocolType); 6] = [
+        (b"STARTTLS\r\n", ProtocolType::SMTP),
+        (b"a001 STARTTLS\r\n", ProtocolType::IMAP),
+        (b"AUTH TLS\r\n", ProtocolType::FTP),
+        (b"AUTH SSL\r\n", ProtocolType::FTP),
+        (b"STLS\r\n", ProtocolType::POP3),
+        // 命令不区分大小写
+        (b"starttls\r\n", ProtocolType::SMTP),
+    ];
+    
+    for (data, expected) in commands {
+        // 命令行短于默认最小长度，不应被拦截
+        assert!(!ProtocolProbe::needs_more_data(&probe, data));
+        let mut context = ProbeContext::new();
+        let info = ProtocolProbe::probe(&probe, data, &mut context)
+            .unwrap()
+            .unwrap_or_else(|| panic!("应检测到 {:?}", String::from_utf8_lossy(data)));
+        assert_eq!(info.protocol_type, expected);
+        assert_eq!(info.metadata.get("starttls_command").map(String::as_str), Some("true"));
+    }
+}
+
+#[test]
+fn test_starttls_requires_exact_line() {
+    let probe = PassiveProbe::new();
+    let not_commands: [&[u8]; 5] = [
+        b"STARTTLS",
+        b"STARTTLS now\r\n",
+        b"AUTH PLAIN\r\n",
+        b"STARTTLS\r\nEHLO example.com\r\n",
+        b"a+1 STARTTLS\r\n",
+    ];
+    
+    for data in not_commands {
+        let mut context = ProbeContext::new();
+        let result = ProtocolProbe::probe(&probe, data, &mut context).unwrap();
+        assert!(
+            result.is_none_or(|info| !info.metadata.contains_key("starttls_command")),
+            "{:?} 不应视为STARTTLS命令",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn test_postgres_ssl_request_detected_end_to_end() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    
+    let detector = DetectorBuilder::new()
+        .enable_postgresql()
+        .build()
+        .unwrap();
+    
+    // 8字节，低于默认最小探测长度
+    let data: &[u8] = &[0x00, 0x00, 0x00, 0x08, 0x04, 0xd2, 0x16, 0x2f];
+    let result = detector.detect(data).expect("SSLRequest 不应被最小长度拦截");
+    assert_eq!(result.protocol_type(), ProtocolType::PostgreSQL);
+    assert_eq!(result.protocol_info.metadata.get("db_ssl_upgrade").map(String::as_str), Some("pending"));
+    
+    // 其他短数据仍按最小长度拒绝
+    assert!(detector.detect(&[0x00, 0x00, 0x00, 0x08]).is_err());
+}
+
+#[test]
+fn test_starttls_command_lines_detected_end_to_end() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    
+    let detector = DetectorBuilder::new()
+        .enable_smtp()
+        .enable_imap()
+        .enable_pop3()
+        .enable_ftp()
+        .build()
+        .unwrap();
+    
+    let commands: [(&[u8], ProtocolType); 5] = [
+        (b"STARTTLS\r\n", ProtocolType::SMTP),
+        (b"a001 STARTTLS\r\n", ProtocolType::IMAP),
+        (b"AUTH TLS\r\n", ProtocolType::FTP),
+        (b"AUTH SSL\r\n", ProtocolType::FTP),
+        (b"STLS\r\n", ProtocolType::POP3),
+    ];
+    
+    for (data, expected) in commands {
+        let result = detector.detect(data)
+            .unwrap_or_else(|e| panic!("{:?} 应被检测到: {}", String::from_utf8_lossy(data), e));
+        assert_eq!(result.protocol_type(), expected);
+        assert_eq!(result.protocol_info.metadata.get("starttls_command").map(String::as_str), Some("true"));
+    }
+}
+
+#[test]
+fn test_websocket_close_frame_metadata() {
+    let probe = PassiveProbe::new();
+    
+    let mut payload = 1000u16.to_be_bytes().to_vec();
+    payload.extend_from_slice(b"normal shutdown, bye");
+    let mut frame = vec![0x88, payload.len() as u8];
+    frame.extend_from_slice(&payload);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &frame, &mut context)
+        .unwrap()
+        .expect("应检测到WebSocket关闭帧");
+    assert_eq!(info.protocol_type, ProtocolType::WebSocket);
+    assert_eq!(info.metadata.get("ws_close_code").map(String::as_str), Some("1000"));
+    assert_eq!(info.metadata.get("ws_close_reason").map(String::as_str), Some("normal shutdown, bye"));
+    
+    // 状态码不合法时不输出关闭信息
+    frame[2..4].copy_from_slice(&1005u16.to_be_bytes());
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &frame, &mut context).unwrap();
+    assert!(result.is_none_or(|info| !info.metadata.contains_key("ws_close_code")));
+}
+
+#[test]
+fn test_memcached_text_and_binary_requests_detected() {
+    let probe = PassiveProbe::new();
+    
+    let requests: [(&[u8], &str); 3] = [
+        (b"get user:1001:profile\r\n", "get"),
+        (b"set session:abc 0 3600 5\r\nhello\r\n", "set"),
+        (b"delete session:abc noreply\r\n", "delete"),
+    ];
+    for (data, command) in requests {
+        let mut context = ProbeContext::new();
+        let info = ProtocolProbe::probe(&probe, data, &mut context)
+            .unwrap()
+            .expect("应检测到Memcached文本命令");
+        assert_eq!(info.protocol_type, ProtocolType::Memcached);
+        assert_eq!(info.metadata.get("memcached_protocol").map(String::as_str), Some("text"));
+        assert_eq!(info.metadata.get("memcached_command").map(String::as_str), Some(command));
+    }
+    
+    // 二进制GET请求：24字节报文头 + 键
+    let key = b"user:1001";
+    let mut binary = vec![0x80, 0x00];
+    binary.extend_from_slice(&(key.len() as u16).to_be_bytes());
+    binary.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    binary.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    binary.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    binary.extend_from_slice(&[0x00; 8]);
+    binary.extend_from_slice(key);
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &binary, &mut context)
+        .unwrap()
+        .expect("应检测到Memcached二进制请求");
+    assert_eq!(info.protocol_type, ProtocolType::Memcached);
+    assert_eq!(info.metadata.get("memcached_protocol").map(String::as_str), Some("binary"));
+    assert_eq!(info.metadata.get("memcached_command").map(String::as_str), Some("0x00"));
+    
+    // 不符合命令语法的文本不是Memcached
+    for data in [&b"get the latest news from the feed"[..], b"set up the environment first\r\n"] {
+        let mut context = ProbeContext::new();
+        let result = ProtocolProbe::probe(&probe, data, &mut context).unwrap();
+        assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::Memcached));
+    }
+}
+
+fn mqtt_connect(name: &[u8], level: u8, flags: u8) -> Vec<u8> {
+    let mut body = vec![0x00, name.len() as u8];
+    body.extend_from_slice(name);
+    body.extend_from_slice(&[level, flags, 0x00, 0x3c]);
+    if level == 5 {
+        // 属性长度为0
+        body.push(0x00);
+    }
+    body.extend_from_slice(b"\x00\x0bsensor-0042");
+    
+    let mut packet = vec![0x10, body.len() as u8];
+    packet.extend(body);
+    packet
+}
+
+#[test]
+fn test_mqtt_connect_parsed_for_each_version() {
+    use psi_detector::utils::mqtt::{check_connect, ConnectCheck};
+    
+    let probe = PassiveProbe::new();
+    for (name, level, version) in [(&b"MQIsdp"[..], 3, "3.1"), (&b"MQTT"[..], 4, "3.1.1"), (&b"MQTT"[..], 5, "5.0")] {
+        let packet = mqtt_connect(name, level, 0x02);
+        let mut context = ProbeContext::new();
+        let info = ProtocolProbe::probe(&probe, &packet, &mut context)
+            .unwrap()
+            .expect("应检测到MQTT CONNECT");
+        assert_eq!(info.protocol_type, ProtocolType::MQTT);
+        assert!(info.confidence >= 0.95);
+        assert_eq!(info.version.as_deref(), Some(version));
+        assert_eq!(info.metadata.get("mqtt_level"), Some(&level.to_string()));
+        
+        // 报文头收全之前只是候选
+        assert_eq!(check_connect(&packet[..6]), ConnectCheck::Partial);
+    }
+    
+    // 协议名与级别不匹配、保留标志位置位、剩余长度过短
+    let inconsistent = [
+        mqtt_connect(b"MQTT", 3, 0x02),
+        mqtt_connect(b"MQIsdp", 4, 0x02),
+        mqtt_connect(b"MQTT", 4, 0x03),
+        mqtt_connect(b"MQTX", 4, 0x02),
+    ];
+    for packet in &inconsistent {
+        assert_eq!(check_connect(packet), ConnectCheck::Invalid);
+        assert_eq!(probe.detect_protocol(ProtocolType::MQTT, packet), None);
+    }
+    let mut short = mqtt_connect(b"MQTT", 4, 0x02);
+    short[1] = 4;
+    assert_eq!(check_connect(&short), ConnectCheck::Invalid);
+}
+
+#[test]
+fn test_mqtt_magic_requires_connect_header() {
+    use psi_detector::core::magic::MagicDetector;
+    
+    let detector = MagicDetector::new();
+    let packet = mqtt_connect(b"MQTT", 4, 0xc2);
+    let info = detector.quick_detect(&packet).expect("应命中MQTT特征");
+    assert_eq!(info.protocol_type, ProtocolType::MQTT);
+    assert!(info.confidence >= 0.95);
+    
+    // 以0x10开头的任意二进制数据不再被当作MQTT
+    let binary = [0x10, 0x8f, 0x3a, 0x00, 0x91, 0x44, 0xfe, 0x07, 0x10, 0x22, 0x5b, 0xc3, 0x01, 0x00, 0x9d, 0x6e];
+    assert!(detector.quick_detect(&binary).is_none_or(|info| info.protocol_type != ProtocolType::MQTT));
+    assert!(detector.deep_detect(&binary).iter().all(|info| info.protocol_type != ProtocolType::MQTT));
+}
+
+#[test]
+fn test_clickhouse_hello_detected() {
+    let probe = PassiveProbe::new();
+    
+    // Hello包：类型0 + 名称 + 主版本 + 次版本 + 修订号(54460) + 数据库 + 用户 + 密码
+    let name = b"ClickHouse client";
+    let mut hello = vec![0x00, name.len() as u8];
+    hello.extend_from_slice(name);
+    hello.extend_from_slice(&[24, 8, 0xbc, 0xa9, 0x03]);
+    hello.extend_from_slice(b"\x07default\x07default\x00");
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &hello, &mut context)
+        .unwrap()
+        .expect("应检测到ClickHouse Hello");
+    assert_eq!(info.protocol_type, ProtocolType::ClickHouse);
+    assert_eq!(info.metadata.get("clickhouse_client").map(String::as_str), Some("ClickHouse client"));
+    assert_eq!(info.metadata.get("clickhouse_revision").map(String::as_str), Some("54460"));
+}
+
+#[test]
+fn test_tls_ech_marks_outer_sni() {
+    let probe = PassiveProbe::new();
+    
+    // ClientHello：SNI + ALPN + encrypted_client_hello
+    let extensions: [(u16, &[u8]); 3] = [
+        (0x0000, b"\x00\x13\x00\x00\x10public.example.com"),
+        (0x0010, b"\x00\x03\x02h2"),
+        (0xfe0d, &[0x00, 0x00, 0x01, 0x00, 0x01, 0x2a, 0x00, 0x00, 0x00, 0x00]),
+    ];
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]);
+    body.extend_from_slice(&[0x00, 0x00, 0x04, 0x13, 0x01, 0x13, 0x02, 0x01, 0x00]);
+    let mut ext_data = Vec::new();
+    for (ext_type, data) in extensions {
+        ext_data.extend_from_slice(&ext_type.to_be_bytes());
+        ext_data.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        ext_data.extend_from_slice(data);
+    }
+    body.extend_from_slice(&(ext_data.len() as u16).to_be_bytes());
+    body.extend_from_slice(&ext_data);
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+    let mut hello = vec![0x16, 0x03, 0x01];
+    hello.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&handshake);
+    
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &hello, &mut context)
+        .unwrap()
+        .expect("应检测到TLS");
+    assert_eq!(info.protocol_type, ProtocolType::TLS);
+    assert_eq!(info.metadata.get("ech_present").map(String::as_str), Some("true"));
+    assert_eq!(info.metadata.get("sni_is_outer").map(String::as_str), Some("true"));
+}
+
+#[test]
+fn test_truncated_tls_record_requests_more_data() {
+    use psi_detector::error::DetectorError;
+    
+    let probe = PassiveProbe::new();
+    
+    // 记录头声明512字节负载，首个分段只带了部分ClientHello
+    let mut record = vec![0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc, 0x03, 0x03];
+    record.resize(5 + 512, 0xab);
+    let first_segment = &record[..64];
+    
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, first_segment, &mut context).unwrap();
+    assert!(result.is_none_or(|info| info.protocol_type != ProtocolType::TLS));
+    assert_eq!(context.requested_bytes, 517);
+    assert!(matches!(ProbeEngine::probe(&probe, first_segment), Err(DetectorError::NeedMoreData(517))));
+    
+    // 补齐后正常识别
+    let mut context = ProbeContext::new();
+    let info = ProtocolProbe::probe(&probe, &record, &mut context).unwrap().expect("应检测到TLS");
+    assert_eq!(info.protocol_type, ProtocolType::TLS);
+    assert_eq!(context.requested_bytes, 0);
+    
+    // 版本非法的记录直接拒绝，不请求更多数据
+    let mut invalid = first_segment.to_vec();
+    invalid[1] = 0x07;
+    let mut context = ProbeContext::new();
+    let result = ProtocolProbe::probe(&probe, &invalid, &mut context).unwrap();
+    assert!(result.is_none_or(|info| info.protocol_type != ProtocolType::TLS));
+    assert_eq!(context.requested_bytes, 0);
+}
+
+#[test]
+fn test_heuristic_high_entropy_classified_as_obfuscated() {
+    // xorshift生成的伪随机字节，模拟obfs4握手
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let data: Vec<u8> = (0..512)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 56) as u8
+        })
+        .collect();
+    
+    let probe = HeuristicProbe::new();
+    let result = ProbeEngine::probe(&probe, &data).expect("高熵数据应给出混淆传输分类");
+    assert_eq!(result.protocol_type(), ProtocolType::Obfuscated);
+    assert!(result.confidence() <= 0.5);
+    
+    // 长度低于obfs4握手下限时不做推断
+    assert!(ProbeEngine::probe(&probe, &data[..100]).is_err());
+}
+
+#[test]
+fn test_heuristic_high_entropy_tagged_likely_encrypted() {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let data: Vec<u8> = (0..16 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 56) as u8
+        })
+        .collect();
+    
+    // 超出obfs4握手长度的高熵数据归为未知加密流
+    let probe = HeuristicProbe::new();
+    let result = ProbeEngine::probe(&probe, &data).expect("高熵数据应给出加密流分类");
+    assert_eq!(result.protocol_type(), ProtocolType::Unknown);
+    assert_eq!(result.protocol_info.metadata.get("likely_encrypted").map(String::as_str), Some("true"));
+    let entropy: f64 = result.protocol_info.metadata.get("entropy").unwrap().parse().unwrap();
+    assert!(entropy > 7.5);
+    
+    // 低熵的无效数据不做加密推断
+    let garbage: Vec<u8> = (0..16 * 1024).map(|i| (i % 16) as u8).collect();
+    if let Ok(result) = ProbeEngine::probe(&probe, &garbage) {
+        assert_ne!(result.protocol_type(), ProtocolType::Unknown);
+        assert!(result.protocol_info.metadata.get("likely_encrypted").is_none());
+    }
+}
+
+#[test]
+fn test_openvpn_hard_reset_detected() {
+    let probe = PassiveProbe::new();
+    let session_id = [0x5a, 0x3c, 0x91, 0x07, 0xee, 0x12, 0x48, 0xb6];
+    
+    // UDP + tls-auth：操作码 + 会话ID + HMAC(SHA1) + 报文ID(1) + 时间戳 + 确认数组 + 报文ID
+    let mut udp = vec![0x38];
+    udp.extend_from_slice(&session_id);
+    udp.extend_from_slice(&[0xa7; 20]);
+    udp.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x65, 0x00, 0x00, 0x00]);
+    udp.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00]);
+    
+    // TCP明文：2字节长度 + 客户端硬重置
+    let mut tcp = vec![0x00, 0x0e, 0x38];
+    tcp.extend_from_slice(&session_id);
+    tcp.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00]);
+    
+    // 服务端硬重置：确认客户端报文0并带回客户端会话ID
+    let mut server = vec![0x40, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x01, 0x00, 0x00, 0x00, 0x00];
+    server.extend_from_slice(&session_id);
+    server.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    
+    for (packet, opcode, protection, transport) in [
+        (&udp, "hard_reset_client_v2", "tls-auth", "udp"),
+        (&tcp, "hard_reset_client_v2", "none", "tcp"),
+        (&server, "hard_reset_server_v2", "none", "udp"),
+    ] {
+        let mut context = ProbeContext::new();
+        let info = ProtocolProbe::probe(&probe, packet, &mut context)
+            .unwrap()
+            .expect("应检测到OpenVPN硬重置");
+        assert_eq!(info.protocol_type, ProtocolType::OpenVPN);
+        assert_eq!(info.metadata.get("openvpn_opcode").map(String::as_str), Some(opcode));
+        assert_eq!(info.metadata.get("openvpn_protection").map(String::as_str), Some(protection));
+        assert_eq!(info.metadata.get("transport").map(String::as_str), Some(transport));
+    }
+    
+    // 数据通道报文和以 '8' 开头的文本都不是会话起始
+    let mut data_v2 = udp.clone();
+    data_v2[0] = 0x48;
+    for packet in [&data_v2[..], b"8888888888888888888888888888"] {
+        let mut context = ProbeContext::new();
+        let result = ProtocolProbe::probe(&probe, packet, &mut context).unwrap();
+        assert!(result.map(|info| info.protocol_type) != Some(ProtocolType::OpenVPN));
+    }
+}
+
+#[test]
+fn test_ike_initial_exchange_detected() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    
+    fn isakmp(version: u8, exchange: u8, flags: u8, next_payload: u8, body_len: usize) -> Vec<u8> {
+        let mut packet = vec![0x8a, 0x1f, 0x03, 0xc4, 0x29, 0x6e, 0xb0, 0x55];
+        packet.extend_from_slice(&[0x00; 8]);
+        packet.extend_from_slice(&[next_payload, version, exchange, flags, 0x00, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&((28 + body_len) as u32).to_be_bytes());
+        packet.resize(28 + body_len, 0x22);
+        packet
+    }
+    
+    let detector = DetectorBuilder::new().enable_ike().enable_openvpn().build().unwrap();
+    
+    // IKEv2 IKE_SA_INIT：发起方标志，首个载荷为SA
+    let result = detector.detect(&isakmp(0x20, 34, 0x08, 33, 40)).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::IKE);
+    let metadata = &result.protocol_info.metadata;
+    assert_eq!(metadata.get("ike_version").map(String::as_str), Some("2"));
+    assert_eq!(metadata.get("ike_exchange").map(String::as_str), Some("ike_sa_init"));
+    assert_eq!(metadata.get("ike_initial").map(String::as_str), Some("true"));
+    assert_eq!(metadata.get("transport").map(String::as_str), Some("udp"));
+    
+    // NAT-T封装的IKEv1主模式
+    let mut nat_t = vec![0x00; 4];
+    nat_t.extend_from_slice(&isakmp(0x10, 2, 0x00, 1, 56));
+    let result = detector.detect(&nat_t).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::IKE);
+    assert_eq!(result.protocol_info.metadata.get("ike_version").map(String::as_str), Some("1"));
+    assert_eq!(result.protocol_info.metadata.get("transport").map(String::as_str), Some("udp-nat-t"));
+    
+    // 响应方SPI为0却不是IKE_SA_INIT、长度字段小于报文、未知版本都不接受
+    let mut too_long = isakmp(0x20, 34, 0x08, 33, 40);
+    too_long.extend_from_slice(&[0x00; 8]);
+    for packet in [isakmp(0x20, 35, 0x08, 46, 40), too_long, isakmp(0x30, 34, 0x08, 33, 40)] {
+        assert!(detector.detect(&packet).is_err());
+    }
+}
+
+/// 返回固定结果的探测引擎
+struct FixedEngine {
+    name: &'static str,
+    probe_type: psi_detector::probe::ProbeType,
+    protocol: ProtocolType,
+    confidence: f32,
+}
+
+impl ProbeEngine for FixedEngine {
+    fn probe(&self, _data: &[u8]) -> psi_detector::error::Result<psi_detector::core::DetectionResult> {
+        use psi_detector::core::detector::{DetectionMethod, DetectionResult};
+        use psi_detector::core::protocol::ProtocolInfo;
+        
+        Ok(DetectionResult::new(
+            ProtocolInfo::new(self.protocol, self.confidence),
+            std::time::Duration::ZERO,
+            DetectionMethod::Passive,
+            self.name.to_string(),
+        ))
+    }
+    
+    fn probe_type(&self) -> psi_detector::probe::ProbeType {
+        self.probe_type
+    }
+    
+    fn needs_more_data(&self, _data: &[u8]) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_probe_aggregator_weights_and_reinforcement() {
+    use psi_detector::core::probe::ProbeConfig;
+    use psi_detector::probe::{ProbeAggregator, ProbeType};
+    
+    let engines = || -> Vec<Box<dyn ProbeEngine>> {
+        vec![
+            Box::new(FixedEngine {
+                name: "frame-heuristic",
+                probe_type: ProbeType::Heuristic,
+                protocol: ProtocolType::WebSocket,
+                confidence: 0.85,
+            }),
+            Box::new(FixedEngine {
+                name: "request-line",
+                probe_type: ProbeType::Passive,
+                protocol: ProtocolType::HTTP1_1,
+                confidence: 0.6,
+            }),
+            Box::new(FixedEngine {
+                name: "header-scan",
+                probe_type: ProbeType::Passive,
+                protocol: ProtocolType::HTTP1_1,
+                confidence: 0.5,
+            }),
+        ]
+    };
+    
+    // 两个指向HTTP/1.1的结果相互加强：1 - 0.4 * 0.5 = 0.8 < 0.85
+    let mut aggregator = ProbeAggregator::new();
+    for engine in engines() {
+        aggregator.add_engine(engine);
+    }
+    let result = aggregator.best_result(b"").unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::WebSocket);
+    
+    // 降低启发式引擎权重后HTTP/1.1胜出
+    let config = ProbeConfig {
+        probe_weights: [(ProbeType::Heuristic, 0.7)].into_iter().collect(),
+        ..ProbeConfig::default()
+    };
+    let mut aggregator = ProbeAggregator::with_config(&config);
+    for engine in engines() {
+        aggregator.add_engine(engine);
+    }
+    let result = aggregator.best_result(b"").unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert!((result.confidence() - 0.8).abs() < 1e-6);
+    assert_eq!(
+        result.contributing_probes,
+        vec![("request-line".to_string(), 0.6), ("header-scan".to_string(), 0.5)]
+    );
+}
+
+#[test]
+fn test_http09_and_lenient_http1_requests() {
+    use psi_detector::{DetectorBuilder, ProtocolDetector};
+    
+    let probe = PassiveProbe::new();
+    
+    // 没有版本号的简单请求按HTTP/0.9处理
+    let simple = b"GET /index.html\r\n";
+    let result = ProbeEngine::probe(&probe, simple).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_0);
+    assert_eq!(result.protocol_info.version.as_deref(), Some("0.9"));
+    assert!(result.confidence() < 0.9);
+    
+    // 请求行前的空行和小写方法仍识别为HTTP/1.1，置信度低于规范请求
+    for data in [
+        &b"\r\nGET /index.html HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+        b"get /index.html HTTP/1.1\r\nHost: a\r\n\r\n",
+    ] {
+        let result = ProbeEngine::probe(&probe, data).unwrap();
+        assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+        assert!((result.confidence() - 0.85).abs() < 1e-6);
+    }
+    
+    let detector = DetectorBuilder::new().enable_http().build().unwrap();
+    let result = detector.detect(simple).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_0);
+    let request = result.http_request().unwrap();
+    assert_eq!((request.method.as_str(), request.version.as_str()), ("GET", "0.9"));
+    
+    let result = detector.detect(b"\r\nget /a HTTP/1.1\r\nHost: example.com\r\n\r\n").unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert_eq!(result.http_request().unwrap().method, "GET");
+}
+
+/// 总是失败或超出时间预算的探测引擎
+struct FaultyEngine {
+    delay: Option<std::time::Duration>,
+}
+
+impl ProbeEngine for FaultyEngine {
+    fn probe(&self, data: &[u8]) -> psi_detector::error::Result<psi_detector::core::DetectionResult> {
+        match self.delay {
+            Some(delay) => {
+                std::thread::sleep(delay);
+                ProbeEngine::probe(&PassiveProbe::new(), data)
+            }
+            None => Err(psi_detector::DetectorError::internal_error("engine bug")),
+        }
+    }
+    
+    fn probe_type(&self) -> psi_detector::probe::ProbeType {
+        psi_detector::probe::ProbeType::Heuristic
+    }
+    
+    fn needs_more_data(&self, _data: &[u8]) -> bool {
+        false
+    }
+    
+    fn timeout(&self) -> Option<std::time::Duration> {
+        self.delay.map(|_| std::time::Duration::from_millis(1))
+    }
+}
+
+#[test]
+fn test_probe_aggregator_isolates_engine_errors() {
+    use psi_detector::probe::{ProbeAggregator, ProbeType};
+    use psi_detector::DetectorError;
+    
+    let data = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    let mut aggregator = ProbeAggregator::new();
+    aggregator.add_engine(Box::new(FaultyEngine { delay: None }));
+    aggregator.add_engine(Box::new(FaultyEngine { delay: Some(std::time::Duration::from_millis(20)) }));
+    aggregator.add_engine(Box::new(PassiveProbe::new()));
+    
+    // 失败和超时的引擎不影响其他引擎
+    let report = aggregator.probe_all(data);
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].protocol_type(), ProtocolType::HTTP1_1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!((report.errors[0].index, report.errors[0].probe_type), (0, ProbeType::Heuristic));
+    assert!(matches!(report.errors[1].error, DetectorError::Timeout { .. }));
+    
+    let (best, report) = aggregator.best_result_with_report(data);
+    assert_eq!(best.unwrap().protocol_type(), ProtocolType::HTTP1_1);
+    assert!(report.has_errors());
+    assert_eq!(aggregator.best_result(data).unwrap().protocol_type(), ProtocolType::HTTP1_1);
+    
+    // 所有引擎都失败时返回第一个引擎错误
+    let mut aggregator = ProbeAggregator::new();
+    aggregator.add_engine(Box::new(FaultyEngine { delay: None }));
+    assert!(aggregator.best_result(data).is_err());
+    let (best, report) = aggregator.best_result_with_report(data);
+    assert!(best.is_none());
+    assert_eq!(report.errors.len(), 1);
+}
+
+#[test]
+fn test_starttls_capable_text_sessions_detected() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    
+    let detector = DetectorBuilder::new()
+        .enable_smtp()
+        .enable_imap()
+        .enable_ftp()
+        .build()
+        .unwrap();
+    
+    let sessions: [(&[u8], ProtocolType, Option<&str>); 9] = [
+        (b"220 mx.example.com ESMTP Postfix (Ubuntu)\r\n", ProtocolType::SMTP, None),
+        (b"220 ProFTPD Server (Debian) [::ffff:192.0.2.10]\r\n", ProtocolType::FTP, None),
+        (b"EHLO client.example.org\r\n", ProtocolType::SMTP, None),
+        (
+            b"250-mx.example.com\r\n250-PIPELINING\r\n250-SIZE 10240000\r\n250-STARTTLS\r\n250-ENHANCEDSTATUSCODES\r\n250 8BITMIME\r\n",
+            ProtocolType::SMTP,
+            Some("true"),
+        ),
+        (
+            b"250-smtp.example.net Hello\r\n250-PIPELINING\r\n250 8BITMIME\r\n",
+            ProtocolType::SMTP,
+            Some("false"),
+        ),
+        (b"A1 CAPABILITY\r\n", ProtocolType::IMAP, None),
+        (
+            b"* OK [CAPABILITY IMAP4rev1 SASL-IR LOGIN-REFERRALS ID ENABLE IDLE LITERAL+ STARTTLS LOGINDISABLED] Dovecot (Ubuntu) ready.\r\n",
+            ProtocolType::IMAP,
+            Some("true"),
+        ),
+        (
+            b"* CAPABILITY IMAP4rev1 UNSELECT IDLE NAMESPACE QUOTA ID XLIST AUTH=PLAIN\r\nA1 OK Success\r\n",
+            ProtocolType::IMAP,
+            Some("false"),
+        ),
+        (
+            b"211-Features:\r\n MDTM\r\n REST STREAM\r\n SIZE\r\n AUTH TLS\r\n PBSZ\r\n PROT\r\n UTF8\r\n211 End\r\n",
+            ProtocolType::FTP,
+            Some("true"),
+        ),
+    ];
+    
+    for (data, expected, offered) in sessions {
+        let result = detector.detect(data)
+            .unwrap_or_else(|e| panic!("{:?} 应被检测到: {}", String::from_utf8_lossy(data), e));
+        assert_eq!(result.protocol_type(), expected, "{:?}", String::from_utf8_lossy(data));
+        assert_eq!(
+            result.protocol_info.metadata.get("starttls_offered").map(String::as_str),
+            offered,
+            "{:?}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn test_grpc_variants_reported_in_metadata() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    use psi_detector::core::probe::MatchMode;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_http2()
+        .enable_grpc()
+        .with_match_mode(MatchMode::BestMatch)
+        .build()
+        .unwrap();
+    
+    let mut h2 = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    h2.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0]);
+    h2.extend_from_slice(b"\x00\x00\x26\x01\x04\x00\x00\x00\x01content-type application/grpc+proto te trailers");
+    
+    let mut web = b"POST /echo.Echo/Say HTTP/1.1\r\nHost: api.example.com\r\nContent-Type: application/grpc-web+proto\r\nX-Grpc-Web: 1\r\n\r\n".to_vec();
+    web.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x03, 0x0a, 0x01, 0x61]);
+    
+    let messages: [(&[u8], ProtocolType, Option<&str>); 5] = [
+        (&h2, ProtocolType::GRPC, Some("h2")),
+        (&web, ProtocolType::GRPC, Some("web")),
+        (
+            b"POST /echo.Echo/Say HTTP/1.1\r\nHost: api.example.com\r\nContent-Type: application/grpc-web-text\r\nAccept: application/grpc-web-text\r\n\r\nAAAAAANhYmM=",
+            ProtocolType::GRPC,
+            Some("text"),
+        ),
+        // 空消息在第8个字符处即有base64填充
+        (
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/grpc-web-text\r\n\r\nAAAAAAA=",
+            ProtocolType::GRPC,
+            Some("text"),
+        ),
+        // 声明文本模式但消息体不是base64编码的gRPC帧
+        (
+            b"POST /echo.Echo/Say HTTP/1.1\r\nHost: api.example.com\r\nContent-Type: application/grpc-web-text\r\n\r\n{\"message\": \"hi\"}",
+            ProtocolType::HTTP1_1,
+            None,
+        ),
+    ];
+    
+    for (data, expected, variant) in messages {
+        let result = detector.detect(data)
+            .unwrap_or_else(|e| panic!("{:?} 应被检测到: {}", String::from_utf8_lossy(data), e));
+        assert_eq!(result.protocol_type(), expected, "{:?}", String::from_utf8_lossy(data));
+        assert_eq!(
+            result.protocol_info.metadata.get("grpc_variant").map(String::as_str),
+            variant,
+            "{:?}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
@@ -127,4 +127,114 @@ fn test_simd_instruction_set_equality() {
     assert_eq!(SimdInstructionSet::None, SimdInstructionSet::None);
     assert_ne!(SimdInstructionSet::None, SimdInstructionSet::SSE2);
     assert_ne!(SimdInstructionSet::SSE2, SimdInstructionSet::AVX2);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_simd_support_is_cached() {
+    let cached = detect_simd_support();
+    assert_eq!(force_refresh(), cached);
+    assert_eq!(detect_simd_support(), cached);
+    
+    // 探测器与缓存的指令集一致
+    let expected = match cached {
+        SimdInstructionSet::AVX512 | SimdInstructionSet::AVX2 | SimdInstructionSet::SSE2
+            if cfg!(target_arch = "x86_64") => cached,
+        SimdInstructionSet::NEON if cfg!(target_arch = "aarch64") => cached,
+        SimdInstructionSet::WasmSimd if cfg!(target_arch = "wasm32") => cached,
+        _ => SimdInstructionSet::None,
+    };
+    assert_eq!(create_best_detector().instruction_set(), expected);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_avx2_detector_matches_generic_on_corpus() {
+    use psi_detector::core::protocol::ProtocolType;
+    use psi_detector::simd::detector::GenericSimdDetector;
+    use psi_detector::simd::x86_64::{Avx2Detector, Avx512Detector};
+    
+    let mut padded = vec![b'x'; 61];
+    padded.extend_from_slice(b"\r\nupgrade: WebSocket\r\nconnection: upgrade\r\n");
+    let mut grpc_frame = vec![0x00, 0x00, 0x00, 0x00, 0x03];
+    grpc_frame.extend_from_slice(b"abc");
+    
+    let corpus: Vec<Vec<u8>> = vec![
+        b"GET /chat HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n".to_vec(),
+        b"GET / HTTP/1.1\r\nUPGRADE: WEBSOCKET\r\nCONNECTION: UPGRADE\r\nsec-websocket-key: x\r\n\r\n".to_vec(),
+        padded,
+        b"POST /pkg.Service/Method HTTP/2\r\ncontent-type: application/grpc+proto\r\n\r\n".to_vec(),
+        b"content-type: application/grpc-web\r\n".to_vec(),
+        b"content-type: APPLICATION/GRPC\r\n".to_vec(),
+        grpc_frame,
+        vec![0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58],
+        b"SSH-2.0-OpenSSH_9.6\r\n".to_vec(),
+        b"U".repeat(100),
+        Vec::new(),
+    ];
+    let protocols = [ProtocolType::GRPC, ProtocolType::WebSocket];
+    
+    let generic = GenericSimdDetector::new();
+    let accelerated: [Box<dyn SimdDetector>; 2] = [Box::new(Avx2Detector::new()), Box::new(Avx512Detector::new())];
+    
+    // 比较时忽略指令集字段
+    let key = |result: SimdDetectionResult| (result.protocol, result.confidence, result.match_positions);
+    
+    for detector in &accelerated {
+        for data in &corpus {
+            assert_eq!(detector.detect_websocket(data).ok().map(key), generic.detect_websocket(data).ok().map(key));
+            assert_eq!(detector.detect_grpc(data).ok().map(key), generic.detect_grpc(data).ok().map(key));
+            
+            let expected: Vec<_> = generic.detect_multiple(data, &protocols).unwrap().into_iter().map(key).collect();
+            let actual: Vec<_> = detector.detect_multiple(data, &protocols).unwrap().into_iter().map(key).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+}
+
+/// 固定种子的xorshift伪随机缓冲区，保证失败可复现
+fn random_buffer(state: &mut u64, len: usize) -> Vec<u8> {
+    (0..len).map(|_| {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        // 限制字母表大小，使每个块内都有较多匹配
+        (*state % 8) as u8
+    }).collect()
+}
+
+#[test]
+fn test_byte_counting_matches_scalar_on_random_buffers() {
+    let mut state = 0x9e37_79b9_7f4a_7c15;
+    for len in (0..=96).chain([255, 1024, 4099]) {
+        let data = random_buffer(&mut state, len);
+        for byte in 0..9u8 {
+            assert_eq!(
+                simd_count_bytes(&data, byte),
+                data.iter().filter(|&&b| b == byte).count(),
+                "len={} byte={}", len, byte
+            );
+            assert_eq!(
+                simd_find_byte(&data, byte),
+                data.iter().position(|&b| b == byte),
+                "len={} byte={}", len, byte
+            );
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[test]
+fn test_neon_byte_counting_matches_scalar() {
+    use psi_detector::simd::aarch64::{neon_count_bytes, neon_find_byte};
+    
+    let mut state = 0x2545_f491_4f6c_dd1d;
+    for len in (0..=64).chain([1000, 4096]) {
+        let data = random_buffer(&mut state, len);
+        for byte in 0..9u8 {
+            unsafe {
+                assert_eq!(neon_count_bytes(&data, byte), data.iter().filter(|&&b| b == byte).count());
+                assert_eq!(neon_find_byte(&data, byte), data.iter().position(|&b| b == byte));
+            }
+        }
+    }
+}
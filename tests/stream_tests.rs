@@ -0,0 +1,454 @@
+//! 流处理模块测试
+
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::stream::analyzer::AnalyzerConfig;
+use psi_detector::stream::{
+    BufferConfig, BufferPool, ProcessorConfig, StreamAnalyzer, StreamBuffer, StreamDirection,
+    StreamManager, StreamMetadata, StreamProcessor,
+};
+
+#[test]
+fn test_buffer_pool_reuses_released_buffers() {
+    let pool = BufferPool::new(2);
+    let mut buffer = StreamBuffer::with_pool(BufferConfig::default(), pool.clone());
+    
+    buffer.push_slice(b"first chunk").unwrap();
+    let popped = buffer.pop(11);
+    assert_eq!(popped, b"first chunk");
+    assert_eq!(pool.available(), 1);
+    pool.release(popped);
+    
+    // 复用的缓冲区不能残留上一次的数据
+    buffer.push_slice(b"2nd").unwrap();
+    assert_eq!(buffer.drain(), b"2nd");
+    
+    let stats = pool.stats();
+    assert_eq!(stats.acquired, 4);
+    assert_eq!(stats.reused, 2);
+    assert_eq!(stats.released, 3);
+}
+
+#[test]
+fn test_buffer_pool_is_bounded() {
+    let pool = BufferPool::new(1);
+    pool.release(vec![1, 2, 3]);
+    pool.release(vec![4, 5, 6]);
+    
+    assert_eq!(pool.available(), 1);
+    assert_eq!(pool.stats().discarded, 1);
+    assert!(pool.acquire().is_empty());
+}
+
+#[test]
+fn test_pooled_processor_keeps_streams_isolated() {
+    let detector = DetectorBuilder::new().enable_http().build().unwrap();
+    let config = ProcessorConfig {
+        buffer_config: BufferConfig {
+            pool_size: 8,
+            ..BufferConfig::default()
+        },
+        // 避免触发探测，只验证缓冲路径
+        min_detection_size: usize::MAX,
+        ..ProcessorConfig::default()
+    };
+    let mut processor = StreamProcessor::new(config, Box::new(detector));
+    
+    for round in 0..4u8 {
+        let ids = [format!("a-{}", round), format!("b-{}", round)];
+        for id in &ids {
+            processor
+                .create_stream(StreamMetadata::new(id.clone(), StreamDirection::Inbound))
+                .unwrap();
+        }
+        
+        let payload_a = vec![0xa0 | round; 64];
+        let payload_b = vec![0xb0 | round; 32];
+        processor.process_slice(&ids[0], &payload_a).unwrap();
+        processor.process_slice(&ids[1], &payload_b).unwrap();
+        processor.process_slice(&ids[0], b"tail").unwrap();
+        
+        let mut expected_a = payload_a.clone();
+        expected_a.extend_from_slice(b"tail");
+        assert_eq!(processor.consume_stream_data(&ids[0], 68).unwrap(), expected_a);
+        assert_eq!(processor.get_stream_data(&ids[1], 32).unwrap(), payload_b);
+        
+        for id in &ids {
+            processor.close_stream(id).unwrap();
+        }
+    }
+    
+    let stats = processor.buffer_pool().expect("应启用缓冲池").stats();
+    assert_eq!(stats.acquired, 16);
+    assert!(stats.reused >= 9, "后续轮次应复用缓冲区: {:?}", stats);
+}
+
+#[test]
+fn test_keep_alive_stream_splits_http_messages() {
+    use psi_detector::core::protocol::ProtocolType;
+    use psi_detector::stream::{StreamEvent, StreamState};
+    
+    let detector = DetectorBuilder::new().enable_http().build().unwrap();
+    let config = ProcessorConfig {
+        min_detection_size: 16,
+        ..ProcessorConfig::default()
+    };
+    let mut processor = StreamProcessor::new(config, Box::new(detector));
+    processor
+        .create_stream(StreamMetadata::new("conn".to_string(), StreamDirection::Inbound))
+        .unwrap();
+    
+    let first = b"POST /submit HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let second = b"GET /next HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec();
+    
+    // 第一条请求完成协议锁定，第二条请求只发来一半
+    processor.process_data("conn", first.clone()).unwrap();
+    assert!(matches!(processor.get_stream_state("conn"), Some(StreamState::Detected(ProtocolType::HTTP1_1))));
+    processor.process_data("conn", second[..10].to_vec()).unwrap();
+    
+    match processor.next_message("conn").unwrap() {
+        Some(StreamEvent::MessageBoundary { protocol, index, data, .. }) => {
+            assert_eq!(protocol, ProtocolType::HTTP1_1);
+            assert_eq!(index, 0);
+            assert_eq!(data, first);
+        }
+        other => panic!("应识别出第一条消息: {:?}", other),
+    }
+    
+    // 第二条消息尚不完整
+    assert!(processor.next_message("conn").unwrap().is_none());
+    processor.process_data("conn", second[10..].to_vec()).unwrap();
+    
+    match processor.next_message("conn").unwrap() {
+        Some(StreamEvent::MessageBoundary { index, data, .. }) => {
+            assert_eq!(index, 1);
+            assert_eq!(data, second);
+        }
+        other => panic!("应识别出第二条消息: {:?}", other),
+    }
+    
+    assert!(processor.next_message("conn").unwrap().is_none());
+    assert_eq!(processor.get_message_count("conn"), Some(2));
+    assert_eq!(processor.get_buffer_size("conn"), Some(0));
+    assert!(matches!(processor.get_stream_state("conn"), Some(StreamState::Detected(ProtocolType::HTTP1_1))));
+}
+
+#[test]
+fn test_analyzer_histograms_are_bounded() {
+    let config = AnalyzerConfig {
+        histogram_bins: 16,
+        ..AnalyzerConfig::default()
+    };
+    let mut analyzer = StreamAnalyzer::new(config);
+    
+    // 数千个不同大小的数据包
+    let payload = vec![0x5au8; 4096];
+    for size in 1..=4000 {
+        analyzer.add_data_point("diverse".to_string(), &payload[..size]);
+    }
+    
+    let result = analyzer.analyze_stream("diverse").unwrap();
+    let sizes = &result.features.packet_size_distribution;
+    assert!(sizes.histogram.len() <= 16);
+    assert_eq!(sizes.histogram.values().sum::<usize>(), 4000);
+    // 等宽分桶保留分布形状：均匀输入落入各桶的数量相近
+    assert!(sizes.histogram.values().all(|&count| count >= 200));
+    assert_eq!(sizes.histogram.keys().min(), Some(&1));
+    
+    let timing = &result.features.timing_distribution;
+    assert!(timing.histogram.len() <= 16);
+    assert_eq!(timing.histogram.values().sum::<usize>(), 3999);
+    
+    // 取值范围小于桶数时保留精确值
+    let mut analyzer = StreamAnalyzer::new(AnalyzerConfig::default());
+    for size in [10, 20, 20, 30, 30, 30, 40, 40, 40, 40] {
+        analyzer.add_data_point("narrow".to_string(), &payload[..size]);
+    }
+    let result = analyzer.analyze_stream("narrow").unwrap();
+    let histogram = &result.features.packet_size_distribution.histogram;
+    assert_eq!(histogram.get(&30), Some(&3));
+    assert_eq!(histogram.get(&40), Some(&4));
+}
+
+#[test]
+fn test_need_more_data_hint_is_total_size() {
+    use psi_detector::core::probe::{ProbeContext, ProtocolProbe};
+    use psi_detector::core::protocol::{ProtocolInfo, ProtocolType};
+    use psi_detector::error::{DetectorError, Result};
+    use psi_detector::stream::StreamState;
+    
+    // 缓冲区不足48字节时要求总共48字节，够了就识别为HTTP/1.1
+    struct TotalSizeProbe;
+    
+    impl ProtocolProbe for TotalSizeProbe {
+        fn name(&self) -> &'static str {
+            "total-size"
+        }
+        
+        fn supported_protocols(&self) -> Vec<ProtocolType> {
+            vec![ProtocolType::HTTP1_1]
+        }
+        
+        fn needs_more_data(&self, _data: &[u8]) -> bool {
+            false
+        }
+        
+        fn probe(&self, data: &[u8], _context: &mut ProbeContext) -> Result<Option<ProtocolInfo>> {
+            if data.len() < 48 {
+                return Err(DetectorError::NeedMoreData(48));
+            }
+            Ok(Some(ProtocolInfo::new(ProtocolType::HTTP1_1, 0.9)))
+        }
+    }
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .add_custom_probe(Box::new(TotalSizeProbe))
+        .build()
+        .unwrap();
+    let config = ProcessorConfig {
+        min_detection_size: 16,
+        detection_interval: std::time::Duration::ZERO,
+        ..ProcessorConfig::default()
+    };
+    let mut processor = StreamProcessor::new(config, Box::new(detector));
+    processor
+        .create_stream(StreamMetadata::new("conn".to_string(), StreamDirection::Inbound))
+        .unwrap();
+    
+    processor.process_slice("conn", &[0x42; 20]).unwrap();
+    assert_eq!(processor.get_bytes_needed("conn"), Some(28));
+    
+    processor.process_slice("conn", &[0x42; 28]).unwrap();
+    assert!(matches!(processor.get_stream_state("conn"), Some(StreamState::Detected(ProtocolType::HTTP1_1))));
+    
+    // 探测成功后提示清零，取走数据也不会再要求补足
+    processor.consume_stream_data("conn", 48).unwrap();
+    assert_eq!(processor.get_bytes_needed("conn"), Some(0));
+}
+
+#[test]
+fn test_custom_anomaly_detector_runs_with_builtins() {
+    use psi_detector::stream::analyzer::{Anomaly, AnomalySeverity, AnomalyType, StreamFeatures};
+    use psi_detector::stream::{AnomalyDetector, DataPoint};
+    use std::time::Instant;
+    
+    /// 固定大小的心跳包反复出现时视为信标
+    struct BeaconDetector;
+    
+    impl AnomalyDetector for BeaconDetector {
+        fn detect(&self, points: &[DataPoint], _features: &StreamFeatures) -> Vec<Anomaly> {
+            let beacons = points.iter()
+                .filter(|point| point.size() == 8 && point.sample().starts_with(b"BEAC"))
+                .count();
+            if beacons < points.len() / 2 {
+                return Vec::new();
+            }
+            vec![Anomaly {
+                anomaly_type: AnomalyType::Custom("beaconing".to_string()),
+                severity: AnomalySeverity::Medium,
+                description: format!("{} beacon packets", beacons),
+                detected_at: Instant::now(),
+                value: beacons as f64,
+                expected_value: 0.0,
+                deviation: beacons as f64,
+            }]
+        }
+    }
+    
+    let mut analyzer = StreamAnalyzer::new(AnalyzerConfig::default());
+    analyzer.add_anomaly_detector(Box::new(BeaconDetector));
+    for _ in 0..12 {
+        analyzer.add_data_point("beacon".to_string(), b"BEACON!!");
+    }
+    
+    let result = analyzer.analyze_stream("beacon").unwrap();
+    let beacon = result.anomalies.iter()
+        .find(|anomaly| anomaly.anomaly_type == AnomalyType::Custom("beaconing".to_string()))
+        .expect("自定义检测器应报告信标");
+    assert_eq!(beacon.value, 12.0);
+}
+
+#[test]
+fn test_ring_buffer_contiguous_slice_across_wrap() {
+    let mut buffer = StreamBuffer::with_ring_capacity(16);
+    assert!(buffer.is_ring());
+    
+    buffer.push_slice(b"0123456789").unwrap();
+    assert_eq!(buffer.pop(6), b"012345");
+    
+    // 写入跨越存储末尾，读取时才整理为连续视图
+    buffer.push_slice(b"abcdefghij").unwrap();
+    assert_eq!(buffer.size(), 14);
+    assert_eq!(buffer.peek(6), b"6789ab");
+    assert_eq!(buffer.contiguous_slice(), b"6789abcdefghij");
+    
+    // 超出容量时丢弃最老的数据
+    buffer.push(b"KLMN".to_vec()).unwrap();
+    assert_eq!(buffer.contiguous_slice(), b"89abcdefghijKLMN");
+    assert!(buffer.is_full());
+    assert_eq!(buffer.stats().dropped_bytes, 2);
+    
+    assert_eq!(buffer.drain(), b"89abcdefghijKLMN");
+    assert!(buffer.is_empty());
+    assert!(buffer.contiguous_slice().is_empty());
+}
+
+#[test]
+fn test_chunked_buffer_contiguous_slice_merges_chunks() {
+    let mut buffer = StreamBuffer::new(BufferConfig::default());
+    buffer.push_slice(b"GET / HTTP/1.1\r\n").unwrap();
+    buffer.push_slice(b"Host: example.com\r\n\r\n").unwrap();
+    
+    assert_eq!(buffer.contiguous_slice(), b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    assert_eq!(buffer.chunk_count(), 1);
+    assert_eq!(buffer.size(), 37);
+}
+
+#[test]
+fn test_analyzer_reports_protocol_switch() {
+    use psi_detector::stream::analyzer::PatternType;
+    
+    let mut analyzer = StreamAnalyzer::new(AnalyzerConfig::default());
+    let stream = "upgrade".to_string();
+    
+    // 明文HTTP请求之后在同一连接上开始TLS握手
+    analyzer.add_data_point(stream.clone(), b"GET /upgrade HTTP/1.1\r\nHost: example.com\r\n\r\n");
+    analyzer.add_data_point(stream.clone(), b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    for _ in 0..4 {
+        analyzer.add_data_point(stream.clone(), &[0x5a; 32]);
+    }
+    for _ in 0..4 {
+        analyzer.add_data_point(stream.clone(), &[0x16, 0x03, 0x01, 0x00, 0x2f, 0x01, 0x00, 0x00]);
+    }
+    
+    let result = analyzer.analyze_stream(&stream).unwrap();
+    let switches: Vec<_> = result.patterns.iter()
+        .filter(|pattern| pattern.pattern_type == PatternType::ProtocolSwitch)
+        .collect();
+    assert_eq!(switches.len(), 1);
+    assert_eq!(switches[0].metadata.get("from_protocol").map(String::as_str), Some("HTTP/1.1"));
+    assert_eq!(switches[0].metadata.get("to_protocol").map(String::as_str), Some("TLS"));
+    
+    // 单一协议的流不报告切换
+    let mut analyzer = StreamAnalyzer::new(AnalyzerConfig::default());
+    for _ in 0..10 {
+        analyzer.add_data_point("tls".to_string(), &[0x17, 0x03, 0x03, 0x00, 0x20, 0xaa, 0xbb, 0xcc]);
+    }
+    let result = analyzer.analyze_stream("tls").unwrap();
+    assert!(result.patterns.iter().all(|pattern| pattern.pattern_type != PatternType::ProtocolSwitch));
+}
+
+#[test]
+fn test_stream_manager_correlates_bidirectional_flows() {
+    let mut manager = StreamManager::default();
+    manager.create_stream("c2s".to_string(), StreamDirection::Inbound).unwrap();
+    manager.create_stream("s2c".to_string(), StreamDirection::Outbound).unwrap();
+    manager.create_stream("other".to_string(), StreamDirection::Outbound).unwrap();
+    
+    let set_addrs = |manager: &mut StreamManager, id: &str, src: &str, dst: &str| {
+        let metadata = manager.get_stream_mut(id).unwrap();
+        metadata.source_addr = Some(src.to_string());
+        metadata.dest_addr = Some(dst.to_string());
+    };
+    set_addrs(&mut manager, "c2s", "10.0.0.1:50000", "10.0.0.2:80");
+    set_addrs(&mut manager, "s2c", "10.0.0.2:80", "10.0.0.1:50000");
+    set_addrs(&mut manager, "other", "10.0.0.2:80", "10.0.0.3:50001");
+    
+    assert_eq!(
+        manager.correlate("10.0.0.1:50000", "10.0.0.2:80"),
+        Some(("c2s".to_string(), "s2c".to_string()))
+    );
+    assert_eq!(manager.get_stream("c2s").unwrap().sibling_id.as_deref(), Some("s2c"));
+    assert_eq!(manager.sibling("s2c").map(|metadata| metadata.stream_id.as_str()), Some("c2s"));
+    assert!(manager.get_stream("other").unwrap().sibling_id.is_none());
+    
+    // 缺少反方向的流时无法关联
+    assert_eq!(manager.correlate("10.0.0.3:50001", "10.0.0.2:80"), None);
+    
+    // 关闭一侧后另一侧的引用被清除
+    manager.close_stream("s2c").unwrap();
+    assert!(manager.get_stream("c2s").unwrap().sibling_id.is_none());
+}
+
+#[test]
+fn test_unified_stream_replays_detection_bytes() {
+    use psi_detector::core::detector::Transport;
+    use psi_detector::core::protocol::ProtocolType;
+    use psi_detector::error::Result;
+    use psi_detector::stream::UnifiedStream;
+    use std::io::{Read, Write};
+    
+    /// 每次最多返回 `chunk` 字节的内存传输层
+    struct ChunkedTransport {
+        inbound: Vec<u8>,
+        position: usize,
+        chunk: usize,
+        outbound: Vec<u8>,
+    }
+    
+    impl Transport for ChunkedTransport {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let remaining = &self.inbound[self.position..];
+            let n = remaining.len().min(buf.len()).min(self.chunk);
+            buf[..n].copy_from_slice(&remaining[..n]);
+            self.position += n;
+            Ok(n)
+        }
+        
+        fn write(&mut self, data: &[u8]) -> Result<usize> {
+            self.outbound.extend_from_slice(data);
+            Ok(data.len())
+        }
+        
+        fn peek(&self, size: usize) -> Result<Vec<u8>> {
+            let remaining = &self.inbound[self.position..];
+            Ok(remaining[..remaining.len().min(size)].to_vec())
+        }
+        
+        fn close(&mut self) -> Result<()> {
+            Ok(())
+        }
+        
+        fn transport_type(&self) -> &str {
+            "memory"
+        }
+    }
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    let mut payload = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+    payload.extend((0..200u8).cycle().take(6000));
+    let transport = ChunkedTransport { inbound: payload.clone(), position: 0, chunk: 16, outbound: Vec::new() };
+    let mut stream = UnifiedStream::new(transport);
+    
+    let result = stream.detect(&detector).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::SSH);
+    assert_eq!(stream.detection().unwrap().protocol_type(), ProtocolType::SSH);
+    assert!(!stream.buffered().is_empty());
+    assert!(stream.buffered().len() < payload.len());
+    assert_eq!(stream.detect(&detector).unwrap().protocol_type(), ProtocolType::SSH);
+    
+    // 使用方先读到探测时消费的字节，再读到传输层剩余的数据
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received).unwrap();
+    assert_eq!(received, payload);
+    assert!(stream.buffered().is_empty());
+    
+    stream.write_all(b"SSH-2.0-psi\r\n").unwrap();
+    stream.flush().unwrap();
+    let (transport, buffered) = stream.into_parts();
+    assert_eq!(transport.outbound, b"SSH-2.0-psi\r\n");
+    assert!(buffered.is_empty());
+    
+    // 探测失败时已读取的字节仍然可以拿回
+    let transport = ChunkedTransport { inbound: vec![0xff; 100], position: 0, chunk: 64, outbound: Vec::new() };
+    let mut stream = UnifiedStream::new(transport);
+    assert!(stream.detect(&detector).is_err());
+    assert!(stream.detection().is_none());
+    let (_, buffered) = stream.into_parts();
+    assert_eq!(buffered, vec![0xff; 100]);
+}
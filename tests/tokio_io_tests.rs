@@ -0,0 +1,67 @@
+//! tokio异步读取适配测试
+#![cfg(feature = "runtime-tokio")]
+
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::core::ProtocolType;
+use psi_detector::error::DetectorError;
+use psi_detector::stream::detect_from_async_read;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn test_detect_from_async_read_returns_consumed_bytes() {
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    let (mut client, mut server) = tokio::io::duplex(64);
+    client.write_all(b"SSH-2.0-").await.unwrap();
+    let writer = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        client.write_all(b"OpenSSH_9.6\r\n").await.unwrap();
+        client
+    });
+    
+    let (result, consumed) = detect_from_async_read(&detector, &mut server, 1024).await;
+    assert_eq!(result.unwrap().protocol_type(), ProtocolType::SSH);
+    assert_eq!(consumed, b"SSH-2.0-OpenSSH_9.6\r\n");
+    drop(writer.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_detect_from_async_read_times_out() {
+    let detector = DetectorBuilder::new()
+        .enable_ssh()
+        .with_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+    
+    // 对端保持连接但不发送数据
+    let (mut client, mut server) = tokio::io::duplex(64);
+    client.write_all(b"SSH-").await.unwrap();
+    let (result, consumed) = detect_from_async_read(&detector, &mut server, 1024).await;
+    assert!(matches!(result, Err(DetectorError::Timeout { .. })));
+    
+    // 超时前已读取的字节仍交还调用方
+    assert_eq!(consumed, b"SSH-");
+}
+
+#[tokio::test]
+async fn test_detect_from_async_read_returns_bytes_on_failure() {
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    let (mut client, mut server) = tokio::io::duplex(256);
+    let payload = vec![0xffu8; 100];
+    client.write_all(&payload).await.unwrap();
+    drop(client);
+    
+    let (result, consumed) = detect_from_async_read(&detector, &mut server, 1024).await;
+    assert!(result.is_err());
+    assert_eq!(consumed, payload);
+}
@@ -0,0 +1,337 @@
+//! 协议升级模块测试
+
+use psi_detector::core::protocol::{ProtocolType, UpgradeMethod};
+use psi_detector::upgrade::{UpgradeResult, UpgradeStats};
+use std::time::Duration;
+
+fn result_with_duration(target: ProtocolType, millis: u64) -> UpgradeResult {
+    UpgradeResult::success(target, Vec::new(), UpgradeMethod::Direct, Duration::from_millis(millis))
+}
+
+#[test]
+fn test_upgrade_stats_path_percentiles() {
+    let mut stats = UpgradeStats::new();
+    
+    // HTTP/1.1 -> WebSocket：1..=100 毫秒
+    for millis in 1..=100 {
+        stats.record_upgrade(&result_with_duration(ProtocolType::WebSocket, millis), ProtocolType::HTTP1_1);
+    }
+    // HTTP/1.1 -> HTTP/2：固定 10 毫秒
+    for _ in 0..20 {
+        stats.record_upgrade(&result_with_duration(ProtocolType::HTTP2, 10), ProtocolType::HTTP1_1);
+    }
+    
+    assert_eq!(
+        stats.percentile(ProtocolType::HTTP1_1, ProtocolType::WebSocket, 95.0),
+        Some(Duration::from_millis(95))
+    );
+    assert_eq!(
+        stats.percentile(ProtocolType::HTTP1_1, ProtocolType::WebSocket, 50.0),
+        Some(Duration::from_millis(50))
+    );
+    assert_eq!(
+        stats.percentile(ProtocolType::HTTP1_1, ProtocolType::HTTP2, 95.0),
+        Some(Duration::from_millis(10))
+    );
+    assert_eq!(stats.percentile(ProtocolType::TCP, ProtocolType::TLS, 95.0), None);
+    
+    let slowest = stats.top_slowest_paths(1);
+    assert_eq!(slowest.len(), 1);
+    assert_eq!(slowest[0].0, (ProtocolType::HTTP1_1, ProtocolType::WebSocket));
+}
+
+#[test]
+fn test_detect_http_upgrade_targets() {
+    use psi_detector::upgrade::detect_http_upgrade;
+    
+    let h2c = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+                Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAoAAAAAIAAAAA\r\n\r\n";
+    let intent = detect_http_upgrade(h2c).expect("应解析到h2c升级");
+    assert!(!intent.is_response);
+    assert_eq!(intent.primary().unwrap().name, "h2c");
+    assert_eq!(intent.protocol_types(), vec![ProtocolType::HTTP2]);
+    
+    let websocket = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\n\
+                      Connection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\r\n";
+    let intent = detect_http_upgrade(websocket).unwrap();
+    assert_eq!(intent.protocol_types(), vec![ProtocolType::WebSocket]);
+    
+    // RFC 2817：带版本号的多个候选
+    let tls = b"OPTIONS * HTTP/1.1\r\nHost: example.com\r\nUpgrade: TLS/1.2, HTTP/1.1\r\n\
+                Connection: Upgrade\r\n\r\n";
+    let intent = detect_http_upgrade(tls).unwrap();
+    assert_eq!(intent.primary().unwrap().version.as_deref(), Some("1.2"));
+    assert_eq!(intent.protocol_types(), vec![ProtocolType::TLS, ProtocolType::HTTP1_1]);
+    assert_eq!(intent.targets_value(), "TLS/1.2,HTTP/1.1");
+    
+    let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: my-proto/3\r\nConnection: Upgrade\r\n\r\n";
+    let intent = detect_http_upgrade(response).unwrap();
+    assert!(intent.is_response);
+    assert_eq!(intent.primary().unwrap().protocol_type(), None);
+    
+    // 非101响应只是通告可升级，并未切换协议
+    let required = b"HTTP/1.1 426 Upgrade Required\r\nUpgrade: TLS/1.2, HTTP/1.1\r\nConnection: Upgrade\r\n\r\n";
+    assert!(detect_http_upgrade(required).is_none());
+    let advertised = b"HTTP/1.1 200 OK\r\nUpgrade: h2c\r\nConnection: Upgrade\r\nContent-Length: 0\r\n\r\n";
+    assert!(detect_http_upgrade(advertised).is_none());
+    
+    assert!(detect_http_upgrade(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n").is_none());
+    // 正文中的Upgrade字样不算
+    assert!(detect_http_upgrade(b"POST / HTTP/1.1\r\nContent-Length: 12\r\n\r\nUpgrade: h2c").is_none());
+}
+
+#[test]
+fn test_upgrade_to_metadata() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_websocket()
+        .build()
+        .unwrap();
+    
+    let h2c = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+                Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAoAAAAAIAAAAA\r\n\r\n";
+    let result = detector.detect(h2c).unwrap();
+    assert_eq!(result.protocol_info.metadata.get("upgrade_to").map(String::as_str), Some("h2c"));
+    
+    let tls = b"OPTIONS * HTTP/1.1\r\nHost: example.com\r\nUpgrade: TLS/1.2\r\nConnection: Upgrade\r\n\r\n";
+    let result = detector.detect(tls).unwrap();
+    assert_eq!(result.protocol_info.metadata.get("upgrade_to").map(String::as_str), Some("TLS/1.2"));
+    
+    let websocket = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\n\
+                      Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                      Sec-WebSocket-Version: 13\r\n\r\n";
+    let result = detector.detect(websocket).unwrap();
+    assert_eq!(result.protocol_info.metadata.get("upgrade_to").map(String::as_str), Some("websocket"));
+}
+
+#[test]
+fn test_detect_and_plan_upgrade_http_to_websocket() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::upgrade::UpgradeManager;
+    
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_websocket()
+        .build()
+        .unwrap();
+    let manager = UpgradeManager::default();
+    let request = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nAccept: text/html\r\n\r\n";
+    
+    let (result, plan) = detector
+        .detect_and_plan_upgrade(request, ProtocolType::WebSocket, &manager)
+        .unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    let plan = plan.expect("HTTP/1.1应可升级到WebSocket");
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].from, ProtocolType::HTTP1_1);
+    assert_eq!(plan[0].to, ProtocolType::WebSocket);
+    
+    // 多跳路径按顺序返回，不可达时为None
+    let hops: Vec<_> = manager.plan_upgrade(ProtocolType::HTTP1_0, ProtocolType::HTTP2)
+        .unwrap()
+        .iter()
+        .map(|path| (path.from, path.to))
+        .collect();
+    assert_eq!(hops, [
+        (ProtocolType::HTTP1_0, ProtocolType::HTTP1_1),
+        (ProtocolType::HTTP1_1, ProtocolType::HTTP2),
+    ]);
+    assert!(manager.plan_upgrade(ProtocolType::WebSocket, ProtocolType::HTTP1_1).is_none());
+}
+
+#[test]
+fn test_http2_upgrader_h2c_flow() {
+    use psi_detector::upgrade::{Http2Upgrader, ProtocolUpgrader, UpgradeManager};
+    use psi_detector::utils::http2;
+    
+    let upgrader = Http2Upgrader::new();
+    assert!(upgrader.can_upgrade(ProtocolType::HTTP1_1, ProtocolType::HTTP2));
+    assert!(!upgrader.can_upgrade(ProtocolType::HTTP1_0, ProtocolType::HTTP2));
+    
+    // 默认管理器使用Http2Upgrader处理h2c升级
+    let mut manager = UpgradeManager::default();
+    let h2c = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+                Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAoAAAAAIAAAAA\r\n\r\n";
+    let result = manager.upgrade(ProtocolType::HTTP1_1, ProtocolType::HTTP2, h2c).unwrap();
+    assert!(result.is_success(), "{:?}", result.error());
+    assert_eq!(result.method, UpgradeMethod::HttpUpgrade);
+    
+    // 连接前言 + 初始SETTINGS帧
+    assert!(result.upgraded_data.starts_with(http2::CONNECTION_PREFACE));
+    let settings = http2::parse_frame_header(&result.upgraded_data[http2::CONNECTION_PREFACE.len()..]).unwrap();
+    assert_eq!(settings.frame_type, http2::FRAME_SETTINGS);
+    assert_eq!(settings.stream_id, 0);
+    assert_eq!(
+        result.upgraded_data.len(),
+        http2::CONNECTION_PREFACE.len() + http2::FRAME_HEADER_LEN + settings.length as usize
+    );
+    
+    // 客户端HTTP2-Settings中的参数回显到元数据
+    let setting = |name: &str| result.metadata.get(&format!("client_settings_{}", name)).map(String::as_str);
+    assert_eq!(setting("max_concurrent_streams"), Some("100"));
+    assert_eq!(setting("initial_window_size"), Some("10485760"));
+    assert_eq!(setting("enable_push"), Some("0"));
+    assert!(upgrader.switching_protocols_response().starts_with(b"HTTP/1.1 101 "));
+    
+    // Connection头缺少HTTP2-Settings令牌时升级失败
+    let missing = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\n\
+                    Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAoAAAAAIAAAAA\r\n\r\n";
+    let result = upgrader.upgrade(ProtocolType::HTTP1_1, ProtocolType::HTTP2, missing).unwrap();
+    assert!(!result.is_success());
+    
+    // 负载长度不是6的倍数
+    let malformed = b"GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+                      Upgrade: h2c\r\nHTTP2-Settings: AAMAAA\r\n\r\n";
+    let result = upgrader.upgrade(ProtocolType::HTTP1_1, ProtocolType::HTTP2, malformed).unwrap();
+    assert!(!result.is_success());
+}
+
+#[test]
+fn test_quic_upgrader_initial_packet() {
+    use psi_detector::builder::DetectorBuilder;
+    use psi_detector::core::detector::ProtocolDetector;
+    use psi_detector::upgrade::quic::QuicConfig;
+    use psi_detector::upgrade::{ProtocolUpgrader, QuicUpgrader, UpgradeManager};
+    use psi_detector::utils::framing::MessageFraming;
+    
+    let upgrader = QuicUpgrader::new();
+    for (from, to) in [
+        (ProtocolType::TCP, ProtocolType::QUIC),
+        (ProtocolType::TLS, ProtocolType::QUIC),
+        (ProtocolType::HTTP2, ProtocolType::HTTP3),
+    ] {
+        assert!(upgrader.can_upgrade(from, to));
+        assert!(upgrader.estimate_upgrade_time(from, to) > Duration::from_millis(100));
+    }
+    assert!(!upgrader.can_upgrade(ProtocolType::HTTP1_1, ProtocolType::QUIC));
+    
+    let mut manager = UpgradeManager::default();
+    let result = manager.upgrade(ProtocolType::HTTP2, ProtocolType::HTTP3, b"alt-svc: h3=\":443\"").unwrap();
+    assert!(result.is_success(), "{:?}", result.error());
+    
+    // 长包头Initial，版本1，8字节连接ID，填充到1200字节且Length字段覆盖整个包
+    let packet = &result.upgraded_data;
+    assert_eq!(packet[0] & 0xf0, 0xc0);
+    assert_eq!(&packet[1..5], &[0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(packet[5], 8);
+    assert_eq!(packet.len(), 1200);
+    assert_eq!(MessageFraming::of(ProtocolType::QUIC, packet), MessageFraming::Complete);
+    assert_eq!(result.metadata.get("dcid").map(String::len), Some(16));
+    assert_eq!(result.metadata.get("alpn").map(String::as_str), Some("h3"));
+    
+    // 探测器能识别生成的数据报
+    let detector = DetectorBuilder::new().enable_quic().enable_http3().build().unwrap();
+    let detected = detector.detect(packet).unwrap().protocol_type();
+    assert!(matches!(detected, ProtocolType::QUIC | ProtocolType::HTTP3));
+    
+    // 每次生成新的连接ID
+    let again = upgrader.upgrade(ProtocolType::TCP, ProtocolType::QUIC, b"\x16\x03\x01").unwrap();
+    assert_ne!(again.metadata.get("dcid"), result.metadata.get("dcid"));
+    
+    // 不含h3的ALPN不能升级到HTTP/3
+    let config = QuicConfig {
+        alpn_protocols: vec!["hq-interop".to_string()],
+        initial_max_stream_data: 65536,
+        ..QuicConfig::default()
+    };
+    let upgrader = QuicUpgrader::with_config(config);
+    assert!(!upgrader.upgrade(ProtocolType::HTTP2, ProtocolType::HTTP3, b"data").unwrap().is_success());
+    let quic = upgrader.upgrade(ProtocolType::TLS, ProtocolType::QUIC, b"data").unwrap();
+    assert_eq!(quic.metadata.get("initial_max_stream_data").map(String::as_str), Some("65536"));
+    
+    // 非法的ALPN配置返回错误而不是生成畸形的Initial包
+    for alpn_protocols in [
+        vec![String::new()],
+        vec!["x".repeat(256)],
+        vec!["h".repeat(255); 80],
+    ] {
+        let upgrader = QuicUpgrader::with_config(QuicConfig { alpn_protocols, ..QuicConfig::default() });
+        let err = upgrader.upgrade(ProtocolType::TCP, ProtocolType::QUIC, b"data").unwrap_err();
+        assert!(err.is_config_error());
+    }
+}
+
+#[cfg(feature = "runtime-tokio")]
+#[tokio::test(flavor = "current_thread")]
+async fn test_upgrade_async_retries_without_blocking_executor() {
+    use psi_detector::error::{DetectorError, Result};
+    use psi_detector::upgrade::{AsyncProtocolUpgrader, ProtocolUpgrader, UpgradeConfig, UpgradeManager};
+    use psi_detector::core::protocol::UpgradePath;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    
+    /// 前两次异步升级失败的升级器
+    #[derive(Debug, Default)]
+    struct FlakyUpgrader {
+        async_attempts: AtomicU32,
+    }
+    
+    impl ProtocolUpgrader for FlakyUpgrader {
+        fn can_upgrade(&self, from: ProtocolType, to: ProtocolType) -> bool {
+            from == ProtocolType::HTTP1_1 && to == ProtocolType::WebSocket
+        }
+        
+        fn upgrade(&self, _from: ProtocolType, _to: ProtocolType, _data: &[u8]) -> Result<UpgradeResult> {
+            panic!("异步升级不应调用同步实现");
+        }
+        
+        fn supported_upgrades(&self) -> Vec<UpgradePath> {
+            Vec::new()
+        }
+        
+        fn name(&self) -> &'static str {
+            "FlakyUpgrader"
+        }
+        
+        fn as_async(&self) -> Option<&dyn AsyncProtocolUpgrader> {
+            Some(self)
+        }
+    }
+    
+    #[async_trait::async_trait]
+    impl AsyncProtocolUpgrader for FlakyUpgrader {
+        async fn upgrade_async(&self, _from: ProtocolType, to: ProtocolType, data: &[u8]) -> Result<UpgradeResult> {
+            if self.async_attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(DetectorError::network_error("握手超时"));
+            }
+            Ok(UpgradeResult::success(to, data.to_vec(), UpgradeMethod::Direct, Duration::ZERO))
+        }
+        
+        async fn check_prerequisites_async(&self, _from: ProtocolType, _to: ProtocolType, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+    
+    let mut manager = UpgradeManager::with_config(UpgradeConfig {
+        max_retries: 3,
+        retry_interval: Duration::from_millis(30),
+        enable_cache: false,
+        ..UpgradeConfig::default()
+    });
+    manager.add_upgrader(Box::new(FlakyUpgrader::default()));
+    
+    // 单线程执行器上的计时任务：重试期间若阻塞线程，计数不会增长
+    let ticks = Arc::new(AtomicU32::new(0));
+    let ticker = {
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                ticks.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    };
+    
+    let result = manager
+        .upgrade_async(ProtocolType::HTTP1_1, ProtocolType::WebSocket, b"GET / HTTP/1.1\r\n\r\n")
+        .await
+        .unwrap();
+    ticker.abort();
+    
+    assert!(result.success);
+    assert_eq!(result.target_protocol, ProtocolType::WebSocket);
+    assert!(ticks.load(Ordering::SeqCst) >= 3);
+}
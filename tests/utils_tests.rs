@@ -0,0 +1,564 @@
+//! 工具模块测试
+
+use psi_detector::builder::DetectorBuilder;
+use psi_detector::core::protocol::ProtocolType;
+use psi_detector::core::detector::{DetectionMethod, DetectionResult, ProtocolDetector};
+use psi_detector::core::protocol::ProtocolInfo;
+use psi_detector::utils::debug::diff_detection;
+use psi_detector::utils::digest::{md5, sha1};
+use psi_detector::utils::encoding::{decode_base64, encode_base64, encode_hex};
+use psi_detector::utils::export::{CsvColumn, CsvWriter, DetectionRecord, NdjsonWriter};
+use psi_detector::utils::features::{byte_histogram, printable_ratio, shannon_entropy};
+use psi_detector::utils::framing::MessageFraming;
+use psi_detector::utils::http::{
+    body_slice, expects_continue, header_block, header_end, interim_response, message_len,
+    response_content_encoding,
+};
+use psi_detector::utils::http2::{self, Http2Anomaly};
+use psi_detector::utils::replay::{replay, DetectionRecorder};
+use psi_detector::utils::tune::{suggest_confidences, SignatureHits};
+use psi_detector::utils::websocket::{parse_close_frame, CloseFrame};
+use psi_detector::core::magic::MagicSignature;
+use std::time::Duration;
+
+#[test]
+fn test_diff_detection_surfaces_http_version() {
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .build()
+        .expect("构建探测器失败");
+    
+    let http10 = b"GET /index.html HTTP/1.0\r\nHost: example.com\r\nUser-Agent: test\r\n\r\n";
+    let http11 = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nUser-Agent: test\r\n\r\n";
+    
+    let diff = diff_detection(&detector, http10, http11);
+    assert!(!diff.is_identical());
+    
+    let passive = diff.probe("PassiveProbe").expect("PassiveProbe 的结果应有差异");
+    assert_eq!(passive.a[0].protocol_type, ProtocolType::HTTP1_1);
+    assert_eq!(passive.a[0].version.as_deref(), Some("1.0"));
+    assert_eq!(passive.b[0].version.as_deref(), Some("1.1"));
+}
+
+#[test]
+fn test_diff_detection_identical_buffers() {
+    let detector = DetectorBuilder::new()
+        .enable_http()
+        .enable_tls()
+        .build()
+        .expect("构建探测器失败");
+    
+    let request = b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n";
+    let diff = diff_detection(&detector, request, request);
+    
+    assert!(diff.is_identical());
+    assert!(diff.a.get("PassiveProbe", ProtocolType::HTTP1_1).is_some());
+}
+
+fn sample_results() -> Vec<DetectionResult> {
+    vec![
+        DetectionResult::new(
+            ProtocolInfo::new(ProtocolType::HTTP1_1, 0.9).with_version("1.1"),
+            Duration::from_micros(1500),
+            DetectionMethod::Passive,
+            "PassiveProbe".to_string(),
+        ),
+        DetectionResult::new(
+            ProtocolInfo::new(ProtocolType::TLS, 0.95),
+            Duration::from_millis(3),
+            DetectionMethod::Passive,
+            "Magic, \"quoted\"".to_string(),
+        ),
+    ]
+}
+
+#[test]
+fn test_ndjson_writer_round_trip() {
+    let results = sample_results();
+    let mut writer = NdjsonWriter::new(Vec::new());
+    writer.write_result(&results[0], Some("10.0.0.1:1234->10.0.0.2:80")).unwrap();
+    writer.write_result(&results[1], None).unwrap();
+    
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    let records: Vec<DetectionRecord> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("每行应为合法JSON"))
+        .collect();
+    
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].protocol, ProtocolType::HTTP1_1);
+    assert_eq!(records[0].flow_key.as_deref(), Some("10.0.0.1:1234->10.0.0.2:80"));
+    assert_eq!(records[0].version.as_deref(), Some("1.1"));
+    assert!((records[0].detection_time_ms - 1.5).abs() < 1e-9);
+    assert!(records[0].timestamp_ms > 0);
+    assert_eq!(records[1].protocol, ProtocolType::TLS);
+    assert_eq!(records[1].flow_key, None);
+    assert_eq!(records[1].detector_name, "Magic, \"quoted\"");
+}
+
+#[test]
+fn test_csv_writer_columns_and_escaping() {
+    let results = sample_results();
+    let columns = vec![CsvColumn::FlowKey, CsvColumn::Protocol, CsvColumn::DetectionTimeMs, CsvColumn::DetectorName];
+    let mut writer = CsvWriter::with_columns(Vec::new(), columns);
+    writer.write_result(&results[0], Some("flow-1")).unwrap();
+    writer.write_result(&results[1], None).unwrap();
+    
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines, vec![
+        "flow_key,protocol,detection_time_ms,detector_name",
+        "flow-1,HTTP1_1,1.5,PassiveProbe",
+        ",TLS,3,\"Magic, \"\"quoted\"\"\"",
+    ]);
+}
+
+#[test]
+fn test_header_end_complete_request() {
+    let data = b"POST /api HTTP/1.1\r\nHost: example.com\r\nContent-Length: 5\r\n\r\nhello";
+    let end = header_end(data).expect("头部应已完整");
+    
+    assert_eq!(&data[..end], &data[..data.len() - 5]);
+    assert_eq!(body_slice(data), Some(&b"hello"[..]));
+    assert!(header_block(data).unwrap().ends_with(b"\r\n\r\n"));
+}
+
+#[test]
+fn test_header_end_incomplete_headers() {
+    let data = b"GET / HTTP/1.1\r\nHost: example.com\r\n";
+    assert_eq!(header_end(data), None);
+    assert_eq!(body_slice(data), None);
+    assert_eq!(header_end(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r"), None);
+    assert_eq!(header_end(b""), None);
+}
+
+#[test]
+fn test_header_end_lf_only_terminator() {
+    let data = b"HTTP/1.1 200 OK\nContent-Type: text/plain\n\nbody";
+    assert_eq!(header_end(data), Some(data.len() - 4));
+    assert_eq!(body_slice(data), Some(&b"body"[..]));
+    
+    // 头部完整但没有正文
+    let data = b"GET / HTTP/1.0\n\n";
+    assert_eq!(body_slice(data), Some(&b""[..]));
+}
+
+#[test]
+fn test_message_len_framing() {
+    let request = b"GET / HTTP/1.1\r\nHost: a\r\n\r\nGET /2 HTTP/1.1\r\n";
+    assert_eq!(message_len(request), Some(27));
+    
+    let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\nnext";
+    assert_eq!(message_len(chunked), Some(chunked.len() - 4));
+    assert_eq!(message_len(&chunked[..chunked.len() - 8]), None);
+    
+    // 响应没有长度信息时无法确定边界
+    assert_eq!(message_len(b"HTTP/1.1 200 OK\r\n\r\nbody"), None);
+    assert_eq!(message_len(b"HTTP/1.1 204 No Content\r\n\r\n"), Some(27));
+}
+
+#[test]
+fn test_response_content_encoding_archive_body() {
+    // 下载gzip文件：没有传输编码，正文魔数属于文件内容
+    let mut download = b"HTTP/1.1 200 OK\r\nContent-Type: application/gzip\r\nContent-Length: 10\r\n\r\n".to_vec();
+    download.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03]);
+    let encoding = response_content_encoding(&download).unwrap();
+    assert_eq!(encoding.declared, "identity");
+    assert_eq!(encoding.mismatch, Some(false));
+    
+    let mut archive = b"HTTP/1.1 200 OK\r\nContent-Type: application/zstd\r\n\r\n".to_vec();
+    archive.extend_from_slice(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]);
+    assert_eq!(response_content_encoding(&archive).unwrap().mismatch, Some(false));
+    
+    // 普通类型的正文带压缩魔数仍视为不符
+    let mut html = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n".to_vec();
+    html.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00]);
+    assert_eq!(response_content_encoding(&html).unwrap().mismatch, Some(true));
+}
+
+#[test]
+fn test_response_content_encoding_stacked_codings() {
+    // 最后列出的编码是最外层：先gzip后br，正文是brotli，无法识别魔数
+    let mut gzip_then_br = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip, br\r\n\r\n".to_vec();
+    gzip_then_br.extend_from_slice(&[0x0b, 0x02, 0x80]);
+    let encoding = response_content_encoding(&gzip_then_br).unwrap();
+    assert_eq!(encoding.declared, "gzip, br");
+    assert_eq!(encoding.mismatch, Some(false));
+    
+    let mut br_then_gzip = b"HTTP/1.1 200 OK\r\nContent-Encoding: br, gzip\r\n\r\n".to_vec();
+    br_then_gzip.extend_from_slice(&[0x1f, 0x8b, 0x08, 0x00]);
+    assert_eq!(response_content_encoding(&br_then_gzip).unwrap().mismatch, Some(false));
+    
+    // 最外层声明gzip，正文却不是gzip
+    let mut wrong_outer = b"HTTP/1.1 200 OK\r\nContent-Encoding: br, gzip\r\n\r\n".to_vec();
+    wrong_outer.extend_from_slice(&[0x28, 0xb5, 0x2f, 0xfd]);
+    assert_eq!(response_content_encoding(&wrong_outer).unwrap().mismatch, Some(true));
+}
+
+#[test]
+fn test_shannon_entropy_known_inputs() {
+    assert_eq!(shannon_entropy(&[]), 0.0);
+    assert_eq!(shannon_entropy(&[0u8; 4096]), 0.0);
+    
+    // 两种字节各占一半，熵为1
+    let half: Vec<u8> = (0..4096).map(|i| if i % 2 == 0 { b'a' } else { b'b' }).collect();
+    assert!((shannon_entropy(&half) - 1.0).abs() < 1e-9);
+    
+    // 所有字节值均匀出现，熵为8
+    let uniform: Vec<u8> = (0..256 * 16).map(|i| i as u8).collect();
+    assert!((shannon_entropy(&uniform) - 8.0).abs() < 1e-9);
+    
+    // 伪随机数据接近8
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let random: Vec<u8> = (0..65536)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect();
+    assert!(shannon_entropy(&random) > 7.99);
+}
+
+#[test]
+fn test_printable_ratio_and_histogram() {
+    assert_eq!(printable_ratio(&[]), 0.0);
+    assert_eq!(printable_ratio(b"GET / HTTP/1.1\r\n\r\n"), 1.0);
+    assert_eq!(printable_ratio(&[0x00, 0xff, b'a', b'b']), 0.5);
+    
+    let data = b"hello, world! 0123456789 abcdefghijklmnopqrstuvwxyz";
+    let histogram = byte_histogram(data);
+    assert_eq!(histogram.iter().sum::<usize>(), data.len());
+    assert_eq!(histogram[b'l' as usize], 4);
+    assert_eq!(histogram[b'o' as usize], 3);
+    assert_eq!(histogram[0], 0);
+}
+
+/// 构造HTTP/2帧（负载按给定长度填充）
+fn h2_frame(length: u32, frame_type: u8, flags: u8, stream_id: u32) -> Vec<u8> {
+    let mut frame = length.to_be_bytes()[1..].to_vec();
+    frame.extend_from_slice(&[frame_type, flags]);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.resize(frame.len() + length.min(64) as usize, 0);
+    frame
+}
+
+#[test]
+fn test_http2_anomaly_detection() {
+    let mut normal = http2::CONNECTION_PREFACE.to_vec();
+    normal.extend(h2_frame(6, 0x4, 0, 0));
+    normal.extend(h2_frame(16, 0x1, 0, 1));
+    normal.extend(h2_frame(8, 0x9, 0x4, 1));
+    assert_eq!(http2::frame_headers(&normal).count(), 3);
+    assert_eq!(http2::detect_anomaly(&normal), None);
+    
+    // HEADERS之后跟随大量未结束头部块的CONTINUATION帧
+    let mut flood = h2_frame(16, 0x1, 0, 1);
+    for _ in 0..20 {
+        flood.extend(h2_frame(8, 0x9, 0, 1));
+    }
+    assert_eq!(http2::detect_anomaly(&flood), Some(Http2Anomaly::ContinuationFlood));
+    
+    // 帧长度超过默认MAX_FRAME_SIZE，即使负载尚未完整到达
+    let oversized = h2_frame(http2::DEFAULT_MAX_FRAME_SIZE + 1, 0x0, 0, 1);
+    assert_eq!(http2::detect_anomaly(&oversized), Some(Http2Anomaly::OversizedFrame));
+    
+    let mut settings = Vec::new();
+    for _ in 0..10 {
+        settings.extend(h2_frame(6, 0x4, 0, 0));
+        settings.extend(h2_frame(0, 0x4, 0x1, 0));
+    }
+    assert_eq!(http2::detect_anomaly(&settings), Some(Http2Anomaly::SettingsFlood));
+    assert_eq!(Http2Anomaly::SettingsFlood.as_str(), "settings_flood");
+}
+
+#[test]
+fn test_http2_oversized_frame_respects_max_frame_size_setting() {
+    // SETTINGS声明 SETTINGS_MAX_FRAME_SIZE = 1 MiB
+    let mut settings = http2::CONNECTION_PREFACE.to_vec();
+    settings.extend_from_slice(&[0x00, 0x00, 0x06, 0x04, 0x00, 0, 0, 0, 0]);
+    settings.extend_from_slice(&[0x00, 0x05, 0x00, 0x10, 0x00, 0x00]);
+    
+    let mut raised = settings.clone();
+    raised.extend(h2_frame(64 * 1024, 0x0, 0, 1));
+    assert_eq!(http2::detect_anomaly(&raised), None);
+    
+    // 超过声明的上限仍报告
+    let mut over = settings;
+    over.extend(h2_frame(2 * 1024 * 1024, 0x0, 0, 1));
+    assert_eq!(http2::detect_anomaly(&over), Some(Http2Anomaly::OversizedFrame));
+    
+    // 调用方已知对端上限时直接传入
+    let bulk = h2_frame(64 * 1024, 0x0, 0, 1);
+    assert_eq!(http2::detect_anomaly(&bulk), Some(Http2Anomaly::OversizedFrame));
+    assert_eq!(http2::detect_anomaly_with_max_frame_size(&bulk, 1 << 20), None);
+}
+
+#[test]
+fn test_http2_anomaly_metadata() {
+    let detector = DetectorBuilder::new()
+        .enable_http2()
+        .build()
+        .unwrap();
+    
+    let mut flood = http2::CONNECTION_PREFACE.to_vec();
+    flood.extend(h2_frame(16, 0x1, 0, 1));
+    for _ in 0..16 {
+        flood.extend(h2_frame(8, 0x9, 0, 1));
+    }
+    let result = detector.detect(&flood).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert_eq!(
+        result.protocol_info.metadata.get("http2_anomaly").map(String::as_str),
+        Some("continuation_flood")
+    );
+    
+    let mut oversized = http2::CONNECTION_PREFACE.to_vec();
+    oversized.extend(h2_frame(0x10000, 0x0, 0, 1));
+    let result = detector.detect(&oversized).unwrap();
+    assert_eq!(
+        result.protocol_info.metadata.get("http2_anomaly").map(String::as_str),
+        Some("oversized_frame")
+    );
+}
+
+#[test]
+fn test_suggest_confidences_penalizes_noisy_signature() {
+    let precise = MagicSignature::new(ProtocolType::SSH, b"SSH-2.0-".to_vec(), 0, 0.9, "SSH banner".to_string());
+    // 过于宽泛：TLS握手和其他0x16开头的数据都会命中
+    let noisy = MagicSignature::new(ProtocolType::TLS, vec![0x16], 0, 0.9, "TLS record type".to_string());
+    let unused = MagicSignature::new(ProtocolType::QUIC, vec![0xc3, 0xff], 0, 0.7, "unused".to_string());
+    
+    let corpus = vec![
+        (b"SSH-2.0-OpenSSH_8.9\r\n".to_vec(), ProtocolType::SSH),
+        (b"SSH-2.0-libssh\r\n".to_vec(), ProtocolType::SSH),
+        (b"SSH-2.0-dropbear\r\n".to_vec(), ProtocolType::SSH),
+        (vec![0x16, 0x03, 0x01, 0x00, 0x10], ProtocolType::TLS),
+        (vec![0x16, 0x00, 0x00, 0x05], ProtocolType::Custom),
+        (vec![0x16, 0x42], ProtocolType::Custom),
+        (vec![0x16, 0x99, 0x01], ProtocolType::Unknown),
+    ];
+    
+    let hits = SignatureHits::measure(&noisy, &corpus);
+    assert_eq!(hits.true_positives, 1);
+    assert_eq!(hits.false_positives, 3);
+    
+    let suggested = suggest_confidences(&[precise, noisy, unused], &corpus);
+    let precise_conf = suggested["SSH banner"];
+    let noisy_conf = suggested["TLS record type"];
+    assert!(noisy_conf < precise_conf);
+    assert!(noisy_conf < 0.9);
+    assert!((precise_conf - 0.8).abs() < 1e-6);
+    assert_eq!(suggested["unused"], 0.7);
+}
+
+#[test]
+fn test_http2_midstream_frame_chain() {
+    // 中途接入：WINDOW_UPDATE、HEADERS、DATA、PING，无连接前言
+    let mut chain = h2_frame(4, 0x8, 0, 0);
+    chain.extend(h2_frame(16, 0x1, 0x4, 3));
+    chain.extend(h2_frame(32, 0x0, 0x1, 3));
+    chain.extend(h2_frame(8, 0x6, 0, 0));
+    assert_eq!(http2::midstream_frame_chain(&chain), 4);
+    
+    // 末尾不完整的帧不计入
+    assert_eq!(http2::midstream_frame_chain(&chain[..chain.len() - 1]), 3);
+    
+    // 未结束的头部块后必须是同一流的CONTINUATION
+    let mut broken = h2_frame(16, 0x1, 0, 1);
+    broken.extend(h2_frame(8, 0x0, 0, 1));
+    assert_eq!(http2::midstream_frame_chain(&broken), 1);
+    
+    // 对端调大SETTINGS_MAX_FRAME_SIZE后的大DATA帧不打断帧链
+    let mut bulk = h2_frame(16, 0x1, 0x4, 1);
+    bulk.extend_from_slice(&[0x00, 0x4e, 0x20, 0x0, 0x0, 0, 0, 0, 1]);
+    bulk.resize(bulk.len() + 20_000, 0);
+    bulk.extend(h2_frame(8, 0x6, 0, 0));
+    assert_eq!(http2::midstream_frame_chain(&bulk), 3);
+    
+    // SETTINGS不能出现在非零流上，PING长度必须为8
+    assert_eq!(http2::midstream_frame_chain(&h2_frame(6, 0x4, 0, 1)), 0);
+    assert_eq!(http2::midstream_frame_chain(&h2_frame(4, 0x6, 0, 0)), 0);
+}
+
+#[test]
+fn test_http2_midstream_detection() {
+    let detector = DetectorBuilder::new()
+        .enable_http2()
+        .build()
+        .unwrap();
+    
+    let mut data = h2_frame(4, 0x8, 0, 0);
+    data.extend(h2_frame(16, 0x1, 0x4, 5));
+    data.extend(h2_frame(32, 0x0, 0, 5));
+    let result = detector.detect(&data).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert_eq!(result.protocol_info.metadata.get("http2_mode").map(String::as_str), Some("midstream"));
+    
+    // 带连接前言的流不标记为中途接入
+    let mut preface = http2::CONNECTION_PREFACE.to_vec();
+    preface.extend(h2_frame(6, 0x4, 0, 0));
+    let result = detector.detect(&preface).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert!(!result.protocol_info.metadata.contains_key("http2_mode"));
+}
+
+#[test]
+fn test_http2_single_server_settings_frame() {
+    let detector = DetectorBuilder::new()
+        .enable_http2()
+        .build()
+        .unwrap();
+    
+    // 服务端连接前言只有一个SETTINGS帧
+    let settings = h2_frame(18, 0x4, 0, 0);
+    let result = detector.detect(&settings).expect("单个SETTINGS帧应识别为HTTP/2");
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP2);
+    assert!(result.confidence() >= 0.8);
+}
+
+#[test]
+fn test_websocket_close_frame_parsing() {
+    // 服务端发出的1000关闭帧，不带原因
+    let frame = [0x88, 0x02, 0x03, 0xe8];
+    assert_eq!(
+        parse_close_frame(&frame),
+        Some(CloseFrame { code: 1000, reason: String::new() })
+    );
+    
+    // 客户端发出的掩码关闭帧，带原因
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut payload = 1000u16.to_be_bytes().to_vec();
+    payload.extend_from_slice(b"bye");
+    let mut frame = vec![0x88, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    assert_eq!(
+        parse_close_frame(&frame),
+        Some(CloseFrame { code: 1000, reason: "bye".to_string() })
+    );
+    
+    // 保留状态码、非法UTF-8原因、截断的帧和非关闭帧
+    assert_eq!(parse_close_frame(&[0x88, 0x02, 0x03, 0xed]), None);
+    assert_eq!(parse_close_frame(&[0x88, 0x03, 0x03, 0xe8, 0xff]), None);
+    assert_eq!(parse_close_frame(&[0x88, 0x05, 0x03, 0xe8]), None);
+    assert_eq!(parse_close_frame(&[0x81, 0x02, 0x03, 0xe8]), None);
+}
+
+#[test]
+fn test_message_framing_complete_and_truncated() {
+    // TLS记录：长度字段为6
+    let record = [0x16, 0x03, 0x01, 0x00, 0x06, 0x01, 0x00, 0x00, 0x02, 0x03, 0x03];
+    assert_eq!(MessageFraming::of(ProtocolType::TLS, &record), MessageFraming::Complete);
+    assert_eq!(MessageFraming::of(ProtocolType::TLS, &record[..8]), MessageFraming::Truncated(11));
+    assert_eq!(MessageFraming::of(ProtocolType::TLS, &record[..3]), MessageFraming::Truncated(5));
+    
+    // HTTP/2：连接前言之后的SETTINGS帧带一个参数
+    let mut h2 = http2::CONNECTION_PREFACE.to_vec();
+    h2.extend_from_slice(&[0x00, 0x00, 0x06, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    h2.extend_from_slice(&[0x00, 0x03, 0x00, 0x00, 0x00, 0x64]);
+    assert!(MessageFraming::of(ProtocolType::HTTP2, &h2).is_complete());
+    assert_eq!(MessageFraming::of(ProtocolType::HTTP2, &h2[..35]).needed(), Some(39));
+    assert_eq!(MessageFraming::of(ProtocolType::HTTP2, &h2[..30]).needed(), Some(33));
+    
+    // WebSocket掩码帧：2字节头 + 4字节掩码 + 5字节负载
+    let frame = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+    assert!(MessageFraming::of(ProtocolType::WebSocket, &frame).is_complete());
+    assert_eq!(MessageFraming::of(ProtocolType::WebSocket, &frame[..4]), MessageFraming::Truncated(11));
+    
+    // MySQL：3字节小端长度 + 序号
+    let packet = [0x03, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00];
+    assert!(MessageFraming::of(ProtocolType::MySQL, &packet).is_complete());
+    assert_eq!(MessageFraming::of(ProtocolType::MySQL, &packet[..5]), MessageFraming::Truncated(7));
+    
+    // 没有长度字段的协议或不符合分帧格式的数据
+    assert_eq!(MessageFraming::of(ProtocolType::HTTP1_1, b"GET / HTTP/1.1\r\n"), MessageFraming::Unframed);
+    assert_eq!(MessageFraming::of(ProtocolType::WebSocket, b"GET /chat HTTP/1.1\r\n"), MessageFraming::Unframed);
+    assert_eq!(MessageFraming::of(ProtocolType::TLS, b"SSH-2.0"), MessageFraming::Unframed);
+}
+
+#[test]
+fn test_http_interim_and_expect_continue() {
+    let detector = DetectorBuilder::new().enable_http().build().unwrap();
+    
+    let request = b"PUT /upload HTTP/1.1\r\nHost: example.com\r\nContent-Length: 1048576\r\nExpect: 100-Continue\r\n\r\n";
+    assert!(expects_continue(request));
+    let result = detector.detect(request).unwrap();
+    assert_eq!(result.protocol_type(), ProtocolType::HTTP1_1);
+    assert_eq!(result.protocol_info.metadata.get("expects_continue").map(String::as_str), Some("true"));
+    assert_eq!(result.protocol_info.metadata.get("http_interim"), None);
+    
+    let hints = b"HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload; as=style\r\n\r\n";
+    let result = detector.detect(hints).unwrap();
+    assert_eq!(result.protocol_info.metadata.get("http_interim").map(String::as_str), Some("early_hints"));
+    assert_eq!(result.protocol_info.metadata.get("expects_continue"), None);
+    
+    assert_eq!(interim_response(b"HTTP/1.1 100 Continue\r\n\r\n"), Some("continue"));
+    assert_eq!(interim_response(b"HTTP/1.1 101 Switching Protocols\r\n\r\n"), None);
+    assert!(!expects_continue(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"));
+}
+
+#[test]
+fn test_record_and_replay_detections() {
+    let build = || DetectorBuilder::new()
+        .enable_http()
+        .enable_http2()
+        .enable_ssh()
+        .build()
+        .unwrap();
+    
+    let path = std::env::temp_dir().join(format!("psi_replay_{}.ndjson", std::process::id()));
+    let recorder = DetectionRecorder::create(&path, build()).unwrap();
+    
+    let inputs: [&[u8]; 4] = [
+        b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n",
+        b"SSH-2.0-OpenSSH_9.6\r\n",
+        b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n",
+        &[0xde, 0xad, 0xbe, 0xef, 0x00, 0x01, 0x02, 0x03],
+    ];
+    for input in inputs {
+        let _ = recorder.detect(input);
+    }
+    recorder.flush().unwrap();
+    
+    // 同一配置回放没有差异
+    let report = replay(&path, &build()).unwrap();
+    assert_eq!(report.total, inputs.len());
+    assert_eq!(report.config_changed, 0);
+    assert!(report.is_clean(), "{:?}", report.diffs);
+    
+    // 去掉SSH后回放报告回归
+    let without_ssh = DetectorBuilder::new().enable_http().enable_http2().build().unwrap();
+    let report = replay(&path, &without_ssh).unwrap();
+    assert_eq!(report.config_changed, inputs.len());
+    assert_eq!(report.diffs.len(), 1);
+    assert_eq!(report.diffs[0].line, 2);
+    assert_eq!(report.diffs[0].expected.protocol, Some(ProtocolType::SSH));
+    
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_digest_and_base64_known_vectors() {
+    assert_eq!(encode_hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(encode_hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    // 跨越两个分组
+    assert_eq!(
+        encode_hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+    
+    assert_eq!(encode_hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(encode_hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(
+        encode_hex(&md5(b"12345678901234567890123456789012345678901234567890123456789012345678901234567890")),
+        "57edf4a22be3c955ac49da2e2107b67a"
+    );
+    
+    assert_eq!(encode_base64(b""), "");
+    assert_eq!(encode_base64(b"f"), "Zg==");
+    assert_eq!(encode_base64(b"fo"), "Zm8=");
+    assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+    assert_eq!(decode_base64(&encode_base64(&[0xff, 0x00, 0x7f])).unwrap(), vec![0xff, 0x00, 0x7f]);
+}